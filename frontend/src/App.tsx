@@ -544,6 +544,12 @@ function App() {
           const savedRoute = await response.json();
           console.log("Itinéraire chargé depuis l'API:", savedRoute);
 
+          // Version chargée dans cet onglet, envoyée en If-Match à la mise à jour
+          const etag = response.headers.get("ETag");
+          if (etag) {
+            sessionStorage.setItem("saved-route-etag", etag);
+          }
+
           // Stocker le nom et l'UUID pour les mises à jour futures
           if (savedRoute.name) {
             localStorage.setItem("saved-route-name", savedRoute.name);
@@ -724,11 +730,13 @@ function App() {
                       let response;
                       if (savedUuid) {
                         // Mettre à jour l'itinéraire existant
+                        const etag = sessionStorage.getItem("saved-route-etag");
                         response = await fetch(`/api/route/${savedUuid}`, {
                           method: "PUT",
                           credentials: "include",
                           headers: {
                             "Content-Type": "application/json",
+                            ...(etag ? { "If-Match": etag } : {}),
                           },
                           body: JSON.stringify({
                             name,
@@ -757,6 +765,11 @@ function App() {
                       const result = await response.json();
                       console.log("Résultat de la sauvegarde:", result);
 
+                      const newEtag = response.headers.get("ETag");
+                      if (newEtag) {
+                        sessionStorage.setItem("saved-route-etag", newEtag);
+                      }
+
                       // Sauvegarder l'UUID et le nom pour les prochaines mises à jour
                       if (result.name) {
                         localStorage.setItem("saved-route-name", result.name);
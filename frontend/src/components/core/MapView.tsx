@@ -351,6 +351,12 @@ export default function MapView({
           const savedRoute = await response.json();
           console.log("Itinéraire chargé depuis l'API:", savedRoute);
 
+          // Version chargée dans cet onglet, envoyée en If-Match à la mise à jour
+          const etag = response.headers.get("ETag");
+          if (etag) {
+            sessionStorage.setItem("saved-route-etag", etag);
+          }
+
           // Stocker le nom et l'UUID pour les mises à jour futures
           if (savedRoute.name) {
             localStorage.setItem("saved-route-name", savedRoute.name);
@@ -491,6 +497,7 @@ export default function MapView({
       localStorage.removeItem("saved-route");
       localStorage.removeItem("saved-route-uuid");
       localStorage.removeItem("saved-route-name");
+      sessionStorage.removeItem("saved-route-etag");
       console.log("Itinéraire supprimé du localStorage");
     }
   }, [startPoint, endPoint, waypoints, transportMode]);
@@ -856,17 +863,17 @@ export default function MapView({
       }
 
       const response = await fetch(
-        `https://nominatim.openstreetmap.org/reverse?format=json&lat=${lat}&lon=${lon}`,
+        `/api/reverse-geocode?lat=${lat}&lng=${lon}`,
       );
       const data = await response.json();
       const displayName = data.name ||
-        data.display_name ||
+        data.label ||
         `${lat.toFixed(4)}, ${lon.toFixed(4)}`;
       const location: AppLocation = {
         lat,
         lon,
         display_name: displayName,
-        address_text: data.display_name || displayName,
+        address_text: data.label || displayName,
         name: extractShortName(displayName),
         is_saved: false,
       };
@@ -1684,16 +1691,16 @@ export default function MapView({
       setIsDraggingMarker(false);
       try {
         const response = await fetch(
-          `https://nominatim.openstreetmap.org/reverse?format=json&lat=${lngLat.lat}&lon=${lngLat.lng}`,
+          `/api/reverse-geocode?lat=${lngLat.lat}&lng=${lngLat.lng}`,
         );
         const data = await response.json();
-        const displayName = data.display_name ||
+        const displayName = data.label ||
           `${lngLat.lat.toFixed(4)}, ${lngLat.lng.toFixed(4)}`;
         setStartPoint({
           lat: lngLat.lat,
           lon: lngLat.lng,
           display_name: displayName,
-          address_text: data.display_name || displayName,
+          address_text: data.label || displayName,
           name: extractShortName(displayName),
           is_saved: false,
         });
@@ -1721,16 +1728,16 @@ export default function MapView({
       setIsDraggingMarker(false);
       try {
         const response = await fetch(
-          `https://nominatim.openstreetmap.org/reverse?format=json&lat=${lngLat.lat}&lon=${lngLat.lng}`,
+          `/api/reverse-geocode?lat=${lngLat.lat}&lng=${lngLat.lng}`,
         );
         const data = await response.json();
-        const displayName = data.display_name ||
+        const displayName = data.label ||
           `${lngLat.lat.toFixed(4)}, ${lngLat.lng.toFixed(4)}`;
         setEndPoint({
           lat: lngLat.lat,
           lon: lngLat.lng,
           display_name: displayName,
-          address_text: data.display_name || displayName,
+          address_text: data.label || displayName,
           name: extractShortName(displayName),
           is_saved: false,
         });
@@ -1788,6 +1795,7 @@ export default function MapView({
     localStorage.removeItem("saved-route");
     localStorage.removeItem("saved-route-uuid");
     localStorage.removeItem("saved-route-name");
+    sessionStorage.removeItem("saved-route-etag");
   }, []);
 
   const handleReorderWaypoints = useCallback((newWaypoints: Waypoint[]) => {
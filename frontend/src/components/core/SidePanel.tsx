@@ -18,8 +18,8 @@ import { useNavigate } from "react-router";
 
 export interface NewLocation {
   address_text?: string;
-  lat?: string;
-  lng?: string;
+  lat?: number;
+  lng?: number;
   name: string;
   created_at: string;
 }
@@ -79,8 +79,8 @@ interface SidePanelProps {
 interface Address {
   id: number;
   name: string;
-  lat: string;
-  lng: string;
+  lat: number | null;
+  lng: number | null;
   address_text: string;
 }
 
@@ -146,18 +146,18 @@ export default function SidePanel({
         try {
           // Recherche inversée pour obtenir l'adresse
           const response = await fetch(
-            `https://nominatim.openstreetmap.org/reverse?format=json&lat=${latitude}&lon=${longitude}`,
+            `/api/reverse-geocode?lat=${latitude}&lng=${longitude}`,
           );
           const data = await response.json();
           const displayName =
-            data.display_name ||
+            data.label ||
             `${latitude.toFixed(4)}, ${longitude.toFixed(4)}`;
 
           const location: AppLocation = {
             lat: latitude,
             lon: longitude,
             display_name: displayName,
-            address_text: data.display_name || displayName,
+            address_text: data.label || displayName,
             name: extractShortName(displayName),
             is_saved: isAddressAlreadySaved(latitude, longitude),
           };
@@ -216,8 +216,10 @@ export default function SidePanel({
     const tolerance = 0.0001;
     return preferedAddreses.some(
       (addr) =>
-        Math.abs(parseFloat(addr.lat) - lat) < tolerance &&
-        Math.abs(parseFloat(addr.lng) - lon) < tolerance,
+        addr.lat !== null &&
+        addr.lng !== null &&
+        Math.abs(addr.lat - lat) < tolerance &&
+        Math.abs(addr.lng - lon) < tolerance,
     );
   };
 
@@ -232,16 +234,14 @@ export default function SidePanel({
       setIsSearching(true);
       try {
         const response = await fetch(
-          `https://nominatim.openstreetmap.org/search?format=json&q=${encodeURIComponent(
-            searchQuery,
-          )}&limit=5`,
+          `/api/geocode?q=${encodeURIComponent(searchQuery)}&limit=5`,
         );
         const data = await response.json();
         setSearchResults(
           data.map((item: any) => {
-            const displayName = item.display_name;
-            const lat = parseFloat(item.lat);
-            const lon = parseFloat(item.lon);
+            const displayName = item.label;
+            const lat = item.lat;
+            const lon = item.lng;
             return {
               lat,
               lon,
@@ -1296,8 +1296,8 @@ export default function SidePanel({
                     disabled={addressesLoading}
                     onClick={() => {
                       saveAddress({
-                        lat: `${startPoint.lat}`,
-                        lng: `${startPoint.lon}`,
+                        lat: startPoint.lat,
+                        lng: startPoint.lon,
                         created_at: new Date().toISOString(),
                         name: startPoint.display_name,
                         address_text: startPoint.address_text,
@@ -1642,8 +1642,8 @@ export default function SidePanel({
                     disabled={addressesLoading}
                     onClick={() => {
                       saveAddress({
-                        lat: `${endPoint.lat}`,
-                        lng: `${endPoint.lon}`,
+                        lat: endPoint.lat,
+                        lng: endPoint.lon,
                         created_at: new Date().toISOString(),
                         name: endPoint.display_name,
                         address_text: endPoint.address_text,
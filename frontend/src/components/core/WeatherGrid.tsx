@@ -350,8 +350,8 @@ function LoaderIcon() {
 }
 
 export type CityInfo = {
-  lat: string;
-  lng: string;
+  lat: number;
+  lng: number;
   name: string;
 };
 
@@ -1303,8 +1303,8 @@ const CityCard = React.forwardRef<
                 created_at: new Date().toISOString(),
                 name: data.location.name,
                 address_text: `(${data.location.lat}, ${data.location.lon})`,
-                lat: `${data.location.lat}`,
-                lng: `${data.location.lon}`,
+                lat: data.location.lat,
+                lng: data.location.lon,
               };
               fetch("/api/prefered_addresses", {
                 method: "POST",
@@ -58,6 +58,7 @@ export default function ExportMenu({
     localStorage.removeItem("saved-route");
     localStorage.removeItem("saved-route-uuid");
     localStorage.removeItem("saved-route-name");
+    sessionStorage.removeItem("saved-route-etag");
 
     // Fermer le menu
     setOpen(false);
@@ -0,0 +1,6 @@
+-- Route documents are validated by the server and versioned from now on
+ALTER TABLE saved_routes ALTER COLUMN route TYPE jsonb USING route::jsonb;
+
+-- Documents saved before versioning are v1, upgraded on load
+UPDATE saved_routes SET route = jsonb_set(route, '{version}', '1'::jsonb)
+WHERE jsonb_typeof(route) = 'object' AND NOT route ? 'version';
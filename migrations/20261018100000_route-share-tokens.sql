@@ -0,0 +1,11 @@
+-- Public read-only links to a saved route
+CREATE TABLE IF NOT EXISTS route_share_tokens (
+  id bigserial PRIMARY KEY,
+  route_id bigint not null references saved_routes(id) on delete cascade,
+  token varchar(64) not null unique,
+  created_at timestamp with time zone not null default CURRENT_TIMESTAMP,
+  expires_at timestamp with time zone,
+  revoked_at timestamp with time zone
+);
+
+CREATE INDEX IF NOT EXISTS route_share_tokens_route_id_idx ON route_share_tokens (route_id);
@@ -0,0 +1,16 @@
+-- Every version of a saved route is kept so an overwrite can be undone
+ALTER TABLE saved_routes ADD COLUMN revision integer not null default 1;
+
+CREATE TABLE IF NOT EXISTS saved_route_revisions (
+  id bigserial PRIMARY KEY,
+  route_id bigint not null references saved_routes(id) on delete cascade,
+  revision integer not null,
+  name varchar(255) not null,
+  route jsonb not null,
+  created_at timestamp with time zone not null default CURRENT_TIMESTAMP,
+  UNIQUE (route_id, revision)
+);
+
+-- Existing routes start their history at their current state
+INSERT INTO saved_route_revisions (route_id, revision, name, route, created_at)
+SELECT id, revision, name, route, updated_at FROM saved_routes;
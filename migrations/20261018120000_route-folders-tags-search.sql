@@ -0,0 +1,45 @@
+-- Folders, tags and full-text search to organize saved routes
+CREATE TABLE IF NOT EXISTS route_folders (
+  id bigserial PRIMARY KEY,
+  user_id bigint not null references users(id),
+  name varchar(255) not null,
+  created_at timestamp with time zone not null default CURRENT_TIMESTAMP,
+  UNIQUE (user_id, name)
+);
+
+ALTER TABLE saved_routes ADD COLUMN folder_id bigint references route_folders(id) on delete set null;
+
+CREATE TABLE IF NOT EXISTS route_tags (
+  id bigserial PRIMARY KEY,
+  user_id bigint not null references users(id),
+  name varchar(64) not null,
+  created_at timestamp with time zone not null default CURRENT_TIMESTAMP,
+  UNIQUE (user_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS saved_route_tags (
+  route_id bigint not null references saved_routes(id) on delete cascade,
+  tag_id bigint not null references route_tags(id) on delete cascade,
+  PRIMARY KEY (route_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS saved_route_tags_tag_id_idx ON saved_route_tags (tag_id);
+
+-- Route name (weight A) and start, end and waypoint labels (weight B)
+CREATE OR REPLACE FUNCTION saved_route_search_vector(name text, route jsonb) RETURNS tsvector
+LANGUAGE sql IMMUTABLE AS $$
+  SELECT setweight(to_tsvector('simple', coalesce(name, '')), 'A')
+    || setweight(to_tsvector('simple', concat_ws(' ',
+         route #>> '{startPoint,display_name}',
+         route #>> '{endPoint,display_name}',
+         (SELECT string_agg(waypoint ->> 'display_name', ' ')
+          FROM jsonb_array_elements(
+            CASE WHEN jsonb_typeof(route -> 'waypoints') = 'array' THEN route -> 'waypoints' ELSE '[]'::jsonb END
+          ) AS waypoint)
+       )), 'B')
+$$;
+
+CREATE INDEX IF NOT EXISTS saved_routes_search_idx ON saved_routes USING gin (saved_route_search_vector(name, route));
+
+-- Keyset pagination of a user's routes
+CREATE INDEX IF NOT EXISTS saved_routes_user_updated_idx ON saved_routes (user_id, updated_at DESC, id DESC);
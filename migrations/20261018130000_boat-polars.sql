@@ -0,0 +1,10 @@
+-- Boat speed tables used by sailing routes
+CREATE TABLE IF NOT EXISTS boat_polars (
+  id bigserial PRIMARY KEY,
+  user_id bigint not null references users(id),
+  name varchar(255) not null,
+  polar jsonb not null,
+  created_at timestamp with time zone not null default CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS boat_polars_user_id_idx ON boat_polars (user_id);
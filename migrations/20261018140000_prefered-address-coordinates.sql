@@ -0,0 +1,35 @@
+-- Numeric coordinates for preferred addresses; values that do not parse or are out of range are dropped.
+-- Only plain decimals of at most 3 integer digits are cast, so the cast cannot overflow, and the range
+-- is checked in a nested CASE so it only runs on values that matched.
+ALTER TABLE prefered_addresses
+  ALTER COLUMN lat TYPE double precision USING (
+    CASE WHEN trim(lat) ~ '^[-+]?([0-9]{1,3}(\.[0-9]*)?|\.[0-9]+)$'
+         THEN CASE WHEN trim(lat)::double precision BETWEEN -90 AND 90
+                   THEN trim(lat)::double precision END
+    END
+  ),
+  ALTER COLUMN lng TYPE double precision USING (
+    CASE WHEN trim(lng) ~ '^[-+]?([0-9]{1,3}(\.[0-9]*)?|\.[0-9]+)$'
+         THEN CASE WHEN trim(lng)::double precision BETWEEN -180 AND 180
+                   THEN trim(lng)::double precision END
+    END
+  );
+
+UPDATE prefered_addresses SET lat = NULL, lng = NULL WHERE (lat IS NULL) <> (lng IS NULL);
+
+ALTER TABLE prefered_addresses
+  ADD CONSTRAINT prefered_addresses_lat_range CHECK (lat BETWEEN -90 AND 90),
+  ADD CONSTRAINT prefered_addresses_lng_range CHECK (lng BETWEEN -180 AND 180),
+  ADD CONSTRAINT prefered_addresses_lat_lng_pair CHECK ((lat IS NULL) = (lng IS NULL));
+
+-- Manual ordering, initially by creation
+ALTER TABLE prefered_addresses ADD COLUMN sort_order integer not null default 0;
+
+UPDATE prefered_addresses p SET sort_order = o.position
+FROM (
+  SELECT id, row_number() OVER (PARTITION BY user_id ORDER BY created_at, id) - 1 AS position
+  FROM prefered_addresses
+) o
+WHERE p.id = o.id;
+
+CREATE INDEX IF NOT EXISTS prefered_addresses_user_order_idx ON prefered_addresses (user_id, sort_order, id);
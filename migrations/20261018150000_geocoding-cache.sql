@@ -0,0 +1,12 @@
+-- Answers of the geocoding provider, so repeated lookups skip the upstream call
+CREATE TABLE IF NOT EXISTS geocoding_cache (
+  id bigserial PRIMARY KEY,
+  provider varchar(32) not null,
+  -- search or reverse
+  kind varchar(16) not null,
+  -- normalized query text, or rounded "lat,lng" for reverse lookups
+  query_key text not null,
+  results jsonb not null,
+  created_at timestamp with time zone not null default CURRENT_TIMESTAMP,
+  UNIQUE (provider, kind, query_key)
+);
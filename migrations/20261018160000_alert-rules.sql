@@ -0,0 +1,39 @@
+-- Weather conditions watched on a preferred address or along a saved route
+CREATE TABLE IF NOT EXISTS alert_rules (
+  id bigserial PRIMARY KEY,
+  user_id bigint not null references users(id),
+  prefered_address_id bigint references prefered_addresses(id) ON DELETE CASCADE,
+  saved_route_id bigint references saved_routes(id) ON DELETE CASCADE,
+  -- wind_speed (km/h), gusts (km/h) or rain_rate (mm/h)
+  metric varchar(32) not null,
+  -- above or below
+  comparison varchar(8) not null,
+  threshold double precision not null,
+  -- forecasts up to this many hours ahead are considered, 0 for current conditions
+  within_hours integer not null default 0,
+  name varchar(255),
+  enabled boolean not null default true,
+  created_at timestamp with time zone not null default CURRENT_TIMESTAMP,
+  updated_at timestamp with time zone not null default CURRENT_TIMESTAMP,
+  CONSTRAINT alert_rules_one_target CHECK ((prefered_address_id IS NULL) <> (saved_route_id IS NULL)),
+  CONSTRAINT alert_rules_within_hours CHECK (within_hours BETWEEN 0 AND 48)
+);
+
+CREATE INDEX IF NOT EXISTS alert_rules_user_id_idx ON alert_rules (user_id);
+
+-- A rule fires once when its condition starts to hold, the event stays open until it stops
+CREATE TABLE IF NOT EXISTS alert_events (
+  id bigserial PRIMARY KEY,
+  rule_id bigint not null references alert_rules(id) ON DELETE CASCADE,
+  user_id bigint not null references users(id),
+  -- forecast time of the grid where the condition was met
+  data_time timestamp with time zone not null,
+  value double precision not null,
+  lat double precision not null,
+  lng double precision not null,
+  fired_at timestamp with time zone not null default CURRENT_TIMESTAMP,
+  resolved_at timestamp with time zone
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS alert_events_open_idx ON alert_events (rule_id) WHERE resolved_at IS NULL;
+CREATE INDEX IF NOT EXISTS alert_events_user_fired_idx ON alert_events (user_id, fired_at DESC);
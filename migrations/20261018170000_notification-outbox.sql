@@ -0,0 +1,50 @@
+-- URLs a user wants notifications POSTed to, signed with the secret
+CREATE TABLE IF NOT EXISTS webhooks (
+  id bigserial PRIMARY KEY,
+  user_id bigint not null references users(id),
+  url text not null,
+  secret varchar(64) not null,
+  enabled boolean not null default true,
+  created_at timestamp with time zone not null default CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS webhooks_user_id_idx ON webhooks (user_id);
+
+-- Notifications waiting to be delivered, one row per channel and recipient
+CREATE TABLE IF NOT EXISTS notification_outbox (
+  id bigserial PRIMARY KEY,
+  user_id bigint not null references users(id),
+  -- email or webhook
+  channel varchar(16) not null,
+  webhook_id bigint references webhooks(id) ON DELETE CASCADE,
+  -- what the notification is about, e.g. alert
+  kind varchar(32) not null,
+  payload jsonb not null,
+  -- pending, sending, delivered or dead
+  status varchar(16) not null default 'pending',
+  attempts integer not null default 0,
+  -- also the lease of a row being sent, so a crashed worker's rows are picked up again
+  next_attempt_at timestamp with time zone not null default CURRENT_TIMESTAMP,
+  last_error text,
+  created_at timestamp with time zone not null default CURRENT_TIMESTAMP,
+  delivered_at timestamp with time zone,
+  CONSTRAINT notification_outbox_webhook CHECK ((channel = 'webhook') = (webhook_id IS NOT NULL))
+);
+
+CREATE INDEX IF NOT EXISTS notification_outbox_due_idx ON notification_outbox (channel, next_attempt_at)
+  WHERE status IN ('pending', 'sending');
+CREATE INDEX IF NOT EXISTS notification_outbox_user_idx ON notification_outbox (user_id, created_at DESC);
+
+-- Every delivery attempt, successful or not
+CREATE TABLE IF NOT EXISTS notification_deliveries (
+  id bigserial PRIMARY KEY,
+  notification_id bigint not null references notification_outbox(id) ON DELETE CASCADE,
+  attempt integer not null,
+  success boolean not null,
+  -- HTTP status of webhook deliveries
+  response_status integer,
+  error text,
+  created_at timestamp with time zone not null default CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS notification_deliveries_notification_idx ON notification_deliveries (notification_id);
@@ -0,0 +1,19 @@
+-- Opt-in daily forecast email for the user's preferred addresses
+CREATE TABLE IF NOT EXISTS digest_subscriptions (
+  user_id bigint PRIMARY KEY references users(id) ON DELETE CASCADE,
+  enabled boolean not null default true,
+  -- local time of the user at which the digest is sent
+  send_at time not null default '07:00',
+  -- IANA time zone name, e.g. Europe/Paris
+  timezone varchar(64) not null default 'Europe/Paris',
+  -- number of days covered, starting today
+  days integer not null default 3,
+  ai_summary boolean not null default false,
+  -- local date of the last digest, one is sent per day at most
+  last_sent_on date,
+  created_at timestamp with time zone not null default CURRENT_TIMESTAMP,
+  updated_at timestamp with time zone not null default CURRENT_TIMESTAMP,
+  CONSTRAINT digest_subscriptions_days CHECK (days BETWEEN 1 AND 7)
+);
+
+CREATE INDEX IF NOT EXISTS digest_subscriptions_enabled_idx ON digest_subscriptions (user_id) WHERE enabled;
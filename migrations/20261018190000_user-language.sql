@@ -0,0 +1,2 @@
+-- Language of the emails sent to the user
+ALTER TABLE users ADD COLUMN IF NOT EXISTS language varchar(8) not null default 'fr';
@@ -0,0 +1,2 @@
+-- Entries past the cache lifetime are deleted every day
+CREATE INDEX IF NOT EXISTS geocoding_cache_created_at_idx ON geocoding_cache (created_at);
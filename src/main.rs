@@ -1,7 +1,9 @@
 use dotenvy::dotenv;
 use crate::utils::misc::{get_env, Env};
 use crate::utils::queries::migrate_db;
+use crate::utils::road_graph::RoadGraph;
 use sqlx::PgPool;
+use std::path::Path;
 use tokio;
 
 mod models;
@@ -11,8 +13,42 @@ mod services;
 mod tests;
 mod utils;
 
+/// `build-road-graph <extract.osm.pbf> <output.graph>`: preprocess an OSM extract
+/// into the road graph used by the offline routing engine (`ROAD_GRAPH_PATH`)
+fn build_road_graph(args: &[String]) {
+    let [input, output] = args else {
+        eprintln!("Usage: build-road-graph <extract.osm.pbf> <output.graph>");
+        std::process::exit(2);
+    };
+
+    let graph = match RoadGraph::from_pbf(Path::new(input)) {
+        Ok(graph) => graph,
+        Err(e) => {
+            eprintln!("Failed to read {}: {:#}", input, e);
+            std::process::exit(1);
+        }
+    };
+    println!(
+        "Road graph: {} nodes, {} edges",
+        graph.node_count(),
+        graph.edge_count()
+    );
+
+    if let Err(e) = graph.save(Path::new(output)) {
+        eprintln!("Failed to write {}: {:#}", output, e);
+        std::process::exit(1);
+    }
+    println!("Written to {}", output);
+}
+
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("build-road-graph") {
+        build_road_graph(&args[2..]);
+        return;
+    }
+
     dotenv().ok();
 
     let app_env: Env = get_env();
@@ -0,0 +1,209 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::FromRow;
+
+/// Forecasts further ahead than this cannot be watched
+pub const MAX_ALERT_WITHIN_HOURS: i32 = 48;
+
+/// Quantity watched by an alert rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertMetric {
+    /// Mean wind, km/h
+    WindSpeed,
+    /// Wind gusts, km/h
+    Gusts,
+    /// Precipitation, mm/h
+    RainRate,
+}
+
+impl AlertMetric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertMetric::WindSpeed => "wind_speed",
+            AlertMetric::Gusts => "gusts",
+            AlertMetric::RainRate => "rain_rate",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertComparison {
+    #[default]
+    Above,
+    Below,
+}
+
+impl AlertComparison {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertComparison::Above => "above",
+            AlertComparison::Below => "below",
+        }
+    }
+
+    pub fn matches(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            AlertComparison::Above => value > threshold,
+            AlertComparison::Below => value < threshold,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AlertRuleRow {
+    pub id: i64,
+    pub user_id: i64,
+    pub prefered_address_id: Option<i64>,
+    pub route_uuid: Option<String>,
+    pub metric: String,
+    pub comparison: String,
+    pub threshold: f64,
+    pub within_hours: i32,
+    pub name: Option<String>,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Condition watched on one of the user's preferred addresses or saved routes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub id: i64,
+    pub user_id: i64,
+    pub prefered_address_id: Option<i64>,
+    pub route_uuid: Option<String>,
+    pub metric: AlertMetric,
+    pub comparison: AlertComparison,
+    pub threshold: f64,
+    /// Forecasts up to this many hours ahead are considered, 0 for current conditions
+    pub within_hours: i32,
+    pub name: Option<String>,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TryFrom<AlertRuleRow> for AlertRule {
+    type Error = sqlx::Error;
+
+    fn try_from(row: AlertRuleRow) -> Result<Self, Self::Error> {
+        let metric = serde_json::from_value(Value::String(row.metric))
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        let comparison = serde_json::from_value(Value::String(row.comparison))
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+        Ok(AlertRule {
+            id: row.id,
+            user_id: row.user_id,
+            prefered_address_id: row.prefered_address_id,
+            route_uuid: row.route_uuid,
+            metric,
+            comparison,
+            threshold: row.threshold,
+            within_hours: row.within_hours,
+            name: row.name,
+            enabled: row.enabled,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+    }
+}
+
+/// Body of `POST /api/alert_rules`, targets either an address or a route
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewAlertRule {
+    pub prefered_address_id: Option<i64>,
+    pub route_uuid: Option<String>,
+    #[serde(flatten)]
+    pub condition: AlertCondition,
+}
+
+impl NewAlertRule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.prefered_address_id.is_some() == self.route_uuid.is_some() {
+            return Err("Give either prefered_address_id or route_uuid".to_string());
+        }
+        self.condition.validate()
+    }
+}
+
+/// Editable part of a rule, also the body of `PUT /api/alert_rules/{id}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlertCondition {
+    pub metric: AlertMetric,
+    #[serde(default)]
+    pub comparison: AlertComparison,
+    pub threshold: f64,
+    #[serde(default)]
+    pub within_hours: i32,
+    pub name: Option<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl AlertCondition {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.threshold.is_finite() || self.threshold < 0.0 {
+            return Err("threshold must be a positive number".to_string());
+        }
+        if !(0..=MAX_ALERT_WITHIN_HOURS).contains(&self.within_hours) {
+            return Err(format!(
+                "within_hours must be within 0..{}",
+                MAX_ALERT_WITHIN_HOURS
+            ));
+        }
+        if self.name.as_ref().is_some_and(|n| n.chars().count() > 255) {
+            return Err("name is limited to 255 characters".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Positions watched by an enabled rule: the address, or the saved route's document
+#[derive(Debug, Clone)]
+pub struct AlertTargetRow {
+    pub rule_id: i64,
+    /// Name of the address or route
+    pub name: Option<String>,
+    pub lat: Option<f64>,
+    pub lng: Option<f64>,
+    pub route: Option<Value>,
+}
+
+/// Time a rule's condition started to hold, open until it stops holding
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AlertEvent {
+    pub id: i64,
+    pub rule_id: i64,
+    pub user_id: i64,
+    /// Forecast time of the grid where the condition was met
+    pub data_time: DateTime<Utc>,
+    /// Worst value found, in the unit of the rule's metric
+    pub value: f64,
+    pub lat: f64,
+    pub lng: f64,
+    pub fired_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+/// Query of `GET /api/alert_events`
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlertEventsQuery {
+    /// Only events still open
+    #[serde(default)]
+    pub open: bool,
+    pub limit: Option<i64>,
+}
+
+impl AlertEventsQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(50).clamp(1, 200)
+    }
+}
@@ -12,12 +12,15 @@ use chrono::Utc;
 use serde;
 use sqlx::FromRow;
 use sqlx::PgPool;
+use std::sync::Arc;
 
+use crate::utils::mail::Mailer;
 use crate::utils::misc::Env;
 
 pub struct AppData {
     pub db: PgPool,
     pub env: Env,
+    pub mailer: Arc<Mailer>,
 }
 
 #[derive(Debug, Clone, FromRow, serde::Serialize, serde::Deserialize)]
@@ -26,6 +29,8 @@ pub struct User {
     pub name: String,
     pub email: String,
     pub api_token: String,
+    /// Language of the emails sent to the user
+    pub language: String,
 }
 
 impl Responder for User {
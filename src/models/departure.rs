@@ -0,0 +1,88 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_DEPARTURE_STEP_MINUTES: i64 = 30;
+pub const MAX_DEPARTURE_CANDIDATES: i64 = 96;
+
+/// Query string of `GET /api/route/{uuid}/best-departure`
+#[derive(Debug, Clone, Deserialize)]
+pub struct DepartureWindowQuery {
+    /// Earliest departure considered
+    pub from: DateTime<Utc>,
+    /// Latest departure considered
+    pub to: DateTime<Utc>,
+    /// Minutes between candidates (15 to 180)
+    pub step: Option<i64>,
+}
+
+impl DepartureWindowQuery {
+    pub fn step(&self) -> Duration {
+        Duration::minutes(
+            self.step
+                .unwrap_or(DEFAULT_DEPARTURE_STEP_MINUTES)
+                .clamp(15, 180),
+        )
+    }
+
+    /// Candidate departures, or why the window is refused
+    pub fn candidates(&self) -> Result<Vec<DateTime<Utc>>, String> {
+        if self.to < self.from {
+            return Err("to must not be before from".to_string());
+        }
+        let step = self.step();
+        let count = (self.to - self.from).num_seconds() / step.num_seconds() + 1;
+        if count > MAX_DEPARTURE_CANDIDATES {
+            return Err(format!(
+                "At most {} candidate departures, widen the step or narrow the window",
+                MAX_DEPARTURE_CANDIDATES
+            ));
+        }
+        Ok((0..count as i32).map(|i| self.from + step * i).collect())
+    }
+}
+
+/// Rain met along the route for one departure
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RainExposure {
+    /// mm/h
+    pub max_rate: f64,
+    /// Meters ridden under more than a trace of rain
+    pub wet_distance: f64,
+    /// Rain received on the way, mm
+    pub expected_rain: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindExposureSummary {
+    /// m/s, negative when the wind mostly helps
+    pub mean_headwind: f64,
+    /// Meters
+    pub headwind_distance: f64,
+    pub tailwind_distance: f64,
+    /// m/s
+    pub max_crosswind: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepartureOption {
+    pub rank: usize,
+    pub departure: DateTime<Utc>,
+    pub arrival: DateTime<Utc>,
+    /// 0 (worst) to 100 (dry, calm or tailwind)
+    pub score: f64,
+    pub rain: RainExposure,
+    pub wind: WindExposureSummary,
+    /// False when part of the ride falls outside the available forecasts
+    pub forecast_coverage: bool,
+    pub explanations: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BestDepartureResponse {
+    /// Route duration in seconds
+    pub duration: f64,
+    /// Forecast times of the grids used
+    pub data_times: Vec<DateTime<Utc>>,
+    /// Best first
+    pub options: Vec<DepartureOption>,
+}
@@ -0,0 +1,90 @@
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Days a digest can cover, GFS forecasts do not go much further
+pub const MAX_DIGEST_DAYS: i32 = 7;
+
+/// Daily forecast email of a user
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct DigestSubscription {
+    pub user_id: i64,
+    pub enabled: bool,
+    /// Local time the digest is sent at
+    pub send_at: NaiveTime,
+    /// IANA time zone name, e.g. `Europe/Paris`
+    pub timezone: String,
+    /// Days covered, starting today
+    pub days: i32,
+    /// Add a summary written by Claude
+    pub ai_summary: bool,
+    /// Local date of the last digest sent
+    pub last_sent_on: Option<NaiveDate>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Body of `PUT /api/digest`
+#[derive(Debug, Clone, Deserialize)]
+pub struct DigestSettings {
+    pub enabled: bool,
+    pub send_at: NaiveTime,
+    pub timezone: String,
+    #[serde(default = "default_digest_days")]
+    pub days: i32,
+    #[serde(default)]
+    pub ai_summary: bool,
+}
+
+fn default_digest_days() -> i32 {
+    3
+}
+
+impl DigestSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.timezone.parse::<Tz>().is_err() {
+            return Err(format!("Unknown timezone {}", self.timezone));
+        }
+        if !(1..=MAX_DIGEST_DAYS).contains(&self.days) {
+            return Err(format!("days must be within 1..{}", MAX_DIGEST_DAYS));
+        }
+        Ok(())
+    }
+}
+
+/// Query of `/api/digest/unsubscribe`, the link of the email
+#[derive(Debug, Clone, Deserialize)]
+pub struct UnsubscribeQuery {
+    pub token: String,
+}
+
+/// Forecast of one place over one local day
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DigestDay {
+    pub date: NaiveDate,
+    /// Strongest mean wind, km/h
+    pub wind_max: Option<f64>,
+    /// Strongest gust, km/h
+    pub gusts_max: Option<f64>,
+    /// Precipitation over the day, mm
+    pub rain_total: Option<f64>,
+}
+
+/// Place of a digest and its coming days
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestPlace {
+    pub name: String,
+    pub address_text: Option<String>,
+    pub days: Vec<DigestDay>,
+}
+
+/// Payload of a `digest` notification, rendered when the email is sent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestPayload {
+    /// Local date of the first day
+    pub date: NaiveDate,
+    pub places: Vec<DigestPlace>,
+    pub summary: Option<String>,
+    pub unsubscribe_url: String,
+}
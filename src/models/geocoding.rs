@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_GEOCODE_LIMIT: u32 = 5;
+pub const MAX_GEOCODE_LIMIT: u32 = 20;
+/// Languages the labels can be asked in, part of the cache key
+pub const GEOCODING_LANGUAGES: [&str; 7] = ["fr", "en", "de", "es", "it", "nl", "pt"];
+
+/// `lang` must be one of GEOCODING_LANGUAGES, absent or empty for the default
+pub fn validate_lang(lang: Option<&str>) -> Result<(), String> {
+    match lang.map(|l| l.trim().to_lowercase()) {
+        Some(lang) if !lang.is_empty() && !GEOCODING_LANGUAGES.contains(&lang.as_str()) => Err(
+            format!("lang must be one of {}", GEOCODING_LANGUAGES.join(", ")),
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Query of `GET /api/geocode`
+#[derive(Debug, Clone, Deserialize)]
+pub struct GeocodeQuery {
+    pub q: String,
+    /// Results returned (1 to 20)
+    pub limit: Option<u32>,
+    /// Preferred language of the labels, e.g. `fr`
+    pub lang: Option<String>,
+}
+
+impl GeocodeQuery {
+    pub fn limit(&self) -> u32 {
+        self.limit
+            .unwrap_or(DEFAULT_GEOCODE_LIMIT)
+            .clamp(1, MAX_GEOCODE_LIMIT)
+    }
+}
+
+/// Query of `GET /api/reverse-geocode`
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReverseGeocodeQuery {
+    pub lat: f64,
+    pub lng: f64,
+    pub lang: Option<String>,
+}
+
+/// Place found by the geocoding provider, the same shape whichever provider answered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeocodeResult {
+    /// Full display name, suitable as `address_text`
+    pub label: String,
+    pub lat: f64,
+    pub lng: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locality: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    /// `[min_lon, min_lat, max_lon, max_lat]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bbox: Option<[f64; 4]>,
+}
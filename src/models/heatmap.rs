@@ -0,0 +1,165 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+pub const DEFAULT_HEATMAP_WIDTH: u32 = 800;
+/// Largest side of a heatmap, in pixels
+pub const MAX_HEATMAP_SIZE: u32 = 2048;
+/// Animations hold a frame per forecast, so they are smaller
+pub const DEFAULT_ANIMATION_WIDTH: u32 = 480;
+pub const MAX_ANIMATION_SIZE: u32 = 1024;
+/// Pixels of all the frames of an animation together, about 32 MB of RGBA
+pub const MAX_ANIMATION_PIXELS: u64 = 8_000_000;
+
+/// Area of a rendered map, in degrees. `west` can be greater than `east`
+/// when the area crosses the antimeridian.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bbox {
+    pub west: f64,
+    pub south: f64,
+    pub east: f64,
+    pub north: f64,
+}
+
+impl Bbox {
+    pub const WORLD: Bbox = Bbox {
+        west: -180.0,
+        south: -90.0,
+        east: 180.0,
+        north: 90.0,
+    };
+
+    /// Parse `west,south,east,north`
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let parts: Vec<f64> = value
+            .split(',')
+            .map(|part| part.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| "bbox must be west,south,east,north".to_string())?;
+        let [west, south, east, north] = parts[..] else {
+            return Err("bbox must be west,south,east,north".to_string());
+        };
+
+        if parts.iter().any(|v| !v.is_finite())
+            || !(-180.0..=180.0).contains(&west)
+            || !(-180.0..=180.0).contains(&east)
+            || !(-90.0..=90.0).contains(&south)
+            || !(-90.0..=90.0).contains(&north)
+        {
+            return Err("bbox is outside of the world".to_string());
+        }
+        if south >= north || west == east {
+            return Err("bbox is empty".to_string());
+        }
+        Ok(Self {
+            west,
+            south,
+            east,
+            north,
+        })
+    }
+
+    /// Width in degrees of longitude
+    pub fn width(&self) -> f64 {
+        if self.east > self.west {
+            self.east - self.west
+        } else {
+            self.east - self.west + 360.0
+        }
+    }
+
+    pub fn height(&self) -> f64 {
+        self.north - self.south
+    }
+}
+
+/// Query of `GET /api/heatmap/{layer}.png`
+#[derive(Debug, Clone, Deserialize)]
+pub struct HeatmapQuery {
+    /// `west,south,east,north`, the whole world by default
+    pub bbox: Option<String>,
+    pub width: Option<u32>,
+    /// Follows the bbox's proportions when only the width is given
+    pub height: Option<u32>,
+    /// Forecast index, the one closest to now by default
+    pub index: Option<u32>,
+    /// Name of the palette, the layer's own by default
+    pub palette: Option<String>,
+    /// Add a color scale under the map
+    #[serde(default)]
+    pub legend: bool,
+}
+
+impl HeatmapQuery {
+    pub fn bbox(&self) -> Result<Bbox, String> {
+        parse_bbox(self.bbox.as_deref())
+    }
+
+    /// Image size for `bbox`, both sides within 1..=MAX_HEATMAP_SIZE
+    pub fn size(&self, bbox: &Bbox) -> Result<(u32, u32), String> {
+        map_size(
+            self.width.unwrap_or(DEFAULT_HEATMAP_WIDTH),
+            self.height,
+            bbox,
+            MAX_HEATMAP_SIZE,
+        )
+    }
+}
+
+/// Query of `GET /api/animation/{layer}.{format}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct AnimationQuery {
+    /// `west,south,east,north`, the whole world by default
+    pub bbox: Option<String>,
+    pub width: Option<u32>,
+    /// Follows the bbox's proportions when only the width is given
+    pub height: Option<u32>,
+    /// First forecast time shown, RFC 3339, all the stored ones by default
+    pub from: Option<DateTime<Utc>>,
+    /// Last forecast time shown, RFC 3339
+    pub to: Option<DateTime<Utc>>,
+    /// Name of the palette, the layer's own by default
+    pub palette: Option<String>,
+    /// Add a color scale under the map
+    #[serde(default)]
+    pub legend: bool,
+}
+
+impl AnimationQuery {
+    pub fn bbox(&self) -> Result<Bbox, String> {
+        parse_bbox(self.bbox.as_deref())
+    }
+
+    /// Frame size for `bbox`, both sides within 1..=MAX_ANIMATION_SIZE
+    pub fn size(&self, bbox: &Bbox) -> Result<(u32, u32), String> {
+        map_size(
+            self.width.unwrap_or(DEFAULT_ANIMATION_WIDTH),
+            self.height,
+            bbox,
+            MAX_ANIMATION_SIZE,
+        )
+    }
+
+    pub fn check_period(&self) -> Result<(), String> {
+        match (self.from, self.to) {
+            (Some(from), Some(to)) if from > to => Err("from must be before to".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn parse_bbox(bbox: Option<&str>) -> Result<Bbox, String> {
+    match bbox {
+        Some(bbox) => Bbox::parse(bbox),
+        None => Ok(Bbox::WORLD),
+    }
+}
+
+fn map_size(width: u32, height: Option<u32>, bbox: &Bbox, max: u32) -> Result<(u32, u32), String> {
+    let height =
+        height.unwrap_or_else(|| (f64::from(width) * bbox.height() / bbox.width()).round() as u32);
+
+    if !(1..=max).contains(&width) || !(1..=max).contains(&height) {
+        return Err(format!("width and height must be within 1..{}", max));
+    }
+    Ok((width, height))
+}
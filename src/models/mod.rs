@@ -1,11 +1,39 @@
+pub mod alert;
 pub mod api_responses;
 pub mod auth;
+pub mod departure;
+pub mod digest;
+pub mod geocoding;
+pub mod heatmap;
+pub mod notification;
 pub mod precipitation;
 pub mod prefered_address;
+pub mod route_document;
+pub mod route_folder;
+pub mod route_revision;
+pub mod route_search;
+pub mod route_share;
+pub mod route_tag;
 pub mod routes;
+pub mod sailing;
 pub mod weather;
 pub mod wind;
+pub mod wind_analysis;
 
+pub use alert::*;
+pub use departure::*;
+pub use digest::*;
+pub use geocoding::*;
+pub use heatmap::*;
+pub use notification::*;
 pub use precipitation::*;
+pub use route_document::*;
+pub use route_folder::*;
+pub use route_revision::*;
+pub use route_search::*;
+pub use route_share::*;
+pub use route_tag::*;
 pub use routes::*;
+pub use sailing::*;
 pub use wind::*;
+pub use wind_analysis::*;
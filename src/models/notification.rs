@@ -0,0 +1,174 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationChannel {
+    Email,
+    Webhook,
+}
+
+impl NotificationChannel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationChannel::Email => "email",
+            NotificationChannel::Webhook => "webhook",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationStatus {
+    Pending,
+    /// Claimed by a worker, picked up again if the lease runs out
+    Sending,
+    Delivered,
+    /// Gave up after too many failed attempts
+    Dead,
+}
+
+impl NotificationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationStatus::Pending => "pending",
+            NotificationStatus::Sending => "sending",
+            NotificationStatus::Delivered => "delivered",
+            NotificationStatus::Dead => "dead",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NotificationRow {
+    pub id: i64,
+    pub user_id: i64,
+    pub channel: String,
+    pub webhook_id: Option<i64>,
+    pub kind: String,
+    pub payload: Value,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// Entry of the outbox, delivered once through one channel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: i64,
+    pub user_id: i64,
+    pub channel: NotificationChannel,
+    pub webhook_id: Option<i64>,
+    pub kind: String,
+    pub payload: Value,
+    pub status: NotificationStatus,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<NotificationRow> for Notification {
+    type Error = sqlx::Error;
+
+    fn try_from(row: NotificationRow) -> Result<Self, Self::Error> {
+        let channel = serde_json::from_value(Value::String(row.channel))
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        let status = serde_json::from_value(Value::String(row.status))
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+        Ok(Notification {
+            id: row.id,
+            user_id: row.user_id,
+            channel,
+            webhook_id: row.webhook_id,
+            kind: row.kind,
+            payload: row.payload,
+            status,
+            attempts: row.attempts,
+            next_attempt_at: row.next_attempt_at,
+            last_error: row.last_error,
+            created_at: row.created_at,
+            delivered_at: row.delivered_at,
+        })
+    }
+}
+
+/// One delivery attempt of a notification
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct NotificationDelivery {
+    pub id: i64,
+    pub notification_id: i64,
+    pub attempt: i32,
+    pub success: bool,
+    /// HTTP status answered to a webhook delivery
+    pub response_status: Option<i32>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Query of `GET /api/notifications`
+#[derive(Debug, Clone, Deserialize)]
+pub struct NotificationsQuery {
+    pub status: Option<NotificationStatus>,
+    pub limit: Option<i64>,
+}
+
+/// Query of `GET /api/notifications/deliveries`
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeliveriesQuery {
+    pub notification_id: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+pub fn notification_limit(limit: Option<i64>) -> i64 {
+    limit.unwrap_or(50).clamp(1, 200)
+}
+
+/// URL notifications are POSTed to, signed with `secret`
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: i64,
+    pub user_id: i64,
+    pub url: String,
+    /// Only shown once, when the webhook is created
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Answer to `POST /api/webhooks`, the only time the secret is returned
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatedWebhook {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+/// Body of `POST /api/webhooks`
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewWebhook {
+    pub url: String,
+}
+
+impl NewWebhook {
+    /// Plain http is only accepted outside production
+    pub fn validate(&self, is_production: bool) -> Result<(), String> {
+        if self.url.len() > 2048 {
+            return Err("url is limited to 2048 characters".to_string());
+        }
+        let url = reqwest::Url::parse(&self.url).map_err(|_| "url is not a valid URL")?;
+        match url.scheme() {
+            "https" => Ok(()),
+            "http" if !is_production => Ok(()),
+            _ => Err("url must use https".to_string()),
+        }
+    }
+}
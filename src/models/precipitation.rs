@@ -28,3 +28,22 @@ pub struct PrecipitationData {
     pub region: String,
     pub bounds: PrecipitationBounds,
 }
+
+/// Metadata of the precipitation texture, the counterpart of `WindMetadata`.
+/// The red channel holds `ln(1 + rate) / ln(1 + rateMax)` scaled to 0..255, so a
+/// pixel decodes to `exp(r / 255 * ln(1 + rateMax)) - 1`. Missing values have alpha 0.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrecipitationMetadata {
+    pub source: String,
+    pub date: String,
+    pub width: usize,
+    pub height: usize,
+    #[serde(rename = "rateMin")]
+    pub rate_min: f64,
+    #[serde(rename = "rateMax")]
+    pub rate_max: f64,
+    /// Encoding of the red channel, `log1p`
+    pub scale: String,
+    pub unit: String,
+    pub tiles: Vec<String>,
+}
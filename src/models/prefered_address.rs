@@ -5,31 +5,109 @@ use actix_web::HttpResponse;
 use actix_web::Responder;
 use chrono::DateTime;
 use chrono::Utc;
+use serde::de::Error;
 use serde::Deserialize;
+use serde::Deserializer;
 use serde::Serialize;
 
 #[derive(Serialize, Deserialize)]
 pub struct PreferedAddress {
     pub id: i64,
     pub address_text: Option<String>,
-    pub lat: Option<String>,
-    pub lng: Option<String>,
+    pub lat: Option<f64>,
+    pub lng: Option<f64>,
     pub user_id: i64,
     pub name: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub deleted_at: Option<DateTime<Utc>>,
+    pub sort_order: i32,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct NewPreferedAddress {
     pub address_text: Option<String>,
-    pub lat: Option<String>,
-    pub lng: Option<String>,
+    #[serde(default, deserialize_with = "coordinate")]
+    pub lat: Option<f64>,
+    #[serde(default, deserialize_with = "coordinate")]
+    pub lng: Option<f64>,
     pub name: String,
     pub created_at: DateTime<Utc>,
 }
 
+/// Body of `PUT /api/prefered_addresses/{id}`, replaces the editable fields
+#[derive(Serialize, Deserialize)]
+pub struct UpdatePreferedAddress {
+    pub address_text: Option<String>,
+    #[serde(default, deserialize_with = "coordinate")]
+    pub lat: Option<f64>,
+    #[serde(default, deserialize_with = "coordinate")]
+    pub lng: Option<f64>,
+    pub name: String,
+}
+
+/// Body of `PUT /api/prefered_addresses/order`, every address id in the wanted order
+#[derive(Serialize, Deserialize)]
+pub struct PreferedAddressOrder {
+    pub ids: Vec<i64>,
+}
+
+/// Coordinates used to be sent as strings, numbers and numeric strings are both accepted
+fn coordinate<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Number(f64),
+        Text(String),
+    }
+
+    match Option::<Raw>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Raw::Number(value)) => Ok(Some(value)),
+        Some(Raw::Text(text)) if text.trim().is_empty() => Ok(None),
+        Some(Raw::Text(text)) => text
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| D::Error::custom(format!("invalid coordinate '{}'", text))),
+    }
+}
+
+/// Both coordinates or neither, within range
+pub fn validate_coordinates(lat: Option<f64>, lng: Option<f64>) -> Result<(), String> {
+    match (lat, lng) {
+        (None, None) => Ok(()),
+        (Some(lat), Some(lng)) => {
+            if !lat.is_finite() || !(-90.0..=90.0).contains(&lat) {
+                Err("lat must be within -90..90".to_string())
+            } else if !lng.is_finite() || !(-180.0..=180.0).contains(&lng) {
+                Err("lng must be within -180..180".to_string())
+            } else {
+                Ok(())
+            }
+        }
+        _ => Err("lat and lng must be given together".to_string()),
+    }
+}
+
+impl NewPreferedAddress {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_coordinates(self.lat, self.lng)
+    }
+}
+
+impl UpdatePreferedAddress {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        validate_coordinates(self.lat, self.lng)
+    }
+}
+
 impl Responder for PreferedAddress {
     type Body = BoxBody;
 
@@ -55,3 +133,39 @@ impl Responder for NewPreferedAddress {
             .body(body)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coordinates_accept_numbers_and_strings() {
+        let address: NewPreferedAddress = serde_json::from_value(serde_json::json!({
+            "name": "Home",
+            "lat": "48.85",
+            "lng": 2.35,
+            "created_at": "2026-01-01T00:00:00Z"
+        }))
+        .unwrap();
+        assert_eq!((address.lat, address.lng), (Some(48.85), Some(2.35)));
+        assert!(address.validate().is_ok());
+
+        let missing: UpdatePreferedAddress =
+            serde_json::from_value(serde_json::json!({ "name": "Work", "lat": "" })).unwrap();
+        assert_eq!((missing.lat, missing.lng), (None, None));
+
+        assert!(serde_json::from_value::<UpdatePreferedAddress>(
+            serde_json::json!({ "name": "Work", "lat": "north" })
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_validate_coordinates() {
+        assert!(validate_coordinates(Some(90.0), Some(-180.0)).is_ok());
+        assert!(validate_coordinates(Some(90.5), Some(0.0)).is_err());
+        assert!(validate_coordinates(Some(0.0), Some(181.0)).is_err());
+        assert!(validate_coordinates(Some(0.0), None).is_err());
+        assert!(validate_coordinates(Some(f64::NAN), Some(0.0)).is_err());
+    }
+}
@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Version written by this server into every route document
+pub const CURRENT_ROUTE_VERSION: u64 = 2;
+
+/// OpenRouteService accepts at most 50 waypoints per directions request
+pub const MAX_ROUTE_POINTS: usize = 50;
+
+#[derive(Debug, Error)]
+pub enum RouteDocumentError {
+    #[error("unsupported route document version {0}")]
+    UnsupportedVersion(u64),
+    #[error("malformed route document: {0}")]
+    Malformed(#[from] serde_json::Error),
+    #[error("invalid route document: {0}")]
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RouteProfile {
+    #[default]
+    DrivingCar,
+    DrivingHgv,
+    CyclingRegular,
+    CyclingRoad,
+    CyclingMountain,
+    CyclingElectric,
+    FootWalking,
+    FootHiking,
+    Wheelchair,
+}
+
+impl RouteProfile {
+    /// Path segment used by the ORS directions API
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RouteProfile::DrivingCar => "driving-car",
+            RouteProfile::DrivingHgv => "driving-hgv",
+            RouteProfile::CyclingRegular => "cycling-regular",
+            RouteProfile::CyclingRoad => "cycling-road",
+            RouteProfile::CyclingMountain => "cycling-mountain",
+            RouteProfile::CyclingElectric => "cycling-electric",
+            RouteProfile::FootWalking => "foot-walking",
+            RouteProfile::FootHiking => "foot-hiking",
+            RouteProfile::Wheelchair => "wheelchair",
+        }
+    }
+
+    pub fn is_cycling(&self) -> bool {
+        matches!(
+            self,
+            RouteProfile::CyclingRegular
+                | RouteProfile::CyclingRoad
+                | RouteProfile::CyclingMountain
+                | RouteProfile::CyclingElectric
+        )
+    }
+}
+
+/// A point picked by the user (start, end or intermediate waypoint)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoutePoint {
+    pub lat: f64,
+    pub lon: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+}
+
+/// Route document stored in `saved_routes.route`.
+/// Field names follow what the frontend keeps in localStorage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteDocument {
+    pub version: u64,
+    #[serde(rename = "startPoint", default)]
+    pub start_point: Option<RoutePoint>,
+    #[serde(rename = "endPoint", default)]
+    pub end_point: Option<RoutePoint>,
+    #[serde(default)]
+    pub waypoints: Vec<RoutePoint>,
+    #[serde(rename = "transportMode", default)]
+    pub profile: RouteProfile,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Utc>>,
+    #[serde(
+        rename = "apiResponse",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub directions: Option<Directions>,
+}
+
+/// GeoJSON directions response from OpenRouteService
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Directions {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub features: Vec<DirectionsFeature>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bbox: Option<Vec<f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<DirectionsMetadata>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectionsMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribution: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectionsFeature {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bbox: Option<Vec<f64>>,
+    pub geometry: RouteGeometry,
+    pub properties: RouteProperties,
+}
+
+/// LineString geometry; positions are `[lon, lat]` or `[lon, lat, elevation]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteGeometry {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub coordinates: Vec<Vec<f64>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteProperties {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ascent: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub descent: Option<f64>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub extras: HashMap<String, RouteExtra>,
+    #[serde(default)]
+    pub segments: Vec<RouteSegment>,
+    #[serde(default)]
+    pub summary: RouteSummary,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<RouteWarning>,
+    #[serde(default)]
+    pub way_points: Vec<usize>,
+}
+
+/// ORS omits `distance`/`duration` when they are zero
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RouteSummary {
+    #[serde(default)]
+    pub distance: f64,
+    #[serde(default)]
+    pub duration: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteSegment {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ascent: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub descent: Option<f64>,
+    #[serde(default)]
+    pub distance: f64,
+    #[serde(default)]
+    pub duration: f64,
+    #[serde(default)]
+    pub steps: Vec<RouteStep>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteStep {
+    #[serde(default)]
+    pub distance: f64,
+    #[serde(default)]
+    pub duration: f64,
+    #[serde(default)]
+    pub instruction: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub kind: u32,
+    pub way_points: [usize; 2],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_number: Option<u32>,
+}
+
+/// Extra info block (surface, waytype, steepness...).
+/// `values` rows are `[start_index, end_index, value]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteExtra {
+    #[serde(default)]
+    pub values: Vec<[i64; 3]>,
+    #[serde(default)]
+    pub summary: Vec<RouteExtraSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteExtraSummary {
+    pub value: f64,
+    pub distance: f64,
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteWarning {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RouteDocument {
+    /// Upgrade a stored or submitted document to the current version and parse it
+    pub fn from_value(value: Value) -> Result<Self, RouteDocumentError> {
+        let upgraded = upgrade(value)?;
+        Ok(serde_json::from_value(upgraded)?)
+    }
+
+    /// Start, intermediate waypoints and end, in travel order
+    pub fn points(&self) -> Vec<&RoutePoint> {
+        self.start_point
+            .iter()
+            .chain(self.waypoints.iter())
+            .chain(self.end_point.iter())
+            .collect()
+    }
+
+    /// First feature of the computed directions, if the route was computed
+    pub fn feature(&self) -> Option<&DirectionsFeature> {
+        self.directions.as_ref().and_then(|d| d.features.first())
+    }
+
+    /// Geometry positions as `[lon, lat, (elevation)]`
+    pub fn coordinates(&self) -> &[Vec<f64>] {
+        self.feature()
+            .map(|f| f.geometry.coordinates.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Check the document is something we are willing to store
+    pub fn validate(&self) -> Result<(), RouteDocumentError> {
+        if self.version != CURRENT_ROUTE_VERSION {
+            return Err(RouteDocumentError::UnsupportedVersion(self.version));
+        }
+
+        let points = self.points();
+        if points.len() > MAX_ROUTE_POINTS {
+            return Err(invalid(format!(
+                "too many points ({} > {})",
+                points.len(),
+                MAX_ROUTE_POINTS
+            )));
+        }
+        for point in points {
+            check_lat_lon(point.lat, point.lon)?;
+        }
+
+        if let Some(directions) = &self.directions {
+            if directions.kind != "FeatureCollection" {
+                return Err(invalid(format!(
+                    "apiResponse must be a FeatureCollection, got {}",
+                    directions.kind
+                )));
+            }
+            for feature in &directions.features {
+                validate_feature(feature)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_feature(feature: &DirectionsFeature) -> Result<(), RouteDocumentError> {
+    if feature.geometry.kind != "LineString" {
+        return Err(invalid(format!(
+            "geometry must be a LineString, got {}",
+            feature.geometry.kind
+        )));
+    }
+
+    let coordinates = &feature.geometry.coordinates;
+    if coordinates.len() < 2 {
+        return Err(invalid("geometry needs at least 2 positions".to_string()));
+    }
+    for position in coordinates {
+        if position.len() < 2 || position.len() > 3 {
+            return Err(invalid(format!(
+                "geometry positions need 2 or 3 values, got {}",
+                position.len()
+            )));
+        }
+        if position.iter().any(|v| !v.is_finite()) {
+            return Err(invalid("geometry contains non-finite values".to_string()));
+        }
+        check_lat_lon(position[1], position[0])?;
+    }
+
+    let summary = feature.properties.summary;
+    check_non_negative("summary.distance", summary.distance)?;
+    check_non_negative("summary.duration", summary.duration)?;
+
+    if let Some(idx) = feature
+        .properties
+        .way_points
+        .iter()
+        .find(|i| **i >= coordinates.len())
+    {
+        return Err(invalid(format!("way_points index {} out of range", idx)));
+    }
+
+    for segment in &feature.properties.segments {
+        check_non_negative("segment.distance", segment.distance)?;
+        check_non_negative("segment.duration", segment.duration)?;
+        for step in &segment.steps {
+            if step.way_points[0] > step.way_points[1] || step.way_points[1] >= coordinates.len() {
+                return Err(invalid(format!(
+                    "step way_points {:?} out of range",
+                    step.way_points
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn check_lat_lon(lat: f64, lon: f64) -> Result<(), RouteDocumentError> {
+    if !lat.is_finite() || !(-90.0..=90.0).contains(&lat) {
+        return Err(invalid(format!("latitude {} out of range", lat)));
+    }
+    if !lon.is_finite() || !(-180.0..=180.0).contains(&lon) {
+        return Err(invalid(format!("longitude {} out of range", lon)));
+    }
+    Ok(())
+}
+
+fn check_non_negative(field: &str, value: f64) -> Result<(), RouteDocumentError> {
+    if !value.is_finite() || value < 0.0 {
+        return Err(invalid(format!("{} must be a non-negative number", field)));
+    }
+    Ok(())
+}
+
+fn invalid(message: String) -> RouteDocumentError {
+    RouteDocumentError::Invalid(message)
+}
+
+/// Bring a raw document up to `CURRENT_ROUTE_VERSION`, one version at a time.
+/// Documents without a `version` field predate versioning and are treated as v1.
+pub fn upgrade(mut value: Value) -> Result<Value, RouteDocumentError> {
+    if !value.is_object() {
+        return Err(invalid("route must be a JSON object".to_string()));
+    }
+
+    let mut version = value.get("version").and_then(Value::as_u64).unwrap_or(1);
+    if version == 0 || version > CURRENT_ROUTE_VERSION {
+        return Err(RouteDocumentError::UnsupportedVersion(version));
+    }
+
+    while version < CURRENT_ROUTE_VERSION {
+        value = match version {
+            1 => upgrade_v1_to_v2(value),
+            _ => return Err(RouteDocumentError::UnsupportedVersion(version)),
+        };
+        version += 1;
+    }
+
+    Ok(value)
+}
+
+/// v1 is whatever the frontend used to dump from localStorage:
+/// nullable points, optional waypoints, any ORS response format and a free-form timestamp.
+fn upgrade_v1_to_v2(mut value: Value) -> Value {
+    let obj = value.as_object_mut().expect("checked by upgrade");
+
+    for key in ["startPoint", "endPoint"] {
+        if obj.get(key).is_some_and(|p| !is_point(p)) {
+            obj.remove(key);
+        }
+    }
+
+    let waypoints: Vec<Value> = obj
+        .get("waypoints")
+        .and_then(Value::as_array)
+        .map(|arr| arr.iter().filter(|p| is_point(p)).cloned().collect())
+        .unwrap_or_default();
+    obj.insert("waypoints".to_string(), Value::Array(waypoints));
+
+    let known_profile = obj
+        .get("transportMode")
+        .cloned()
+        .is_some_and(|p| serde_json::from_value::<RouteProfile>(p).is_ok());
+    if !known_profile {
+        obj.insert(
+            "transportMode".to_string(),
+            Value::String(RouteProfile::default().as_str().to_string()),
+        );
+    }
+
+    let valid_timestamp = obj
+        .get("timestamp")
+        .and_then(Value::as_str)
+        .is_some_and(|t| DateTime::parse_from_rfc3339(t).is_ok());
+    if !valid_timestamp {
+        obj.remove("timestamp");
+    }
+
+    // Older clients asked ORS for the plain JSON format (`routes` with an encoded polyline).
+    // It can be recomputed from the points, so only keep GeoJSON responses.
+    let geojson_response = obj
+        .get("apiResponse")
+        .and_then(|r| r.get("type"))
+        .and_then(Value::as_str)
+        == Some("FeatureCollection");
+    if !geojson_response {
+        obj.remove("apiResponse");
+    }
+
+    obj.insert("version".to_string(), Value::from(2));
+    value
+}
+
+fn is_point(value: &Value) -> bool {
+    value.get("lat").is_some_and(Value::is_number) && value.get("lon").is_some_and(Value::is_number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn directions() -> Value {
+        json!({
+            "type": "FeatureCollection",
+            "features": [{
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": [[2.35, 48.85, 35.0], [2.36, 48.86, 40.0], [2.37, 48.87, 38.0]]
+                },
+                "properties": {
+                    "segments": [{
+                        "distance": 2500.0,
+                        "duration": 600.0,
+                        "steps": [{
+                            "distance": 2500.0,
+                            "duration": 600.0,
+                            "instruction": "Head north",
+                            "name": "Rue de Rivoli",
+                            "type": 11,
+                            "way_points": [0, 2]
+                        }]
+                    }],
+                    "summary": { "distance": 2500.0, "duration": 600.0 },
+                    "way_points": [0, 2]
+                }
+            }]
+        })
+    }
+
+    #[test]
+    fn test_upgrade_legacy_document() {
+        let legacy = json!({
+            "startPoint": { "lat": 48.85, "lon": 2.35, "display_name": "Paris" },
+            "endPoint": null,
+            "waypoints": [{ "id": "a", "lat": 48.86, "lon": 2.36 }, { "id": "broken" }],
+            "transportMode": "hovercraft",
+            "timestamp": "2026-01-27T10:00:00.000Z",
+            "apiResponse": { "routes": [] }
+        });
+
+        let doc = RouteDocument::from_value(legacy).unwrap();
+        assert_eq!(doc.version, CURRENT_ROUTE_VERSION);
+        assert!(doc.end_point.is_none());
+        assert_eq!(doc.waypoints.len(), 1);
+        assert_eq!(doc.profile, RouteProfile::DrivingCar);
+        assert!(doc.directions.is_none());
+        assert!(doc.validate().is_ok());
+    }
+
+    #[test]
+    fn test_current_document_round_trip() {
+        let value = json!({
+            "version": 2,
+            "startPoint": { "lat": 48.85, "lon": 2.35 },
+            "endPoint": { "lat": 48.87, "lon": 2.37 },
+            "transportMode": "cycling-regular",
+            "apiResponse": directions()
+        });
+
+        let doc = RouteDocument::from_value(value).unwrap();
+        doc.validate().unwrap();
+        assert_eq!(doc.points().len(), 2);
+
+        let again = RouteDocument::from_value(serde_json::to_value(&doc).unwrap()).unwrap();
+        let feature = &again.directions.unwrap().features[0];
+        assert_eq!(feature.properties.summary.distance, 2500.0);
+        assert_eq!(feature.geometry.coordinates.len(), 3);
+        assert_eq!(feature.properties.segments[0].steps[0].way_points, [0, 2]);
+    }
+
+    #[test]
+    fn test_rejects_invalid_documents() {
+        let out_of_range = json!({
+            "version": 2,
+            "startPoint": { "lat": 95.0, "lon": 2.35 }
+        });
+        let doc = RouteDocument::from_value(out_of_range).unwrap();
+        assert!(doc.validate().is_err());
+
+        let mut bad_step = directions();
+        bad_step["features"][0]["properties"]["segments"][0]["steps"][0]["way_points"] =
+            json!([0, 9]);
+        let doc =
+            RouteDocument::from_value(json!({ "version": 2, "apiResponse": bad_step })).unwrap();
+        assert!(doc.validate().is_err());
+
+        assert!(matches!(
+            RouteDocument::from_value(json!({ "version": 99 })),
+            Err(RouteDocumentError::UnsupportedVersion(99))
+        ));
+        assert!(RouteDocument::from_value(json!([1, 2])).is_err());
+    }
+}
@@ -0,0 +1,24 @@
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct RouteFolder {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewRouteFolder {
+    pub name: String,
+}
+
+/// Body of `PUT /api/route/{uuid}/folder`, `null` takes the route out of its folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteFolderAssignment {
+    pub folder_id: Option<i64>,
+}
@@ -0,0 +1,201 @@
+use chrono::DateTime;
+use chrono::Utc;
+use serde;
+use serde_json::Value;
+use sqlx::FromRow;
+
+use super::route_document::{RouteDocument, RoutePoint, RouteProfile};
+
+/// Raw `saved_route_revisions` row
+#[derive(Debug, Clone, FromRow)]
+pub struct RouteRevisionRow {
+    pub revision: i32,
+    pub name: String,
+    pub route: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A past (or the current) version of a saved route
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RouteRevision {
+    pub revision: i32,
+    pub name: String,
+    pub route: RouteDocument,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TryFrom<RouteRevisionRow> for RouteRevision {
+    type Error = sqlx::Error;
+
+    fn try_from(row: RouteRevisionRow) -> Result<Self, Self::Error> {
+        let route =
+            RouteDocument::from_value(row.route).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+        Ok(RouteRevision {
+            revision: row.revision,
+            name: row.name,
+            route,
+            created_at: row.created_at,
+        })
+    }
+}
+
+/// Listing entry, without the full document
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RouteRevisionSummary {
+    pub revision: i32,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub profile: RouteProfile,
+    pub points: usize,
+    pub distance: Option<f64>,
+    pub duration: Option<f64>,
+}
+
+impl From<&RouteRevision> for RouteRevisionSummary {
+    fn from(revision: &RouteRevision) -> Self {
+        let summary = revision.route.feature().map(|f| f.properties.summary);
+
+        RouteRevisionSummary {
+            revision: revision.revision,
+            name: revision.name.clone(),
+            created_at: revision.created_at,
+            profile: revision.route.profile,
+            points: revision.route.points().len(),
+            distance: summary.map(|s| s.distance),
+            duration: summary.map(|s| s.duration),
+        }
+    }
+}
+
+/// Differences between two revisions of the same route
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RevisionComparison {
+    pub from: RouteRevisionSummary,
+    pub to: RouteRevisionSummary,
+    pub name_changed: bool,
+    pub profile_changed: bool,
+    /// Points present in `to` only
+    pub added_points: Vec<RoutePoint>,
+    /// Points present in `from` only
+    pub removed_points: Vec<RoutePoint>,
+    /// Same points, different order
+    pub reordered: bool,
+    /// `to` minus `from`, when both revisions were computed
+    pub distance_delta: Option<f64>,
+    pub duration_delta: Option<f64>,
+}
+
+/// Points closer than this (in degrees, about 1 m) are the same place
+const SAME_POINT_EPSILON: f64 = 1e-5;
+
+fn same_place(a: &RoutePoint, b: &RoutePoint) -> bool {
+    (a.lat - b.lat).abs() < SAME_POINT_EPSILON && (a.lon - b.lon).abs() < SAME_POINT_EPSILON
+}
+
+/// Points of `points` that have no match in `others`, each match being used once
+fn unmatched_points(points: &[&RoutePoint], others: &[&RoutePoint]) -> Vec<RoutePoint> {
+    let mut used = vec![false; others.len()];
+
+    points
+        .iter()
+        .filter(|point| {
+            let matched = others
+                .iter()
+                .enumerate()
+                .position(|(i, other)| !used[i] && same_place(point, other));
+            match matched {
+                Some(i) => {
+                    used[i] = true;
+                    false
+                }
+                None => true,
+            }
+        })
+        .map(|point| (*point).clone())
+        .collect()
+}
+
+impl RevisionComparison {
+    pub fn new(from: &RouteRevision, to: &RouteRevision) -> Self {
+        let from_points = from.route.points();
+        let to_points = to.route.points();
+
+        let added_points = unmatched_points(&to_points, &from_points);
+        let removed_points = unmatched_points(&from_points, &to_points);
+        let reordered = added_points.is_empty()
+            && removed_points.is_empty()
+            && from_points
+                .iter()
+                .zip(to_points.iter())
+                .any(|(a, b)| !same_place(a, b));
+
+        let from_summary = RouteRevisionSummary::from(from);
+        let to_summary = RouteRevisionSummary::from(to);
+        let delta = |a: Option<f64>, b: Option<f64>| a.zip(b).map(|(a, b)| b - a);
+
+        RevisionComparison {
+            name_changed: from.name != to.name,
+            profile_changed: from.route.profile != to.route.profile,
+            added_points,
+            removed_points,
+            reordered,
+            distance_delta: delta(from_summary.distance, to_summary.distance),
+            duration_delta: delta(from_summary.duration, to_summary.duration),
+            from: from_summary,
+            to: to_summary,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn revision(revision: i32, points: Value) -> RouteRevision {
+        let mut document = json!({
+            "version": 2,
+            "startPoint": { "lat": 48.85, "lon": 2.35 },
+            "endPoint": { "lat": 48.87, "lon": 2.37 },
+            "transportMode": "cycling-regular"
+        });
+        document["waypoints"] = points;
+
+        RouteRevision {
+            revision,
+            name: format!("Revision {}", revision),
+            route: serde_json::from_value(document).unwrap(),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_compare_added_and_removed_points() {
+        let from = revision(1, json!([{ "lat": 48.86, "lon": 2.36 }]));
+        let to = revision(2, json!([{ "lat": 48.80, "lon": 2.30 }]));
+
+        let comparison = RevisionComparison::new(&from, &to);
+        assert!(comparison.name_changed);
+        assert!(!comparison.profile_changed);
+        assert_eq!(comparison.added_points.len(), 1);
+        assert_eq!(comparison.added_points[0].lat, 48.80);
+        assert_eq!(comparison.removed_points.len(), 1);
+        assert_eq!(comparison.removed_points[0].lat, 48.86);
+        assert!(!comparison.reordered);
+        assert!(comparison.distance_delta.is_none());
+    }
+
+    #[test]
+    fn test_compare_reordered_points() {
+        let a = json!({ "lat": 48.86, "lon": 2.36 });
+        let b = json!({ "lat": 48.80, "lon": 2.30 });
+        let from = revision(1, json!([a.clone(), b.clone()]));
+        let to = revision(2, json!([b, a]));
+
+        let comparison = RevisionComparison::new(&from, &to);
+        assert!(comparison.added_points.is_empty());
+        assert!(comparison.removed_points.is_empty());
+        assert!(comparison.reordered);
+    }
+}
@@ -0,0 +1,161 @@
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::route_document::RouteProfile;
+use super::routes::SavedRoute;
+
+pub const DEFAULT_SEARCH_LIMIT: i64 = 20;
+pub const MAX_SEARCH_LIMIT: i64 = 100;
+
+/// Query string of `GET /api/routes/search`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RouteSearchQuery {
+    /// Words searched in route names and waypoint labels, matched as prefixes
+    pub q: Option<String>,
+    pub profile: Option<RouteProfile>,
+    /// Meters
+    pub min_distance: Option<f64>,
+    pub max_distance: Option<f64>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub folder_id: Option<i64>,
+    /// Comma separated, routes must carry all of them
+    pub tags: Option<String>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl RouteSearchQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .clamp(1, MAX_SEARCH_LIMIT)
+    }
+
+    /// Distinct tag names, sorted: the search counts matching tags against their number
+    pub fn tag_names(&self) -> Option<Vec<String>> {
+        let mut tags: Vec<String> = self
+            .tags
+            .as_deref()?
+            .split(',')
+            .map(|tag| tag.trim().to_lowercase())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        (!tags.is_empty()).then_some(tags)
+    }
+
+    /// Postgres `tsquery` where every word of `q` is a prefix that must match
+    pub fn ts_query(&self) -> Option<String> {
+        let terms: Vec<String> = self
+            .q
+            .as_deref()?
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| format!("{}:*", word.to_lowercase()))
+            .collect();
+        (!terms.is_empty()).then(|| terms.join(" & "))
+    }
+}
+
+/// Position in the `updated_at DESC, id DESC` ordering, opaque to clients
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RouteCursor {
+    pub updated_at: DateTime<Utc>,
+    pub id: i64,
+}
+
+impl RouteCursor {
+    pub fn encode(&self) -> String {
+        general_purpose::URL_SAFE_NO_PAD.encode(format!(
+            "{}:{}",
+            self.updated_at.timestamp_micros(),
+            self.id
+        ))
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let bytes = general_purpose::URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        let text = String::from_utf8(bytes).ok()?;
+        let (micros, id) = text.split_once(':')?;
+
+        Some(RouteCursor {
+            updated_at: DateTime::from_timestamp_micros(micros.parse().ok()?)?,
+            id: id.parse().ok()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteListItem {
+    #[serde(flatten)]
+    pub route: SavedRoute,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteSearchResponse {
+    pub routes: Vec<RouteListItem>,
+    /// Absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ts_query_prefixes() {
+        let query = RouteSearchQuery {
+            q: Some("  Saint-Malo  bret ".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            query.ts_query().as_deref(),
+            Some("saint:* & malo:* & bret:*")
+        );
+
+        let injection = RouteSearchQuery {
+            q: Some("a' | !b:*".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(injection.ts_query().as_deref(), Some("a:* & b:*"));
+
+        let empty = RouteSearchQuery {
+            q: Some(" & ".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(empty.ts_query(), None);
+    }
+
+    #[test]
+    fn test_tag_names() {
+        let query = |tags: &str| RouteSearchQuery {
+            tags: Some(tags.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            query("vélo, Bretagne,,velo,bretagne ").tag_names(),
+            Some(vec![
+                "bretagne".to_string(),
+                "velo".to_string(),
+                "vélo".to_string()
+            ])
+        );
+        assert_eq!(query("a,a").tag_names(), Some(vec!["a".to_string()]));
+        assert_eq!(query(" , ").tag_names(), None);
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = RouteCursor {
+            updated_at: DateTime::from_timestamp_micros(1_760_000_000_123_456).unwrap(),
+            id: 42,
+        };
+        assert_eq!(RouteCursor::decode(&cursor.encode()), Some(cursor));
+        assert_eq!(RouteCursor::decode("not a cursor"), None);
+    }
+}
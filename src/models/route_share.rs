@@ -0,0 +1,33 @@
+use chrono::DateTime;
+use chrono::Utc;
+use serde;
+use serde::Serialize;
+use sqlx::FromRow;
+
+use super::route_document::RouteDocument;
+use super::weather::RouteWeather;
+
+#[derive(Debug, Clone, FromRow, serde::Serialize, serde::Deserialize)]
+pub struct RouteShare {
+    pub id: i64,
+    pub route_id: i64,
+    pub token: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct NewRouteShare {
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// What an anonymous visitor gets for a share token: no ids, no owner
+#[derive(Debug, Clone, Serialize)]
+pub struct SharedRoute {
+    pub name: String,
+    pub route: RouteDocument,
+    pub updated_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub weather: Option<RouteWeather>,
+}
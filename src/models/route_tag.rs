@@ -0,0 +1,59 @@
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use sqlx::FromRow;
+
+pub const MAX_TAG_LENGTH: usize = 64;
+pub const MAX_TAGS_PER_ROUTE: usize = 20;
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct RouteTag {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A tag with the number of (non deleted) routes carrying it
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteTagUsage {
+    pub id: i64,
+    pub name: String,
+    pub routes: i64,
+}
+
+/// Body of `PUT /api/route/{uuid}/tags`, the complete set of tags of the route
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteTagsInput {
+    pub tags: Vec<String>,
+}
+
+impl RouteTagsInput {
+    /// Trimmed, lowercased and deduplicated tag names
+    pub fn normalized(&self) -> Result<Vec<String>, String> {
+        let mut tags: Vec<String> = Vec::new();
+
+        for tag in &self.tags {
+            let tag = tag.trim().to_lowercase();
+            if tag.is_empty() {
+                continue;
+            }
+            if tag.chars().count() > MAX_TAG_LENGTH {
+                return Err(format!("Tags are limited to {} characters", MAX_TAG_LENGTH));
+            }
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        if tags.len() > MAX_TAGS_PER_ROUTE {
+            return Err(format!(
+                "A route can have at most {} tags",
+                MAX_TAGS_PER_ROUTE
+            ));
+        }
+
+        Ok(tags)
+    }
+}
@@ -8,9 +8,13 @@ use chrono::Utc;
 use serde;
 use serde_json::Value;
 use sqlx::FromRow;
+use tracing::error;
 
-#[derive(Debug, Clone, FromRow, serde::Serialize, serde::Deserialize)]
-pub struct SavedRoute {
+use super::route_document::RouteDocument;
+
+/// Raw `saved_routes` row, before the route document is upgraded and typed
+#[derive(Debug, Clone, FromRow)]
+pub struct SavedRouteRow {
     pub id: i64,
     pub user_id: i64,
     pub name: String,
@@ -19,6 +23,57 @@ pub struct SavedRoute {
     pub updated_at: DateTime<Utc>,
     pub deleted_at: Option<DateTime<Utc>>,
     pub uuid: String,
+    pub revision: i32,
+    pub folder_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SavedRoute {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub route: RouteDocument,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub uuid: String,
+    pub revision: i32,
+    pub folder_id: Option<i64>,
+}
+
+impl TryFrom<SavedRouteRow> for SavedRoute {
+    type Error = sqlx::Error;
+
+    fn try_from(row: SavedRouteRow) -> Result<Self, Self::Error> {
+        let route =
+            RouteDocument::from_value(row.route).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+        Ok(SavedRoute {
+            id: row.id,
+            user_id: row.user_id,
+            name: row.name,
+            route,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            deleted_at: row.deleted_at,
+            uuid: row.uuid,
+            revision: row.revision,
+            folder_id: row.folder_id,
+        })
+    }
+}
+
+/// Routes of `rows`, leaving out and logging those whose document cannot be read,
+/// so one corrupt route does not hide the others
+pub fn decode_saved_routes(rows: Vec<SavedRouteRow>) -> Vec<SavedRoute> {
+    rows.into_iter()
+        .filter_map(|row| {
+            let id = row.id;
+            SavedRoute::try_from(row)
+                .map_err(|e| error!("Skipping unreadable saved route {}: {}", id, e))
+                .ok()
+        })
+        .collect()
 }
 
 impl Responder for SavedRoute {
@@ -0,0 +1,179 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::utils::polar::PolarTable;
+
+pub const DEFAULT_ISOCHRONE_STEP_MINUTES: i64 = 60;
+pub const DEFAULT_HEADING_STEP: f64 = 5.0;
+pub const DEFAULT_MAX_SAILING_HOURS: i64 = 72;
+pub const MAX_SAILING_HOURS: i64 = 240;
+/// Isochrone steps times headings tried from each point of a front: the defaults use
+/// 72 × 72, a week at 1 h and 5° still fits
+pub const MAX_ISOCHRONE_EVALUATIONS: i64 = 20_000;
+
+#[derive(Debug, Clone)]
+pub struct BoatPolarRow {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub polar: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Speed table of a boat uploaded by a user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoatPolar {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub polar: PolarTable,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TryFrom<BoatPolarRow> for BoatPolar {
+    type Error = sqlx::Error;
+
+    fn try_from(row: BoatPolarRow) -> Result<Self, Self::Error> {
+        let polar =
+            serde_json::from_value(row.polar).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+        Ok(BoatPolar {
+            id: row.id,
+            user_id: row.user_id,
+            name: row.name,
+            polar,
+            created_at: row.created_at,
+        })
+    }
+}
+
+/// Body of `POST /api/sailing/route`
+#[derive(Debug, Clone, Deserialize)]
+pub struct SailingRouteRequest {
+    pub polar_id: i64,
+    /// `[lon, lat]`
+    pub start: [f64; 2],
+    pub end: [f64; 2],
+    /// Defaults to now
+    pub departure: Option<DateTime<Utc>>,
+    /// Minutes between isochrones (10 to 360)
+    pub time_step: Option<i64>,
+    /// Degrees between the headings tried from each point (1 to 30)
+    pub heading_step: Option<f64>,
+    /// Give up after this many hours (1 to 240)
+    pub max_duration: Option<i64>,
+    /// Return the isochrones themselves, for display
+    #[serde(default)]
+    pub isochrones: bool,
+}
+
+impl SailingRouteRequest {
+    pub fn time_step(&self) -> Duration {
+        Duration::minutes(
+            self.time_step
+                .unwrap_or(DEFAULT_ISOCHRONE_STEP_MINUTES)
+                .clamp(10, 360),
+        )
+    }
+
+    pub fn heading_step(&self) -> f64 {
+        self.heading_step
+            .filter(|s| s.is_finite())
+            .unwrap_or(DEFAULT_HEADING_STEP)
+            .clamp(1.0, 30.0)
+    }
+
+    pub fn max_duration(&self) -> Duration {
+        Duration::hours(
+            self.max_duration
+                .unwrap_or(DEFAULT_MAX_SAILING_HOURS)
+                .clamp(1, MAX_SAILING_HOURS),
+        )
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, [lon, lat]) in [("start", self.start), ("end", self.end)] {
+            if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                return Err(format!("{} must be a valid [lon, lat] position", name));
+            }
+        }
+
+        let step_seconds = self.time_step().num_seconds();
+        let steps = (self.max_duration().num_seconds() + step_seconds - 1) / step_seconds;
+        let headings = (360.0 / self.heading_step()).round() as i64;
+        if steps * headings > MAX_ISOCHRONE_EVALUATIONS {
+            return Err(format!(
+                "{} steps of {} headings exceed the limit of {}, increase time_step or heading_step, or reduce max_duration",
+                steps, headings, MAX_ISOCHRONE_EVALUATIONS
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Position reached at an isochrone step, with the leg sailed from it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SailingTrackPoint {
+    pub time: DateTime<Utc>,
+    /// `[lon, lat]`
+    pub position: [f64; 2],
+    /// Course of the next leg, degrees from north, absent at the destination
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heading: Option<f64>,
+    /// Knots
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boat_speed: Option<f64>,
+    /// True wind speed in knots
+    pub tws: f64,
+    /// Degrees the true wind blows from
+    pub twd: f64,
+    /// True wind angle of the next leg, 0 to 180
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub twa: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SailingRoute {
+    pub departure: DateTime<Utc>,
+    pub eta: DateTime<Utc>,
+    /// Seconds
+    pub duration: f64,
+    /// Meters sailed
+    pub distance: f64,
+    /// Forecast times of the wind grids used
+    pub data_times: Vec<DateTime<Utc>>,
+    /// False when part of the passage falls outside the available forecasts
+    pub forecast_coverage: bool,
+    pub track: Vec<SailingTrackPoint>,
+    /// `[lon, lat]` fronts reached after each step, when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub isochrones: Option<Vec<Vec<[f64; 2]>>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(time_step: i64, heading_step: f64, max_duration: i64) -> SailingRouteRequest {
+        SailingRouteRequest {
+            polar_id: 1,
+            start: [-4.5, 48.4],
+            end: [-1.2, 46.2],
+            departure: None,
+            time_step: Some(time_step),
+            heading_step: Some(heading_step),
+            max_duration: Some(max_duration),
+            isochrones: false,
+        }
+    }
+
+    #[test]
+    fn test_isochrone_budget() {
+        assert!(request(60, 5.0, 72).validate().is_ok());
+        assert!(request(60, 5.0, 168).validate().is_ok());
+        // 1440 steps of 360 headings
+        assert!(request(10, 1.0, 240).validate().is_err());
+        assert!(request(60, 1.0, 240).validate().is_err());
+    }
+}
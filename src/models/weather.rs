@@ -11,3 +11,28 @@ pub struct WeatherApiResponse {
 
 // For now, we'll just use serde_json::Value for weather responses
 // since we're proxying them directly to the frontend
+
+/// Weather sampled along a saved route from the latest stored GFS grids
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteWeather {
+    pub data_time: Option<String>,
+    pub run_name: Option<String>,
+    pub points: Vec<RouteWeatherPoint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteWeatherPoint {
+    pub lat: f64,
+    pub lon: f64,
+    /// Set for the start, end and waypoints, absent for samples along the geometry
+    pub name: Option<String>,
+    pub u: Option<f64>,
+    pub v: Option<f64>,
+    pub speed: Option<f64>,
+    pub direction: Option<f64>,
+    /// Surface gusts in m/s
+    pub gusts: Option<f64>,
+    /// Precipitation rate in mm/h
+    pub precipitation: Option<f64>,
+}
@@ -8,11 +8,12 @@ pub struct WindPoint {
     pub v: f64,
     pub speed: f64,
     pub direction: f64,
+    /// Surface gusts, m/s
     pub gusts: f64,
 }
 
 impl WindPoint {
-    pub fn new(lat: f64, lon: f64, u: f64, v: f64) -> Self {
+    pub fn new(lat: f64, lon: f64, u: f64, v: f64, gusts: f64) -> Self {
         let speed = (u * u + v * v).sqrt();
         let direction = (270.0 - (v.atan2(u) * 180.0 / std::f64::consts::PI)) % 360.0;
 
@@ -23,7 +24,7 @@ impl WindPoint {
             v,
             speed,
             direction,
-            gusts: 0.0, // Not available in NOAA GFS data
+            gusts,
         }
     }
 }
@@ -44,6 +45,29 @@ pub struct WindData {
     pub bounds: WindBounds,
 }
 
+/// Layout of u and v in the windgl texture, both scaled between their min and max
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindEncoding {
+    /// u in red, v in green, 0..255. Missing values have alpha 0.
+    #[default]
+    Uint8,
+    /// u in red (high byte) and green (low byte), v in blue and alpha, 1..65535.
+    /// 0 marks a missing value, decode with `min + (code - 1) / 65534 * (max - min)`.
+    /// The texture must be uploaded without premultiplied alpha.
+    Uint16,
+}
+
+impl WindEncoding {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "uint8" => Some(WindEncoding::Uint8),
+            "uint16" => Some(WindEncoding::Uint16),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WindMetadata {
     pub source: String,
@@ -58,5 +82,8 @@ pub struct WindMetadata {
     pub v_min: f64,
     #[serde(rename = "vMax")]
     pub v_max: f64,
+    /// Textures stored before the option existed are 8-bit
+    #[serde(default)]
+    pub encoding: WindEncoding,
     pub tiles: Vec<String>,
 }
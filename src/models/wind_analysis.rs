@@ -0,0 +1,120 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_WIND_SEGMENT_LENGTH: f64 = 1000.0;
+pub const DEFAULT_WORST_SEGMENTS: usize = 5;
+
+/// Query string of `GET /api/route/{uuid}/wind-analysis`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WindAnalysisQuery {
+    /// Planned departure, defaults to now
+    pub departure: Option<DateTime<Utc>>,
+    /// Length of the analysed segments in meters (200 to 10000)
+    pub segment_length: Option<f64>,
+    /// Sustained rider power in watts, for the effort estimate
+    pub power: Option<f64>,
+    /// Rider and bike mass in kg
+    pub mass: Option<f64>,
+    /// Number of worst segments returned
+    pub worst: Option<usize>,
+}
+
+impl WindAnalysisQuery {
+    pub fn segment_length(&self) -> f64 {
+        self.segment_length
+            .filter(|l| l.is_finite())
+            .unwrap_or(DEFAULT_WIND_SEGMENT_LENGTH)
+            .clamp(200.0, 10_000.0)
+    }
+
+    pub fn worst(&self) -> usize {
+        self.worst.unwrap_or(DEFAULT_WORST_SEGMENTS).min(50)
+    }
+}
+
+/// Which wind component dominates on a segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindExposure {
+    Headwind,
+    Crosswind,
+    Tailwind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindSegment {
+    pub index: usize,
+    /// Geometry positions covered by the segment
+    pub way_points: [usize; 2],
+    /// `[lon, lat]`
+    pub start: [f64; 2],
+    pub end: [f64; 2],
+    /// Meters
+    pub distance: f64,
+    /// Estimated passage at the middle of the segment
+    pub time: DateTime<Utc>,
+    /// Degrees from north, start to end
+    pub bearing: f64,
+    /// m/s
+    pub wind_speed: f64,
+    /// Degrees the wind blows from
+    pub wind_direction: f64,
+    /// m/s, `None` where the forecast has no gust value
+    pub gusts: Option<f64>,
+    /// Wind component against the direction of travel in m/s, negative for a tailwind
+    pub headwind: f64,
+    /// Wind component across the direction of travel in m/s
+    pub crosswind: f64,
+    pub exposure: WindExposure,
+    /// Riding time gained (negative) or lost (positive) to the wind, cycling profiles only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra_seconds: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WindTotals {
+    /// Meters
+    pub distance: f64,
+    pub headwind_distance: f64,
+    pub crosswind_distance: f64,
+    pub tailwind_distance: f64,
+    /// Distance-weighted, negative when the wind mostly helps
+    pub mean_headwind: f64,
+    pub max_headwind: f64,
+    pub max_crosswind: f64,
+    /// m/s, `None` when no segment has a gust value
+    pub max_gusts: Option<f64>,
+}
+
+/// Riding with a constant power, compared to the same route in still air
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffortEstimate {
+    /// W
+    pub power: f64,
+    /// kg
+    pub mass: f64,
+    /// Seconds
+    pub still_air_duration: f64,
+    pub wind_duration: f64,
+    /// Positive when the wind slows the ride down
+    pub extra_duration: f64,
+    /// Energy in kJ needed on top of the still-air effort to keep the still-air speed
+    pub extra_energy: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindAnalysis {
+    pub departure: DateTime<Utc>,
+    pub arrival: DateTime<Utc>,
+    /// Forecast times of the wind grids used
+    pub data_times: Vec<DateTime<Utc>>,
+    /// False when part of the ride falls outside the available forecasts
+    pub forecast_coverage: bool,
+    pub totals: WindTotals,
+    /// Absent for non-cycling profiles
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effort: Option<EffortEstimate>,
+    /// Segments with the strongest headwind first
+    pub worst_segments: Vec<WindSegment>,
+    pub segments: Vec<WindSegment>,
+}
@@ -1,15 +1,24 @@
 use crate::{
     models::{
         auth::AppData,
-        prefered_address::{NewPreferedAddress, PreferedAddress},
+        prefered_address::{
+            validate_coordinates, NewPreferedAddress, PreferedAddress, PreferedAddressOrder,
+            UpdatePreferedAddress,
+        },
+        RouteProfile,
     },
+    routes::routing::ors_error_response,
+    services::{nearest_prefered_addresses, rank_prefered_addresses, Geocoder, OrsClient},
     utils::queries::{
-        do_delete_prefered_address, do_save_address, get_prefered_addresses,
-        get_user_from_api_token,
+        do_delete_prefered_address, do_restore_prefered_address, do_save_address,
+        get_prefered_addresses, get_trashed_prefered_addresses, get_user_from_api_token,
+        reorder_prefered_addresses, update_prefered_address,
     },
 };
 use actix_web::{dev::Path, web, HttpRequest, HttpResponse, Result};
 use serde::Deserialize;
+use std::sync::Arc;
+use tracing::warn;
 
 pub async fn fetch_adresses(req: HttpRequest, data: web::Data<AppData>) -> Result<HttpResponse> {
     let maybe_cookie = req.cookie("auth");
@@ -33,18 +42,58 @@ pub async fn fetch_adresses(req: HttpRequest, data: web::Data<AppData>) -> Resul
     }
 }
 
+/// Label of the position when an address comes with coordinates but no text.
+/// A failed lookup only leaves the text empty, the address is saved anyway.
+async fn fill_address_text(
+    address_text: &mut Option<String>,
+    lat: Option<f64>,
+    lng: Option<f64>,
+    geocoder: &Geocoder,
+    data: &AppData,
+) {
+    if address_text
+        .as_deref()
+        .is_some_and(|t| !t.trim().is_empty())
+    {
+        return;
+    }
+    let (Some(lat), Some(lng)) = (lat, lng) else {
+        return;
+    };
+
+    match geocoder.reverse(lat, lng, None, data).await {
+        Ok((Some(place), _)) => *address_text = Some(place.label),
+        Ok((None, _)) => {}
+        Err(e) => warn!("Failed to reverse geocode address: {}", e),
+    }
+}
+
 pub async fn save_address(
     req: HttpRequest,
     json: web::Json<NewPreferedAddress>,
     data: web::Data<AppData>,
+    geocoder: web::Data<Arc<Geocoder>>,
 ) -> Result<HttpResponse> {
     let maybe_cookie = req.cookie("auth");
-    let address: NewPreferedAddress = json.into_inner();
+    let mut address: NewPreferedAddress = json.into_inner();
     match maybe_cookie {
         Some(cook) => {
             let user = get_user_from_api_token(cook.value().to_string(), &data).await;
             match user {
                 Ok(u) => {
+                    if let Err(e) = address.validate() {
+                        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                            "error": e
+                        })));
+                    }
+                    fill_address_text(
+                        &mut address.address_text,
+                        address.lat,
+                        address.lng,
+                        &geocoder,
+                        &data,
+                    )
+                    .await;
                     let saved_address = do_save_address(address, u.id, data).await;
                     match saved_address {
                         Ok(addr) => Ok(HttpResponse::Ok().json(addr)),
@@ -69,23 +118,108 @@ pub struct DeletePath {
     id: i64,
 }
 
-pub async fn delete_prefered_adress(
+/// PUT /api/prefered_addresses/{id} - edit the name, text and coordinates of an address
+pub async fn update_address(
     req: HttpRequest,
+    json: web::Json<UpdatePreferedAddress>,
     data: web::Data<AppData>,
+    geocoder: web::Data<Arc<Geocoder>>,
     path: web::Path<DeletePath>,
 ) -> Result<HttpResponse> {
     let maybe_cookie = req.cookie("auth");
     let id = path.into_inner().id;
+    let mut address = json.into_inner();
     match maybe_cookie {
         Some(cook) => {
             let user = get_user_from_api_token(cook.value().to_string(), &data).await;
             match user {
                 Ok(u) => {
-                    let saved_address = do_delete_prefered_address(u.id, id, data).await;
-                    match saved_address {
-                        Ok(addr) => Ok(HttpResponse::Ok().json(addr)),
+                    if let Err(e) = address.validate() {
+                        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                            "error": e
+                        })));
+                    }
+                    fill_address_text(
+                        &mut address.address_text,
+                        address.lat,
+                        address.lng,
+                        &geocoder,
+                        &data,
+                    )
+                    .await;
+                    match update_prefered_address(u.id, id, &address, &data).await {
+                        Ok(Some(addr)) => Ok(HttpResponse::Ok().json(addr)),
+                        Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                            "error": "Address not found"
+                        }))),
                         Err(_) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
-                            "error": "Failed to save address"
+                            "error": "Failed to update address"
+                        }))),
+                    }
+                }
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// PUT /api/prefered_addresses/order - manual ordering, `ids` lists every address once
+pub async fn reorder_addresses(
+    req: HttpRequest,
+    json: web::Json<PreferedAddressOrder>,
+    data: web::Data<AppData>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+            match user {
+                Ok(u) => match reorder_prefered_addresses(u.id, &json.ids, &data).await {
+                    Ok(Some(addr)) => Ok(HttpResponse::Ok().json(addr)),
+                    Ok(None) => Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                        "error": "ids must list each of your addresses exactly once"
+                    }))),
+                    Err(_) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": "Failed to reorder addresses"
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// DELETE /api/prefered_addresses/{id} - move an address to the trash
+pub async fn delete_prefered_address(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<DeletePath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+    let id = path.into_inner().id;
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+            match user {
+                Ok(u) => {
+                    let deleted_address = do_delete_prefered_address(u.id, id, data).await;
+                    match deleted_address {
+                        Ok(Some(addr)) => Ok(HttpResponse::Ok().json(addr)),
+                        Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                            "error": "Address not found"
+                        }))),
+                        Err(_) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": "Failed to delete address"
                         }))),
                     }
                 }
@@ -99,3 +233,171 @@ pub async fn delete_prefered_adress(
         }))),
     }
 }
+
+/// GET /api/prefered_addresses/trash - deleted addresses, most recently deleted first
+pub async fn fetch_trashed_adresses(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+            match user {
+                Ok(u) => match get_trashed_prefered_addresses(u.id, &data).await {
+                    Ok(addr) => Ok(HttpResponse::Ok().json(addr)),
+                    Err(_) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": "Failed to fetch addresses"
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// POST /api/prefered_addresses/{id}/restore - take an address out of the trash
+pub async fn restore_prefered_address(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<DeletePath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+    let id = path.into_inner().id;
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+            match user {
+                Ok(u) => match do_restore_prefered_address(u.id, id, &data).await {
+                    Ok(Some(addr)) => Ok(HttpResponse::Ok().json(addr)),
+                    Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": "Address not found in trash"
+                    }))),
+                    Err(_) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": "Failed to restore address"
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NearestAddressesQuery {
+    lat: f64,
+    lng: f64,
+    limit: Option<usize>,
+}
+
+/// GET /api/prefered_addresses/nearest?lat=&lng=&limit= - closest addresses as the crow flies
+pub async fn fetch_nearest_adresses(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    query: web::Query<NearestAddressesQuery>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    if let Err(e) = validate_coordinates(Some(query.lat), Some(query.lng)) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e
+        })));
+    }
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+            match user {
+                Ok(u) => match get_prefered_addresses(u.id, &data).await {
+                    Ok(addresses) => Ok(HttpResponse::Ok().json(nearest_prefered_addresses(
+                        addresses,
+                        query.lat,
+                        query.lng,
+                        query.limit.unwrap_or(5).clamp(1, 100),
+                    ))),
+                    Err(_) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": "Failed to fetch addresses"
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RankedAddressesQuery {
+    lat: f64,
+    lng: f64,
+    #[serde(default)]
+    profile: RouteProfile,
+}
+
+/// GET /api/prefered_addresses/by_travel_time?lat=&lng=&profile= - closest addresses first
+pub async fn fetch_adresses_by_travel_time(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    ors: web::Data<Arc<OrsClient>>,
+    query: web::Query<RankedAddressesQuery>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    if !(-90.0..=90.0).contains(&query.lat) || !(-180.0..=180.0).contains(&query.lng) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "lat must be within -90..90 and lng within -180..180"
+        })));
+    }
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+            match user {
+                Ok(u) => {
+                    let addresses = match get_prefered_addresses(u.id, &data).await {
+                        Ok(addresses) => addresses,
+                        Err(_) => {
+                            return Ok(HttpResponse::InternalServerError().json(
+                                serde_json::json!({
+                                    "error": "Failed to fetch addresses"
+                                }),
+                            ))
+                        }
+                    };
+
+                    match rank_prefered_addresses(
+                        &ors,
+                        query.profile,
+                        [query.lng, query.lat],
+                        addresses,
+                    )
+                    .await
+                    {
+                        Ok(ranked) => Ok(HttpResponse::Ok().json(ranked)),
+                        Err(e) => Ok(ors_error_response(e)),
+                    }
+                }
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
@@ -0,0 +1,187 @@
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use serde::Deserialize;
+
+use crate::{
+    models::{auth::AppData, AlertCondition, AlertEventsQuery, NewAlertRule},
+    utils::queries::{
+        delete_alert_rule, get_alert_events, get_alert_rules, get_user_from_api_token,
+        insert_alert_rule, update_alert_rule,
+    },
+};
+
+#[derive(Deserialize)]
+pub struct AlertRulePath {
+    id: i64,
+}
+
+/// GET /api/alert_rules - the user's alert rules
+pub async fn get_rules(req: HttpRequest, data: web::Data<AppData>) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match get_alert_rules(u.id, &data).await {
+                    Ok(rules) => Ok(HttpResponse::Ok().json(rules)),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to fetch alert rules: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// POST /api/alert_rules - watch a condition on a preferred address or a saved route
+pub async fn post_rule(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    json: web::Json<NewAlertRule>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => {
+                    if let Err(e) = json.validate() {
+                        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                            "error": e
+                        })));
+                    }
+
+                    match insert_alert_rule(u.id, &json, &data).await {
+                        Ok(Some(rule)) => Ok(HttpResponse::Ok().json(rule)),
+                        Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                            "error": "Address or route not found"
+                        }))),
+                        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": format!("Failed to save alert rule: {}", e)
+                        }))),
+                    }
+                }
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// PUT /api/alert_rules/{id} - replace the condition of a rule, its target stays the same
+pub async fn put_rule(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<AlertRulePath>,
+    json: web::Json<AlertCondition>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => {
+                    if let Err(e) = json.validate() {
+                        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                            "error": e
+                        })));
+                    }
+
+                    match update_alert_rule(u.id, path.id, &json, &data).await {
+                        Ok(Some(rule)) => Ok(HttpResponse::Ok().json(rule)),
+                        Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                            "error": "Alert rule not found"
+                        }))),
+                        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": format!("Failed to update alert rule: {}", e)
+                        }))),
+                    }
+                }
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// DELETE /api/alert_rules/{id} - the rule and its events
+pub async fn delete_rule(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<AlertRulePath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match delete_alert_rule(u.id, path.id, &data).await {
+                    Ok(Some(rule)) => Ok(HttpResponse::Ok().json(rule)),
+                    Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": "Alert rule not found"
+                    }))),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to delete alert rule: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// GET /api/alert_events - latest events of the user's rules, `?open=true` for ongoing ones
+pub async fn get_events(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    query: web::Query<AlertEventsQuery>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match get_alert_events(u.id, query.open, query.limit(), &data).await {
+                    Ok(events) => Ok(HttpResponse::Ok().json(events)),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to fetch alert events: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
@@ -0,0 +1,191 @@
+use actix_web::{get, web, HttpResponse, Result};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::models::{AnimationQuery, MAX_ANIMATION_PIXELS};
+use crate::services::{
+    AnimationCache, AnimationKey, GridCache, GridKey, PRECIPITATION_POINTS_KEY, WIND_POINTS_KEY,
+};
+use crate::utils::animation::{encode_animation, AnimationFormat, FRAME_DELAY_MS};
+use crate::utils::heatmap::{draw_caption, render_heatmap, HeatmapLayer, LEGEND_HEIGHT};
+use crate::utils::palette::Palettes;
+
+/// GET /api/animation/{layer}.{gif|apng|webp} - timelapse of wind speed, gusts or
+/// precipitation over `bbox`, a frame per stored forecast between `from` and `to`.
+/// The frames together are limited to MAX_ANIMATION_PIXELS.
+#[get("/animation/{layer}.{format}")]
+pub async fn get_animation(
+    path: web::Path<(String, String)>,
+    query: web::Query<AnimationQuery>,
+    grids: web::Data<Arc<GridCache>>,
+    palettes: web::Data<Arc<Palettes>>,
+    animations: web::Data<Arc<AnimationCache>>,
+) -> Result<HttpResponse> {
+    let (layer, format) = path.into_inner();
+    let Some(layer) = HeatmapLayer::parse(&layer) else {
+        let layers: Vec<&str> = HeatmapLayer::ALL.iter().map(HeatmapLayer::as_str).collect();
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("Unknown layer {}, expected one of {}", layer, layers.join(", "))
+        })));
+    };
+    let Some(format) = AnimationFormat::parse(&format) else {
+        let formats: Vec<&str> = AnimationFormat::ALL
+            .iter()
+            .map(AnimationFormat::as_str)
+            .collect();
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("Unknown format {}, expected one of {}", format, formats.join(", "))
+        })));
+    };
+    let area = query
+        .bbox()
+        .and_then(|bbox| Ok((bbox, query.size(&bbox)?)))
+        .and_then(|area| query.check_period().map(|_| area));
+    let (bbox, (width, height)) = match area {
+        Ok(area) => area,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e })));
+        }
+    };
+    let palette_name = query.palette.as_deref().unwrap_or(layer.as_str());
+    let Some(palette) = palettes.get(palette_name).cloned() else {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!(
+                "Unknown palette {}, expected one of {}",
+                palette_name,
+                palettes.names().join(", ")
+            )
+        })));
+    };
+
+    let base_key = match layer {
+        HeatmapLayer::WindSpeed | HeatmapLayer::Gusts => WIND_POINTS_KEY,
+        HeatmapLayer::Precipitation => PRECIPITATION_POINTS_KEY,
+    };
+    let timeline = match grids.timeline(base_key, query.from, query.to).await {
+        Ok(timeline) => timeline,
+        Err(e) => {
+            error!("Failed to list {} forecasts: {}", layer.as_str(), e);
+            return Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to load weather data"
+            })));
+        }
+    };
+
+    if timeline.is_empty() {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("No {} data in this period", layer.as_str())
+        })));
+    }
+
+    let legend = query.legend;
+    let frame_height = if legend {
+        height + LEGEND_HEIGHT
+    } else {
+        height
+    };
+    let pixels = timeline.len() as u64 * u64::from(width) * u64::from(frame_height);
+    if pixels > MAX_ANIMATION_PIXELS {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!(
+                "{} frames of {}x{} are too large, narrow from and to or reduce width",
+                timeline.len(),
+                width,
+                frame_height
+            )
+        })));
+    }
+
+    let timeline: Vec<_> = timeline
+        .into_iter()
+        .map(|(time, entry)| (time, GridKey::new(base_key, &entry, layer.fields())))
+        .collect();
+    let key = AnimationKey::new(
+        layer,
+        format,
+        palette_name,
+        &bbox,
+        width,
+        height,
+        legend,
+        timeline.iter().map(|(_, grid)| grid.clone()).collect(),
+    );
+    if let Some(animation) = animations.get(&key) {
+        return Ok(animation_response(format, animation));
+    }
+
+    // Rendered one at a time, so only one grid is held outside of the cache
+    let palette = Arc::new(palette);
+    let mut complete = true;
+    let mut frames = Vec::with_capacity(timeline.len());
+    for (time, grid_key) in timeline {
+        let grid = match grids.get(&grid_key).await {
+            Ok(Some(grid)) => grid,
+            // Replaced since the timeline was read
+            Ok(None) => {
+                complete = false;
+                continue;
+            }
+            Err(e) => {
+                error!("Failed to load {} data: {}", layer.as_str(), e);
+                return Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                    "error": "Failed to load weather data"
+                })));
+            }
+        };
+        let palette = palette.clone();
+        let frame = tokio::task::spawn_blocking(move || {
+            let mut img = render_heatmap(&grid.grid, layer, &palette, &bbox, width, height, legend);
+            draw_caption(&mut img, &time.format("%Y-%m-%d %H:%M UTC").to_string());
+            img
+        })
+        .await;
+        match frame {
+            Ok(frame) => frames.push(frame),
+            Err(e) => {
+                error!("Failed to render {} frame: {}", layer.as_str(), e);
+                return Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                    "error": "Failed to render animation"
+                })));
+            }
+        }
+    }
+    if frames.is_empty() {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("No {} data in this period", layer.as_str())
+        })));
+    }
+
+    let animation =
+        tokio::task::spawn_blocking(move || encode_animation(&frames, format, FRAME_DELAY_MS))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|animation| animation);
+
+    match animation {
+        Ok(buffer) => {
+            if complete {
+                animations.insert(key, buffer.clone());
+            }
+            Ok(animation_response(format, buffer))
+        }
+        Err(e) => {
+            error!(
+                "Failed to encode {} {} animation: {}",
+                layer.as_str(),
+                format.as_str(),
+                e
+            );
+            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to render animation"
+            })))
+        }
+    }
+}
+
+fn animation_response(format: AnimationFormat, buffer: Vec<u8>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(("Cache-Control", "public, max-age=300"))
+        .body(buffer)
+}
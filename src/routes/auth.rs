@@ -18,13 +18,19 @@ use mime_guess::from_path;
 use serde::Deserialize;
 use serde_json::Value;
 use sqlx::Error;
+use std::sync::Arc;
+use tracing::{error, warn};
 
-use crate::utils::mail::send_one_time_code_mail;
+use crate::services::RedisClient;
+use crate::utils::mail::{send_one_time_code_mail, MailError};
+use crate::utils::mail_templates::{
+    language_from_accept, mail_language, DEFAULT_MAIL_LANGUAGE, MAIL_LANGUAGES,
+};
 use crate::utils::misc::{generate_one_time_code, generate_random_string};
 use crate::models::auth::{ActualResponse, AppData, Response, User};
 use crate::utils::queries::{
     get_user_from_api_token, insert_one_time_code, insert_user, select_user_from_email,
-    select_user_from_unused_one_time_code, update_one_time_code_to_used,
+    select_user_from_unused_one_time_code, update_one_time_code_to_used, update_user_language,
 };
 use anyhow::{anyhow, Error as AnyError};
 use rust_embed::RustEmbed;
@@ -36,6 +42,57 @@ pub async fn health() -> impl Responder {
     "Alive"
 }
 
+/// GET /ready - 200 when the database, Redis and the mail transport answer, 503 otherwise
+/// with `ok` or `failed` for each of them
+pub async fn ready(data: web::Data<AppData>, redis: web::Data<Arc<RedisClient>>) -> impl Responder {
+    let database = sqlx::query("SELECT 1").execute(&data.db).await.map(|_| ());
+    let redis = redis.ping().await;
+    let mail = data.mailer.check().await;
+
+    let checks = [
+        ("database", database.map_err(|e| e.to_string())),
+        ("redis", redis.map_err(|e| e.to_string())),
+        ("mail", mail.map_err(|e| e.to_string())),
+    ];
+    let mut body = serde_json::Map::new();
+    let mut ready = true;
+    for (name, check) in checks {
+        // The details stay in the logs, the endpoint is public
+        let status = match check {
+            Ok(()) => "ok",
+            Err(e) => {
+                warn!("Readiness check {} failed: {}", name, e);
+                ready = false;
+                "failed"
+            }
+        };
+        body.insert(name.to_string(), Value::String(status.to_string()));
+    }
+
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+/// API error of a one time code email that could not be sent
+fn mail_error_response(e: MailError) -> Response {
+    error!("Failed to send the one time code: {}", e);
+    let (code, message) = match &e {
+        MailError::InvalidAddress { .. } => (StatusCode::BAD_REQUEST, "invalid email address"),
+        MailError::Smtp(smtp) if smtp.is_permanent() => {
+            (StatusCode::BAD_GATEWAY, "the mail server refused the email")
+        }
+        MailError::Smtp(_) | MailError::Unreachable => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "mail server unavailable, please try again later",
+        ),
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, "cannot send otc"),
+    };
+    Response::new(code, Some(message.to_string()))
+}
+
 pub async fn hello(data: web::Data<AppData>) -> ActixResult<impl Responder> {
     let users: Result<Vec<User>, Error> = sqlx::query_as::<_, User>("SELECT * FROM users")
         .fetch_all(&data.db)
@@ -59,14 +116,31 @@ pub struct LoginPayload {
 pub struct RegisterForm {
     name: String,
     email: String,
+    /// Language of the emails, from the browser when not given
+    language: Option<String>,
+}
+
+/// Language of a new user: the one asked for, else the browser's, else the default one
+fn signup_language(req: &HttpRequest, requested: Option<&str>) -> &'static str {
+    match requested {
+        Some(language) => mail_language(language),
+        None => req
+            .headers()
+            .get("Accept-Language")
+            .and_then(|value| value.to_str().ok())
+            .and_then(language_from_accept)
+            .unwrap_or(DEFAULT_MAIL_LANGUAGE),
+    }
 }
 
 pub async fn register(
+    req: HttpRequest,
     data: web::Data<AppData>,
     form: web::Json<RegisterForm>,
 ) -> ActixResult<impl Responder> {
     let name = &form.name;
     let email = &form.email;
+    let language = signup_language(&req, form.language.as_deref());
 
     let maybe_user = select_user_from_email(email, &data).await;
 
@@ -79,7 +153,7 @@ pub async fn register(
 
     let api_token = generate_random_string(255);
 
-    let maybe_user = insert_user(name, email, &api_token, &data).await;
+    let maybe_user = insert_user(name, email, &api_token, language, &data).await;
 
     if maybe_user.is_err() {
         return Err(Response::new(
@@ -97,16 +171,17 @@ pub async fn register(
     match maybe_one_time_code {
         Ok(one_time_code) => {
             // send email
-            match send_one_time_code_mail(&one_time_code.code, &user.email, data.env.clone()).await
+            match send_one_time_code_mail(
+                &one_time_code.code,
+                &user.email,
+                &user.language,
+                data.env.otc_exp_minutes,
+                &data.mailer,
+            )
+            .await
             {
                 Ok(_) => Ok(HttpResponse::Ok().body("Register successful, check your emails")),
-                Err(e) => {
-                    println!("{:#?}", e);
-                    return Err(Response::new(
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Some("cannot send otc".to_string()),
-                    ))?;
-                }
+                Err(e) => Err(mail_error_response(e))?,
             }
         }
         Err(e) => Err(Response::new(
@@ -146,17 +221,19 @@ pub async fn send_one_time_code(
         match maybe_one_time_code {
             Ok(_) => {
                 //send otc by email
-                match send_one_time_code_mail(&code, &user.email, data.env.clone()).await {
+                match send_one_time_code_mail(
+                    &code,
+                    &user.email,
+                    &user.language,
+                    data.env.otc_exp_minutes,
+                    &data.mailer,
+                )
+                .await
+                {
                     Ok(_) => Ok(ActualResponse {
                         message: Some("Code send by email".to_string()),
                     }),
-                    Err(e) => {
-                        println!("{:#?}", e);
-                        return Err(Response::new(
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            Some("cannot send otc".to_string()),
-                        ))?;
-                    }
+                    Err(e) => Err(mail_error_response(e))?,
                 }
             }
             Err(e) => {
@@ -233,6 +310,7 @@ pub struct GsiQuery {
 }
 
 pub async fn gsi(
+    req: HttpRequest,
     form: web::Form<GsiQuery>,
     data: web::Data<AppData>,
 ) -> ActixResult<impl Responder> {
@@ -258,7 +336,14 @@ pub async fn gsi(
     let api_token = generate_random_string(255);
 
     let maybe_user = if let Err(_e) = maybe_user {
-        insert_user(&name, &email, &api_token, &data).await
+        insert_user(
+            &name,
+            &email,
+            &api_token,
+            signup_language(&req, None),
+            &data,
+        )
+        .await
     } else {
         maybe_user
     };
@@ -365,6 +450,54 @@ pub async fn me(req: HttpRequest, data: web::Data<AppData>) -> ActixResult<impl
     }
 }
 
+#[derive(Deserialize)]
+pub struct LanguageForm {
+    language: String,
+}
+
+/// PUT /api/me/language - language of the emails sent to the user
+pub async fn update_language(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    form: web::Json<LanguageForm>,
+) -> ActixResult<impl Responder> {
+    if !MAIL_LANGUAGES.contains(&form.language.as_str()) {
+        return Err(Response::new(
+            StatusCode::BAD_REQUEST,
+            Some(format!(
+                "language must be one of {}",
+                MAIL_LANGUAGES.join(", ")
+            )),
+        ))?;
+    }
+
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match update_user_language(u.id, &form.language, &data).await {
+                    Ok(user) => Ok(user),
+                    Err(e) => Err(Response::new(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Some(e.to_string()),
+                    ))?,
+                },
+                Err(_e) => Err(Response::new(
+                    StatusCode::NOT_FOUND,
+                    Some("User not found".to_string()),
+                ))?,
+            }
+        }
+        None => Err(Response::new(
+            StatusCode::UNAUTHORIZED,
+            Some("Not Authenticated".to_string()),
+        ))?,
+    }
+}
+
 pub async fn logout(data: web::Data<AppData>) -> HttpResponse {
     let env = data.env.clone();
     match env.is_prod {
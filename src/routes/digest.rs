@@ -0,0 +1,172 @@
+use actix_web::{
+    http::header::ContentType, web, HttpRequest, HttpResponse, HttpResponseBuilder, Result,
+};
+use chrono::Utc;
+use minijinja::{context, Value};
+use tracing::error;
+
+use crate::{
+    models::{auth::AppData, DigestSettings, UnsubscribeQuery},
+    utils::{
+        config::Config,
+        mail_templates::{language_from_accept, render_page, DEFAULT_MAIL_LANGUAGE},
+        queries::{
+            get_digest_subscription, get_user_from_api_token, save_digest_subscription,
+            unsubscribe_digest,
+        },
+        unsubscribe::verify_unsubscribe_token,
+    },
+};
+
+/// GET /api/digest - the user's daily digest settings, null when never set
+pub async fn get_digest(req: HttpRequest, data: web::Data<AppData>) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match get_digest_subscription(u.id, &data).await {
+                    Ok(subscription) => Ok(HttpResponse::Ok().json(subscription)),
+                    Err(e) => {
+                        error!("Failed to fetch digest settings: {}", e);
+                        Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": "Failed to fetch digest settings"
+                        })))
+                    }
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// PUT /api/digest - subscribe, change the send time or unsubscribe
+pub async fn put_digest(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    json: web::Json<DigestSettings>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => {
+                    if let Err(e) = json.validate() {
+                        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                            "error": e
+                        })));
+                    }
+
+                    match save_digest_subscription(u.id, &json, &data).await {
+                        Ok(subscription) => Ok(HttpResponse::Ok().json(subscription)),
+                        Err(e) => {
+                            error!("Failed to save digest settings: {}", e);
+                            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                                "error": "Failed to save digest settings"
+                            })))
+                        }
+                    }
+                }
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// GET /api/digest/unsubscribe?token= - confirmation page of the email's unsubscribe link.
+/// Link scanners follow links, so only the form's POST unsubscribes.
+pub async fn unsubscribe_form(
+    req: HttpRequest,
+    config: web::Data<Config>,
+    query: web::Query<UnsubscribeQuery>,
+) -> Result<HttpResponse> {
+    let user_id = verify_unsubscribe_token(&config.unsubscribe_secret, &query.token, Utc::now());
+    if user_id.is_none() {
+        return Ok(unsubscribe_page(
+            &req,
+            HttpResponse::BadRequest(),
+            context! { status => "invalid" },
+        ));
+    }
+
+    Ok(unsubscribe_page(
+        &req,
+        HttpResponse::Ok(),
+        context! { status => "confirm", token => &query.token },
+    ))
+}
+
+/// POST /api/digest/unsubscribe?token= - one-click unsubscribe, no login. Sent by the
+/// confirmation page and by mail clients for the `List-Unsubscribe-Post` header.
+pub async fn unsubscribe(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    config: web::Data<Config>,
+    query: web::Query<UnsubscribeQuery>,
+) -> Result<HttpResponse> {
+    let user_id = verify_unsubscribe_token(&config.unsubscribe_secret, &query.token, Utc::now());
+    let Some(user_id) = user_id else {
+        return Ok(unsubscribe_page(
+            &req,
+            HttpResponse::BadRequest(),
+            context! { status => "invalid" },
+        ));
+    };
+
+    match unsubscribe_digest(user_id, &data).await {
+        Ok(_) => Ok(unsubscribe_page(
+            &req,
+            HttpResponse::Ok(),
+            context! { status => "done" },
+        )),
+        Err(e) => {
+            error!(
+                "Failed to unsubscribe user {} from the digest: {}",
+                user_id, e
+            );
+            Ok(unsubscribe_page(
+                &req,
+                HttpResponse::InternalServerError(),
+                context! { status => "failed" },
+            ))
+        }
+    }
+}
+
+/// Unsubscribe page in the browser's language, `status` is `confirm`, `invalid`, `done`
+/// or `failed`
+fn unsubscribe_page(
+    req: &HttpRequest,
+    mut response: HttpResponseBuilder,
+    context: Value,
+) -> HttpResponse {
+    let language = req
+        .headers()
+        .get("Accept-Language")
+        .and_then(|v| v.to_str().ok())
+        .and_then(language_from_accept)
+        .unwrap_or(DEFAULT_MAIL_LANGUAGE);
+
+    match render_page("unsubscribe", language, context) {
+        Ok(html) => response.content_type(ContentType::html()).body(html),
+        Err(e) => {
+            error!("Failed to render the unsubscribe page: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
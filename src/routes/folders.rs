@@ -0,0 +1,205 @@
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use serde::Deserialize;
+
+use crate::{
+    models::{auth::AppData, NewRouteFolder, RouteFolderAssignment},
+    routes::routes::RoutingPath,
+    utils::queries::{
+        delete_route_folder, get_route_folders, get_user_from_api_token, insert_route_folder,
+        rename_route_folder, set_route_folder,
+    },
+};
+
+const MAX_FOLDER_NAME_LENGTH: usize = 255;
+
+#[derive(Deserialize)]
+pub struct FolderPath {
+    id: i64,
+}
+
+/// Trimmed folder name, or the 400 response explaining why it is refused
+fn validated_folder_name(folder: &NewRouteFolder) -> Result<String, HttpResponse> {
+    let name = folder.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_FOLDER_NAME_LENGTH {
+        return Err(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Folder name must be 1 to {} characters", MAX_FOLDER_NAME_LENGTH)
+        })));
+    }
+    Ok(name.to_string())
+}
+
+fn folder_error_response(e: sqlx::Error) -> HttpResponse {
+    match e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => {
+            HttpResponse::Conflict().json(serde_json::json!({
+                "error": "A folder with this name already exists"
+            }))
+        }
+        e => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Failed to save folder: {}", e)
+        })),
+    }
+}
+
+/// GET /api/route_folders
+pub async fn get_folders(req: HttpRequest, data: web::Data<AppData>) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match get_route_folders(u.id, &data).await {
+                    Ok(folders) => Ok(HttpResponse::Ok().json(folders)),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to fetch folders: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// POST /api/route_folders
+pub async fn post_folder(
+    req: HttpRequest,
+    json: web::Json<NewRouteFolder>,
+    data: web::Data<AppData>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    let name = match validated_folder_name(&json) {
+        Ok(name) => name,
+        Err(response) => return Ok(response),
+    };
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match insert_route_folder(u.id, &name, &data).await {
+                    Ok(folder) => Ok(HttpResponse::Created().json(folder)),
+                    Err(e) => Ok(folder_error_response(e)),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// PUT /api/route_folders/{id} - rename a folder
+pub async fn put_folder(
+    req: HttpRequest,
+    json: web::Json<NewRouteFolder>,
+    data: web::Data<AppData>,
+    path: web::Path<FolderPath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    let name = match validated_folder_name(&json) {
+        Ok(name) => name,
+        Err(response) => return Ok(response),
+    };
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match rename_route_folder(u.id, path.id, &name, &data).await {
+                    Ok(Some(folder)) => Ok(HttpResponse::Ok().json(folder)),
+                    Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": "Folder not found"
+                    }))),
+                    Err(e) => Ok(folder_error_response(e)),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// DELETE /api/route_folders/{id} - routes inside are kept, without folder
+pub async fn delete_folder(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<FolderPath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match delete_route_folder(u.id, path.id, &data).await {
+                    Ok(Some(folder)) => Ok(HttpResponse::Ok().json(folder)),
+                    Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": "Folder not found"
+                    }))),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to delete folder: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// PUT /api/route/{uuid}/folder - move a route to a folder, or out of it with `null`
+pub async fn put_route_folder(
+    req: HttpRequest,
+    json: web::Json<RouteFolderAssignment>,
+    data: web::Data<AppData>,
+    path: web::Path<RoutingPath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match set_route_folder(&path.uuid, u.id, json.folder_id, &data).await {
+                    Ok(Some(route)) => Ok(HttpResponse::Ok().json(route)),
+                    Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": "Route or folder not found"
+                    }))),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to move route: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
@@ -0,0 +1,98 @@
+use actix_web::{get, web, HttpResponse, Result};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::models::{
+    auth::AppData, prefered_address::validate_coordinates, validate_lang, GeocodeQuery,
+    ReverseGeocodeQuery,
+};
+use crate::services::{Geocoder, GeocodingError};
+
+/// Longest free-text query forwarded to the provider
+const MAX_QUERY_LENGTH: usize = 200;
+
+pub fn geocoding_error_response(e: GeocodingError) -> HttpResponse {
+    match e {
+        GeocodingError::Upstream { status, body } => {
+            error!("Geocoding provider error {} ({} bytes)", status, body.len());
+            if status == 429 {
+                HttpResponse::TooManyRequests().json(serde_json::json!({
+                    "error": "Geocoding quota exhausted, please try again later"
+                }))
+            } else {
+                HttpResponse::BadGateway().json(serde_json::json!({
+                    "error": "Geocoding service failed"
+                }))
+            }
+        }
+        GeocodingError::InvalidResponse(e) => {
+            error!("Unexpected geocoding response: {}", e);
+            HttpResponse::BadGateway().json(serde_json::json!({
+                "error": "Unexpected geocoding service response"
+            }))
+        }
+        GeocodingError::Request(e) => {
+            error!("Failed to reach the geocoding provider: {}", e);
+            HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "error": "Geocoding service unavailable"
+            }))
+        }
+    }
+}
+
+/// GET /api/geocode - places matching `q`, through the configured provider and cached
+#[get("/geocode")]
+pub async fn get_geocode(
+    query: web::Query<GeocodeQuery>,
+    geocoder: web::Data<Arc<Geocoder>>,
+    data: web::Data<AppData>,
+) -> Result<HttpResponse> {
+    let q = query.q.trim();
+    if q.is_empty() || q.chars().count() > MAX_QUERY_LENGTH {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("q must be 1 to {} characters", MAX_QUERY_LENGTH)
+        })));
+    }
+    if let Err(e) = validate_lang(query.lang.as_deref()) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e })));
+    }
+
+    match geocoder
+        .search(q, query.limit(), query.lang.as_deref(), &data)
+        .await
+    {
+        Ok(lookup) => Ok(HttpResponse::Ok()
+            .insert_header(("X-Cache", if lookup.cached { "HIT" } else { "MISS" }))
+            .json(lookup.results)),
+        Err(e) => Ok(geocoding_error_response(e)),
+    }
+}
+
+/// GET /api/reverse-geocode - closest place to `lat`/`lng`, 404 when there is none
+#[get("/reverse-geocode")]
+pub async fn get_reverse_geocode(
+    query: web::Query<ReverseGeocodeQuery>,
+    geocoder: web::Data<Arc<Geocoder>>,
+    data: web::Data<AppData>,
+) -> Result<HttpResponse> {
+    if let Err(e) = validate_coordinates(Some(query.lat), Some(query.lng))
+        .and_then(|_| validate_lang(query.lang.as_deref()))
+    {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e
+        })));
+    }
+
+    match geocoder
+        .reverse(query.lat, query.lng, query.lang.as_deref(), &data)
+        .await
+    {
+        Ok((Some(place), cached)) => Ok(HttpResponse::Ok()
+            .insert_header(("X-Cache", if cached { "HIT" } else { "MISS" }))
+            .json(place)),
+        Ok((None, _)) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "error": "No address found at this position"
+        }))),
+        Err(e) => Ok(geocoding_error_response(e)),
+    }
+}
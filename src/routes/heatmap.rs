@@ -0,0 +1,96 @@
+use actix_web::{get, web, HttpResponse, Result};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::models::HeatmapQuery;
+use crate::services::{GridCache, GridKey, PRECIPITATION_POINTS_KEY, WIND_POINTS_KEY};
+use crate::utils::heatmap::{encode_png, render_heatmap, HeatmapLayer};
+use crate::utils::palette::Palettes;
+
+/// GET /api/heatmap/{layer}.png - colored map of wind speed, gusts or precipitation
+/// over `bbox`, ready to display where WebGL is not available (emails, share previews)
+#[get("/heatmap/{layer}.png")]
+pub async fn get_heatmap(
+    layer: web::Path<String>,
+    query: web::Query<HeatmapQuery>,
+    grids: web::Data<Arc<GridCache>>,
+    palettes: web::Data<Arc<Palettes>>,
+) -> Result<HttpResponse> {
+    let layer = layer.into_inner();
+    let Some(layer) = HeatmapLayer::parse(&layer) else {
+        let layers: Vec<&str> = HeatmapLayer::ALL.iter().map(HeatmapLayer::as_str).collect();
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("Unknown layer {}, expected one of {}", layer, layers.join(", "))
+        })));
+    };
+    let (bbox, (width, height)) = match query.bbox().and_then(|bbox| Ok((bbox, query.size(&bbox)?)))
+    {
+        Ok(area) => area,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e })));
+        }
+    };
+    let palette_name = query.palette.as_deref().unwrap_or(layer.as_str());
+    let Some(palette) = palettes.get(palette_name).cloned() else {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!(
+                "Unknown palette {}, expected one of {}",
+                palette_name,
+                palettes.names().join(", ")
+            )
+        })));
+    };
+
+    let base_key = match layer {
+        HeatmapLayer::WindSpeed | HeatmapLayer::Gusts => WIND_POINTS_KEY,
+        HeatmapLayer::Precipitation => PRECIPITATION_POINTS_KEY,
+    };
+    let grid = match grids.entry(base_key, query.index).await {
+        Ok(Some(entry)) => {
+            grids
+                .get(&GridKey::new(base_key, &entry, layer.fields()))
+                .await
+        }
+        Ok(None) => Ok(None),
+        Err(e) => Err(e),
+    };
+    let grid = match grid {
+        Ok(Some(grid)) => grid,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(serde_json::json!({
+                "error": match query.index {
+                    Some(index) => format!("No {} data at index {}", layer.as_str(), index),
+                    None => format!("No {} data yet", layer.as_str()),
+                }
+            })));
+        }
+        Err(e) => {
+            error!("Failed to load {} data: {}", layer.as_str(), e);
+            return Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to load weather data"
+            })));
+        }
+    };
+
+    let legend = query.legend;
+    let png = tokio::task::spawn_blocking(move || {
+        let img = render_heatmap(&grid.grid, layer, &palette, &bbox, width, height, legend);
+        encode_png(&img)
+    })
+    .await
+    .map_err(anyhow::Error::from)
+    .and_then(|png| png);
+
+    match png {
+        Ok(png_buffer) => Ok(HttpResponse::Ok()
+            .content_type("image/png")
+            .insert_header(("Cache-Control", "public, max-age=300"))
+            .body(png_buffer)),
+        Err(e) => {
+            error!("Failed to render {} heatmap: {}", layer.as_str(), e);
+            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to render heatmap"
+            })))
+        }
+    }
+}
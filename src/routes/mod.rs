@@ -1,15 +1,33 @@
 pub mod addresses;
 pub mod ai;
+pub mod alerts;
+pub mod animation;
 pub mod auth;
+pub mod digest;
+pub mod folders;
+pub mod geocoding;
+pub mod heatmap;
+pub mod notifications;
+pub mod precipgl;
+pub mod route_files;
+pub mod route_revisions;
 pub mod routes;
 pub mod routing;
+pub mod sailing;
 pub mod scheduler;
+pub mod shares;
+pub mod tags;
+pub mod tiles;
 pub mod weather;
 pub mod wind;
+pub mod wind_analysis;
 pub mod windgl;
 
 // Re-export auth functions for convenience
-pub use auth::{gsi, health, index, login, logout, me, register, send_one_time_code, serve};
+pub use auth::{
+    gsi, health, index, login, logout, me, ready, register, send_one_time_code, serve,
+    update_language,
+};
 
 // Re-export addresses functions for convenience
 pub use addresses::*;
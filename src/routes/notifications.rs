@@ -0,0 +1,226 @@
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use serde::Deserialize;
+
+use crate::{
+    models::{auth::AppData, CreatedWebhook, DeliveriesQuery, NewWebhook, NotificationsQuery},
+    utils::{
+        misc::generate_random_string,
+        queries::{
+            delete_webhook, get_notification_deliveries, get_notifications,
+            get_user_from_api_token, get_webhooks, insert_webhook, retry_notification,
+        },
+        webhook::check_webhook_url,
+    },
+};
+
+const WEBHOOK_SECRET_LENGTH: usize = 40;
+
+#[derive(Deserialize)]
+pub struct IdPath {
+    id: i64,
+}
+
+/// GET /api/notifications - the user's outbox, `?status=dead` for dead letters
+pub async fn get_outbox(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    query: web::Query<NotificationsQuery>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match get_notifications(u.id, query.status, query.limit, &data).await {
+                    Ok(notifications) => Ok(HttpResponse::Ok().json(notifications)),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to fetch notifications: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// GET /api/notifications/deliveries - delivery attempts, optionally of one notification
+pub async fn get_deliveries(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    query: web::Query<DeliveriesQuery>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match get_notification_deliveries(
+                    u.id,
+                    query.notification_id,
+                    query.limit,
+                    &data,
+                )
+                .await
+                {
+                    Ok(deliveries) => Ok(HttpResponse::Ok().json(deliveries)),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to fetch deliveries: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// POST /api/notifications/{id}/retry - send a dead-lettered notification again
+pub async fn post_retry(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<IdPath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match retry_notification(u.id, path.id, &data).await {
+                    Ok(Some(notification)) => Ok(HttpResponse::Ok().json(notification)),
+                    Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": "Dead notification not found"
+                    }))),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to retry notification: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// GET /api/webhooks - the user's webhooks, without their secrets
+pub async fn get_hooks(req: HttpRequest, data: web::Data<AppData>) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match get_webhooks(u.id, &data).await {
+                    Ok(webhooks) => Ok(HttpResponse::Ok().json(webhooks)),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to fetch webhooks: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// POST /api/webhooks - register a URL, the answer holds the signing secret
+pub async fn post_hook(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    json: web::Json<NewWebhook>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => {
+                    if let Err(e) = json.validate(data.env.is_prod) {
+                        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                            "error": e
+                        })));
+                    }
+                    if let Err(e) = check_webhook_url(&json.url).await {
+                        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                            "error": e
+                        })));
+                    }
+
+                    let secret = generate_random_string(WEBHOOK_SECRET_LENGTH);
+                    match insert_webhook(u.id, &json.url, &secret, &data).await {
+                        Ok(webhook) => {
+                            Ok(HttpResponse::Ok().json(CreatedWebhook { webhook, secret }))
+                        }
+                        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": format!("Failed to save webhook: {}", e)
+                        }))),
+                    }
+                }
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// DELETE /api/webhooks/{id} - the webhook and its pending deliveries
+pub async fn delete_hook(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<IdPath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match delete_webhook(u.id, path.id, &data).await {
+                    Ok(Some(webhook)) => Ok(HttpResponse::Ok().json(webhook)),
+                    Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": "Webhook not found"
+                    }))),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to delete webhook: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
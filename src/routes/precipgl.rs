@@ -0,0 +1,130 @@
+use actix_web::{get, web, HttpRequest, HttpResponse, Result};
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::services::{RedisClient, PRECIPITATION_METADATA_KEY, PRECIPITATION_PNG_KEY};
+
+/// GET /api/precipgl/metadata.json - Get latest precipgl metadata
+#[get("/precipgl/metadata.json")]
+pub async fn get_precipgl_metadata(redis: web::Data<Arc<RedisClient>>) -> Result<HttpResponse> {
+    info!("Request for precipgl metadata");
+
+    match redis.get_wind_data(PRECIPITATION_METADATA_KEY).await {
+        Ok(Some(data)) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .insert_header(("Cache-Control", "public, max-age=300"))
+            .json(data)),
+        Ok(None) => {
+            error!("Precipitation metadata not found in Redis");
+            Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "error": "Precipitation metadata not yet available"
+            })))
+        }
+        Err(e) => {
+            error!("Failed to fetch precipitation metadata: {}", e);
+            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to fetch precipitation metadata"
+            })))
+        }
+    }
+}
+
+/// GET /api/precipgl/metadata.json/{index} - Get precipgl metadata by index
+#[get("/precipgl/metadata.json/{index}")]
+pub async fn get_precipgl_metadata_by_index(
+    index: web::Path<u32>,
+    redis: web::Data<Arc<RedisClient>>,
+) -> Result<HttpResponse> {
+    let index = index.into_inner();
+    info!("Request for precipgl metadata at index {}", index);
+
+    let indexed_key = format!("{}:{}", PRECIPITATION_METADATA_KEY, index);
+
+    match redis.get_wind_data(&indexed_key).await {
+        Ok(Some(data)) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .insert_header(("Cache-Control", "public, max-age=300"))
+            .json(data)),
+        Ok(None) => {
+            error!("Precipitation metadata not found at index {}", index);
+            Ok(HttpResponse::NotFound().json(serde_json::json!({
+                "error": format!("Precipitation metadata not found at index {}", index)
+            })))
+        }
+        Err(e) => {
+            error!(
+                "Failed to fetch precipitation metadata at index {}: {}",
+                index, e
+            );
+            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to fetch precipitation metadata"
+            })))
+        }
+    }
+}
+
+/// GET /api/precipgl/precipitation.png - Get latest precipgl PNG
+#[get("/precipgl/precipitation.png")]
+pub async fn get_precipgl_png(
+    redis: web::Data<Arc<RedisClient>>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    info!(
+        "Request for precipgl PNG from {}",
+        req.connection_info().peer_addr().unwrap_or("unknown")
+    );
+
+    match redis.get_binary_data(PRECIPITATION_PNG_KEY).await {
+        Ok(Some(png_buffer)) => Ok(HttpResponse::Ok()
+            .content_type("image/png")
+            .insert_header(("Cache-Control", "public, max-age=300"))
+            .body(png_buffer)),
+        Ok(None) => {
+            error!("Precipitation PNG not found in Redis");
+            Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "error": "Precipitation PNG not yet available"
+            })))
+        }
+        Err(e) => {
+            error!("Failed to fetch precipitation PNG: {}", e);
+            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to fetch precipitation PNG"
+            })))
+        }
+    }
+}
+
+/// GET /api/precipgl/precipitation.png/{index} - Get precipgl PNG by index
+#[get("/precipgl/precipitation.png/{index}")]
+pub async fn get_precipgl_png_by_index(
+    index: web::Path<u32>,
+    redis: web::Data<Arc<RedisClient>>,
+) -> Result<HttpResponse> {
+    let index = index.into_inner();
+    info!("Request for precipgl PNG at index {}", index);
+
+    match redis
+        .get_binary_data_by_index(PRECIPITATION_PNG_KEY, index)
+        .await
+    {
+        Ok(Some(png_buffer)) => Ok(HttpResponse::Ok()
+            .content_type("image/png")
+            .insert_header(("Cache-Control", "public, max-age=300"))
+            .body(png_buffer)),
+        Ok(None) => {
+            error!("Precipitation PNG not found at index {}", index);
+            Ok(HttpResponse::NotFound().json(serde_json::json!({
+                "error": format!("Precipitation PNG not found at index {}", index)
+            })))
+        }
+        Err(e) => {
+            error!(
+                "Failed to fetch precipitation PNG at index {}: {}",
+                index, e
+            );
+            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to fetch precipitation PNG"
+            })))
+        }
+    }
+}
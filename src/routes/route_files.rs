@@ -0,0 +1,263 @@
+use actix_multipart::Multipart;
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use serde::Deserialize;
+use tracing::{error, info};
+
+use crate::{
+    models::{auth::AppData, RouteProfile},
+    routes::routes::{route_etag, RoutingPath},
+    utils::{
+        queries::{get_saved_route, get_user_from_api_token, insert_saved_route},
+        route_formats::{
+            export_geojson, export_gpx, export_kml, parse_route_file, RouteFileFormat,
+        },
+    },
+};
+
+const MAX_IMPORT_BYTES: usize = 10 * 1024 * 1024; // 10 MB
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    format: String,
+    /// Planned departure used for track point timestamps, defaults to the last update
+    departure: Option<DateTime<Utc>>,
+}
+
+/// Keep exported file names portable
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if stem.is_empty() {
+        "route".to_string()
+    } else {
+        stem
+    }
+}
+
+/// GET /api/route/{uuid}/export?format=gpx|kml|geojson
+pub async fn export_route(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<RoutingPath>,
+    query: web::Query<ExportQuery>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    let Some(format) = RouteFileFormat::parse(&query.format) else {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "format must be one of gpx, kml, geojson"
+        })));
+    };
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match get_saved_route(&path.uuid, u.id, &data).await {
+                    Ok(route) => {
+                        let departure = query.departure.unwrap_or(route.updated_at);
+                        let body = match format {
+                            RouteFileFormat::Gpx => export_gpx(&route, departure),
+                            RouteFileFormat::Kml => export_kml(&route, departure),
+                            RouteFileFormat::GeoJson => {
+                                export_geojson(&route, departure).to_string()
+                            }
+                        };
+
+                        Ok(HttpResponse::Ok()
+                            .content_type(format.content_type())
+                            .insert_header((
+                                "Content-Disposition",
+                                format!(
+                                    "attachment; filename=\"{}.{}\"",
+                                    file_stem(&route.name),
+                                    format.extension()
+                                ),
+                            ))
+                            .body(body))
+                    }
+                    Err(e) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": format!("Route not found: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+#[derive(Default)]
+struct ImportForm {
+    file: Option<(Option<String>, Vec<u8>)>,
+    name: Option<String>,
+    profile: Option<String>,
+}
+
+/// Read the multipart fields: `file` (required), `name` and `profile` (optional)
+async fn read_import_form(mut payload: Multipart) -> Result<ImportForm, HttpResponse> {
+    let mut form = ImportForm::default();
+
+    while let Some(mut field) = payload.try_next().await.map_err(|e| {
+        HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Invalid multipart body: {}", e)
+        }))
+    })? {
+        let disposition = field.content_disposition();
+        let field_name = disposition
+            .and_then(|d| d.get_name())
+            .unwrap_or_default()
+            .to_string();
+        let filename = disposition.and_then(|d| d.get_filename()).map(String::from);
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.try_next().await.map_err(|e| {
+            HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("Invalid multipart body: {}", e)
+            }))
+        })? {
+            if bytes.len() + chunk.len() > MAX_IMPORT_BYTES {
+                return Err(HttpResponse::PayloadTooLarge().json(serde_json::json!({
+                    "error": format!("Files are limited to {} bytes", MAX_IMPORT_BYTES)
+                })));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        match field_name.as_str() {
+            "file" => form.file = Some((filename, bytes)),
+            "name" => form.name = Some(String::from_utf8_lossy(&bytes).trim().to_string()),
+            "profile" => form.profile = Some(String::from_utf8_lossy(&bytes).trim().to_string()),
+            _ => {}
+        }
+    }
+
+    Ok(form)
+}
+
+/// POST /api/route/import - multipart upload of a GPX, KML or GeoJSON file
+pub async fn import_route(
+    req: HttpRequest,
+    payload: Multipart,
+    data: web::Data<AppData>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => {
+                    let form = match read_import_form(payload).await {
+                        Ok(form) => form,
+                        Err(response) => return Ok(response),
+                    };
+
+                    let Some((filename, bytes)) = form.file else {
+                        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                            "error": "Missing file field"
+                        })));
+                    };
+                    let content = String::from_utf8_lossy(&bytes);
+
+                    let Some(format) = RouteFileFormat::detect(filename.as_deref(), &content)
+                    else {
+                        return Ok(
+                            HttpResponse::UnsupportedMediaType().json(serde_json::json!({
+                                "error": "Unrecognized file, expected GPX, KML or GeoJSON"
+                            })),
+                        );
+                    };
+
+                    let profile = match form.profile.filter(|p| !p.is_empty()) {
+                        Some(p) => match serde_json::from_value::<RouteProfile>(p.clone().into()) {
+                            Ok(profile) => profile,
+                            Err(_) => {
+                                return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                                    "error": format!("Unknown profile {}", p)
+                                })))
+                            }
+                        },
+                        None => RouteProfile::default(),
+                    };
+
+                    let imported = match parse_route_file(format, &content) {
+                        Ok(imported) => imported,
+                        Err(e) => {
+                            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                                "error": format!("Failed to parse {}: {:#}", format.extension(), e)
+                            })))
+                        }
+                    };
+
+                    let route_name = form
+                        .name
+                        .filter(|n| !n.is_empty())
+                        .or(imported.name.clone())
+                        .unwrap_or_else(|| "Imported route".to_string());
+                    let route_name: String = route_name.chars().take(255).collect();
+
+                    let document = match imported.into_document(profile) {
+                        Ok(document) => document,
+                        Err(e) => {
+                            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                                "error": e.to_string()
+                            })))
+                        }
+                    };
+                    if let Err(e) = document.validate() {
+                        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                            "error": e.to_string()
+                        })));
+                    }
+
+                    info!(
+                        "Importing {} route with {} points",
+                        format.extension(),
+                        document.coordinates().len()
+                    );
+
+                    let route = match serde_json::to_value(&document) {
+                        Ok(route) => route,
+                        Err(e) => {
+                            error!("Failed to serialize imported route: {}", e);
+                            return Ok(HttpResponse::InternalServerError()
+                                .json(serde_json::json!({ "error": "Failed to save route" })));
+                        }
+                    };
+
+                    match insert_saved_route(u.id, &route_name, &route, &data).await {
+                        Ok(route) => Ok(HttpResponse::Ok()
+                            .insert_header(route_etag(&route))
+                            .json(route)),
+                        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": format!("Failed to save route: {}", e)
+                        }))),
+                    }
+                }
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
@@ -0,0 +1,211 @@
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use serde::Deserialize;
+use tracing::error;
+
+use crate::{
+    models::{auth::AppData, RevisionComparison, RouteRevisionSummary},
+    routes::routes::{route_etag, RoutingPath},
+    utils::queries::{
+        get_route_revision, get_saved_route, get_user_from_api_token, list_route_revisions,
+        update_saved_route,
+    },
+};
+
+#[derive(Deserialize)]
+pub struct RevisionPath {
+    pub uuid: String,
+    pub revision: i32,
+}
+
+#[derive(Deserialize)]
+pub struct CompareQuery {
+    from: i32,
+    to: i32,
+}
+
+/// GET /api/route/{uuid}/revisions - history of a route, newest first
+pub async fn get_route_revisions(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<RoutingPath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match get_saved_route(&path.uuid, u.id, &data).await {
+                    Ok(route) => match list_route_revisions(route.id, &data).await {
+                        Ok(revisions) => Ok(HttpResponse::Ok().json(
+                            revisions
+                                .iter()
+                                .map(RouteRevisionSummary::from)
+                                .collect::<Vec<_>>(),
+                        )),
+                        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": format!("Failed to fetch revisions: {}", e)
+                        }))),
+                    },
+                    Err(e) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": format!("Route not found: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// GET /api/route/{uuid}/revisions/{revision} - full document of one revision
+pub async fn get_route_revision_by_number(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<RevisionPath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match get_saved_route(&path.uuid, u.id, &data).await {
+                    Ok(route) => match get_route_revision(route.id, path.revision, &data).await {
+                        Ok(revision) => Ok(HttpResponse::Ok().json(revision)),
+                        Err(e) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                            "error": format!("Revision not found: {}", e)
+                        }))),
+                    },
+                    Err(e) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": format!("Route not found: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// POST /api/route/{uuid}/revisions/{revision}/restore - save an old revision as the newest one
+pub async fn post_restore_route_revision(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<RevisionPath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => {
+                    let revision = match get_saved_route(&path.uuid, u.id, &data).await {
+                        Ok(route) => match get_route_revision(route.id, path.revision, &data).await
+                        {
+                            Ok(revision) => revision,
+                            Err(e) => {
+                                return Ok(HttpResponse::NotFound().json(serde_json::json!({
+                                    "error": format!("Revision not found: {}", e)
+                                })))
+                            }
+                        },
+                        Err(e) => {
+                            return Ok(HttpResponse::NotFound().json(serde_json::json!({
+                                "error": format!("Route not found: {}", e)
+                            })))
+                        }
+                    };
+
+                    // Restoring appends a revision, so the restore itself can be undone
+                    let route = match serde_json::to_value(&revision.route) {
+                        Ok(route) => route,
+                        Err(e) => {
+                            error!("Failed to serialize route revision: {}", e);
+                            return Ok(HttpResponse::InternalServerError()
+                                .json(serde_json::json!({ "error": "Failed to restore route" })));
+                        }
+                    };
+
+                    match update_saved_route(&path.uuid, u.id, &revision.name, &route, None, &data)
+                        .await
+                    {
+                        Ok(route) => Ok(HttpResponse::Ok()
+                            .insert_header(route_etag(&route))
+                            .json(route)),
+                        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": format!("Failed to restore route: {}", e)
+                        }))),
+                    }
+                }
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// GET /api/route/{uuid}/revisions/compare?from=&to= - waypoint and summary differences
+pub async fn get_compare_route_revisions(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<RoutingPath>,
+    query: web::Query<CompareQuery>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => {
+                    let route = match get_saved_route(&path.uuid, u.id, &data).await {
+                        Ok(route) => route,
+                        Err(e) => {
+                            return Ok(HttpResponse::NotFound().json(serde_json::json!({
+                                "error": format!("Route not found: {}", e)
+                            })))
+                        }
+                    };
+
+                    let from = get_route_revision(route.id, query.from, &data).await;
+                    let to = get_route_revision(route.id, query.to, &data).await;
+
+                    match (from, to) {
+                        (Ok(from), Ok(to)) => {
+                            Ok(HttpResponse::Ok().json(RevisionComparison::new(&from, &to)))
+                        }
+                        (Err(e), _) | (_, Err(e)) => {
+                            Ok(HttpResponse::NotFound().json(serde_json::json!({
+                                "error": format!("Revision not found: {}", e)
+                            })))
+                        }
+                    }
+                }
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
@@ -1,12 +1,24 @@
+use actix_web::http::header::{ETag, EntityTag, Header, IfMatch};
 use actix_web::{web, HttpRequest, HttpResponse, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use crate::{
-    models::{auth::AppData, SavedRoute},
-    utils::queries::get_user_from_api_token,
+    models::{
+        auth::AppData, decode_saved_routes, RouteCursor, RouteDocument, RouteDocumentError,
+        RouteListItem, RouteSearchQuery, RouteSearchResponse, SavedRoute, SavedRouteRow,
+    },
+    utils::{
+        merge_patch::merge_patch,
+        queries::{
+            get_route_tag_names, get_saved_route, get_trashed_routes, get_user_from_api_token,
+            insert_saved_route, restore_saved_route, search_saved_routes, trash_saved_route,
+            update_saved_route,
+        },
+    },
 };
-use chrono::Utc;
+
+const MAX_ROUTE_NAME_LENGTH: usize = 255;
 
 #[derive(Deserialize)]
 pub struct PostRouteRequest {
@@ -14,6 +26,81 @@ pub struct PostRouteRequest {
     name: Option<String>,
 }
 
+impl PostRouteRequest {
+    /// Validate the submitted name and route, returning them ready to be stored
+    fn validated(&self) -> Result<(String, Value), HttpResponse> {
+        let route_name = self
+            .name
+            .clone()
+            .unwrap_or_else(|| "Untitled Route".to_string());
+
+        if route_name.trim().is_empty() || route_name.chars().count() > MAX_ROUTE_NAME_LENGTH {
+            return Err(HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("Route name must be 1 to {} characters", MAX_ROUTE_NAME_LENGTH)
+            })));
+        }
+
+        match validated_route_document(self.route.clone()) {
+            Ok(route) => Ok((route_name, route)),
+            Err(e) => Err(HttpResponse::BadRequest().json(serde_json::json!({
+                "error": e.to_string()
+            }))),
+        }
+    }
+}
+
+/// ETag of a saved route, its revision number
+pub(crate) fn route_etag(route: &SavedRoute) -> ETag {
+    ETag(EntityTag::new_strong(route.revision.to_string()))
+}
+
+/// Revisions accepted by the `If-Match` header, `None` for `If-Match: *`.
+/// Updates without the header are refused so a stale tab cannot overwrite a newer save.
+fn if_match_revisions(req: &HttpRequest) -> Result<Option<Vec<i32>>, HttpResponse> {
+    if !req.headers().contains_key(IfMatch::name()) {
+        return Err(
+            HttpResponse::PreconditionRequired().json(serde_json::json!({
+                "error": "If-Match header with the route ETag is required"
+            })),
+        );
+    }
+
+    match IfMatch::parse(req) {
+        Ok(IfMatch::Any) => Ok(None),
+        // Tags are compared by value only, proxies may weaken them when compressing
+        Ok(IfMatch::Items(tags)) => Ok(Some(
+            tags.iter()
+                .filter_map(|tag| tag.tag().parse().ok())
+                .collect(),
+        )),
+        Err(_) => Err(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "Invalid If-Match header"
+        }))),
+    }
+}
+
+/// Response for a conditional update that matched no row: the route is gone or has moved on
+async fn failed_update_response(uuid: &str, user_id: i64, data: &AppData) -> HttpResponse {
+    match get_saved_route(uuid, user_id, data).await {
+        Ok(current) => HttpResponse::PreconditionFailed()
+            .insert_header(route_etag(&current))
+            .json(serde_json::json!({
+                "error": "Route was modified since it was loaded",
+                "revision": current.revision
+            })),
+        Err(e) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("Route not found: {}", e)
+        })),
+    }
+}
+
+/// Upgrade a client-submitted route to the current schema and validate it
+fn validated_route_document(value: Value) -> Result<Value, RouteDocumentError> {
+    let document = RouteDocument::from_value(value)?;
+    document.validate()?;
+    Ok(serde_json::to_value(&document)?)
+}
+
 pub async fn post_routing(
     req: HttpRequest,
     json: web::Json<PostRouteRequest>,
@@ -27,26 +114,16 @@ pub async fn post_routing(
 
             match user {
                 Ok(u) => {
-                    let route_name = json
-                        .name
-                        .clone()
-                        .unwrap_or_else(|| "Untitled Route".to_string());
-                    let now = Utc::now().into();
-
-                    let saved_route = sqlx::query_as!(
-                        SavedRoute,
-                        "INSERT INTO saved_routes (user_id, name, route, created_at, updated_at) values ($1, $2, $3, $4, $5) returning *",
-                        u.id,
-                        route_name,
-                        json.route,
-                        now,
-                        now
-                    )
-                    .fetch_one(&data.db)
-                    .await;
+                    let (route_name, route) = match json.validated() {
+                        Ok(valid) => valid,
+                        Err(response) => return Ok(response),
+                    };
+                    let saved_route = insert_saved_route(u.id, &route_name, &route, &data).await;
 
                     match saved_route {
-                        Ok(route) => Ok(HttpResponse::Ok().json(route)),
+                        Ok(route) => Ok(HttpResponse::Ok()
+                            .insert_header(route_etag(&route))
+                            .json(route)),
                         Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
                             "error": format!("Failed to save route: {}", e)
                         }))),
@@ -65,7 +142,7 @@ pub async fn post_routing(
 
 #[derive(Deserialize)]
 pub struct RoutingPath {
-    uuid: String,
+    pub uuid: String,
 }
 
 pub async fn get_routing(
@@ -81,17 +158,12 @@ pub async fn get_routing(
 
             match user {
                 Ok(u) => {
-                    let saved_route = sqlx::query_as!(
-                        SavedRoute,
-                        "SELECT * FROM saved_routes WHERE uuid = $1 AND user_id = $2 AND deleted_at IS NULL",
-                        path.uuid,
-                        u.id
-                    )
-                    .fetch_one(&data.db)
-                    .await;
+                    let saved_route = get_saved_route(&path.uuid, u.id, &data).await;
 
                     match saved_route {
-                        Ok(route) => Ok(HttpResponse::Ok().json(route)),
+                        Ok(route) => Ok(HttpResponse::Ok()
+                            .insert_header(route_etag(&route))
+                            .json(route)),
                         Err(e) => Ok(HttpResponse::NotFound().json(serde_json::json!({
                             "error": format!("Route not found: {}", e)
                         }))),
@@ -122,26 +194,31 @@ pub async fn put_routing(
 
             match user {
                 Ok(u) => {
-                    let route_name = json
-                        .name
-                        .clone()
-                        .unwrap_or_else(|| "Untitled Route".to_string());
-                    let now = Utc::now().into();
-
-                    let saved_route = sqlx::query_as!(
-                        SavedRoute,
-                        "UPDATE saved_routes SET name = $1, route = $2, updated_at = $3 WHERE uuid = $4 and user_id = $5 RETURNING *",
-                        route_name,
-                        json.route,
-                        now,
-                        path.uuid,
-                        u.id
+                    let expected_revisions = match if_match_revisions(&req) {
+                        Ok(revisions) => revisions,
+                        Err(response) => return Ok(response),
+                    };
+                    let (route_name, route) = match json.validated() {
+                        Ok(valid) => valid,
+                        Err(response) => return Ok(response),
+                    };
+                    let saved_route = update_saved_route(
+                        &path.uuid,
+                        u.id,
+                        &route_name,
+                        &route,
+                        expected_revisions.as_deref(),
+                        &data,
                     )
-                    .fetch_one(&data.db)
                     .await;
 
                     match saved_route {
-                        Ok(route) => Ok(HttpResponse::Ok().json(route)),
+                        Ok(route) => Ok(HttpResponse::Ok()
+                            .insert_header(route_etag(&route))
+                            .json(route)),
+                        Err(sqlx::Error::RowNotFound) => {
+                            Ok(failed_update_response(&path.uuid, u.id, &data).await)
+                        }
                         Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
                             "error": format!("Failed to save route: {}", e)
                         }))),
@@ -158,6 +235,184 @@ pub async fn put_routing(
     }
 }
 
+/// PATCH /api/route/{uuid} - JSON Merge Patch over `{"name": ..., "route": ...}`
+pub async fn patch_routing(
+    req: HttpRequest,
+    json: web::Json<Value>,
+    data: web::Data<AppData>,
+    path: web::Path<RoutingPath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => {
+                    let expected_revisions = match if_match_revisions(&req) {
+                        Ok(revisions) => revisions,
+                        Err(response) => return Ok(response),
+                    };
+
+                    let current = match get_saved_route(&path.uuid, u.id, &data).await {
+                        Ok(route) => route,
+                        Err(e) => {
+                            return Ok(HttpResponse::NotFound().json(serde_json::json!({
+                                "error": format!("Route not found: {}", e)
+                            })))
+                        }
+                    };
+
+                    let mut document = serde_json::json!({
+                        "name": current.name,
+                        "route": current.route,
+                    });
+                    merge_patch(&mut document, &json);
+
+                    let patched: PostRouteRequest = match serde_json::from_value(document) {
+                        Ok(patched) => patched,
+                        Err(e) => {
+                            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                                "error": format!("Invalid patch: {}", e)
+                            })))
+                        }
+                    };
+                    let (route_name, route) = match patched.validated() {
+                        Ok(valid) => valid,
+                        Err(response) => return Ok(response),
+                    };
+
+                    // The revision check still happens in the UPDATE, the route may have
+                    // changed since it was read above
+                    let saved_route = update_saved_route(
+                        &path.uuid,
+                        u.id,
+                        &route_name,
+                        &route,
+                        expected_revisions.as_deref(),
+                        &data,
+                    )
+                    .await;
+
+                    match saved_route {
+                        Ok(route) => Ok(HttpResponse::Ok()
+                            .insert_header(route_etag(&route))
+                            .json(route)),
+                        Err(sqlx::Error::RowNotFound) => {
+                            Ok(failed_update_response(&path.uuid, u.id, &data).await)
+                        }
+                        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": format!("Failed to save route: {}", e)
+                        }))),
+                    }
+                }
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// DELETE /api/route/{uuid} - move a route to the trash
+pub async fn delete_routing(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<RoutingPath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match trash_saved_route(&path.uuid, u.id, &data).await {
+                    Ok(Some(route)) => Ok(HttpResponse::Ok().json(route)),
+                    Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": "Route not found"
+                    }))),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to delete route: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// GET /api/routes/trash - deleted routes, most recently deleted first
+pub async fn get_trashed_routing(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match get_trashed_routes(u.id, &data).await {
+                    Ok(routes) => Ok(HttpResponse::Ok().json(routes)),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to fetch routes: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// POST /api/route/{uuid}/restore - take a route out of the trash
+pub async fn restore_routing(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<RoutingPath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match restore_saved_route(&path.uuid, u.id, &data).await {
+                    Ok(Some(route)) => Ok(HttpResponse::Ok().json(route)),
+                    Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": "Route not found"
+                    }))),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to restore route: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
 #[derive(Deserialize)]
 pub struct PaginationQuery {
     #[serde(default = "default_page")]
@@ -220,14 +475,15 @@ pub async fn get_routes_paginated(
 
                     // Get paginated routes
                     let routes_result = sqlx::query_as!(
-                        SavedRoute,
+                        SavedRouteRow,
                         "SELECT * FROM saved_routes WHERE user_id = $1 AND deleted_at IS NULL ORDER BY updated_at DESC LIMIT $2 OFFSET $3",
                         u.id,
                         limit,
                         offset
                     )
                     .fetch_all(&data.db)
-                    .await;
+                    .await
+                    .map(decode_saved_routes);
 
                     match routes_result {
                         Ok(routes) => {
@@ -256,3 +512,95 @@ pub async fn get_routes_paginated(
         }))),
     }
 }
+
+/// GET /api/routes/search - full-text search and filters over the user's routes,
+/// newest first, paginated with `cursor`
+pub async fn get_routes_search(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    query: web::Query<RouteSearchQuery>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    let cursor = match query.cursor.as_deref().map(RouteCursor::decode) {
+        Some(None) => {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Invalid cursor"
+            })))
+        }
+        Some(cursor) => cursor,
+        None => None,
+    };
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => {
+                    let mut rows = match search_saved_routes(u.id, &query, cursor, &data).await {
+                        Ok(rows) => rows,
+                        Err(e) => {
+                            return Ok(HttpResponse::InternalServerError().json(
+                                serde_json::json!({
+                                    "error": format!("Failed to search routes: {}", e)
+                                }),
+                            ))
+                        }
+                    };
+
+                    // One extra row was fetched to know whether there is a next page
+                    let limit = query.limit() as usize;
+                    let next_cursor = if rows.len() > limit {
+                        rows.truncate(limit);
+                        rows.last().map(|row| {
+                            RouteCursor {
+                                updated_at: row.updated_at,
+                                id: row.id,
+                            }
+                            .encode()
+                        })
+                    } else {
+                        None
+                    };
+                    let routes = decode_saved_routes(rows);
+
+                    let ids: Vec<i64> = routes.iter().map(|route| route.id).collect();
+                    let tags = match get_route_tag_names(&ids, &data).await {
+                        Ok(tags) => tags,
+                        Err(e) => {
+                            return Ok(HttpResponse::InternalServerError().json(
+                                serde_json::json!({
+                                    "error": format!("Failed to fetch tags: {}", e)
+                                }),
+                            ))
+                        }
+                    };
+
+                    let routes = routes
+                        .into_iter()
+                        .map(|route| RouteListItem {
+                            tags: tags
+                                .iter()
+                                .filter(|(route_id, _)| *route_id == route.id)
+                                .map(|(_, name)| name.clone())
+                                .collect(),
+                            route,
+                        })
+                        .collect();
+
+                    Ok(HttpResponse::Ok().json(RouteSearchResponse {
+                        routes,
+                        next_cursor,
+                    }))
+                }
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
@@ -1,9 +1,14 @@
 use actix_web::{post, web, HttpResponse, Result};
-use reqwest;
+use chrono::Utc;
 use serde::Deserialize;
-use tracing::{error, info};
+use std::sync::Arc;
+use tracing::{error, info, warn};
 
-use crate::utils::config::Config;
+use crate::models::RouteProfile;
+use crate::services::{
+    LocalRouter, LocalRoutingError, OrsClient, OrsError, OrsResponse, MAX_MATRIX_LOCATIONS,
+};
+use crate::utils::config::{Config, RoutingEngine};
 
 #[derive(Debug, Deserialize)]
 pub struct RoutingRequest {
@@ -22,28 +27,202 @@ fn default_profile() -> String {
     "driving-car".to_string()
 }
 
+#[derive(Debug, Deserialize)]
+pub struct IsochronesRequest {
+    /// `[lon, lat]` of each starting point
+    locations: Vec<[f64; 2]>,
+    #[serde(default = "default_profile")]
+    profile: String,
+    /// Travel time limits, one area per value
+    minutes: Vec<u32>,
+    attributes: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MatrixRequest {
+    /// `[lon, lat]` of each point
+    locations: Vec<[f64; 2]>,
+    #[serde(default = "default_profile")]
+    profile: String,
+    /// Indices into `locations`, all of them when absent
+    sources: Option<Vec<usize>>,
+    destinations: Option<Vec<usize>>,
+    metrics: Option<Vec<String>>,
+}
+
+/// ORS isochrone limits on the standard plan
+const MAX_ISOCHRONE_LOCATIONS: usize = 5;
+const MAX_ISOCHRONE_RANGES: usize = 10;
+const MAX_ISOCHRONE_MINUTES: u32 = 60;
+
+/// ORS directions limit on the standard plan
+const MAX_DIRECTIONS_WAYPOINTS: usize = 50;
+
+/// Response formats accepted by the ORS directions endpoint
+const ROUTING_FORMATS: [&str; 3] = ["json", "geojson", "gpx"];
+
+/// Map an ORS client error to the response sent to our clients
+pub fn ors_error_response(e: OrsError) -> HttpResponse {
+    match e {
+        OrsError::QuotaExhausted { reset } => {
+            let retry_after = reset
+                .map(|reset| (reset - Utc::now()).num_seconds().max(0))
+                .unwrap_or(3600);
+            HttpResponse::TooManyRequests()
+                .insert_header(("Retry-After", retry_after.to_string()))
+                .json(serde_json::json!({
+                    "error": "Daily routing quota exhausted, please try again later",
+                    "reset": reset
+                }))
+        }
+        OrsError::Upstream { status, body } => {
+            error!("OpenRouteService error {} ({} bytes)", status, body.len());
+            HttpResponse::build(
+                actix_web::http::StatusCode::from_u16(status)
+                    .unwrap_or(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR),
+            )
+            .body(body)
+        }
+        OrsError::InvalidResponse(e) => {
+            error!("Unexpected OpenRouteService response: {}", e);
+            HttpResponse::BadGateway().json(serde_json::json!({
+                "error": "Unexpected routing service response"
+            }))
+        }
+        OrsError::Request(e) => {
+            error!("Failed to fetch from OpenRouteService: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to fetch routing data"
+            }))
+        }
+    }
+}
+
+/// Successful ORS response, flagged with whether it came from the cache
+pub fn ors_response(response: OrsResponse) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(response.content_type)
+        .insert_header(("X-Cache", if response.cached { "HIT" } else { "MISS" }))
+        .body(response.body)
+}
+
+/// Answer a directions request from the offline road graph
+async fn local_directions_response(
+    router: Arc<LocalRouter>,
+    profile: String,
+    format: String,
+    coordinates: Vec<[f64; 2]>,
+) -> HttpResponse {
+    let content_type = if format == "geojson" {
+        "application/geo+json"
+    } else {
+        "application/json"
+    };
+
+    // A* over a regional graph is CPU bound, keep it off the async workers
+    let result = web::block(move || router.directions(&profile, &format, &coordinates)).await;
+
+    match result {
+        Ok(Ok(directions)) => HttpResponse::Ok()
+            .content_type(content_type)
+            .insert_header(("X-Routing-Engine", "local"))
+            .json(directions),
+        Ok(Err(
+            e
+            @ (LocalRoutingError::UnsupportedProfile(_) | LocalRoutingError::UnsupportedFormat(_)),
+        )) => HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e.to_string()
+        })),
+        Ok(Err(e)) => HttpResponse::NotFound().json(serde_json::json!({
+            "error": e.to_string()
+        })),
+        Err(e) => {
+            error!("Offline routing failed: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to compute the route"
+            }))
+        }
+    }
+}
+
+/// Profiles end up in the URL path, only known values are forwarded
+fn validate_profile(profile: &str) -> Result<(), HttpResponse> {
+    match serde_json::from_value::<RouteProfile>(serde_json::json!(profile)) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Unknown profile {}", profile)
+        }))),
+    }
+}
+
+/// Every `[lon, lat]` of the `field` array must be a position on Earth
+fn validate_locations(field: &str, locations: &[[f64; 2]]) -> Result<(), HttpResponse> {
+    let invalid = locations.iter().position(|[lon, lat]| {
+        !lat.is_finite()
+            || !lon.is_finite()
+            || !(-90.0..=90.0).contains(lat)
+            || !(-180.0..=180.0).contains(lon)
+    });
+    match invalid {
+        Some(index) => Err(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("{}[{}] must be a valid [lon, lat] position", field, index)
+        }))),
+        None => Ok(()),
+    }
+}
+
 /// POST /api/routing - Proxy to OpenRouteService
 #[post("/routing")]
 pub async fn post_routing(
     req: web::Json<RoutingRequest>,
+    ors: web::Data<Arc<OrsClient>>,
+    local: web::Data<Option<Arc<LocalRouter>>>,
     config: web::Data<Config>,
 ) -> Result<HttpResponse> {
-    info!("Routing request with {} coordinates", req.coordinates.len());
+    info!(
+        "Routing request with {} coordinates ({})",
+        req.coordinates.len(),
+        req.profile
+    );
 
-    if req.coordinates.len() < 2 {
+    if req.coordinates.len() < 2 || req.coordinates.len() > MAX_DIRECTIONS_WAYPOINTS {
         return Ok(HttpResponse::BadRequest().json(serde_json::json!({
-            "error": "At least 2 coordinates are required"
+            "error": format!("Between 2 and {} coordinates are required", MAX_DIRECTIONS_WAYPOINTS)
         })));
     }
+    if let Err(response) = validate_locations("coordinates", &req.coordinates) {
+        return Ok(response);
+    }
 
-    println!("Using API key: {:#?}", &config.openrouteservice_token[..10]); // premiers caractères seulement
-
-    // Format goes in the URL path, not the body
+    if let Err(response) = validate_profile(&req.profile) {
+        return Ok(response);
+    }
     let format_path = req.format.as_deref().unwrap_or("json");
-    let url = format!(
-        "https://api.openrouteservice.org/v2/directions/{}/{}",
-        req.profile, format_path
-    );
+    if !ROUTING_FORMATS.contains(&format_path) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("format must be one of {}", ROUTING_FORMATS.join(", "))
+        })));
+    }
+
+    let local = local.get_ref().clone();
+    if config.routing_engine == RoutingEngine::Local {
+        return Ok(match local {
+            Some(router) => {
+                local_directions_response(
+                    router,
+                    req.profile.clone(),
+                    format_path.to_string(),
+                    req.coordinates.clone(),
+                )
+                .await
+            }
+            None => HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "error": "Offline routing graph is not loaded"
+            })),
+        });
+    }
+
+    let path = format!("/v2/directions/{}/{}", req.profile, format_path);
 
     let mut body = serde_json::json!({
         "coordinates": req.coordinates,
@@ -67,43 +246,140 @@ pub async fn post_routing(
 
     // Note: format is in the URL path, not the body
 
-    info!("Request URL: {}", url);
-    info!("Request body to OpenRouteService: {}", serde_json::to_string_pretty(&body).unwrap_or_default());
-
-    let client = reqwest::Client::new();
-    let response = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header(
-            "Accept",
-            "application/json, application/geo+json, application/gpx+xml, img/png; charset=utf-8",
-        )
-        .header("Authorization", config.openrouteservice_token.as_str())
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch from OpenRouteService: {}", e);
-            actix_web::error::ErrorInternalServerError("Failed to fetch routing data")
-        })?;
+    match (ors.post("directions", &path, &body).await, local) {
+        (Ok(response), _) => Ok(ors_response(response)),
+        (Err(e), Some(router)) if e.is_unavailable() => {
+            warn!("OpenRouteService unavailable ({}), routing offline", e);
+            Ok(local_directions_response(
+                router,
+                req.profile.clone(),
+                format_path.to_string(),
+                req.coordinates.clone(),
+            )
+            .await)
+        }
+        (Err(e), _) => Ok(ors_error_response(e)),
+    }
+}
+
+/// POST /api/isochrones - Areas reachable within the given minutes
+#[post("/isochrones")]
+pub async fn post_isochrones(
+    req: web::Json<IsochronesRequest>,
+    ors: web::Data<Arc<OrsClient>>,
+) -> Result<HttpResponse> {
+    info!(
+        "Isochrones request with {} locations ({})",
+        req.locations.len(),
+        req.profile
+    );
+
+    if req.locations.is_empty() || req.locations.len() > MAX_ISOCHRONE_LOCATIONS {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Between 1 and {} locations are required", MAX_ISOCHRONE_LOCATIONS)
+        })));
+    }
+
+    if let Err(response) = validate_locations("locations", &req.locations) {
+        return Ok(response);
+    }
+
+    if req.minutes.is_empty()
+        || req.minutes.len() > MAX_ISOCHRONE_RANGES
+        || req
+            .minutes
+            .iter()
+            .any(|m| *m == 0 || *m > MAX_ISOCHRONE_MINUTES)
+    {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!(
+                "Between 1 and {} ranges of 1 to {} minutes are required",
+                MAX_ISOCHRONE_RANGES, MAX_ISOCHRONE_MINUTES
+            )
+        })));
+    }
+
+    if let Err(response) = validate_profile(&req.profile) {
+        return Ok(response);
+    }
+
+    let path = format!("/v2/isochrones/{}", req.profile);
+
+    let mut minutes = req.minutes.clone();
+    minutes.sort_unstable();
+    minutes.dedup();
+
+    let mut body = serde_json::json!({
+        "locations": req.locations,
+        "range": minutes.iter().map(|m| m * 60).collect::<Vec<_>>(),
+        "range_type": "time",
+    });
+
+    if let Some(attributes) = &req.attributes {
+        body["attributes"] = serde_json::json!(attributes);
+    }
 
-    if !response.status().is_success() {
-        let status_code = response.status().as_u16();
-        let error_text = response.text().await.unwrap_or_default();
-        error!("OpenRouteService error {}: {}", status_code, error_text);
-        return Ok(HttpResponse::build(
-            actix_web::http::StatusCode::from_u16(status_code)
-                .unwrap_or(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR),
-        )
-        .body(error_text));
+    match ors.post("isochrones", &path, &body).await {
+        Ok(response) => Ok(ors_response(response)),
+        Err(e) => Ok(ors_error_response(e)),
+    }
+}
+
+/// POST /api/matrix - Durations and distances between many points
+#[post("/matrix")]
+pub async fn post_matrix(
+    req: web::Json<MatrixRequest>,
+    ors: web::Data<Arc<OrsClient>>,
+) -> Result<HttpResponse> {
+    info!(
+        "Matrix request with {} locations ({})",
+        req.locations.len(),
+        req.profile
+    );
+
+    if req.locations.len() < 2 || req.locations.len() > MAX_MATRIX_LOCATIONS {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Between 2 and {} locations are required", MAX_MATRIX_LOCATIONS)
+        })));
     }
 
-    let data = response.json::<serde_json::Value>().await.map_err(|e| {
-        error!("Failed to parse OpenRouteService response: {}", e);
-        actix_web::error::ErrorInternalServerError("Failed to parse routing data")
-    })?;
+    if let Err(response) = validate_locations("locations", &req.locations) {
+        return Ok(response);
+    }
 
-    info!("OpenRouteService response: {}", serde_json::to_string_pretty(&data).unwrap_or_default());
+    let indices = [&req.sources, &req.destinations];
+    if indices
+        .iter()
+        .filter_map(|i| i.as_ref())
+        .flatten()
+        .any(|i| *i >= req.locations.len())
+    {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "sources and destinations must be indices into locations"
+        })));
+    }
 
-    Ok(HttpResponse::Ok().json(data))
+    if let Err(response) = validate_profile(&req.profile) {
+        return Ok(response);
+    }
+
+    let path = format!("/v2/matrix/{}", req.profile);
+
+    let mut body = serde_json::json!({
+        "locations": req.locations,
+        "metrics": req.metrics.clone().unwrap_or_else(|| vec!["duration".to_string()]),
+    });
+
+    if let Some(sources) = &req.sources {
+        body["sources"] = serde_json::json!(sources);
+    }
+
+    if let Some(destinations) = &req.destinations {
+        body["destinations"] = serde_json::json!(destinations);
+    }
+
+    match ors.post("matrix", &path, &body).await {
+        Ok(response) => Ok(ors_response(response)),
+        Err(e) => Ok(ors_error_response(e)),
+    }
 }
@@ -0,0 +1,266 @@
+use actix_multipart::Multipart;
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use futures::TryStreamExt;
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::error;
+
+use crate::{
+    models::{auth::AppData, SailingRouteRequest},
+    services::{sailing_route, GridCache, SailingError},
+    utils::{
+        polar::parse_polar_csv,
+        queries::{
+            delete_boat_polar, get_boat_polar, get_boat_polars, get_user_from_api_token,
+            insert_boat_polar,
+        },
+    },
+};
+
+const MAX_POLAR_BYTES: usize = 256 * 1024;
+
+#[derive(Deserialize)]
+pub struct PolarPath {
+    id: i64,
+}
+
+/// GET /api/polars - the user's boat polars
+pub async fn get_polars(req: HttpRequest, data: web::Data<AppData>) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match get_boat_polars(u.id, &data).await {
+                    Ok(polars) => Ok(HttpResponse::Ok().json(polars)),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to fetch polars: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// Read the multipart fields: `file` (required) and `name` (optional)
+async fn read_polar_form(
+    mut payload: Multipart,
+) -> Result<(Option<(Option<String>, Vec<u8>)>, Option<String>), HttpResponse> {
+    let (mut file, mut name) = (None, None);
+
+    while let Some(mut field) = payload.try_next().await.map_err(|e| {
+        HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Invalid multipart body: {}", e)
+        }))
+    })? {
+        let disposition = field.content_disposition();
+        let field_name = disposition
+            .and_then(|d| d.get_name())
+            .unwrap_or_default()
+            .to_string();
+        let filename = disposition.and_then(|d| d.get_filename()).map(String::from);
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.try_next().await.map_err(|e| {
+            HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("Invalid multipart body: {}", e)
+            }))
+        })? {
+            if bytes.len() + chunk.len() > MAX_POLAR_BYTES {
+                return Err(HttpResponse::PayloadTooLarge().json(serde_json::json!({
+                    "error": format!("Polars are limited to {} bytes", MAX_POLAR_BYTES)
+                })));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        match field_name.as_str() {
+            "file" => file = Some((filename, bytes)),
+            "name" => name = Some(String::from_utf8_lossy(&bytes).trim().to_string()),
+            _ => {}
+        }
+    }
+
+    Ok((file, name))
+}
+
+/// POST /api/polars - multipart upload of a polar CSV (`file`, optional `name`)
+pub async fn post_polar(
+    req: HttpRequest,
+    payload: Multipart,
+    data: web::Data<AppData>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => {
+                    let (file, name) = match read_polar_form(payload).await {
+                        Ok(form) => form,
+                        Err(response) => return Ok(response),
+                    };
+
+                    let Some((filename, bytes)) = file else {
+                        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                            "error": "Missing file field"
+                        })));
+                    };
+
+                    let polar = match parse_polar_csv(&String::from_utf8_lossy(&bytes)) {
+                        Ok(polar) => polar,
+                        Err(e) => {
+                            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                                "error": format!("Failed to parse polar: {}", e)
+                            })))
+                        }
+                    };
+
+                    let name = name
+                        .filter(|n| !n.is_empty())
+                        .or_else(|| {
+                            filename
+                                .as_deref()
+                                .map(|f| f.rsplit_once('.').map_or(f, |(stem, _)| stem).to_string())
+                        })
+                        .unwrap_or_else(|| "Polar".to_string());
+                    let name: String = name.chars().take(255).collect();
+
+                    let polar = match serde_json::to_value(&polar) {
+                        Ok(polar) => polar,
+                        Err(e) => {
+                            error!("Failed to serialize polar: {}", e);
+                            return Ok(HttpResponse::InternalServerError()
+                                .json(serde_json::json!({ "error": "Failed to save polar" })));
+                        }
+                    };
+
+                    match insert_boat_polar(u.id, &name, &polar, &data).await {
+                        Ok(polar) => Ok(HttpResponse::Ok().json(polar)),
+                        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": format!("Failed to save polar: {}", e)
+                        }))),
+                    }
+                }
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// DELETE /api/polars/{id}
+pub async fn delete_polar(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<PolarPath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match delete_boat_polar(u.id, path.id, &data).await {
+                    Ok(Some(polar)) => Ok(HttpResponse::Ok().json(polar)),
+                    Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": "Polar not found"
+                    }))),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to delete polar: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// POST /api/sailing/route - fastest passage between two positions for one of the user's
+/// polars, by the isochrone method over the GFS wind grids
+pub async fn post_sailing_route(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    grids: web::Data<Arc<GridCache>>,
+    json: web::Json<SailingRouteRequest>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => {
+                    if let Err(e) = json.validate() {
+                        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                            "error": e
+                        })));
+                    }
+
+                    let polar = match get_boat_polar(u.id, json.polar_id, &data).await {
+                        Ok(Some(polar)) => polar,
+                        Ok(None) => {
+                            return Ok(HttpResponse::NotFound().json(serde_json::json!({
+                                "error": "Polar not found"
+                            })))
+                        }
+                        Err(e) => {
+                            return Ok(HttpResponse::InternalServerError().json(
+                                serde_json::json!({
+                                    "error": format!("Failed to fetch polar: {}", e)
+                                }),
+                            ))
+                        }
+                    };
+
+                    match sailing_route(&grids, polar.polar, &json).await {
+                        Ok(route) => Ok(HttpResponse::Ok().json(route)),
+                        Err(e @ (SailingError::NoWindData | SailingError::NoLandMask)) => {
+                            Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                                "error": e.to_string()
+                            })))
+                        }
+                        Err(e @ (SailingError::Inland(_) | SailingError::Unreachable(_))) => {
+                            Ok(HttpResponse::UnprocessableEntity().json(serde_json::json!({
+                                "error": e.to_string()
+                            })))
+                        }
+                        Err(SailingError::Other(e)) => {
+                            error!("Sailing route failed: {}", e);
+                            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                                "error": "Failed to compute the sailing route"
+                            })))
+                        }
+                    }
+                }
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
@@ -0,0 +1,375 @@
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use chrono::Utc;
+use minijinja::{context, Environment, ErrorKind, Value};
+use serde::Deserialize;
+use std::sync::{Arc, OnceLock};
+use tracing::{error, warn};
+
+use crate::{
+    models::{auth::AppData, NewRouteShare, RouteDocument, SavedRoute, SharedRoute},
+    routes::routes::RoutingPath,
+    services::{route_weather, RedisClient},
+    utils::{
+        config::Config,
+        misc::Asset,
+        queries::{
+            get_saved_route, get_shared_route, get_user_from_api_token, insert_route_share,
+            list_route_shares, revoke_route_share,
+        },
+    },
+};
+
+const PREVIEW_WIDTH: f64 = 600.0;
+const PREVIEW_HEIGHT: f64 = 360.0;
+const PREVIEW_PADDING: f64 = 20.0;
+
+#[derive(Deserialize)]
+pub struct RouteSharePath {
+    pub uuid: String,
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct SharedRoutePath {
+    pub token: String,
+}
+
+/// POST /api/route/{uuid}/shares - create a public link, optionally expiring
+pub async fn post_route_share(
+    req: HttpRequest,
+    json: web::Json<NewRouteShare>,
+    data: web::Data<AppData>,
+    path: web::Path<RoutingPath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    if json
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now())
+    {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "expires_at must be in the future"
+        })));
+    }
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match get_saved_route(&path.uuid, u.id, &data).await {
+                    Ok(route) => match insert_route_share(route.id, json.expires_at, &data).await {
+                        Ok(share) => Ok(HttpResponse::Created().json(share)),
+                        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": format!("Failed to create share link: {}", e)
+                        }))),
+                    },
+                    Err(e) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": format!("Route not found: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// GET /api/route/{uuid}/shares - every link of a route, including revoked and expired ones
+pub async fn get_route_shares(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<RoutingPath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match get_saved_route(&path.uuid, u.id, &data).await {
+                    Ok(route) => match list_route_shares(route.id, &data).await {
+                        Ok(shares) => Ok(HttpResponse::Ok().json(shares)),
+                        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": format!("Failed to list share links: {}", e)
+                        }))),
+                    },
+                    Err(e) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": format!("Route not found: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// DELETE /api/route/{uuid}/shares/{token} - revoke a link
+pub async fn delete_route_share(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<RouteSharePath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match get_saved_route(&path.uuid, u.id, &data).await {
+                    Ok(route) => match revoke_route_share(route.id, &path.token, &data).await {
+                        Ok(Some(share)) => Ok(HttpResponse::Ok().json(share)),
+                        Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                            "error": "Share link not found"
+                        }))),
+                        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": format!("Failed to revoke share link: {}", e)
+                        }))),
+                    },
+                    Err(e) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": format!("Route not found: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// GET /api/shared/route/{token} - public, read-only route with its current weather
+pub async fn get_shared(
+    data: web::Data<AppData>,
+    redis: web::Data<Arc<RedisClient>>,
+    path: web::Path<SharedRoutePath>,
+) -> Result<HttpResponse> {
+    match get_shared_route(&path.token, &data).await {
+        Ok((share, route)) => {
+            // The route is still worth returning when the weather overlay is unavailable
+            let weather = match route_weather(&redis, &route.route).await {
+                Ok(weather) => weather,
+                Err(e) => {
+                    warn!("Failed to sample weather for shared route: {}", e);
+                    None
+                }
+            };
+
+            Ok(HttpResponse::Ok().json(SharedRoute {
+                name: route.name,
+                route: route.route,
+                updated_at: route.updated_at,
+                expires_at: share.expires_at,
+                weather,
+            }))
+        }
+        Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Share link not found or expired"
+        }))),
+    }
+}
+
+/// GET /share/route/{token} - HTML preview with OpenGraph tags for link unfurling
+pub async fn get_shared_preview(
+    data: web::Data<AppData>,
+    config: web::Data<Config>,
+    path: web::Path<SharedRoutePath>,
+) -> Result<HttpResponse> {
+    let route = match get_shared_route(&path.token, &data).await {
+        Ok((_, route)) => route,
+        Err(_) => return Ok(HttpResponse::NotFound().body("404 Not Found")),
+    };
+
+    // Not the request's Host header, which the client chooses
+    match render_preview(&route, &config.public_url, &path.token) {
+        Ok(html) => Ok(HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .insert_header(("Content-Security-Policy", "frame-ancestors 'self'"))
+            .body(html)),
+        Err(e) => {
+            error!("Failed to render the share preview: {}", e);
+            Ok(HttpResponse::InternalServerError().body("Failed to render preview"))
+        }
+    }
+}
+
+fn render_preview(
+    route: &SavedRoute,
+    public_url: &str,
+    token: &str,
+) -> Result<String, minijinja::Error> {
+    preview_templates()
+        .get_template("route.html")?
+        .render(context! {
+            title => route.name,
+            description => describe_route(&route.route),
+            url => format!("{}/share/route/{}", public_url, token),
+            app_url => format!("{}/", public_url),
+            // Only numbers and fixed markup, built by `route_svg`
+            map => Value::from_safe_string(route_svg(&route.route)),
+        })
+}
+
+/// Templates of `embedded/share`, values are escaped in `.html` templates
+fn preview_templates() -> &'static Environment<'static> {
+    static TEMPLATES: OnceLock<Environment<'static>> = OnceLock::new();
+    TEMPLATES.get_or_init(|| {
+        let mut env = Environment::new();
+        env.set_loader(|name| {
+            let Some(file) = Asset::get(&format!("share/{}", name)) else {
+                return Ok(None);
+            };
+            String::from_utf8(file.data.into_owned())
+                .map(Some)
+                .map_err(|e| {
+                    minijinja::Error::new(ErrorKind::InvalidOperation, "template is not utf-8")
+                        .with_source(e)
+                })
+        });
+        env
+    })
+}
+
+/// One-line summary used for the page and OpenGraph descriptions
+fn describe_route(route: &RouteDocument) -> String {
+    let mut parts = Vec::new();
+
+    if let Some(feature) = route.feature() {
+        let summary = feature.properties.summary;
+        parts.push(format!("{:.1} km", summary.distance / 1000.0));
+
+        let minutes = (summary.duration / 60.0).round() as i64;
+        if minutes >= 60 {
+            parts.push(format!("{} h {:02} min", minutes / 60, minutes % 60));
+        } else {
+            parts.push(format!("{} min", minutes));
+        }
+    }
+
+    let stops = route.points().len();
+    if stops > 0 {
+        parts.push(format!("{} stops", stops));
+    }
+    parts.push(route.profile.as_str().to_string());
+
+    parts.join(" · ")
+}
+
+/// Inline SVG drawing of the route, projected equirectangularly around its center
+fn route_svg(route: &RouteDocument) -> String {
+    let mut positions: Vec<(f64, f64)> = route
+        .coordinates()
+        .iter()
+        .map(|position| (position[0], position[1]))
+        .collect();
+    if positions.len() < 2 {
+        positions = route.points().iter().map(|p| (p.lon, p.lat)).collect();
+    }
+    if positions.is_empty() {
+        return String::new();
+    }
+
+    let (min_lon, max_lon, min_lat, max_lat) = positions.iter().fold(
+        (f64::MAX, f64::MIN, f64::MAX, f64::MIN),
+        |(min_lon, max_lon, min_lat, max_lat), (lon, lat)| {
+            (
+                min_lon.min(*lon),
+                max_lon.max(*lon),
+                min_lat.min(*lat),
+                max_lat.max(*lat),
+            )
+        },
+    );
+
+    let x_scale = ((min_lat + max_lat) / 2.0).to_radians().cos();
+    let width = ((max_lon - min_lon) * x_scale).max(1e-9);
+    let height = (max_lat - min_lat).max(1e-9);
+    let scale = ((PREVIEW_WIDTH - 2.0 * PREVIEW_PADDING) / width)
+        .min((PREVIEW_HEIGHT - 2.0 * PREVIEW_PADDING) / height);
+    let x_offset = (PREVIEW_WIDTH - width * scale) / 2.0;
+    let y_offset = (PREVIEW_HEIGHT - height * scale) / 2.0;
+
+    let project = |lon: f64, lat: f64| {
+        (
+            x_offset + (lon - min_lon) * x_scale * scale,
+            PREVIEW_HEIGHT - y_offset - (lat - min_lat) * scale,
+        )
+    };
+
+    let polyline: Vec<String> = positions
+        .iter()
+        .map(|(lon, lat)| {
+            let (x, y) = project(*lon, *lat);
+            format!("{:.1},{:.1}", x, y)
+        })
+        .collect();
+
+    let markers: String = route
+        .points()
+        .iter()
+        .map(|p| {
+            let (x, y) = project(p.lon, p.lat);
+            format!(
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"5\" fill=\"white\" stroke=\"rgb(13, 110, 253)\" stroke-width=\"2\" />",
+                x, y
+            )
+        })
+        .collect();
+
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\" role=\"img\"><polyline points=\"{}\" fill=\"none\" stroke=\"rgb(13, 110, 253)\" stroke-width=\"3\" stroke-linejoin=\"round\" stroke-linecap=\"round\" />{}</svg>",
+        PREVIEW_WIDTH,
+        PREVIEW_HEIGHT,
+        polyline.join(" "),
+        markers
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_preview_escapes_values() {
+        let document = json!({
+            "version": 2,
+            "startPoint": { "lat": 48.85, "lon": 2.35 },
+            "endPoint": { "lat": 48.87, "lon": 2.37 },
+            "transportMode": "cycling-regular"
+        });
+        let route = SavedRoute {
+            id: 1,
+            user_id: 1,
+            name: "\"><script>alert(1)</script>".to_string(),
+            route: RouteDocument::from_value(document).unwrap(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+            uuid: "uuid".to_string(),
+            revision: 1,
+            folder_id: None,
+        };
+
+        let html = render_preview(&route, "https://example.com", "abc").unwrap();
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(html.contains("<svg"));
+        assert!(html
+            .contains("content=\"https:&#x2f;&#x2f;example.com&#x2f;share&#x2f;route&#x2f;abc\""));
+    }
+}
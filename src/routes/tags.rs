@@ -0,0 +1,134 @@
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use serde::Deserialize;
+
+use crate::{
+    models::{auth::AppData, RouteTagsInput},
+    routes::routes::RoutingPath,
+    utils::queries::{
+        delete_route_tag, get_route_tag_names, get_route_tags, get_saved_route,
+        get_user_from_api_token, set_route_tags,
+    },
+};
+
+#[derive(Deserialize)]
+pub struct TagPath {
+    id: i64,
+}
+
+/// GET /api/route_tags - the user's tags with their route counts
+pub async fn get_tags(req: HttpRequest, data: web::Data<AppData>) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match get_route_tags(u.id, &data).await {
+                    Ok(tags) => Ok(HttpResponse::Ok().json(tags)),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to fetch tags: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// DELETE /api/route_tags/{id} - remove a tag from every route
+pub async fn delete_tag(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<TagPath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => match delete_route_tag(u.id, path.id, &data).await {
+                    Ok(Some(tag)) => Ok(HttpResponse::Ok().json(tag)),
+                    Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                        "error": "Tag not found"
+                    }))),
+                    Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                        "error": format!("Failed to delete tag: {}", e)
+                    }))),
+                },
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// PUT /api/route/{uuid}/tags - replace the tags of a route
+pub async fn put_route_tags(
+    req: HttpRequest,
+    json: web::Json<RouteTagsInput>,
+    data: web::Data<AppData>,
+    path: web::Path<RoutingPath>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    let names = match json.normalized() {
+        Ok(names) => names,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                "error": e
+            })))
+        }
+    };
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => {
+                    let route = match get_saved_route(&path.uuid, u.id, &data).await {
+                        Ok(route) => route,
+                        Err(e) => {
+                            return Ok(HttpResponse::NotFound().json(serde_json::json!({
+                                "error": format!("Route not found: {}", e)
+                            })))
+                        }
+                    };
+
+                    if let Err(e) = set_route_tags(route.id, u.id, &names, &data).await {
+                        return Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": format!("Failed to save tags: {}", e)
+                        })));
+                    }
+
+                    match get_route_tag_names(&[route.id], &data).await {
+                        Ok(tags) => Ok(HttpResponse::Ok().json(serde_json::json!({
+                            "tags": tags.into_iter().map(|(_, name)| name).collect::<Vec<_>>()
+                        }))),
+                        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                            "error": format!("Failed to fetch tags: {}", e)
+                        }))),
+                    }
+                }
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
@@ -0,0 +1,59 @@
+use actix_web::{get, web, HttpResponse, Result};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::services::TileServer;
+use crate::utils::map_tiles::{TileLayer, MAX_TILE_ZOOM};
+
+/// GET /api/tiles/{layer}/{index}/{z}/{x}/{y}.png - Web Mercator tile of a stored forecast.
+/// `index` is one of `/api/wind-indices` for the wind layers, of `/api/precipitation-indices`
+/// for precipitation.
+#[get("/tiles/{layer}/{index}/{z}/{x}/{y}.png")]
+pub async fn get_tile(
+    path: web::Path<(String, u32, u8, u32, u32)>,
+    tiles: web::Data<Arc<TileServer>>,
+) -> Result<HttpResponse> {
+    let (layer, index, z, x, y) = path.into_inner();
+
+    let Some(layer) = TileLayer::parse(&layer) else {
+        let layers: Vec<&str> = TileLayer::ALL.iter().map(TileLayer::as_str).collect();
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("Unknown layer {}, expected one of {}", layer, layers.join(", "))
+        })));
+    };
+    if z > MAX_TILE_ZOOM {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Zoom must be at most {}", MAX_TILE_ZOOM)
+        })));
+    }
+    let tiles_per_side = 1u32 << z;
+    if x >= tiles_per_side || y >= tiles_per_side {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Tile {}/{}/{} is outside the map", z, x, y)
+        })));
+    }
+
+    match tiles.tile(layer, index, z, x, y).await {
+        Ok(Some(png_buffer)) => Ok(HttpResponse::Ok()
+            .content_type("image/png")
+            .insert_header(("Cache-Control", "public, max-age=300"))
+            .body(png_buffer)),
+        Ok(None) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("No {} data at index {}", layer.as_str(), index)
+        }))),
+        Err(e) => {
+            error!(
+                "Failed to render {} tile {}/{}/{} at index {}: {}",
+                layer.as_str(),
+                z,
+                x,
+                y,
+                index,
+                e
+            );
+            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Failed to render tile"
+            })))
+        }
+    }
+}
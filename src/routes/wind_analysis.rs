@@ -0,0 +1,135 @@
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::{
+    models::{auth::AppData, DepartureWindowQuery, WindAnalysisQuery},
+    routes::routes::RoutingPath,
+    services::{best_departures, route_wind_analysis, RedisClient},
+    utils::queries::{get_saved_route, get_user_from_api_token},
+};
+
+/// GET /api/route/{uuid}/wind-analysis?departure=&segment_length=&power=&mass=&worst=
+/// - head, cross and tailwind along the route for a departure time
+pub async fn get_route_wind_analysis(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    redis: web::Data<Arc<RedisClient>>,
+    path: web::Path<RoutingPath>,
+    query: web::Query<WindAnalysisQuery>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => {
+                    let route = match get_saved_route(&path.uuid, u.id, &data).await {
+                        Ok(route) => route,
+                        Err(e) => {
+                            return Ok(HttpResponse::NotFound().json(serde_json::json!({
+                                "error": format!("Route not found: {}", e)
+                            })))
+                        }
+                    };
+
+                    if route.route.coordinates().len() < 2 {
+                        return Ok(HttpResponse::UnprocessableEntity().json(serde_json::json!({
+                            "error": "Route has no computed geometry"
+                        })));
+                    }
+
+                    match route_wind_analysis(&redis, &route.route, &query).await {
+                        Ok(Some(analysis)) => Ok(HttpResponse::Ok().json(analysis)),
+                        Ok(None) => {
+                            Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                                "error": "No wind data available yet"
+                            })))
+                        }
+                        Err(e) => {
+                            error!("Wind analysis failed: {}", e);
+                            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                                "error": "Failed to analyse wind along the route"
+                            })))
+                        }
+                    }
+                }
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
+
+/// GET /api/route/{uuid}/best-departure?from=&to=&step=
+/// - departures within a window ranked by rain and headwind along the route
+pub async fn get_route_best_departure(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    redis: web::Data<Arc<RedisClient>>,
+    path: web::Path<RoutingPath>,
+    query: web::Query<DepartureWindowQuery>,
+) -> Result<HttpResponse> {
+    let maybe_cookie = req.cookie("auth");
+
+    match maybe_cookie {
+        Some(cook) => {
+            let user = get_user_from_api_token(cook.value().to_string(), &data).await;
+
+            match user {
+                Ok(u) => {
+                    let candidates = match query.candidates() {
+                        Ok(candidates) => candidates,
+                        Err(e) => {
+                            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                                "error": e
+                            })))
+                        }
+                    };
+
+                    let route = match get_saved_route(&path.uuid, u.id, &data).await {
+                        Ok(route) => route,
+                        Err(e) => {
+                            return Ok(HttpResponse::NotFound().json(serde_json::json!({
+                                "error": format!("Route not found: {}", e)
+                            })))
+                        }
+                    };
+
+                    if route.route.coordinates().len() < 2 {
+                        return Ok(HttpResponse::UnprocessableEntity().json(serde_json::json!({
+                            "error": "Route has no computed geometry"
+                        })));
+                    }
+
+                    match best_departures(&redis, &route.route, &candidates).await {
+                        Ok(Some(response)) => Ok(HttpResponse::Ok().json(response)),
+                        Ok(None) => {
+                            Ok(HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                                "error": "No wind data available yet"
+                            })))
+                        }
+                        Err(e) => {
+                            error!("Departure optimization failed: {}", e);
+                            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                                "error": "Failed to rank departure times"
+                            })))
+                        }
+                    }
+                }
+                Err(_) => Ok(HttpResponse::NotFound().json(serde_json::json!({
+                    "error": "User not found"
+                }))),
+            }
+        }
+        None => Ok(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Not Authenticated"
+        }))),
+    }
+}
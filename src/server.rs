@@ -1,5 +1,9 @@
 use crate::routes;
-use crate::{models::auth::AppData, utils::misc::Env};
+use crate::{
+    models::auth::AppData,
+    utils::mail::Mailer,
+    utils::misc::{redact_url_password, Env},
+};
 use actix_cors::Cors;
 use actix_governor::{Governor, GovernorConfigBuilder};
 use actix_web::{middleware, HttpServer};
@@ -7,11 +11,15 @@ use actix_web::{web, App};
 use sqlx::PgPool;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber;
 
-use crate::services::{AnthropicClient, RedisClient, Scheduler};
-use crate::utils::config::Config;
+use crate::services::{
+    AnimationCache, AnthropicClient, DigestSender, Geocoder, GeocodingCachePurger, GridCache,
+    LocalRouter, NotificationWorker, OrsClient, RedisClient, Scheduler, TileServer, TrashPurger,
+};
+use crate::utils::config::{Config, RoutingEngine};
+use crate::utils::palette::Palettes;
 
 pub async fn run(pool: PgPool, app_env: Env) -> std::io::Result<()> {
     let env_clone = app_env.clone();
@@ -30,8 +38,9 @@ pub async fn run(pool: PgPool, app_env: Env) -> std::io::Result<()> {
 
     info!("Configuration loaded:");
     info!("  Port: {}", config.port);
-    info!("  Redis URL: {}", config.redis_url);
+    info!("  Redis URL: {}", redact_url_password(&config.redis_url));
     info!("  Is Production: {}", config.is_production);
+    info!("  Trash retention: {} days", config.trash_retention_days);
 
     // Initialize Redis client
     let redis_client = Arc::new(
@@ -40,11 +49,57 @@ pub async fn run(pool: PgPool, app_env: Env) -> std::io::Result<()> {
             .expect("Failed to connect to Redis"),
     );
 
+    // Mail transport, SMTP connections are pooled for the whole process
+    let mailer = Arc::new(Mailer::new(&app_env).expect("Invalid mail configuration"));
+    info!("  Mail TLS: {:?}", app_env.mail_tls);
+
     // Initialize Anthropic client
     let anthropic_client = Arc::new(AnthropicClient::new(config.anthropic_api_key.clone()));
 
+    // Initialize OpenRouteService client (cached, quota aware)
+    let ors_client = Arc::new(OrsClient::new(
+        config.openrouteservice_token.clone(),
+        redis_client.clone(),
+    ));
+
+    // Geocoding proxy, answers cached in Postgres
+    let geocoder = Arc::new(Geocoder::new(&config));
+    info!("  Geocoding provider: {:?}", config.geocoding_provider);
+
+    // Maps rendered from the stored forecasts: palettes, decoded grids and cached tiles
+    let palettes = Arc::new(
+        Palettes::load(config.palette_dir.as_deref()).expect("Failed to load map palettes"),
+    );
+    let grid_cache = Arc::new(GridCache::new(redis_client.clone()));
+    let tile_server = Arc::new(TileServer::new(
+        grid_cache.clone(),
+        palettes.clone(),
+        &config,
+    ));
+    let animation_cache = Arc::new(AnimationCache::default());
+
+    // Load the offline road graph, used on its own or as fallback when ORS is unreachable
+    let local_router = match &config.road_graph_path {
+        Some(path) => match LocalRouter::load(path) {
+            Ok(router) => Some(Arc::new(router)),
+            Err(e) if config.routing_engine == RoutingEngine::Local => {
+                return Err(std::io::Error::other(format!(
+                    "Failed to load road graph: {:#}",
+                    e
+                )));
+            }
+            Err(e) => {
+                warn!("Offline routing fallback disabled: {:#}", e);
+                None
+            }
+        },
+        None => None,
+    };
+    info!("  Routing engine: {:?}", config.routing_engine);
+    info!("  Wind texture encoding: {:?}", config.wind_encoding);
+
     // Initialize scheduler
-    let scheduler = Scheduler::new(redis_client.clone());
+    let scheduler = Scheduler::new(redis_client.clone(), pool.clone(), config.wind_encoding);
     let scheduler = Arc::new(RwLock::new(scheduler));
 
     // Start scheduler
@@ -52,6 +107,30 @@ pub async fn run(pool: PgPool, app_env: Env) -> std::io::Result<()> {
         let scheduler = scheduler.read().await;
         scheduler.start().await;
     }
+
+    // Purge the trash once a day
+    TrashPurger::new(pool.clone(), config.trash_retention_days)
+        .start()
+        .await;
+
+    // Drop the expired geocoding answers once a day
+    GeocodingCachePurger::new(pool.clone()).start().await;
+
+    // Deliver the notification outbox
+    NotificationWorker::new(pool.clone(), mailer.clone())
+        .start()
+        .await;
+
+    // Queue the daily forecast digests
+    DigestSender::new(
+        pool.clone(),
+        redis_client.clone(),
+        anthropic_client.clone(),
+        &config,
+    )
+    .start()
+    .await;
+
     let governor_conf = if is_production {
         GovernorConfigBuilder::default()
             .per_second(60)
@@ -70,12 +149,14 @@ pub async fn run(pool: PgPool, app_env: Env) -> std::io::Result<()> {
         let cors = if is_prod == true {
             Cors::default()
                 .allowed_origin(&env_clone.http_domain)
-                .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
+                .allowed_methods(vec!["GET", "POST", "PUT", "PATCH", "DELETE"])
                 .allowed_headers(vec![
                     actix_web::http::header::AUTHORIZATION,
                     actix_web::http::header::ACCEPT,
                     actix_web::http::header::CONTENT_TYPE,
+                    actix_web::http::header::IF_MATCH,
                 ])
+                .expose_headers(vec![actix_web::http::header::ETAG])
                 .supports_credentials()
                 .max_age(3600)
         } else {
@@ -89,12 +170,21 @@ pub async fn run(pool: PgPool, app_env: Env) -> std::io::Result<()> {
             .app_data(web::Data::new(config.clone()))
             .app_data(web::Data::new(redis_client.clone()))
             .app_data(web::Data::new(anthropic_client.clone()))
+            .app_data(web::Data::new(ors_client.clone()))
+            .app_data(web::Data::new(geocoder.clone()))
+            .app_data(web::Data::new(palettes.clone()))
+            .app_data(web::Data::new(grid_cache.clone()))
+            .app_data(web::Data::new(tile_server.clone()))
+            .app_data(web::Data::new(animation_cache.clone()))
+            .app_data(web::Data::new(local_router.clone()))
             .app_data(web::Data::new(scheduler.clone()))
             .app_data(web::Data::new(AppData {
                 db: pool.clone(),
                 env: env_clone.clone(),
+                mailer: mailer.clone(),
             }))
             .route("/health", web::get().to(routes::health))
+            .route("/ready", web::get().to(routes::ready))
             .route("/oauth/gsi", web::post().to(routes::gsi))
             .service(
                 web::scope("/api")
@@ -103,18 +193,190 @@ pub async fn run(pool: PgPool, app_env: Env) -> std::io::Result<()> {
                     .route("/register", web::post().to(routes::register))
                     .route("/otc", web::post().to(routes::send_one_time_code))
                     .route("/me", web::get().to(routes::me))
+                    .route("/me/language", web::put().to(routes::update_language))
                     .route("/route", web::post().to(routes::routes::post_routing))
+                    .route(
+                        "/route/import",
+                        web::post().to(routes::route_files::import_route),
+                    )
                     .route("/route/{uuid}", web::get().to(routes::routes::get_routing))
                     .route("/route/{uuid}", web::put().to(routes::routes::put_routing))
+                    .route(
+                        "/route/{uuid}",
+                        web::patch().to(routes::routes::patch_routing),
+                    )
+                    .route(
+                        "/route/{uuid}",
+                        web::delete().to(routes::routes::delete_routing),
+                    )
+                    .route(
+                        "/route/{uuid}/restore",
+                        web::post().to(routes::routes::restore_routing),
+                    )
+                    .route(
+                        "/route/{uuid}/export",
+                        web::get().to(routes::route_files::export_route),
+                    )
+                    .route(
+                        "/route/{uuid}/wind-analysis",
+                        web::get().to(routes::wind_analysis::get_route_wind_analysis),
+                    )
+                    .route(
+                        "/route/{uuid}/best-departure",
+                        web::get().to(routes::wind_analysis::get_route_best_departure),
+                    )
+                    .route(
+                        "/route/{uuid}/revisions",
+                        web::get().to(routes::route_revisions::get_route_revisions),
+                    )
+                    .route(
+                        "/route/{uuid}/revisions/compare",
+                        web::get().to(routes::route_revisions::get_compare_route_revisions),
+                    )
+                    .route(
+                        "/route/{uuid}/revisions/{revision}",
+                        web::get().to(routes::route_revisions::get_route_revision_by_number),
+                    )
+                    .route(
+                        "/route/{uuid}/revisions/{revision}/restore",
+                        web::post().to(routes::route_revisions::post_restore_route_revision),
+                    )
+                    .route(
+                        "/route/{uuid}/folder",
+                        web::put().to(routes::folders::put_route_folder),
+                    )
+                    .route(
+                        "/route/{uuid}/tags",
+                        web::put().to(routes::tags::put_route_tags),
+                    )
+                    .route(
+                        "/route/{uuid}/shares",
+                        web::post().to(routes::shares::post_route_share),
+                    )
+                    .route(
+                        "/route/{uuid}/shares",
+                        web::get().to(routes::shares::get_route_shares),
+                    )
+                    .route(
+                        "/route/{uuid}/shares/{token}",
+                        web::delete().to(routes::shares::delete_route_share),
+                    )
+                    .route(
+                        "/shared/route/{token}",
+                        web::get().to(routes::shares::get_shared),
+                    )
                     .route(
                         "/routes",
                         web::get().to(routes::routes::get_routes_paginated),
                     )
+                    .route(
+                        "/routes/trash",
+                        web::get().to(routes::routes::get_trashed_routing),
+                    )
+                    .route(
+                        "/routes/search",
+                        web::get().to(routes::routes::get_routes_search),
+                    )
+                    .route(
+                        "/route_folders",
+                        web::get().to(routes::folders::get_folders),
+                    )
+                    .route(
+                        "/route_folders",
+                        web::post().to(routes::folders::post_folder),
+                    )
+                    .route(
+                        "/route_folders/{id}",
+                        web::put().to(routes::folders::put_folder),
+                    )
+                    .route(
+                        "/route_folders/{id}",
+                        web::delete().to(routes::folders::delete_folder),
+                    )
+                    .route("/polars", web::get().to(routes::sailing::get_polars))
+                    .route("/polars", web::post().to(routes::sailing::post_polar))
+                    .route(
+                        "/polars/{id}",
+                        web::delete().to(routes::sailing::delete_polar),
+                    )
+                    .route("/alert_rules", web::get().to(routes::alerts::get_rules))
+                    .route("/alert_rules", web::post().to(routes::alerts::post_rule))
+                    .route("/alert_rules/{id}", web::put().to(routes::alerts::put_rule))
+                    .route(
+                        "/alert_rules/{id}",
+                        web::delete().to(routes::alerts::delete_rule),
+                    )
+                    .route("/alert_events", web::get().to(routes::alerts::get_events))
+                    .route(
+                        "/notifications",
+                        web::get().to(routes::notifications::get_outbox),
+                    )
+                    .route(
+                        "/notifications/deliveries",
+                        web::get().to(routes::notifications::get_deliveries),
+                    )
+                    .route(
+                        "/notifications/{id}/retry",
+                        web::post().to(routes::notifications::post_retry),
+                    )
+                    .route("/digest", web::get().to(routes::digest::get_digest))
+                    .route("/digest", web::put().to(routes::digest::put_digest))
+                    .route(
+                        "/digest/unsubscribe",
+                        web::get().to(routes::digest::unsubscribe_form),
+                    )
+                    .route(
+                        "/digest/unsubscribe",
+                        web::post().to(routes::digest::unsubscribe),
+                    )
+                    .route("/webhooks", web::get().to(routes::notifications::get_hooks))
+                    .route(
+                        "/webhooks",
+                        web::post().to(routes::notifications::post_hook),
+                    )
+                    .route(
+                        "/webhooks/{id}",
+                        web::delete().to(routes::notifications::delete_hook),
+                    )
+                    .route("/route_tags", web::get().to(routes::tags::get_tags))
+                    .route(
+                        "/route_tags/{id}",
+                        web::delete().to(routes::tags::delete_tag),
+                    )
                     .route("/prefered_addresses", web::get().to(routes::fetch_adresses))
                     .route("/prefered_addresses", web::post().to(routes::save_address))
+                    .route(
+                        "/prefered_addresses/by_travel_time",
+                        web::get().to(routes::fetch_adresses_by_travel_time),
+                    )
+                    .route(
+                        "/prefered_addresses/nearest",
+                        web::get().to(routes::fetch_nearest_adresses),
+                    )
+                    .route(
+                        "/prefered_addresses/trash",
+                        web::get().to(routes::fetch_trashed_adresses),
+                    )
+                    .route(
+                        "/prefered_addresses/order",
+                        web::put().to(routes::reorder_addresses),
+                    )
+                    .route(
+                        "/prefered_addresses/{id}",
+                        web::put().to(routes::update_address),
+                    )
                     .route(
                         "/prefered_addresses/{id}",
-                        web::delete().to(routes::delete_prefered_adress),
+                        web::delete().to(routes::delete_prefered_address),
+                    )
+                    // Misspelled path of older clients
+                    .route(
+                        "/prefered_adresses/{id}",
+                        web::delete().to(routes::delete_prefered_address),
+                    )
+                    .route(
+                        "/prefered_addresses/{id}/restore",
+                        web::post().to(routes::restore_prefered_address),
                     )
                     // Weather routes
                     .service(routes::weather::get_weather)
@@ -130,19 +392,38 @@ pub async fn run(pool: PgPool, app_env: Env) -> std::io::Result<()> {
                     .service(routes::windgl::get_windgl_metadata_by_index)
                     .service(routes::windgl::get_windgl_png)
                     .service(routes::windgl::get_windgl_png_by_index)
+                    .service(routes::precipgl::get_precipgl_metadata)
+                    .service(routes::precipgl::get_precipgl_metadata_by_index)
+                    .service(routes::precipgl::get_precipgl_png)
+                    .service(routes::precipgl::get_precipgl_png_by_index)
+                    .service(routes::tiles::get_tile)
+                    .service(routes::heatmap::get_heatmap)
                     // AI routes (with rate limiting in production)
                     .service(
                         web::scope("")
                             .wrap(Governor::new(&governor_conf))
+                            .service(routes::animation::get_animation)
+                            .route(
+                                "/sailing/route",
+                                web::post().to(routes::sailing::post_sailing_route),
+                            )
                             .service(routes::ai::post_weather_summary)
                             .service(routes::ai::post_chart_analysis)
                             .service(routes::routing::post_routing)
+                            .service(routes::routing::post_isochrones)
+                            .service(routes::routing::post_matrix)
+                            .service(routes::geocoding::get_geocode)
+                            .service(routes::geocoding::get_reverse_geocode)
                             .service(routes::scheduler::get_wind_status)
                             .service(routes::scheduler::post_wind_refresh)
                             .service(routes::scheduler::post_wind_refresh_latest),
                     ), // Routing routes
                        // Scheduler routes
             )
+            .route(
+                "/share/route/{token}",
+                web::get().to(routes::shares::get_shared_preview),
+            )
             .route("/{filename:.*\\.[^/]+}", web::get().to(routes::serve))
             .default_service(web::to(routes::index)) // Matches any path not matched by other routes
     })
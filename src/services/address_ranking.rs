@@ -0,0 +1,168 @@
+use serde::Serialize;
+use std::cmp::Ordering;
+
+use crate::models::{prefered_address::PreferedAddress, RouteProfile};
+use crate::services::{OrsClient, OrsError, TravelTime};
+use crate::utils::geo::haversine_distance;
+
+/// A preferred address with the travel needed to reach it
+#[derive(Serialize)]
+pub struct RankedAddress {
+    #[serde(flatten)]
+    pub address: PreferedAddress,
+    /// `None` when the address has no usable coordinates or cannot be reached
+    pub travel: Option<TravelTime>,
+}
+
+/// A preferred address with its straight-line distance to a position
+#[derive(Serialize)]
+pub struct NearbyAddress {
+    #[serde(flatten)]
+    pub address: PreferedAddress,
+    /// Meters
+    pub distance: f64,
+}
+
+/// `[lon, lat]` of an address, as ORS expects them
+fn address_location(address: &PreferedAddress) -> Option<[f64; 2]> {
+    Some([address.lng?, address.lat?])
+}
+
+/// The `limit` addresses closest to (lat, lng) as the crow flies, skipping those without
+/// coordinates
+pub fn nearest_prefered_addresses(
+    addresses: Vec<PreferedAddress>,
+    lat: f64,
+    lng: f64,
+    limit: usize,
+) -> Vec<NearbyAddress> {
+    let mut nearby: Vec<NearbyAddress> = addresses
+        .into_iter()
+        .filter_map(|address| {
+            let [address_lng, address_lat] = address_location(&address)?;
+            let distance = haversine_distance(lat, lng, address_lat, address_lng);
+            Some(NearbyAddress { address, distance })
+        })
+        .collect();
+
+    nearby.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+    nearby.truncate(limit);
+    nearby
+}
+
+/// Closest first, addresses without travel time last
+fn sort_by_travel_time(ranked: &mut [RankedAddress]) {
+    ranked.sort_by(|a, b| match (&a.travel, &b.travel) {
+        (Some(a), Some(b)) => a
+            .duration
+            .partial_cmp(&b.duration)
+            .unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+}
+
+/// Rank `addresses` by travel time from `origin` (`[lon, lat]`) with one ORS matrix lookup
+pub async fn rank_prefered_addresses(
+    ors: &OrsClient,
+    profile: RouteProfile,
+    origin: [f64; 2],
+    addresses: Vec<PreferedAddress>,
+) -> Result<Vec<RankedAddress>, OrsError> {
+    let locations: Vec<Option<[f64; 2]>> = addresses.iter().map(address_location).collect();
+    let destinations: Vec<[f64; 2]> = locations.iter().flatten().copied().collect();
+
+    let mut times = if destinations.is_empty() {
+        Vec::new()
+    } else {
+        ors.travel_times(profile.as_str(), origin, &destinations)
+            .await?
+    }
+    .into_iter();
+
+    let mut ranked: Vec<RankedAddress> = addresses
+        .into_iter()
+        .zip(locations)
+        .map(|(address, location)| RankedAddress {
+            address,
+            travel: location.and_then(|_| times.next().flatten()),
+        })
+        .collect();
+
+    sort_by_travel_time(&mut ranked);
+    Ok(ranked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn address(id: i64, lat: Option<f64>, lng: Option<f64>) -> PreferedAddress {
+        PreferedAddress {
+            id,
+            address_text: None,
+            lat,
+            lng,
+            user_id: 1,
+            name: format!("address {}", id),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+            sort_order: id as i32,
+        }
+    }
+
+    #[test]
+    fn test_address_location() {
+        assert_eq!(
+            address_location(&address(1, Some(48.85), Some(2.35))),
+            Some([2.35, 48.85])
+        );
+        assert_eq!(address_location(&address(3, None, Some(2.35))), None);
+    }
+
+    #[test]
+    fn test_nearest_prefered_addresses() {
+        let addresses = vec![
+            address(1, Some(48.86), Some(2.35)),
+            address(2, None, None),
+            address(3, Some(45.76), Some(4.84)),
+            address(4, Some(48.85), Some(2.34)),
+        ];
+
+        let nearest = nearest_prefered_addresses(addresses, 48.85, 2.35, 2);
+        let ids: Vec<i64> = nearest.iter().map(|n| n.address.id).collect();
+        assert_eq!(ids, vec![4, 1]);
+        assert!(nearest[0].distance < nearest[1].distance);
+    }
+
+    #[test]
+    fn test_sort_by_travel_time() {
+        let travel = |duration| {
+            Some(TravelTime {
+                duration,
+                distance: duration * 10.0,
+            })
+        };
+        let mut ranked = vec![
+            RankedAddress {
+                address: address(1, None, None),
+                travel: None,
+            },
+            RankedAddress {
+                address: address(2, None, None),
+                travel: travel(900.0),
+            },
+            RankedAddress {
+                address: address(3, None, None),
+                travel: travel(300.0),
+            },
+        ];
+
+        sort_by_travel_time(&mut ranked);
+        let ids: Vec<i64> = ranked.iter().map(|r| r.address.id).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+    }
+}
@@ -0,0 +1,275 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+use crate::models::{
+    AlertMetric, AlertRule, AlertTargetRow, RouteDocument, MAX_ALERT_WITHIN_HOURS,
+};
+use crate::services::{
+    load_weather_timeline, RedisClient, PRECIPITATION_POINTS_KEY, WIND_POINTS_KEY,
+};
+use crate::utils::geo::haversine_distance;
+use crate::utils::queries::{
+    get_alert_targets, get_enabled_alert_rules, open_alert_event, resolve_alert_event,
+};
+use crate::utils::weather_grid::{WeatherGrid, WIND_FIELDS};
+
+const MS_TO_KMH: f64 = 3.6;
+/// GFS frames are 3 hours apart, an older one no longer describes current conditions
+const CURRENT_TOLERANCE_HOURS: i64 = 3;
+/// Meters between the route positions checked, the grids are 0.5° (about 50 km)
+const ROUTE_SAMPLE_SPACING: f64 = 10_000.0;
+
+/// Outcome of one evaluation pass
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AlertRun {
+    pub checked: usize,
+    pub fired: usize,
+    pub resolved: usize,
+}
+
+/// Worst value of a rule meeting its condition, and where and when it was found
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlertObservation {
+    pub data_time: DateTime<Utc>,
+    pub value: f64,
+    pub lat: f64,
+    pub lng: f64,
+}
+
+/// Check every enabled rule against the indexed grids.
+/// A rule whose condition holds opens an event, unless one is already open; an open event
+/// is resolved once the condition stops holding. Rules without data in their window are
+/// left as they are.
+pub async fn evaluate_alert_rules(redis: &RedisClient, pool: &PgPool) -> Result<AlertRun> {
+    let rules = get_enabled_alert_rules(pool).await?;
+    if rules.is_empty() {
+        return Ok(AlertRun::default());
+    }
+    let mut targets: HashMap<i64, AlertTargetRow> = get_alert_targets(pool)
+        .await?
+        .into_iter()
+        .map(|target| (target.rule_id, target))
+        .collect();
+
+    let now = Utc::now();
+    let from = now - Duration::hours(CURRENT_TOLERANCE_HOURS);
+    let to = now + Duration::hours(MAX_ALERT_WITHIN_HOURS as i64);
+    let wind = load_weather_timeline(redis, WIND_POINTS_KEY, &WIND_FIELDS, from, to).await?;
+    let rain = load_weather_timeline(redis, PRECIPITATION_POINTS_KEY, &["rate"], from, to).await?;
+
+    let mut run = AlertRun::default();
+    for rule in &rules {
+        let timeline = match rule.metric {
+            AlertMetric::RainRate => rain.as_ref(),
+            AlertMetric::WindSpeed | AlertMetric::Gusts => wind.as_ref(),
+        };
+        let until = now + Duration::hours(rule.within_hours as i64);
+        let frames: Vec<(DateTime<Utc>, &WeatherGrid)> = timeline
+            .map(|t| t.frames())
+            .unwrap_or_default()
+            .iter()
+            .filter(|(time, _)| *time >= from && *time <= until)
+            .map(|(time, grid)| (*time, grid))
+            .collect();
+        if frames.is_empty() {
+            continue;
+        }
+
+        let target = targets.remove(&rule.id);
+        let positions = target.as_ref().map(target_positions).unwrap_or_default();
+        run.checked += 1;
+
+        match evaluate_rule(rule, &positions, &frames) {
+            Some(found) => {
+                let target_name = target.as_ref().and_then(|t| t.name.as_deref());
+                if open_alert_event(
+                    rule,
+                    found.data_time,
+                    found.value,
+                    found.lat,
+                    found.lng,
+                    target_name,
+                    pool,
+                )
+                .await?
+                .is_some()
+                {
+                    info!(
+                        "Alert rule {} fired: {} {} {} ({:.1})",
+                        rule.id,
+                        rule.metric.as_str(),
+                        rule.comparison.as_str(),
+                        rule.threshold,
+                        found.value
+                    );
+                    run.fired += 1;
+                }
+            }
+            None => {
+                if resolve_alert_event(rule.id, pool).await? {
+                    run.resolved += 1;
+                }
+            }
+        }
+    }
+
+    Ok(run)
+}
+
+/// `(lat, lon)` watched for a target: the address itself, or positions about
+/// `ROUTE_SAMPLE_SPACING` apart along a route (its waypoints when it was never computed)
+pub fn target_positions(target: &AlertTargetRow) -> Vec<(f64, f64)> {
+    if let (Some(lat), Some(lng)) = (target.lat, target.lng) {
+        return vec![(lat, lng)];
+    }
+    let Some(route) = &target.route else {
+        return Vec::new();
+    };
+    let document = match RouteDocument::from_value(route.clone()) {
+        Ok(document) => document,
+        Err(e) => {
+            warn!("Alert rule {}: unreadable route: {}", target.rule_id, e);
+            return Vec::new();
+        }
+    };
+
+    let coordinates: Vec<(f64, f64)> = document
+        .coordinates()
+        .iter()
+        .filter(|c| c.len() >= 2)
+        .map(|c| (c[1], c[0]))
+        .collect();
+    if coordinates.is_empty() {
+        return document.points().iter().map(|p| (p.lat, p.lon)).collect();
+    }
+
+    let mut positions = vec![coordinates[0]];
+    let mut since_last = 0.0;
+    for pair in coordinates.windows(2) {
+        since_last += haversine_distance(pair[0].0, pair[0].1, pair[1].0, pair[1].1);
+        if since_last >= ROUTE_SAMPLE_SPACING {
+            positions.push(pair[1]);
+            since_last = 0.0;
+        }
+    }
+    if since_last > 0.0 {
+        positions.extend(coordinates.last().copied());
+    }
+    positions
+}
+
+/// Value of the rule's metric in a sample of `u`, `v`, `gusts` (m/s) or `rate` (mm/h)
+fn metric_value(metric: AlertMetric, sample: &[f64]) -> Option<f64> {
+    let value = match metric {
+        AlertMetric::WindSpeed => sample.first()?.hypot(*sample.get(1)?) * MS_TO_KMH,
+        AlertMetric::Gusts => sample.get(2)? * MS_TO_KMH,
+        AlertMetric::RainRate => *sample.first()?,
+    };
+    value.is_finite().then_some(value)
+}
+
+/// Worst value meeting the rule's condition over every position and frame,
+/// `None` when the condition holds nowhere
+pub fn evaluate_rule(
+    rule: &AlertRule,
+    positions: &[(f64, f64)],
+    frames: &[(DateTime<Utc>, &WeatherGrid)],
+) -> Option<AlertObservation> {
+    let mut worst: Option<AlertObservation> = None;
+    for (time, grid) in frames {
+        for &(lat, lng) in positions {
+            let Some(value) = grid
+                .sample(lat, lng)
+                .and_then(|sample| metric_value(rule.metric, &sample))
+            else {
+                continue;
+            };
+            if rule.comparison.matches(value, rule.threshold)
+                && worst.is_none_or(|w| rule.comparison.matches(value, w.value))
+            {
+                worst = Some(AlertObservation {
+                    data_time: *time,
+                    value,
+                    lat,
+                    lng,
+                });
+            }
+        }
+    }
+    worst
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AlertComparison, AlertCondition};
+
+    fn rule(metric: AlertMetric, comparison: AlertComparison, threshold: f64) -> AlertRule {
+        AlertRule {
+            id: 1,
+            user_id: 1,
+            prefered_address_id: Some(1),
+            route_uuid: None,
+            metric,
+            comparison,
+            threshold,
+            within_hours: 0,
+            name: None,
+            enabled: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_evaluate_rule() {
+        // 10 m/s (36 km/h) gusting 15 m/s (54 km/h) west of lon 1,
+        // 15 m/s (54 km/h) gusting 25 m/s (90 km/h) from lon 1
+        let mut points = Vec::new();
+        for lat in [0.0, 1.0] {
+            for lon in [0.0, 1.0, 2.0] {
+                let (u, gusts) = if lon >= 1.0 {
+                    (15.0, 25.0)
+                } else {
+                    (10.0, 15.0)
+                };
+                points.push(serde_json::json!({
+                    "lat": lat, "lon": lon, "u": u, "v": 0.0, "gusts": gusts
+                }));
+            }
+        }
+        let grid = WeatherGrid::from_points(&serde_json::json!({ "points": points }), &WIND_FIELDS)
+            .unwrap();
+        let time = Utc::now();
+        let frames = [(time, &grid)];
+        let positions = [(0.0, 0.0), (0.5, 2.0)];
+
+        let found = evaluate_rule(
+            &rule(AlertMetric::WindSpeed, AlertComparison::Above, 40.0),
+            &positions,
+            &frames,
+        )
+        .unwrap();
+        assert!((found.value - 54.0).abs() < 1e-9);
+        assert_eq!((found.lat, found.lng, found.data_time), (0.5, 2.0, time));
+
+        let calm = rule(AlertMetric::WindSpeed, AlertComparison::Below, 40.0);
+        assert_eq!(evaluate_rule(&calm, &positions, &frames).unwrap().lng, 0.0);
+
+        let gusts = rule(AlertMetric::Gusts, AlertComparison::Above, 72.0);
+        let found = evaluate_rule(&gusts, &positions, &frames).unwrap();
+        assert!((found.value - 90.0).abs() < 1e-9);
+        assert_eq!(found.lng, 2.0);
+        let storm = rule(AlertMetric::Gusts, AlertComparison::Above, 100.0);
+        assert!(evaluate_rule(&storm, &positions, &frames).is_none());
+        assert!(evaluate_rule(&gusts, &[], &frames).is_none());
+
+        let condition: AlertCondition =
+            serde_json::from_value(serde_json::json!({ "metric": "gusts", "threshold": 50.0 }))
+                .unwrap();
+        assert!(condition.validate().is_ok());
+    }
+}
@@ -0,0 +1,73 @@
+use hashlink::LruCache;
+use std::sync::Mutex;
+
+use crate::models::Bbox;
+use crate::services::GridKey;
+use crate::utils::animation::AnimationFormat;
+use crate::utils::heatmap::HeatmapLayer;
+
+/// Encoded animations kept in memory, up to a few MB each
+const ANIMATION_CACHE_SIZE: usize = 16;
+
+/// Everything the pixels of an animation depend on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AnimationKey {
+    layer: HeatmapLayer,
+    format: AnimationFormat,
+    palette: String,
+    /// Bounds of the bbox as bits, floats cannot be hashed
+    bbox: [u64; 4],
+    width: u32,
+    height: u32,
+    legend: bool,
+    /// Forecasts shown, with the time they were stored
+    frames: Vec<GridKey>,
+}
+
+impl AnimationKey {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        layer: HeatmapLayer,
+        format: AnimationFormat,
+        palette: &str,
+        bbox: &Bbox,
+        width: u32,
+        height: u32,
+        legend: bool,
+        frames: Vec<GridKey>,
+    ) -> Self {
+        Self {
+            layer,
+            format,
+            palette: palette.to_string(),
+            bbox: [bbox.west, bbox.south, bbox.east, bbox.north].map(f64::to_bits),
+            width,
+            height,
+            legend,
+            frames,
+        }
+    }
+}
+
+/// Last animations encoded, the same timelapse is often shared and opened many times
+pub struct AnimationCache {
+    animations: Mutex<LruCache<AnimationKey, Vec<u8>>>,
+}
+
+impl Default for AnimationCache {
+    fn default() -> Self {
+        Self {
+            animations: Mutex::new(LruCache::new(ANIMATION_CACHE_SIZE)),
+        }
+    }
+}
+
+impl AnimationCache {
+    pub fn get(&self, key: &AnimationKey) -> Option<Vec<u8>> {
+        self.animations.lock().unwrap().get(key).cloned()
+    }
+
+    pub fn insert(&self, key: AnimationKey, animation: Vec<u8>) {
+        self.animations.lock().unwrap().insert(key, animation);
+    }
+}
@@ -0,0 +1,278 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use tracing::warn;
+
+use crate::models::{
+    BestDepartureResponse, DepartureOption, RainExposure, RouteDocument, WindExposureSummary,
+    WindSegment, WindTotals,
+};
+use crate::services::{
+    analyze_wind, load_weather_timeline, RedisClient, PRECIPITATION_POINTS_KEY, WIND_POINTS_KEY,
+};
+use crate::utils::geo::polyline_length;
+use crate::utils::route_formats::position_times;
+use crate::utils::weather_grid::{round_to, WeatherTimeline, WIND_FIELDS};
+
+/// Coarser than the wind analysis, every candidate samples the whole route
+const SCORING_SEGMENT_LENGTH: f64 = 2000.0;
+/// Below this rate (mm/h) precipitation is only a trace
+const WET_RATE: f64 = 0.1;
+/// Crosswind (m/s) from which riding gets uncomfortable
+const STRONG_CROSSWIND: f64 = 8.0;
+const MAX_RAIN_PENALTY: f64 = 60.0;
+const MAX_WIND_PENALTY: f64 = 40.0;
+const UNCOVERED_PENALTY: f64 = 15.0;
+
+/// Score every candidate departure of `document` and rank them, best first.
+/// Returns `None` when no indexed wind grid is available.
+pub async fn best_departures(
+    redis: &RedisClient,
+    document: &RouteDocument,
+    candidates: &[DateTime<Utc>],
+) -> Result<Option<BestDepartureResponse>> {
+    let (Some(first), Some(last)) = (candidates.first(), candidates.last()) else {
+        return Ok(None);
+    };
+
+    let base_times = position_times(document, *first);
+    let duration = base_times
+        .last()
+        .map(|arrival| *arrival - *first)
+        .unwrap_or_default();
+    let window_end = *last + duration;
+
+    let Some(wind) =
+        load_weather_timeline(redis, WIND_POINTS_KEY, &WIND_FIELDS, *first, window_end).await?
+    else {
+        return Ok(None);
+    };
+
+    // Wind alone still gives a useful ranking
+    let precipitation = match load_weather_timeline(
+        redis,
+        PRECIPITATION_POINTS_KEY,
+        &["rate"],
+        *first,
+        window_end,
+    )
+    .await
+    {
+        Ok(timeline) => timeline,
+        Err(e) => {
+            warn!("Scoring departures without precipitation: {}", e);
+            None
+        }
+    };
+
+    let coordinates = document.coordinates();
+    let total_distance = polyline_length(coordinates);
+    let seconds_per_meter = if total_distance > 0.0 {
+        duration.num_milliseconds() as f64 / 1000.0 / total_distance
+    } else {
+        0.0
+    };
+
+    let mut options: Vec<DepartureOption> = candidates
+        .iter()
+        .map(|departure| {
+            let shift = *departure - *first;
+            let times: Vec<DateTime<Utc>> = base_times.iter().map(|t| *t + shift).collect();
+            let arrival = times.last().copied().unwrap_or(*departure);
+
+            let analysis =
+                analyze_wind(coordinates, &times, &wind, None, SCORING_SEGMENT_LENGTH, 0);
+            let rain = rain_exposure(
+                &analysis.segments,
+                precipitation.as_ref(),
+                seconds_per_meter,
+            );
+            let forecast_coverage = analysis.forecast_coverage
+                && precipitation
+                    .as_ref()
+                    .is_none_or(|p| p.covers(*departure) && p.covers(arrival));
+            let (score, explanations) = score_departure(&analysis.totals, &rain, forecast_coverage);
+
+            DepartureOption {
+                rank: 0,
+                departure: *departure,
+                arrival,
+                score,
+                rain,
+                wind: WindExposureSummary {
+                    mean_headwind: analysis.totals.mean_headwind,
+                    headwind_distance: analysis.totals.headwind_distance,
+                    tailwind_distance: analysis.totals.tailwind_distance,
+                    max_crosswind: analysis.totals.max_crosswind,
+                },
+                forecast_coverage,
+                explanations,
+            }
+        })
+        .collect();
+
+    options.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(a.departure.cmp(&b.departure))
+    });
+    for (rank, option) in options.iter_mut().enumerate() {
+        option.rank = rank + 1;
+    }
+
+    Ok(Some(BestDepartureResponse {
+        duration: round_to(duration.num_milliseconds() as f64 / 1000.0, 1),
+        data_times: wind.times(),
+        options,
+    }))
+}
+
+/// Precipitation sampled in the middle of each segment at its passage time
+fn rain_exposure(
+    segments: &[WindSegment],
+    precipitation: Option<&WeatherTimeline>,
+    seconds_per_meter: f64,
+) -> RainExposure {
+    let Some(precipitation) = precipitation else {
+        return RainExposure::default();
+    };
+
+    let mut exposure = RainExposure::default();
+    for segment in segments {
+        let lon = (segment.start[0] + segment.end[0]) / 2.0;
+        let lat = (segment.start[1] + segment.end[1]) / 2.0;
+        let Some(rate) = precipitation
+            .sample(lat, lon, segment.time)
+            .and_then(|values| values.first().copied())
+            .filter(|rate| rate.is_finite())
+        else {
+            continue;
+        };
+        let rate = rate.max(0.0);
+
+        exposure.max_rate = exposure.max_rate.max(rate);
+        if rate >= WET_RATE {
+            exposure.wet_distance += segment.distance;
+        }
+        exposure.expected_rain += rate * segment.distance * seconds_per_meter / 3600.0;
+    }
+
+    RainExposure {
+        max_rate: round_to(exposure.max_rate, 1),
+        wet_distance: round_to(exposure.wet_distance, 1),
+        expected_rain: round_to(exposure.expected_rain, 1),
+    }
+}
+
+/// Score out of 100 with the reasons behind it.
+/// Rain weighs up to 60 points, headwind and strong crosswind up to 40.
+pub fn score_departure(
+    wind: &WindTotals,
+    rain: &RainExposure,
+    forecast_coverage: bool,
+) -> (f64, Vec<String>) {
+    let share = |distance: f64| {
+        if wind.distance > 0.0 {
+            (distance / wind.distance).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    };
+    let wet_share = share(rain.wet_distance);
+    let headwind_share = share(wind.headwind_distance);
+
+    let rain_penalty = (rain.expected_rain * 15.0
+        + wet_share * 25.0
+        + if rain.max_rate > 2.0 { 10.0 } else { 0.0 })
+    .min(MAX_RAIN_PENALTY);
+    let wind_penalty = (wind.mean_headwind.max(0.0) * 5.0
+        + headwind_share * 10.0
+        + (wind.max_crosswind - STRONG_CROSSWIND).max(0.0) * 2.0)
+        .min(MAX_WIND_PENALTY);
+
+    let mut explanations = Vec::new();
+    if rain.wet_distance > 0.0 {
+        explanations.push(format!(
+            "Rain on {:.0}% of the route, up to {:.1} mm/h (about {:.1} mm in total)",
+            wet_share * 100.0,
+            rain.max_rate,
+            rain.expected_rain
+        ));
+    } else {
+        explanations.push("Dry along the whole route".to_string());
+    }
+
+    if wind.mean_headwind > 0.5 {
+        explanations.push(format!(
+            "Mostly headwind, {:.1} m/s on average, against you on {:.0}% of the route",
+            wind.mean_headwind,
+            headwind_share * 100.0
+        ));
+    } else if wind.mean_headwind < -0.5 {
+        explanations.push(format!(
+            "Mostly tailwind, {:.1} m/s on average",
+            -wind.mean_headwind
+        ));
+    } else {
+        explanations.push("Little head or tailwind overall".to_string());
+    }
+
+    if wind.max_crosswind >= STRONG_CROSSWIND {
+        explanations.push(format!(
+            "Strong crosswind up to {:.1} m/s",
+            wind.max_crosswind
+        ));
+    }
+
+    let mut score = 100.0 - rain_penalty - wind_penalty;
+    if !forecast_coverage {
+        score -= UNCOVERED_PENALTY;
+        explanations.push("Partly beyond the available forecasts, less reliable".to_string());
+    }
+
+    (round_to(score.max(0.0), 1), explanations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn totals(mean_headwind: f64, headwind_distance: f64, max_crosswind: f64) -> WindTotals {
+        WindTotals {
+            distance: 20_000.0,
+            headwind_distance,
+            tailwind_distance: 20_000.0 - headwind_distance,
+            mean_headwind,
+            max_crosswind,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_dry_tailwind_beats_wet_headwind() {
+        let dry = RainExposure::default();
+        let wet = RainExposure {
+            max_rate: 3.0,
+            wet_distance: 10_000.0,
+            expected_rain: 1.2,
+        };
+
+        let (best, reasons) = score_departure(&totals(-2.0, 0.0, 1.0), &dry, true);
+        assert_eq!(best, 100.0);
+        assert_eq!(
+            reasons,
+            vec![
+                "Dry along the whole route",
+                "Mostly tailwind, 2.0 m/s on average"
+            ]
+        );
+
+        let (worst, reasons) = score_departure(&totals(4.0, 16_000.0, 10.0), &wet, true);
+        assert!(worst < 30.0);
+        assert!(reasons[0].starts_with("Rain on 50% of the route"));
+        assert!(reasons[2].starts_with("Strong crosswind"));
+
+        let (uncovered, reasons) = score_departure(&totals(-2.0, 0.0, 1.0), &dry, false);
+        assert_eq!(uncovered, 100.0 - UNCOVERED_PENALTY);
+        assert_eq!(reasons.len(), 3);
+    }
+}
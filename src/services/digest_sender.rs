@@ -0,0 +1,331 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use crate::models::{DigestDay, DigestPayload, DigestPlace, DigestSubscription, MAX_DIGEST_DAYS};
+use crate::services::{
+    load_weather_timeline, AnthropicClient, RedisClient, PRECIPITATION_POINTS_KEY, WIND_POINTS_KEY,
+};
+use crate::utils::config::Config;
+use crate::utils::queries::{get_digest_addresses, get_enabled_digest_subscriptions, queue_digest};
+use crate::utils::unsubscribe::unsubscribe_token;
+use crate::utils::weather_grid::{WeatherTimeline, WIND_FIELDS};
+
+const MS_TO_KMH: f64 = 3.6;
+/// Hours a precipitation frame stands for when it has no successor, GFS frames are 3 hours apart
+const DEFAULT_FRAME_HOURS: f64 = 3.0;
+
+/// Queues the daily forecast digests once each user's send time has passed.
+/// The emails themselves go through the notification outbox.
+#[derive(Clone)]
+pub struct DigestSender {
+    pool: PgPool,
+    redis: Arc<RedisClient>,
+    anthropic: Arc<AnthropicClient>,
+    public_url: String,
+    unsubscribe_secret: String,
+}
+
+impl DigestSender {
+    pub fn new(
+        pool: PgPool,
+        redis: Arc<RedisClient>,
+        anthropic: Arc<AnthropicClient>,
+        config: &Config,
+    ) -> Self {
+        Self {
+            pool,
+            redis,
+            anthropic,
+            public_url: config.public_url.clone(),
+            unsubscribe_secret: config.unsubscribe_secret.clone(),
+        }
+    }
+
+    /// Start the job, checking for due digests every 5 minutes
+    pub async fn start(&self) {
+        info!("Starting digest job (every 5 minutes)");
+
+        let sender = self.clone();
+
+        tokio::spawn(async move {
+            use tokio_cron_scheduler::{Job, JobScheduler};
+
+            let sched = JobScheduler::new().await.unwrap();
+
+            let job = Job::new_async("0 */5 * * * *", move |_uuid, _l| {
+                let sender = sender.clone();
+
+                Box::pin(async move {
+                    match sender.send_due().await {
+                        Ok(0) => {}
+                        Ok(count) => info!("Queued {} digests", count),
+                        Err(e) => error!("Digest job failed: {}", e),
+                    }
+                })
+            })
+            .unwrap();
+
+            sched.add(job).await.unwrap();
+            sched.start().await.unwrap();
+
+            // Keep the scheduler running
+            loop {
+                tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            }
+        });
+    }
+
+    /// Queue the digest of every subscription that is due, returns how many were queued
+    pub async fn send_due(&self) -> Result<usize> {
+        let now = Utc::now();
+        let due: Vec<(DigestSubscription, Tz, NaiveDate)> =
+            get_enabled_digest_subscriptions(&self.pool)
+                .await?
+                .into_iter()
+                .filter_map(|subscription| {
+                    let (tz, date) = digest_due(&subscription, now)?;
+                    Some((subscription, tz, date))
+                })
+                .collect();
+        if due.is_empty() {
+            return Ok(0);
+        }
+
+        // Local days start up to 14 hours before UTC ones
+        let from = now - Duration::hours(24);
+        let to = now + Duration::days(MAX_DIGEST_DAYS as i64 + 1);
+        let wind =
+            load_weather_timeline(&self.redis, WIND_POINTS_KEY, &WIND_FIELDS, from, to).await?;
+        let rain =
+            load_weather_timeline(&self.redis, PRECIPITATION_POINTS_KEY, &["rate"], from, to)
+                .await?;
+
+        let mut queued = 0;
+        for (subscription, tz, date) in &due {
+            match self
+                .queue_one(subscription, *tz, *date, wind.as_ref(), rain.as_ref())
+                .await
+            {
+                Ok(true) => queued += 1,
+                Ok(false) => {}
+                Err(e) => error!("Digest of user {} failed: {}", subscription.user_id, e),
+            }
+        }
+        Ok(queued)
+    }
+
+    async fn queue_one(
+        &self,
+        subscription: &DigestSubscription,
+        tz: Tz,
+        date: NaiveDate,
+        wind: Option<&WeatherTimeline>,
+        rain: Option<&WeatherTimeline>,
+    ) -> Result<bool> {
+        let places: Vec<DigestPlace> = get_digest_addresses(subscription.user_id, &self.pool)
+            .await?
+            .into_iter()
+            .filter_map(|address| {
+                let days = summarize_days(
+                    address.lat?,
+                    address.lng?,
+                    tz,
+                    date,
+                    subscription.days,
+                    wind,
+                    rain,
+                );
+                Some(DigestPlace {
+                    name: address.name,
+                    address_text: address.address_text,
+                    days,
+                })
+            })
+            .collect();
+
+        let summary = if subscription.ai_summary && !places.is_empty() {
+            let data = serde_json::to_string(&places)?;
+            match self.anthropic.generate_weather_summary(&data).await {
+                Ok(summary) => Some(summary),
+                Err(e) => {
+                    warn!(
+                        "Digest of user {} sent without summary: {}",
+                        subscription.user_id, e
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let payload = DigestPayload {
+            date,
+            places,
+            summary,
+            unsubscribe_url: format!(
+                "{}/api/digest/unsubscribe?token={}",
+                self.public_url,
+                unsubscribe_token(&self.unsubscribe_secret, subscription.user_id, Utc::now())
+            ),
+        };
+        Ok(queue_digest(
+            subscription.user_id,
+            date,
+            &serde_json::to_value(&payload)?,
+            &self.pool,
+        )
+        .await?)
+    }
+}
+
+/// Time zone and local date of a digest to send now, `None` before the send time
+/// or when today's digest already went out
+pub fn digest_due(
+    subscription: &DigestSubscription,
+    now: DateTime<Utc>,
+) -> Option<(Tz, NaiveDate)> {
+    let tz: Tz = subscription.timezone.parse().ok()?;
+    let local = now.with_timezone(&tz);
+    let date = local.date_naive();
+    let due = local.time() >= subscription.send_at
+        && subscription.last_sent_on.is_none_or(|sent| sent < date);
+    due.then_some((tz, date))
+}
+
+/// Wind and precipitation at (lat, lng) over `days` local days from `first_day`.
+/// A value is `None` when no frame falls on that day.
+pub fn summarize_days(
+    lat: f64,
+    lng: f64,
+    tz: Tz,
+    first_day: NaiveDate,
+    days: i32,
+    wind: Option<&WeatherTimeline>,
+    rain: Option<&WeatherTimeline>,
+) -> Vec<DigestDay> {
+    let mut summary: Vec<DigestDay> = first_day
+        .iter_days()
+        .take(days.max(0) as usize)
+        .map(|date| DigestDay {
+            date,
+            wind_max: None,
+            gusts_max: None,
+            rain_total: None,
+        })
+        .collect();
+    let count = summary.len();
+    let day_of = |time: &DateTime<Utc>| {
+        let date = time.with_timezone(&tz).date_naive();
+        let offset = (date - first_day).num_days();
+        usize::try_from(offset).ok().filter(|&i| i < count)
+    };
+
+    for (time, grid) in wind.map(|t| t.frames()).unwrap_or_default() {
+        let (Some(i), Some(sample)) = (day_of(time), grid.sample(lat, lng)) else {
+            continue;
+        };
+        let speed = sample[0].hypot(sample[1]) * MS_TO_KMH;
+        let gusts = sample[2] * MS_TO_KMH;
+        let day = &mut summary[i];
+        if speed.is_finite() {
+            day.wind_max = Some(day.wind_max.map_or(speed, |max| max.max(speed)));
+        }
+        if gusts.is_finite() {
+            day.gusts_max = Some(day.gusts_max.map_or(gusts, |max| max.max(gusts)));
+        }
+    }
+
+    // Each rate holds until the next frame
+    let frames = rain.map(|t| t.frames()).unwrap_or_default();
+    for (k, (time, grid)) in frames.iter().enumerate() {
+        let (Some(i), Some(sample)) = (day_of(time), grid.sample(lat, lng)) else {
+            continue;
+        };
+        let hours = frames
+            .get(k + 1)
+            .map(|(next, _)| (*next - *time).num_minutes() as f64 / 60.0)
+            .unwrap_or(DEFAULT_FRAME_HOURS);
+        if sample[0].is_finite() {
+            let day = &mut summary[i];
+            day.rain_total = Some(day.rain_total.unwrap_or(0.0) + sample[0].max(0.0) * hours);
+        }
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::weather_grid::WeatherGrid;
+    use chrono::{NaiveTime, TimeZone};
+
+    fn grid(fields: &[(&str, f64)]) -> WeatherGrid {
+        let mut points = Vec::new();
+        for lat in [0.0, 1.0] {
+            for lon in [0.0, 1.0] {
+                let mut point = serde_json::json!({ "lat": lat, "lon": lon });
+                for (name, value) in fields {
+                    point[*name] = serde_json::json!(value);
+                }
+                points.push(point);
+            }
+        }
+        let names: Vec<&str> = fields.iter().map(|(name, _)| *name).collect();
+        WeatherGrid::from_points(&serde_json::json!({ "points": points }), &names).unwrap()
+    }
+
+    #[test]
+    fn test_digest_due() {
+        let mut subscription = DigestSubscription {
+            user_id: 1,
+            enabled: true,
+            send_at: NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+            timezone: "Europe/Paris".to_string(),
+            days: 3,
+            ai_summary: false,
+            last_sent_on: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        // 07:30 in Paris (UTC+2 in summer)
+        let now = Utc.with_ymd_and_hms(2026, 6, 1, 5, 30, 0).unwrap();
+        let today = NaiveDate::from_ymd_opt(2026, 6, 1).unwrap();
+
+        assert_eq!(digest_due(&subscription, now).map(|(_, d)| d), Some(today));
+        assert!(digest_due(&subscription, now - Duration::hours(1)).is_none());
+        subscription.last_sent_on = Some(today);
+        assert!(digest_due(&subscription, now).is_none());
+        subscription.timezone = "Mars/Olympus".to_string();
+        subscription.last_sent_on = None;
+        assert!(digest_due(&subscription, now).is_none());
+    }
+
+    #[test]
+    fn test_summarize_days() {
+        let day = NaiveDate::from_ymd_opt(2026, 6, 1).unwrap();
+        let at = |hour| Utc.with_ymd_and_hms(2026, 6, 1, hour, 0, 0).unwrap();
+        let wind = WeatherTimeline::new(vec![
+            (at(6), grid(&[("u", 3.0), ("v", 4.0), ("gusts", 10.0)])),
+            (at(9), grid(&[("u", 6.0), ("v", 8.0), ("gusts", 5.0)])),
+        ]);
+        let rain = WeatherTimeline::new(vec![
+            (at(6), grid(&[("rate", 1.0)])),
+            (at(9), grid(&[("rate", 0.5)])),
+        ]);
+
+        let days = summarize_days(0.5, 0.5, Tz::UTC, day, 2, wind.as_ref(), rain.as_ref());
+        assert_eq!(days.len(), 2);
+        assert!((days[0].wind_max.unwrap() - 36.0).abs() < 1e-9);
+        assert!((days[0].gusts_max.unwrap() - 36.0).abs() < 1e-9);
+        // 3 h at 1 mm/h, then the last frame stands for 3 h at 0.5 mm/h
+        assert!((days[0].rain_total.unwrap() - 4.5).abs() < 1e-9);
+        assert_eq!(days[1].wind_max, None);
+        assert_eq!(days[1].rain_total, None);
+    }
+}
@@ -0,0 +1,340 @@
+use serde_json::Value;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::models::{auth::AppData, GeocodeResult};
+use crate::utils::config::{Config, GeocodingProvider};
+use crate::utils::queries::{get_geocoding_cache, store_geocoding_cache};
+
+/// Places rarely move, cached answers are refreshed after three months
+pub const GEOCODING_CACHE_MAX_AGE_DAYS: i32 = 90;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Nominatim's usage policy asks for an identifying user agent
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug, Error)]
+pub enum GeocodingError {
+    #[error("geocoding provider returned status {status}")]
+    Upstream { status: u16, body: String },
+    #[error("failed to reach the geocoding provider: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("unexpected geocoding response: {0}")]
+    InvalidResponse(#[from] serde_json::Error),
+}
+
+/// Results of a lookup, flagged with whether they came from the cache
+#[derive(Debug, Clone)]
+pub struct GeocodeLookup {
+    pub results: Vec<GeocodeResult>,
+    pub cached: bool,
+}
+
+/// Forward and reverse geocoding through the configured provider.
+/// Every answer, including empty ones, is kept in the `geocoding_cache` table.
+pub struct Geocoder {
+    provider: GeocodingProvider,
+    base_url: String,
+    token: String,
+    client: reqwest::Client,
+}
+
+impl Geocoder {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            provider: config.geocoding_provider,
+            base_url: config.geocoding_url.clone(),
+            token: config.openrouteservice_token.clone(),
+            client: reqwest::Client::builder()
+                .user_agent(USER_AGENT)
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Places matching a free-text query, best match first
+    pub async fn search(
+        &self,
+        query: &str,
+        limit: u32,
+        lang: Option<&str>,
+        data: &AppData,
+    ) -> Result<GeocodeLookup, GeocodingError> {
+        let lang = normalize_lang(lang);
+        let key = format!("{}|{}|{}", lang, limit, normalize_query(query));
+
+        let limit = limit.to_string();
+        let params: Vec<(&str, &str)> = match self.provider {
+            GeocodingProvider::Ors => vec![("text", query), ("size", &limit), ("lang", &lang)],
+            GeocodingProvider::Nominatim => vec![
+                ("format", "jsonv2"),
+                ("addressdetails", "1"),
+                ("q", query),
+                ("limit", &limit),
+                ("accept-language", &lang),
+            ],
+        };
+        let path = match self.provider {
+            GeocodingProvider::Ors => "/geocode/search",
+            GeocodingProvider::Nominatim => "/search",
+        };
+
+        self.lookup("search", &key, path, &params, data).await
+    }
+
+    /// Closest place to a position, `None` when the provider knows nothing there
+    pub async fn reverse(
+        &self,
+        lat: f64,
+        lng: f64,
+        lang: Option<&str>,
+        data: &AppData,
+    ) -> Result<(Option<GeocodeResult>, bool), GeocodingError> {
+        let lang = normalize_lang(lang);
+        let key = format!("{}|{}", lang, position_key(lat, lng));
+
+        let (lat, lng) = (lat.to_string(), lng.to_string());
+        let params: Vec<(&str, &str)> = match self.provider {
+            GeocodingProvider::Ors => vec![
+                ("point.lat", &lat),
+                ("point.lon", &lng),
+                ("size", "1"),
+                ("lang", &lang),
+            ],
+            GeocodingProvider::Nominatim => vec![
+                ("format", "jsonv2"),
+                ("addressdetails", "1"),
+                ("lat", &lat),
+                ("lon", &lng),
+                ("accept-language", &lang),
+            ],
+        };
+        let path = match self.provider {
+            GeocodingProvider::Ors => "/geocode/reverse",
+            GeocodingProvider::Nominatim => "/reverse",
+        };
+
+        let lookup = self.lookup("reverse", &key, path, &params, data).await?;
+        Ok((lookup.results.into_iter().next(), lookup.cached))
+    }
+
+    async fn lookup(
+        &self,
+        kind: &str,
+        key: &str,
+        path: &str,
+        params: &[(&str, &str)],
+        data: &AppData,
+    ) -> Result<GeocodeLookup, GeocodingError> {
+        let provider = self.provider.as_str();
+
+        match get_geocoding_cache(provider, kind, key, GEOCODING_CACHE_MAX_AGE_DAYS, data).await {
+            Ok(Some(cached)) => match serde_json::from_value::<Vec<GeocodeResult>>(cached) {
+                Ok(results) => {
+                    info!("Geocoding {}: cache hit", kind);
+                    return Ok(GeocodeLookup {
+                        results,
+                        cached: true,
+                    });
+                }
+                Err(e) => warn!("Geocoding {}: ignoring unreadable cache entry: {}", kind, e),
+            },
+            Ok(None) => {}
+            Err(e) => warn!("Geocoding {}: cache unavailable: {}", kind, e),
+        }
+
+        let mut request = self
+            .client
+            .get(format!("{}{}", self.base_url, path))
+            .query(params);
+        if self.provider == GeocodingProvider::Ors && !self.token.is_empty() {
+            request = request.header("Authorization", self.token.as_str());
+        }
+        let response = request.send().await?;
+
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(GeocodingError::Upstream {
+                status: status.as_u16(),
+                body: text,
+            });
+        }
+
+        let body: Value = serde_json::from_str(&text)?;
+        let results = match self.provider {
+            GeocodingProvider::Ors => parse_pelias(&body),
+            GeocodingProvider::Nominatim => parse_nominatim(&body),
+        };
+
+        match serde_json::to_value(&results) {
+            Ok(value) => {
+                if let Err(e) = store_geocoding_cache(provider, kind, key, &value, data).await {
+                    warn!("Geocoding {}: failed to cache response: {}", kind, e);
+                }
+            }
+            Err(e) => warn!("Geocoding {}: failed to serialize response: {}", kind, e),
+        }
+
+        Ok(GeocodeLookup {
+            results,
+            cached: false,
+        })
+    }
+}
+
+/// Case and spacing do not change the answer, so they do not change the cache key either
+pub fn normalize_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn normalize_lang(lang: Option<&str>) -> String {
+    lang.map(|l| l.trim().to_lowercase())
+        .filter(|l| !l.is_empty())
+        .unwrap_or_else(|| "fr".to_string())
+}
+
+/// Position rounded to 5 decimals (about a meter), so nearby clicks share an entry
+pub fn position_key(lat: f64, lng: f64) -> String {
+    // Adding 0.0 turns a rounded -0 into 0
+    let round = |v: f64| (v * 1e5).round() / 1e5 + 0.0;
+    format!("{:.5},{:.5}", round(lat), round(lng))
+}
+
+/// Features of a Pelias (ORS) GeoJSON answer
+pub fn parse_pelias(body: &Value) -> Vec<GeocodeResult> {
+    let text = |value: &Value| value.as_str().map(String::from);
+
+    body["features"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|feature| {
+            let coordinates = feature["geometry"]["coordinates"].as_array()?;
+            let properties = &feature["properties"];
+            let bbox = feature["bbox"]
+                .as_array()
+                .filter(|b| b.len() == 4)
+                .and_then(|b| {
+                    Some([
+                        b[0].as_f64()?,
+                        b[1].as_f64()?,
+                        b[2].as_f64()?,
+                        b[3].as_f64()?,
+                    ])
+                });
+
+            Some(GeocodeResult {
+                label: text(&properties["label"]).or_else(|| text(&properties["name"]))?,
+                lat: coordinates.get(1)?.as_f64()?,
+                lng: coordinates.first()?.as_f64()?,
+                name: text(&properties["name"]),
+                locality: text(&properties["locality"]),
+                country: text(&properties["country"]),
+                bbox,
+            })
+        })
+        .collect()
+}
+
+/// Places of a Nominatim `jsonv2` answer: a list for searches, a single object for
+/// reverse lookups, or `{"error": ...}` when nothing was found
+pub fn parse_nominatim(body: &Value) -> Vec<GeocodeResult> {
+    let places = match body {
+        Value::Array(places) => places.iter().collect(),
+        Value::Object(place) if place.contains_key("lat") => vec![body],
+        _ => Vec::new(),
+    };
+    // Nominatim sends numbers as strings
+    let number = |value: &Value| {
+        value
+            .as_str()
+            .and_then(|v| v.parse::<f64>().ok())
+            .or_else(|| value.as_f64())
+    };
+    let text = |value: &Value| value.as_str().filter(|v| !v.is_empty()).map(String::from);
+
+    places
+        .into_iter()
+        .filter_map(|place| {
+            let address = &place["address"];
+            // boundingbox is [min_lat, max_lat, min_lon, max_lon]
+            let bbox = place["boundingbox"]
+                .as_array()
+                .filter(|b| b.len() == 4)
+                .and_then(|b| {
+                    Some([
+                        number(&b[2])?,
+                        number(&b[0])?,
+                        number(&b[3])?,
+                        number(&b[1])?,
+                    ])
+                });
+
+            Some(GeocodeResult {
+                label: text(&place["display_name"])?,
+                lat: number(&place["lat"])?,
+                lng: number(&place["lon"])?,
+                name: text(&place["name"]),
+                locality: ["city", "town", "village", "hamlet", "municipality"]
+                    .iter()
+                    .find_map(|field| text(&address[*field])),
+                country: text(&address["country"]),
+                bbox,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_keys() {
+        assert_eq!(
+            normalize_query("  Rue de  Rivoli\tPARIS "),
+            "rue de rivoli paris"
+        );
+        assert_eq!(position_key(48.8566123, 2.3522219), "48.85661,2.35222");
+        assert_eq!(position_key(-0.000001, 0.0), "0.00000,0.00000");
+    }
+
+    #[test]
+    fn test_parse_providers() {
+        let pelias = serde_json::json!({
+            "features": [{
+                "geometry": { "coordinates": [2.3522, 48.8566] },
+                "properties": { "label": "Paris, France", "name": "Paris", "country": "France" },
+                "bbox": [2.22, 48.81, 2.47, 48.9]
+            }, {
+                "geometry": { "coordinates": [] },
+                "properties": { "label": "Broken" }
+            }]
+        });
+        let results = parse_pelias(&pelias);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].label, "Paris, France");
+        assert_eq!((results[0].lat, results[0].lng), (48.8566, 2.3522));
+        assert_eq!(results[0].bbox, Some([2.22, 48.81, 2.47, 48.9]));
+
+        let nominatim = serde_json::json!({
+            "lat": "43.2965", "lon": "5.3698",
+            "display_name": "Vieux-Port, Marseille, France",
+            "name": "Vieux-Port",
+            "address": { "town": "", "city": "Marseille", "country": "France" },
+            "boundingbox": ["43.29", "43.30", "5.36", "5.37"]
+        });
+        let results = parse_nominatim(&nominatim);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].locality.as_deref(), Some("Marseille"));
+        assert_eq!(results[0].bbox, Some([5.36, 43.29, 5.37, 43.30]));
+        assert_eq!(parse_nominatim(&serde_json::json!([nominatim])), results);
+        assert!(parse_nominatim(&serde_json::json!({ "error": "Unable to geocode" })).is_empty());
+    }
+}
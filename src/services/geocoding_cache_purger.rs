@@ -0,0 +1,64 @@
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::services::GEOCODING_CACHE_MAX_AGE_DAYS;
+use crate::utils::queries::purge_geocoding_cache;
+
+/// Deletes geocoding answers older than the cache lifetime, which are never read again
+#[derive(Clone)]
+pub struct GeocodingCachePurger {
+    pool: PgPool,
+}
+
+impl GeocodingCachePurger {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Start the daily purge job
+    pub async fn start(&self) {
+        info!(
+            "Starting geocoding cache purge job (max age: {} days, daily at 04:00 UTC)",
+            GEOCODING_CACHE_MAX_AGE_DAYS
+        );
+
+        let purger = self.clone();
+
+        tokio::spawn(async move {
+            use tokio_cron_scheduler::{Job, JobScheduler};
+
+            let sched = JobScheduler::new().await.unwrap();
+
+            let job = Job::new_async("0 0 4 * * *", move |_uuid, _l| {
+                let purger = purger.clone();
+
+                Box::pin(async move {
+                    info!("[{}] Scheduled geocoding cache purge triggered", Utc::now());
+                    if let Err(e) = purger.purge().await {
+                        error!("Geocoding cache purge failed: {}", e);
+                    }
+                })
+            })
+            .unwrap();
+
+            sched.add(job).await.unwrap();
+            sched.start().await.unwrap();
+
+            // Keep the scheduler running
+            loop {
+                tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            }
+        });
+    }
+
+    pub async fn purge(&self) -> Result<()> {
+        let deleted = purge_geocoding_cache(GEOCODING_CACHE_MAX_AGE_DAYS, &self.pool).await?;
+        info!(
+            "Geocoding cache purge: deleted {} entries older than {} days",
+            deleted, GEOCODING_CACHE_MAX_AGE_DAYS
+        );
+        Ok(())
+    }
+}
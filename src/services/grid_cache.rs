@@ -0,0 +1,197 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use hashlink::LruCache;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+use crate::services::{IndexEntry, RedisClient, LAND_MASK_KEY};
+use crate::utils::land_mask::LandMask;
+use crate::utils::map_tiles::channel_ranges;
+use crate::utils::weather_grid::{WeatherGrid, WeatherTimeline};
+
+/// Decoded grids kept in memory, a few MB each: enough for every stored wind forecast
+/// of a sailing route besides the maps
+const GRID_CACHE_SIZE: usize = 24;
+
+/// Fields of a stored forecast. The scheduler reuses indices,
+/// so the time the data was stored is part of the key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GridKey {
+    pub base_key: &'static str,
+    pub index: u32,
+    stored_at: String,
+    fields: &'static [&'static str],
+}
+
+impl GridKey {
+    pub fn new(
+        base_key: &'static str,
+        entry: &IndexEntry,
+        fields: &'static [&'static str],
+    ) -> Self {
+        Self {
+            base_key,
+            index: entry.index,
+            stored_at: entry.timestamp.clone(),
+            fields,
+        }
+    }
+}
+
+/// Decoded grid and the value range of each field
+pub struct CachedGrid {
+    pub grid: WeatherGrid,
+    pub ranges: Vec<(f64, f64)>,
+}
+
+/// Indexed forecasts decoded for the map renderers and the sailing router,
+/// the last ones used are kept in memory
+pub struct GridCache {
+    redis: Arc<RedisClient>,
+    grids: Mutex<LruCache<GridKey, Arc<CachedGrid>>>,
+    /// Downloaded once by the scheduler and never replaced
+    land_mask: Mutex<Option<Arc<LandMask>>>,
+    /// A map view asks for many tiles at once, the first one decodes the grid for all
+    loading: tokio::sync::Mutex<()>,
+}
+
+impl GridCache {
+    pub fn new(redis: Arc<RedisClient>) -> Self {
+        Self {
+            redis,
+            grids: Mutex::new(LruCache::new(GRID_CACHE_SIZE)),
+            land_mask: Mutex::new(None),
+            loading: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Stored forecast `index` of `base_key`, or the one closest to now without index
+    pub async fn entry(&self, base_key: &str, index: Option<u32>) -> Result<Option<IndexEntry>> {
+        let entries = self.redis.get_available_indices(base_key).await?;
+        let entry = match index {
+            Some(index) => entries.into_iter().find(|entry| entry.index == index),
+            None => {
+                let now = Utc::now();
+                entries.into_iter().min_by_key(|entry| {
+                    data_time(entry).map_or(i64::MAX, |time| (time - now).num_seconds().abs())
+                })
+            }
+        };
+        Ok(entry)
+    }
+
+    /// Stored forecasts of `base_key` valid between `from` and `to`, in chronological order
+    pub async fn timeline(
+        &self,
+        base_key: &str,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<(DateTime<Utc>, IndexEntry)>> {
+        let mut timeline: Vec<_> = self
+            .redis
+            .get_available_indices(base_key)
+            .await?
+            .into_iter()
+            .filter_map(|entry| Some((data_time(&entry)?, entry)))
+            .filter(|(time, _)| from.is_none_or(|from| *time >= from))
+            .filter(|(time, _)| to.is_none_or(|to| *time <= to))
+            .collect();
+        timeline.sort_by_key(|(time, _)| *time);
+        Ok(timeline)
+    }
+
+    /// Grid of `key`, `None` when its data is no longer stored
+    pub async fn get(&self, key: &GridKey) -> Result<Option<Arc<CachedGrid>>> {
+        if let Some(grid) = self.grids.lock().unwrap().get(key) {
+            return Ok(Some(grid.clone()));
+        }
+
+        let _loading = self.loading.lock().await;
+        // Loaded while waiting for the lock
+        if let Some(grid) = self.grids.lock().unwrap().get(key) {
+            return Ok(Some(grid.clone()));
+        }
+
+        let Some(data) = self
+            .redis
+            .get_wind_data_by_index(key.base_key, key.index)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let fields = key.fields;
+        let grid = tokio::task::spawn_blocking(move || -> Result<CachedGrid> {
+            let grid = WeatherGrid::from_points(&data, fields)?;
+            let ranges = channel_ranges(&grid);
+            Ok(CachedGrid { grid, ranges })
+        })
+        .await??;
+        info!("Decoded {} index {} for maps", key.base_key, key.index);
+
+        let grid = Arc::new(grid);
+        self.grids.lock().unwrap().insert(key.clone(), grid.clone());
+        Ok(Some(grid))
+    }
+
+    /// Cached grids of `base_key` needed to cover `from..=to`: every frame inside the
+    /// window plus the closest one on each side, like `load_weather_timeline`.
+    /// Returns `None` when no indexed data is available.
+    pub async fn weather_timeline(
+        &self,
+        base_key: &'static str,
+        fields: &'static [&'static str],
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Option<WeatherTimeline>> {
+        let entries = self.timeline(base_key, None, None).await?;
+        let first = entries
+            .iter()
+            .rposition(|(time, _)| *time <= from)
+            .unwrap_or(0);
+        let last = entries
+            .iter()
+            .position(|(time, _)| *time >= to)
+            .unwrap_or(entries.len().saturating_sub(1));
+
+        let mut frames = Vec::new();
+        for (time, entry) in entries.iter().take(last + 1).skip(first) {
+            match self.get(&GridKey::new(base_key, entry, fields)).await {
+                Ok(Some(grid)) => frames.push((*time, grid.grid.clone())),
+                Ok(None) => {}
+                Err(e) => warn!("Skipping {} index {}: {}", base_key, entry.index, e),
+            }
+        }
+
+        Ok(WeatherTimeline::new(frames))
+    }
+
+    /// Land mask, `None` until the scheduler has stored it
+    pub async fn land_mask(&self) -> Result<Option<Arc<LandMask>>> {
+        if let Some(mask) = self.land_mask.lock().unwrap().as_ref() {
+            return Ok(Some(mask.clone()));
+        }
+
+        let _loading = self.loading.lock().await;
+        if let Some(mask) = self.land_mask.lock().unwrap().as_ref() {
+            return Ok(Some(mask.clone()));
+        }
+
+        let Some(data) = self.redis.get_wind_data(LAND_MASK_KEY).await? else {
+            return Ok(None);
+        };
+        let mask = tokio::task::spawn_blocking(move || -> Result<LandMask> {
+            Ok(LandMask::new(WeatherGrid::from_points(&data, &["land"])?))
+        })
+        .await??;
+        info!("Decoded the land mask");
+
+        let mask = Arc::new(mask);
+        *self.land_mask.lock().unwrap() = Some(mask.clone());
+        Ok(Some(mask))
+    }
+}
+
+fn data_time(entry: &IndexEntry) -> Option<DateTime<Utc>> {
+    let time = DateTime::parse_from_rfc3339(entry.data_time.as_deref()?).ok()?;
+    Some(time.with_timezone(&Utc))
+}
@@ -0,0 +1,384 @@
+use anyhow::Result;
+use chrono::Utc;
+use serde_json::Value;
+use std::path::Path;
+use thiserror::Error;
+use tracing::info;
+
+use crate::models::{
+    Directions, DirectionsFeature, DirectionsMetadata, RouteGeometry, RouteProfile,
+    RouteProperties, RouteSegment, RouteStep, RouteSummary,
+};
+use crate::utils::geo::{bearing, encode_polyline};
+use crate::utils::road_graph::{GraphPath, GraphProfile, RoadGraph};
+use crate::utils::weather_grid::round_to;
+
+/// Response formats the local engine can produce
+pub const LOCAL_ROUTING_FORMATS: [&str; 2] = ["json", "geojson"];
+
+#[derive(Debug, Error)]
+pub enum LocalRoutingError {
+    #[error("profile {0} is not supported by the offline routing engine")]
+    UnsupportedProfile(String),
+    #[error("format {0} is not supported by the offline routing engine")]
+    UnsupportedFormat(String),
+    #[error("coordinate {0} is not close to a road of the offline graph")]
+    NotNearRoad(usize),
+    #[error("no route found between coordinates {0} and {1}")]
+    NoRoute(usize, usize),
+}
+
+// ORS instruction types
+const STEP_LEFT: u32 = 0;
+const STEP_RIGHT: u32 = 1;
+const STEP_SHARP_LEFT: u32 = 2;
+const STEP_SHARP_RIGHT: u32 = 3;
+const STEP_SLIGHT_LEFT: u32 = 4;
+const STEP_SLIGHT_RIGHT: u32 = 5;
+const STEP_STRAIGHT: u32 = 6;
+const STEP_U_TURN: u32 = 9;
+const STEP_GOAL: u32 = 10;
+const STEP_DEPART: u32 = 11;
+
+/// Routing engine answering from a preprocessed road graph, without network.
+/// Responses follow the ORS directions `json` and `geojson` shapes.
+pub struct LocalRouter {
+    graph: RoadGraph,
+}
+
+fn turn_type(incoming: f64, outgoing: f64) -> u32 {
+    // Positive to the right, within (-180, 180]
+    let delta = (outgoing - incoming + 540.0) % 360.0 - 180.0;
+    match delta {
+        d if d.abs() < 20.0 => STEP_STRAIGHT,
+        d if d.abs() >= 170.0 => STEP_U_TURN,
+        d if d >= 120.0 => STEP_SHARP_RIGHT,
+        d if d <= -120.0 => STEP_SHARP_LEFT,
+        d if d >= 60.0 => STEP_RIGHT,
+        d if d <= -60.0 => STEP_LEFT,
+        d if d > 0.0 => STEP_SLIGHT_RIGHT,
+        _ => STEP_SLIGHT_LEFT,
+    }
+}
+
+fn cardinal(bearing: f64) -> &'static str {
+    const DIRECTIONS: [&str; 8] = [
+        "north",
+        "northeast",
+        "east",
+        "southeast",
+        "south",
+        "southwest",
+        "west",
+        "northwest",
+    ];
+    DIRECTIONS[((bearing + 22.5) / 45.0) as usize % 8]
+}
+
+fn instruction(kind: u32, name: &str, heading: f64) -> String {
+    let action = match kind {
+        STEP_DEPART => format!("Head {}", cardinal(heading)),
+        STEP_LEFT => "Turn left".to_string(),
+        STEP_RIGHT => "Turn right".to_string(),
+        STEP_SHARP_LEFT => "Turn sharp left".to_string(),
+        STEP_SHARP_RIGHT => "Turn sharp right".to_string(),
+        STEP_SLIGHT_LEFT => "Turn slight left".to_string(),
+        STEP_SLIGHT_RIGHT => "Turn slight right".to_string(),
+        STEP_U_TURN => "Make a U-turn".to_string(),
+        _ => "Continue straight".to_string(),
+    };
+    match (kind, name.is_empty()) {
+        (_, true) => action,
+        (STEP_DEPART, false) => format!("{} on {}", action, name),
+        (_, false) => format!("{} onto {}", action, name),
+    }
+}
+
+impl LocalRouter {
+    pub fn load(path: &str) -> Result<Self> {
+        let graph = RoadGraph::load(Path::new(path))?;
+        info!(
+            "Offline road graph loaded from {}: {} nodes, {} edges",
+            path,
+            graph.node_count(),
+            graph.edge_count()
+        );
+        Ok(LocalRouter { graph })
+    }
+
+    /// ORS-compatible directions through `coordinates` (`[lon, lat]`)
+    pub fn directions(
+        &self,
+        profile: &str,
+        format: &str,
+        coordinates: &[[f64; 2]],
+    ) -> Result<Value, LocalRoutingError> {
+        let graph_profile = serde_json::from_value::<RouteProfile>(serde_json::json!(profile))
+            .ok()
+            .and_then(GraphProfile::from_route_profile)
+            .ok_or_else(|| LocalRoutingError::UnsupportedProfile(profile.to_string()))?;
+        if !LOCAL_ROUTING_FORMATS.contains(&format) {
+            return Err(LocalRoutingError::UnsupportedFormat(format.to_string()));
+        }
+
+        let feature = self.route_feature(graph_profile, coordinates)?;
+        let bbox = feature.bbox.clone();
+        let metadata = DirectionsMetadata {
+            attribution: Some("© OpenStreetMap contributors".to_string()),
+            service: Some("routing".to_string()),
+            timestamp: Some(Utc::now().timestamp_millis()),
+        };
+
+        if format == "geojson" {
+            return Ok(serde_json::json!(Directions {
+                kind: "FeatureCollection".to_string(),
+                features: vec![feature],
+                bbox,
+                metadata: Some(metadata),
+            }));
+        }
+
+        let properties = feature.properties;
+        Ok(serde_json::json!({
+            "bbox": bbox,
+            "routes": [{
+                "summary": properties.summary,
+                "segments": properties.segments,
+                "bbox": bbox,
+                "geometry": encode_polyline(&feature.geometry.coordinates),
+                "way_points": properties.way_points,
+            }],
+            "metadata": metadata,
+        }))
+    }
+
+    fn route_feature(
+        &self,
+        profile: GraphProfile,
+        coordinates: &[[f64; 2]],
+    ) -> Result<DirectionsFeature, LocalRoutingError> {
+        let snapped = coordinates
+            .iter()
+            .enumerate()
+            .map(|(i, [lon, lat])| {
+                self.graph
+                    .nearest_node(*lat, *lon, profile)
+                    .ok_or(LocalRoutingError::NotNearRoad(i))
+            })
+            .collect::<Result<Vec<u32>, _>>()?;
+
+        let position = |node: u32| {
+            let (lat, lon) = self.graph.position(node);
+            vec![lon, lat]
+        };
+
+        let mut positions = vec![position(snapped[0])];
+        let mut way_points = vec![0];
+        let mut segments = Vec::new();
+
+        for (leg, pair) in snapped.windows(2).enumerate() {
+            let path = if pair[0] == pair[1] {
+                GraphPath {
+                    nodes: vec![pair[0]],
+                    edges: Vec::new(),
+                }
+            } else {
+                self.graph
+                    .shortest_path(pair[0], pair[1], profile)
+                    .ok_or(LocalRoutingError::NoRoute(leg, leg + 1))?
+            };
+
+            let offset = positions.len() - 1;
+            positions.extend(path.nodes[1..].iter().map(|n| position(*n)));
+            let is_last = leg + 2 == snapped.len();
+            segments.push(self.segment(&path, offset, profile, leg + 1, is_last));
+            way_points.push(positions.len() - 1);
+        }
+
+        let summary = RouteSummary {
+            distance: round_to(segments.iter().map(|s| s.distance).sum(), 1),
+            duration: round_to(segments.iter().map(|s| s.duration).sum(), 1),
+        };
+
+        let bbox = positions
+            .iter()
+            .fold(vec![f64::MAX, f64::MAX, f64::MIN, f64::MIN], |b, p| {
+                vec![
+                    b[0].min(p[0]),
+                    b[1].min(p[1]),
+                    b[2].max(p[0]),
+                    b[3].max(p[1]),
+                ]
+            });
+
+        Ok(DirectionsFeature {
+            kind: "Feature".to_string(),
+            bbox: Some(bbox),
+            geometry: RouteGeometry {
+                kind: "LineString".to_string(),
+                coordinates: positions,
+            },
+            properties: RouteProperties {
+                ascent: None,
+                descent: None,
+                extras: Default::default(),
+                segments,
+                summary,
+                warnings: Vec::new(),
+                way_points,
+            },
+        })
+    }
+
+    /// One leg of the route; `offset` is the index of its first position in the whole geometry
+    fn segment(
+        &self,
+        path: &GraphPath,
+        offset: usize,
+        profile: GraphProfile,
+        waypoint: usize,
+        is_last: bool,
+    ) -> RouteSegment {
+        let edge_bearing = |i: usize| {
+            let (lat1, lon1) = self.graph.position(path.nodes[i]);
+            let (lat2, lon2) = self.graph.position(path.nodes[i + 1]);
+            bearing(lat1, lon1, lat2, lon2)
+        };
+
+        let mut steps: Vec<RouteStep> = Vec::new();
+        let mut start = 0;
+        while start < path.edges.len() {
+            let name = self.graph.name(self.graph.edge(path.edges[start]));
+            let mut end = start;
+            while end < path.edges.len()
+                && self.graph.name(self.graph.edge(path.edges[end])) == name
+            {
+                end += 1;
+            }
+
+            let edges = &path.edges[start..end];
+            let kind = if start == 0 {
+                STEP_DEPART
+            } else {
+                turn_type(edge_bearing(start - 1), edge_bearing(start))
+            };
+
+            steps.push(RouteStep {
+                distance: round_to(
+                    edges
+                        .iter()
+                        .map(|e| f64::from(self.graph.edge(*e).distance))
+                        .sum(),
+                    1,
+                ),
+                duration: round_to(
+                    edges
+                        .iter()
+                        .map(|e| self.graph.edge(*e).duration(profile))
+                        .sum(),
+                    1,
+                ),
+                instruction: instruction(kind, name, edge_bearing(start)),
+                name: if name.is_empty() { "-" } else { name }.to_string(),
+                kind,
+                way_points: [offset + start, offset + end],
+                exit_number: None,
+            });
+            start = end;
+        }
+
+        let arrival = offset + path.edges.len();
+        steps.push(RouteStep {
+            distance: 0.0,
+            duration: 0.0,
+            instruction: if is_last {
+                "Arrive at your destination".to_string()
+            } else {
+                format!("Arrive at waypoint {}", waypoint)
+            },
+            name: "-".to_string(),
+            kind: STEP_GOAL,
+            way_points: [arrival, arrival],
+            exit_number: None,
+        });
+
+        RouteSegment {
+            ascent: None,
+            descent: None,
+            distance: round_to(steps.iter().map(|s| s.distance).sum(), 1),
+            duration: round_to(steps.iter().map(|s| s.duration).sum(), 1),
+            steps,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::osm_pbf::{OsmNode, OsmWay};
+
+    fn router() -> LocalRouter {
+        let nodes = [
+            (1, 48.85, 2.35),
+            (2, 48.86, 2.35),
+            (3, 48.86, 2.365),
+            (4, 48.87, 2.365),
+        ]
+        .map(|(id, lat, lon)| OsmNode { id, lat, lon });
+        let way = |id, refs: &[i64], name: &str| OsmWay {
+            id,
+            refs: refs.to_vec(),
+            tags: [("highway", "residential"), ("name", name)]
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        };
+        let ways = [
+            way(10, &[1, 2], "Rue A"),
+            way(11, &[2, 3], "Rue B"),
+            way(12, &[3, 4], "Rue C"),
+        ];
+        LocalRouter {
+            graph: RoadGraph::from_osm(&ways, &nodes),
+        }
+    }
+
+    #[test]
+    fn test_geojson_directions() {
+        let router = router();
+        let coordinates = [[2.35, 48.85], [2.365, 48.86], [2.365, 48.87]];
+        let value = router
+            .directions("cycling-regular", "geojson", &coordinates)
+            .unwrap();
+        let directions: Directions = serde_json::from_value(value).unwrap();
+
+        let properties = &directions.features[0].properties;
+        assert_eq!(directions.features[0].geometry.coordinates.len(), 4);
+        assert_eq!(properties.way_points, vec![0, 2, 3]);
+        assert_eq!(properties.segments.len(), 2);
+
+        let kinds: Vec<u32> = properties.segments[0]
+            .steps
+            .iter()
+            .map(|s| s.kind)
+            .collect();
+        assert_eq!(kinds, vec![STEP_DEPART, STEP_RIGHT, STEP_GOAL]);
+        assert_eq!(
+            properties.segments[0].steps[1].instruction,
+            "Turn right onto Rue B"
+        );
+        assert_eq!(properties.segments[1].steps[0].kind, STEP_DEPART);
+
+        let total: f64 = properties.segments.iter().map(|s| s.distance).sum();
+        assert!((properties.summary.distance - total).abs() < 0.2);
+
+        assert!(matches!(
+            router.directions("foot-walking", "geojson", &coordinates),
+            Err(LocalRoutingError::UnsupportedProfile(_))
+        ));
+        assert!(matches!(
+            router.directions("driving-car", "json", &[[2.35, 48.85], [150.0, -40.0]]),
+            Err(LocalRoutingError::NotNearRoad(1))
+        ));
+    }
+}
@@ -2,7 +2,41 @@ pub mod redis_client;
 pub mod opendap_downloader;
 pub mod scheduler;
 pub mod anthropic_client;
+pub mod openrouteservice;
+pub mod route_weather;
+pub mod trash_purger;
+pub mod address_ranking;
+pub mod local_router;
+pub mod weather_timeline;
+pub mod wind_analysis;
+pub mod departure_optimizer;
+pub mod sailing_router;
+pub mod geocoder;
+pub mod geocoding_cache_purger;
+pub mod alert_evaluator;
+pub mod notification_worker;
+pub mod digest_sender;
+pub mod grid_cache;
+pub mod tile_server;
+pub mod animation_cache;
 
 pub use redis_client::*;
 pub use scheduler::*;
 pub use anthropic_client::*;
+pub use openrouteservice::*;
+pub use route_weather::*;
+pub use trash_purger::*;
+pub use address_ranking::*;
+pub use local_router::*;
+pub use weather_timeline::*;
+pub use wind_analysis::*;
+pub use departure_optimizer::*;
+pub use sailing_router::*;
+pub use geocoder::*;
+pub use geocoding_cache_purger::*;
+pub use alert_evaluator::*;
+pub use notification_worker::*;
+pub use digest_sender::*;
+pub use grid_cache::*;
+pub use tile_server::*;
+pub use animation_cache::*;
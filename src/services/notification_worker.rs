@@ -0,0 +1,269 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use crate::models::{Notification, NotificationChannel};
+use crate::utils::mail::{send_alert_mail, send_digest_mail, Mailer};
+use crate::utils::queries::{claim_notifications, finish_delivery_attempt, get_user, get_webhook};
+use crate::utils::webhook::{
+    check_webhook_url, sign_payload, PublicResolver, SIGNATURE_HEADER, TIMESTAMP_HEADER,
+};
+
+/// A notification failing this many times is dead-lettered
+pub const MAX_DELIVERY_ATTEMPTS: i32 = 8;
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+const BATCH_SIZE: i64 = 20;
+/// Time a claimed notification stays reserved to its worker
+const LEASE_SECONDS: i32 = 300;
+const WEBHOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+const RETRY_BASE_SECONDS: i64 = 30;
+const RETRY_MAX_SECONDS: i64 = 6 * 60 * 60;
+
+/// Result of one delivery attempt
+struct DeliveryOutcome {
+    response_status: Option<i32>,
+    error: Option<String>,
+    /// Retrying cannot help, the notification is dead-lettered right away
+    permanent: bool,
+}
+
+impl DeliveryOutcome {
+    fn failed(error: impl ToString) -> Self {
+        Self {
+            response_status: None,
+            error: Some(error.to_string()),
+            permanent: false,
+        }
+    }
+}
+
+/// Delivers the notification outbox: one worker sends the emails, another POSTs to webhooks
+#[derive(Clone)]
+pub struct NotificationWorker {
+    pool: PgPool,
+    mailer: Arc<Mailer>,
+    client: reqwest::Client,
+}
+
+impl NotificationWorker {
+    pub fn new(pool: PgPool, mailer: Arc<Mailer>) -> Self {
+        Self {
+            pool,
+            mailer,
+            // A redirect could lead to a private address
+            client: reqwest::Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .redirect(reqwest::redirect::Policy::none())
+                .dns_resolver(Arc::new(PublicResolver))
+                .build()
+                .expect("Failed to build the webhook client"),
+        }
+    }
+
+    /// Start both workers, each polling the outbox for its channel
+    pub async fn start(&self) {
+        info!(
+            "Starting notification workers (every {}s, {} attempts max)",
+            POLL_INTERVAL.as_secs(),
+            MAX_DELIVERY_ATTEMPTS
+        );
+
+        for channel in [NotificationChannel::Email, NotificationChannel::Webhook] {
+            let worker = self.clone();
+            tokio::spawn(async move {
+                loop {
+                    match worker.deliver_due(channel).await {
+                        Ok(0) => {}
+                        Ok(count) => {
+                            info!("Delivered {} {} notifications", count, channel.as_str())
+                        }
+                        Err(e) => error!("{} notification worker failed: {}", channel.as_str(), e),
+                    }
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+            });
+        }
+    }
+
+    /// Attempt every due notification of `channel`, returns how many were delivered
+    pub async fn deliver_due(&self, channel: NotificationChannel) -> Result<usize, sqlx::Error> {
+        let notifications =
+            claim_notifications(channel, BATCH_SIZE, LEASE_SECONDS, &self.pool).await?;
+        let mut delivered = 0;
+
+        for notification in &notifications {
+            let outcome = match channel {
+                NotificationChannel::Email => self.send_email(notification).await,
+                NotificationChannel::Webhook => self.send_webhook(notification).await,
+            };
+
+            let retry_at = outcome.error.as_ref().and_then(|e| {
+                let attempt = notification.attempts + 1;
+                let retry_at = if outcome.permanent {
+                    None
+                } else {
+                    next_attempt_at(attempt, Utc::now())
+                };
+                match retry_at {
+                    Some(at) => warn!(
+                        "Notification {} attempt {} failed, retrying at {}: {}",
+                        notification.id, attempt, at, e
+                    ),
+                    None if outcome.permanent => error!(
+                        "Notification {} dead after a permanent failure: {}",
+                        notification.id, e
+                    ),
+                    None => error!(
+                        "Notification {} dead after {} attempts: {}",
+                        notification.id, attempt, e
+                    ),
+                }
+                retry_at
+            });
+            if outcome.error.is_none() {
+                delivered += 1;
+            }
+
+            finish_delivery_attempt(
+                notification,
+                outcome.response_status,
+                outcome.error.as_deref(),
+                retry_at,
+                &self.pool,
+            )
+            .await?;
+        }
+
+        Ok(delivered)
+    }
+
+    async fn send_email(&self, notification: &Notification) -> DeliveryOutcome {
+        let user = match get_user(notification.user_id, &self.pool).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return DeliveryOutcome::failed("User not found"),
+            Err(e) => return DeliveryOutcome::failed(e),
+        };
+
+        let result = match notification.kind.as_str() {
+            "alert" => {
+                send_alert_mail(
+                    &notification.payload,
+                    &user.email,
+                    &user.language,
+                    &self.mailer,
+                )
+                .await
+            }
+            "digest" => {
+                send_digest_mail(
+                    &notification.payload,
+                    &user.email,
+                    &user.language,
+                    &self.mailer,
+                )
+                .await
+            }
+            kind => {
+                return DeliveryOutcome {
+                    permanent: true,
+                    ..DeliveryOutcome::failed(format!("No email for {} notifications", kind))
+                }
+            }
+        };
+
+        match result {
+            Ok(_) => DeliveryOutcome {
+                response_status: None,
+                error: None,
+                permanent: false,
+            },
+            Err(e) => DeliveryOutcome {
+                permanent: e.is_permanent(),
+                ..DeliveryOutcome::failed(e)
+            },
+        }
+    }
+
+    async fn send_webhook(&self, notification: &Notification) -> DeliveryOutcome {
+        let webhook = match notification.webhook_id {
+            Some(id) => match get_webhook(id, &self.pool).await {
+                Ok(Some(webhook)) => webhook,
+                Ok(None) => return DeliveryOutcome::failed("Webhook not found"),
+                Err(e) => return DeliveryOutcome::failed(e),
+            },
+            None => return DeliveryOutcome::failed("Notification without webhook"),
+        };
+        // Names can be pointed elsewhere after the webhook was registered
+        if let Err(e) = check_webhook_url(&webhook.url).await {
+            return DeliveryOutcome::failed(e);
+        }
+
+        let body = serde_json::json!({
+            "id": notification.id,
+            "kind": notification.kind,
+            "created_at": notification.created_at,
+            "data": notification.payload,
+        });
+        let body = match serde_json::to_vec(&body) {
+            Ok(body) => body,
+            Err(e) => return DeliveryOutcome::failed(e),
+        };
+        let timestamp = Utc::now().timestamp();
+
+        let response = self
+            .client
+            .post(&webhook.url)
+            .header("Content-Type", "application/json")
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(
+                SIGNATURE_HEADER,
+                sign_payload(&webhook.secret, timestamp, &body),
+            )
+            .body(body)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) => {
+                let status = response.status();
+                DeliveryOutcome {
+                    response_status: Some(status.as_u16() as i32),
+                    error: (!status.is_success())
+                        .then(|| format!("Webhook answered {}", status.as_u16())),
+                    permanent: false,
+                }
+            }
+            Err(e) => DeliveryOutcome::failed(e),
+        }
+    }
+}
+
+/// Time of the next attempt after `attempt` failed ones: 30s, 1 min, 2 min... capped at 6h.
+/// `None` once the attempts are used up.
+pub fn next_attempt_at(attempt: i32, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if attempt >= MAX_DELIVERY_ATTEMPTS {
+        return None;
+    }
+    let delay = RETRY_BASE_SECONDS
+        .saturating_mul(1 << (attempt - 1).clamp(0, 30))
+        .min(RETRY_MAX_SECONDS);
+    Some(now + Duration::seconds(delay))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let now = Utc::now();
+        let delay = |attempt| next_attempt_at(attempt, now).map(|at| (at - now).num_seconds());
+
+        assert_eq!(delay(1), Some(30));
+        assert_eq!(delay(2), Some(60));
+        assert_eq!(delay(4), Some(240));
+        assert_eq!(delay(MAX_DELIVERY_ATTEMPTS - 1), Some(1920));
+        assert_eq!(delay(MAX_DELIVERY_ATTEMPTS), None);
+    }
+}
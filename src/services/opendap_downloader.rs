@@ -3,11 +3,13 @@ use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
 use reqwest;
 use tracing::{error, info};
 
-use crate::models::{PrecipitationPoint, WindMetadata, WindPoint};
+use crate::models::{
+    PrecipitationMetadata, PrecipitationPoint, WindEncoding, WindMetadata, WindPoint,
+};
 use crate::utils::opendap_parser::{
-    parse_opendap_ascii, parse_opendap_precipitation_ascii,
+    parse_opendap_ascii, parse_opendap_precipitation_ascii, parse_opendap_variable_ascii,
 };
-use crate::utils::png_converter::convert_to_png;
+use crate::utils::png_converter::{convert_precipitation_to_png, convert_to_png};
 
 #[derive(Debug, Clone)]
 pub struct ForecastRun {
@@ -29,12 +31,23 @@ pub struct DownloadedWindData {
 
 #[derive(Debug, Clone)]
 pub struct DownloadedPrecipitationData {
+    pub png_buffer: Vec<u8>,
+    pub metadata: PrecipitationMetadata,
     pub precip_points: Vec<PrecipitationPoint>,
     pub run_name: String,
     pub data_time: String,
     pub forecast_offset: i32,
 }
 
+#[derive(Debug, Clone)]
+pub struct DownloadedLandMask {
+    pub lat_values: Vec<f64>,
+    pub lon_values: Vec<f64>,
+    /// Row-major, 1 over land and 0 over water
+    pub land: Vec<f64>,
+    pub run_name: String,
+}
+
 /// Get available GFS forecast runs in order of preference
 /// GFS runs at 00Z, 06Z, 12Z, 18Z and takes ~5-6 hours to be fully available
 pub fn get_available_forecast_runs() -> Vec<ForecastRun> {
@@ -179,6 +192,7 @@ pub fn get_historical_forecast_run(run_age: i64) -> Vec<ForecastRun> {
 }
 
 /// Download wind data from NOAA OpenDAP service with automatic fallback
+#[allow(clippy::too_many_arguments)]
 pub async fn download_wind_data_opendap(
     forecast_offset: i32,
     run_age: i64,
@@ -186,6 +200,7 @@ pub async fn download_wind_data_opendap(
     lat_max: f64,
     lon_min: f64,
     lon_max: f64,
+    encoding: WindEncoding,
 ) -> Result<DownloadedWindData> {
     // If runAge is specified, calculate the specific historical run to fetch
     let available_runs = if run_age > 0 {
@@ -223,6 +238,7 @@ pub async fn download_wind_data_opendap(
             lat_max,
             lon_min,
             lon_max,
+            encoding,
         )
         .await
         {
@@ -244,6 +260,7 @@ pub async fn download_wind_data_opendap(
 }
 
 /// Download wind data for a specific forecast run
+#[allow(clippy::too_many_arguments)]
 async fn download_wind_data_for_run(
     date: &str,
     hour: &str,
@@ -252,6 +269,7 @@ async fn download_wind_data_for_run(
     lat_max: f64,
     lon_min: f64,
     lon_max: f64,
+    encoding: WindEncoding,
 ) -> Result<DownloadedWindData> {
     let base_url = format!(
         "https://nomads.ncep.noaa.gov/dods/gfs_0p50/gfs{}/gfs_0p50_{}z",
@@ -271,7 +289,7 @@ async fn download_wind_data_for_run(
     );
     info!("Zone: {}° to {}° (Global coverage)", lon_min, lon_max);
 
-    let (all_lat_values, all_lon_values, all_u_values, all_v_values) = if needs_wrap {
+    let (all_lat_values, all_lon_values, all_u_values, all_v_values, all_gusts) = if needs_wrap {
         info!("Handling longitude wraparound with two requests...");
 
         // Western part: lonMin to 0° (converted to 360+lonMin to 359.5°)
@@ -283,16 +301,12 @@ async fn download_wind_data_for_run(
             west_lon_start, west_lon_end, lon_min
         );
 
-        let west_constraint = format!(
-            ".ascii?ugrd10m[{}:1:{}][{}:1:{}][{}:1:{}],vgrd10m[{}:1:{}][{}:1:{}][{}:1:{}],lat[{}:1:{}],lon[{}:{}]",
-            forecast_offset, forecast_offset,
-            lat_start_index, lat_end_index,
-            west_lon_start, west_lon_end,
-            forecast_offset, forecast_offset,
-            lat_start_index, lat_end_index,
-            west_lon_start, west_lon_end,
-            lat_start_index, lat_end_index,
-            west_lon_start, west_lon_end
+        let west_constraint = wind_constraint(
+            forecast_offset,
+            lat_start_index,
+            lat_end_index,
+            west_lon_start,
+            west_lon_end,
         );
         let west_url = format!("{}{}", base_url, west_constraint);
 
@@ -328,16 +342,12 @@ async fn download_wind_data_for_run(
 
         info!("  East: lon indices {}:{} (0° to {}°)", east_lon_start, east_lon_end, lon_max);
 
-        let east_constraint = format!(
-            ".ascii?ugrd10m[{}:1:{}][{}:1:{}][{}:1:{}],vgrd10m[{}:1:{}][{}:1:{}][{}:1:{}],lat[{}:1:{}],lon[{}:{}]",
-            forecast_offset, forecast_offset,
-            lat_start_index, lat_end_index,
-            east_lon_start, east_lon_end,
-            forecast_offset, forecast_offset,
-            lat_start_index, lat_end_index,
-            east_lon_start, east_lon_end,
-            lat_start_index, lat_end_index,
-            east_lon_start, east_lon_end
+        let east_constraint = wind_constraint(
+            forecast_offset,
+            lat_start_index,
+            lat_end_index,
+            east_lon_start,
+            east_lon_end,
         );
         let east_url = format!("{}{}", base_url, east_constraint);
 
@@ -374,6 +384,7 @@ async fn download_wind_data_for_run(
 
         let mut all_u_values = Vec::new();
         let mut all_v_values = Vec::new();
+        let mut all_gusts = Vec::new();
 
         for lat_idx in 0..num_lats {
             let west_row_start = lat_idx * west_lon_count;
@@ -383,12 +394,14 @@ async fn download_wind_data_for_run(
             for i in 0..west_lon_count {
                 all_u_values.push(west_data.u_data[west_row_start + i]);
                 all_v_values.push(west_data.v_data[west_row_start + i]);
+                all_gusts.push(west_data.gust_data[west_row_start + i]);
             }
 
             // Add east row
             for i in 0..east_lon_count {
                 all_u_values.push(east_data.u_data[east_row_start + i]);
                 all_v_values.push(east_data.v_data[east_row_start + i]);
+                all_gusts.push(east_data.gust_data[east_row_start + i]);
             }
         }
 
@@ -399,7 +412,13 @@ async fn download_wind_data_for_run(
             all_u_values.len()
         );
 
-        (all_lat_values, all_lon_values, all_u_values, all_v_values)
+        (
+            all_lat_values,
+            all_lon_values,
+            all_u_values,
+            all_v_values,
+            all_gusts,
+        )
     } else {
         // Single request: no wraparound
         let lon_start = (lon_min / 0.5).floor() as i32;
@@ -407,16 +426,12 @@ async fn download_wind_data_for_run(
 
         info!("Single request: lon indices {}:{}", lon_start, lon_end);
 
-        let constraint = format!(
-            ".ascii?ugrd10m[{}:1:{}][{}:1:{}][{}:1:{}],vgrd10m[{}:1:{}][{}:1:{}][{}:1:{}],lat[{}:1:{}],lon[{}:{}]",
-            forecast_offset, forecast_offset,
-            lat_start_index, lat_end_index,
-            lon_start, lon_end,
-            forecast_offset, forecast_offset,
-            lat_start_index, lat_end_index,
-            lon_start, lon_end,
-            lat_start_index, lat_end_index,
-            lon_start, lon_end
+        let constraint = wind_constraint(
+            forecast_offset,
+            lat_start_index,
+            lat_end_index,
+            lon_start,
+            lon_end,
         );
         let data_url = format!("{}{}", base_url, constraint);
 
@@ -452,6 +467,7 @@ async fn download_wind_data_for_run(
             parsed_data.lon_values,
             parsed_data.u_data,
             parsed_data.v_data,
+            parsed_data.gust_data,
         )
     };
 
@@ -468,27 +484,8 @@ async fn download_wind_data_for_run(
         );
     }
 
-    // Calculate min/max
-    let u_min = all_u_values
-        .iter()
-        .cloned()
-        .fold(f64::INFINITY, f64::min);
-    let u_max = all_u_values
-        .iter()
-        .cloned()
-        .fold(f64::NEG_INFINITY, f64::max);
-    let v_min = all_v_values
-        .iter()
-        .cloned()
-        .fold(f64::INFINITY, f64::min);
-    let v_max = all_v_values
-        .iter()
-        .cloned()
-        .fold(f64::NEG_INFINITY, f64::max);
-
-    // Convert to PNG
-    let wind_png_data =
-        convert_to_png(width, height, &all_u_values, &all_v_values, u_min, u_max, v_min, v_max)?;
+    // Convert to PNG, scaled between the min and max of the finite values
+    let wind_png_data = convert_to_png(width, height, &all_u_values, &all_v_values, encoding)?;
 
     // Create wind points
     let mut wind_points = Vec::with_capacity(width * height);
@@ -499,20 +496,22 @@ async fn download_wind_data_for_run(
             let lon = all_lon_values[x];
             let u = all_u_values[idx];
             let v = all_v_values[idx];
+            let gusts = all_gusts[idx];
 
-            wind_points.push(WindPoint::new(lat, lon, u, v));
+            wind_points.push(WindPoint::new(lat, lon, u, v, gusts));
         }
     }
 
     let metadata = WindMetadata {
         source: "NOAA GFS 0.5° via OpenDAP".to_string(),
         date: Utc::now().to_rfc3339(),
-        width,
-        height,
-        u_min,
-        u_max,
-        v_min,
-        v_max,
+        width: wind_png_data.width,
+        height: wind_png_data.height,
+        u_min: wind_png_data.u_min,
+        u_max: wind_png_data.u_max,
+        v_min: wind_png_data.v_min,
+        v_max: wind_png_data.v_max,
+        encoding,
         tiles: vec!["/api/windgl/wind.png".to_string()],
     };
 
@@ -782,6 +781,7 @@ async fn download_precipitation_data_for_run(
     let width = all_lon_values.len();
     let height = all_lat_values.len();
     let mut precip_points = Vec::with_capacity(width * height);
+    let mut rates = Vec::with_capacity(width * height);
 
     for y in 0..height {
         for x in 0..width {
@@ -791,11 +791,29 @@ async fn download_precipitation_data_for_run(
             let rate_kg_per_m2s = all_prate_values[idx];
             let rate_mm_per_hour = rate_kg_per_m2s * 3600.0; // Convert to mm/h
 
+            rates.push(rate_mm_per_hour);
             precip_points.push(PrecipitationPoint::new(lat, lon, rate_mm_per_hour));
         }
     }
 
+    // Texture for the WebGL layer, laid out like the wind one
+    let precip_png_data = convert_precipitation_to_png(width, height, &rates)?;
+
+    let metadata = PrecipitationMetadata {
+        source: "NOAA GFS 0.5° via OpenDAP".to_string(),
+        date: Utc::now().to_rfc3339(),
+        width: precip_png_data.width,
+        height: precip_png_data.height,
+        rate_min: precip_png_data.rate_min,
+        rate_max: precip_png_data.rate_max,
+        scale: "log1p".to_string(),
+        unit: "mm/h".to_string(),
+        tiles: vec!["/api/precipgl/precipitation.png".to_string()],
+    };
+
     Ok(DownloadedPrecipitationData {
+        png_buffer: precip_png_data.png_buffer,
+        metadata,
         precip_points,
         run_name: String::new(),
         data_time: String::new(),
@@ -803,6 +821,111 @@ async fn download_precipitation_data_for_run(
     })
 }
 
+/// Download the global GFS land-sea mask (`landsfc`), static between runs
+pub async fn download_land_mask_opendap() -> Result<DownloadedLandMask> {
+    let mut last_error = None;
+
+    for run in &get_available_forecast_runs() {
+        info!(
+            "Attempting to fetch land mask from {} {}Z via OpenDAP...",
+            run.date, run.hour
+        );
+
+        match download_land_mask_for_run(&run.date, &run.hour).await {
+            Ok(mut data) => {
+                info!(
+                    "✓ Successfully fetched land mask from {} {}Z",
+                    run.date, run.hour
+                );
+                data.run_name = format!("{} {}Z", run.date, run.hour);
+                return Ok(data);
+            }
+            Err(e) => {
+                error!(
+                    "✗ Failed to fetch land mask {} {}Z: {}",
+                    run.date, run.hour, e
+                );
+                last_error = Some(e);
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("All forecast runs failed for the land mask")))
+}
+
+/// Download the land mask of a specific run, the whole 0.5° grid in one request
+async fn download_land_mask_for_run(date: &str, hour: &str) -> Result<DownloadedLandMask> {
+    let base_url = format!(
+        "https://nomads.ncep.noaa.gov/dods/gfs_0p50/gfs{}/gfs_0p50_{}z",
+        date, hour
+    );
+    let data_url = format!(
+        "{}.ascii?landsfc[0:1:0][0:1:360][0:1:719],lat[0:1:360],lon[0:1:719]",
+        base_url
+    );
+
+    let client = reqwest::Client::new();
+    let data_response = client
+        .get(&data_url)
+        .timeout(std::time::Duration::from_secs(60))
+        .send()
+        .await?;
+
+    if !data_response.status().is_success() {
+        anyhow::bail!("Land mask request failed: {}", data_response.status());
+    }
+
+    let ascii_data = data_response.text().await?;
+    info!(
+        "Downloaded {} bytes of land mask ASCII data",
+        ascii_data.len()
+    );
+
+    if ascii_data.trim().starts_with('<')
+        || ascii_data.contains("<!DOCTYPE")
+        || ascii_data.contains("<html")
+    {
+        let error_msg = extract_opendap_error(&ascii_data);
+        anyhow::bail!("OpenDAP error: {}", error_msg);
+    }
+
+    let parsed = parse_opendap_variable_ascii(&ascii_data, "landsfc")?;
+    if parsed.values.len() != parsed.lat_values.len() * parsed.lon_values.len() {
+        anyhow::bail!(
+            "Land mask size mismatch: {} values for {}x{} grid",
+            parsed.values.len(),
+            parsed.lat_values.len(),
+            parsed.lon_values.len()
+        );
+    }
+
+    Ok(DownloadedLandMask {
+        lat_values: parsed.lat_values,
+        lon_values: parsed.lon_values,
+        land: parsed.values,
+        run_name: String::new(),
+    })
+}
+
+/// OpenDAP constraint for the 10 m wind components and the surface gusts of one
+/// forecast time, over the given lat and lon index ranges
+fn wind_constraint(
+    forecast_offset: i32,
+    lat_start: i32,
+    lat_end: i32,
+    lon_start: i32,
+    lon_end: i32,
+) -> String {
+    let grid = format!(
+        "[{}:1:{}][{}:1:{}][{}:1:{}]",
+        forecast_offset, forecast_offset, lat_start, lat_end, lon_start, lon_end
+    );
+    format!(
+        ".ascii?ugrd10m{},vgrd10m{},gustsfc{},lat[{}:1:{}],lon[{}:{}]",
+        grid, grid, grid, lat_start, lat_end, lon_start, lon_end
+    )
+}
+
 /// Extract error message from OpenDAP HTML error page
 fn extract_opendap_error(html: &str) -> String {
     if let Some(start) = html.find("<b>") {
@@ -0,0 +1,335 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::services::RedisClient;
+
+const ORS_API_URL: &str = "https://api.openrouteservice.org";
+const CACHE_TTL: u64 = 24 * 60 * 60; // 1 day in seconds
+const QUOTA_TTL: u64 = 24 * 60 * 60; // ORS quotas are daily
+const CACHE_KEY_PREFIX: &str = "ors:cache";
+const QUOTA_KEY_PREFIX: &str = "ors:quota";
+/// Locations accepted in one matrix request on the standard plan
+pub const MAX_MATRIX_LOCATIONS: usize = 50;
+
+#[derive(Debug, Error)]
+pub enum OrsError {
+    #[error("OpenRouteService daily quota exhausted")]
+    QuotaExhausted { reset: Option<DateTime<Utc>> },
+    #[error("OpenRouteService returned status {status}")]
+    Upstream { status: u16, body: String },
+    #[error("failed to reach OpenRouteService: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("unexpected OpenRouteService response: {0}")]
+    InvalidResponse(#[from] serde_json::Error),
+}
+
+impl OrsError {
+    /// ORS could not be reached or failed on its side, as opposed to refusing the request
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            OrsError::Request(_) => true,
+            OrsError::Upstream { status, .. } => *status >= 500,
+            _ => false,
+        }
+    }
+}
+
+/// Remaining requests of one ORS endpoint, from the `x-ratelimit-*` response headers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrsQuota {
+    pub limit: Option<u64>,
+    pub remaining: u64,
+    pub reset: Option<DateTime<Utc>>,
+}
+
+impl OrsQuota {
+    fn from_headers(headers: &reqwest::header::HeaderMap) -> Option<Self> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<i64>().ok())
+        };
+
+        Some(OrsQuota {
+            limit: header("x-ratelimit-limit").and_then(|v| u64::try_from(v).ok()),
+            remaining: u64::try_from(header("x-ratelimit-remaining")?).unwrap_or(0),
+            reset: header("x-ratelimit-reset").and_then(|v| DateTime::from_timestamp(v, 0)),
+        })
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.remaining == 0 && self.reset.is_none_or(|reset| reset > Utc::now())
+    }
+}
+
+/// Body and content type of a successful ORS response, as returned to clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrsResponse {
+    pub content_type: String,
+    pub body: String,
+    #[serde(skip)]
+    pub cached: bool,
+}
+
+/// Travel from one source to one destination of a matrix request
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TravelTime {
+    /// Seconds
+    pub duration: f64,
+    /// Meters
+    pub distance: f64,
+}
+
+#[derive(Deserialize)]
+struct MatrixResponse {
+    durations: Vec<Vec<Option<f64>>>,
+    distances: Vec<Vec<Option<f64>>>,
+}
+
+/// OpenRouteService client shared by the routing endpoints.
+/// Successful responses are cached in Redis and the daily quota is tracked per endpoint,
+/// so an exhausted quota is reported without calling ORS again.
+pub struct OrsClient {
+    token: String,
+    client: reqwest::Client,
+    redis: Arc<RedisClient>,
+}
+
+impl OrsClient {
+    pub fn new(token: String, redis: Arc<RedisClient>) -> Self {
+        Self {
+            token,
+            client: reqwest::Client::new(),
+            redis,
+        }
+    }
+
+    /// POST `body` to `path` (e.g. `/v2/directions/driving-car/geojson`).
+    /// `endpoint` names the quota bucket (`directions`, `isochrones`, ...).
+    pub async fn post(
+        &self,
+        endpoint: &str,
+        path: &str,
+        body: &Value,
+    ) -> Result<OrsResponse, OrsError> {
+        let cache_key = Self::cache_key(path, body);
+
+        match self.redis.get_string(&cache_key).await {
+            Ok(Some(cached)) => match serde_json::from_str::<OrsResponse>(&cached) {
+                Ok(mut response) => {
+                    info!("ORS {}: cache hit", endpoint);
+                    response.cached = true;
+                    return Ok(response);
+                }
+                Err(e) => warn!("ORS {}: ignoring unreadable cache entry: {}", endpoint, e),
+            },
+            Ok(None) => {}
+            Err(e) => warn!("ORS {}: cache unavailable: {}", endpoint, e),
+        }
+
+        if let Some(quota) = self.quota(endpoint).await {
+            if quota.is_exhausted() {
+                return Err(OrsError::QuotaExhausted { reset: quota.reset });
+            }
+        }
+
+        let response = self
+            .client
+            .post(format!("{}{}", ORS_API_URL, path))
+            .header("Content-Type", "application/json")
+            .header(
+                "Accept",
+                "application/json, application/geo+json, application/gpx+xml, img/png; charset=utf-8",
+            )
+            .header("Authorization", self.token.as_str())
+            .json(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let quota = OrsQuota::from_headers(response.headers());
+        if let Some(quota) = &quota {
+            info!(
+                "ORS {}: {} requests remaining today",
+                endpoint, quota.remaining
+            );
+            self.store_quota(endpoint, quota).await;
+        }
+
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let reset = quota.and_then(|q| q.reset);
+            self.store_quota(
+                endpoint,
+                &OrsQuota {
+                    limit: None,
+                    remaining: 0,
+                    reset,
+                },
+            )
+            .await;
+            return Err(OrsError::QuotaExhausted { reset });
+        }
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("application/json")
+            .to_string();
+        let text = response.text().await?;
+
+        if !status.is_success() {
+            return Err(OrsError::Upstream {
+                status: status.as_u16(),
+                body: text,
+            });
+        }
+
+        let response = OrsResponse {
+            content_type,
+            body: text,
+            cached: false,
+        };
+
+        match serde_json::to_string(&response) {
+            Ok(serialized) => {
+                if let Err(e) = self
+                    .redis
+                    .set_string(&cache_key, &serialized, CACHE_TTL)
+                    .await
+                {
+                    warn!("ORS {}: failed to cache response: {}", endpoint, e);
+                }
+            }
+            Err(e) => warn!("ORS {}: failed to serialize response: {}", endpoint, e),
+        }
+
+        Ok(response)
+    }
+
+    /// Travel times from `origin` to each of `destinations` (`[lon, lat]`), in order.
+    /// `None` where ORS found no route. Destinations are sent in batches that fit one matrix request.
+    pub async fn travel_times(
+        &self,
+        profile: &str,
+        origin: [f64; 2],
+        destinations: &[[f64; 2]],
+    ) -> Result<Vec<Option<TravelTime>>, OrsError> {
+        let path = format!("/v2/matrix/{}", profile);
+        let mut times = Vec::with_capacity(destinations.len());
+
+        for batch in destinations.chunks(MAX_MATRIX_LOCATIONS - 1) {
+            let mut locations = vec![origin];
+            locations.extend_from_slice(batch);
+
+            let body = serde_json::json!({
+                "locations": locations,
+                "sources": [0],
+                "destinations": (1..locations.len()).collect::<Vec<_>>(),
+                "metrics": ["duration", "distance"],
+            });
+
+            let response = self.post("matrix", &path, &body).await?;
+            let matrix: MatrixResponse = serde_json::from_str(&response.body)?;
+
+            let durations = matrix.durations.into_iter().next().unwrap_or_default();
+            let distances = matrix.distances.into_iter().next().unwrap_or_default();
+            times.extend((0..batch.len()).map(|i| {
+                Some(TravelTime {
+                    duration: (*durations.get(i)?)?,
+                    distance: (*distances.get(i)?)?,
+                })
+            }));
+        }
+
+        Ok(times)
+    }
+
+    /// Last known quota of an endpoint
+    pub async fn quota(&self, endpoint: &str) -> Option<OrsQuota> {
+        let stored = self
+            .redis
+            .get_string(&format!("{}:{}", QUOTA_KEY_PREFIX, endpoint))
+            .await
+            .ok()??;
+        serde_json::from_str(&stored).ok()
+    }
+
+    async fn store_quota(&self, endpoint: &str, quota: &OrsQuota) {
+        let ttl = quota
+            .reset
+            .map(|reset| (reset - Utc::now()).num_seconds())
+            .filter(|seconds| *seconds > 0)
+            .map(|seconds| seconds as u64)
+            .unwrap_or(QUOTA_TTL);
+
+        let stored = match serde_json::to_string(quota) {
+            Ok(stored) => stored,
+            Err(_) => return,
+        };
+
+        if let Err(e) = self
+            .redis
+            .set_string(&format!("{}:{}", QUOTA_KEY_PREFIX, endpoint), &stored, ttl)
+            .await
+        {
+            warn!("ORS {}: failed to store quota: {}", endpoint, e);
+        }
+    }
+
+    /// Requests are identified by path (profile and format) and body (coordinates and options).
+    /// `serde_json` keeps object keys sorted, so equal bodies serialize identically.
+    fn cache_key(path: &str, body: &Value) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(path.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(body.to_string().as_bytes());
+        format!("{}:{:x}", CACHE_KEY_PREFIX, hasher.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use serde_json::json;
+
+    #[test]
+    fn test_cache_key_ignores_key_order() {
+        let a = json!({ "coordinates": [[2.35, 48.85], [2.37, 48.87]], "elevation": true });
+        let b: Value =
+            serde_json::from_str(r#"{"elevation":true,"coordinates":[[2.35,48.85],[2.37,48.87]]}"#)
+                .unwrap();
+
+        let path = "/v2/directions/cycling-regular/geojson";
+        assert_eq!(
+            OrsClient::cache_key(path, &a),
+            OrsClient::cache_key(path, &b)
+        );
+        assert_ne!(
+            OrsClient::cache_key(path, &a),
+            OrsClient::cache_key("/v2/directions/driving-car/geojson", &a)
+        );
+    }
+
+    #[test]
+    fn test_quota_from_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-limit", HeaderValue::from_static("2000"));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("0"));
+        headers.insert("x-ratelimit-reset", HeaderValue::from_static("4102444800"));
+
+        let quota = OrsQuota::from_headers(&headers).unwrap();
+        assert_eq!(quota.limit, Some(2000));
+        assert_eq!(quota.remaining, 0);
+        assert!(quota.is_exhausted());
+
+        assert!(OrsQuota::from_headers(&HeaderMap::new()).is_none());
+    }
+}
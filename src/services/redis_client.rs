@@ -5,6 +5,8 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tracing::info;
 
+use crate::utils::misc::redact_url_password;
+
 const REDIS_TTL: u64 = 60 * 60; // 1 hour in seconds
 const MAX_SIZE: usize = 8 * 1024 * 1024; // 8 MB
 
@@ -33,7 +35,7 @@ pub struct IndexEntry {
 
 impl RedisClient {
     pub async fn new(redis_url: &str) -> Result<Self> {
-        info!("Connecting to Redis at {}", redis_url);
+        info!("Connecting to Redis at {}", redact_url_password(redis_url));
 
         let client = Client::open(redis_url)
             .context("Failed to create Redis client")?;
@@ -49,6 +51,13 @@ impl RedisClient {
         })
     }
 
+    /// PING, for the readiness check
+    pub async fn ping(&self) -> Result<()> {
+        let mut conn = self.conn.as_ref().clone();
+        redis::cmd("PING").query_async::<_, ()>(&mut conn).await?;
+        Ok(())
+    }
+
     /// Store wind data in Redis with automatic chunking for large datasets
     pub async fn set_wind_data(&self, data: &serde_json::Value, key: &str) -> Result<()> {
         let data_string = serde_json::to_string(data)?;
@@ -286,6 +295,28 @@ impl RedisClient {
         }
     }
 
+    /// Store a plain string value with its own TTL
+    pub async fn set_string(&self, key: &str, value: &str, ttl: u64) -> Result<()> {
+        let mut conn = self.conn.as_ref().clone();
+        conn.set_ex::<_, _, ()>(key, value, ttl).await?;
+        Ok(())
+    }
+
+    /// Get a plain string value
+    pub async fn get_string(&self, key: &str) -> Result<Option<String>> {
+        let mut conn = self.conn.as_ref().clone();
+        Ok(conn.get(key).await?)
+    }
+
+    /// Whether `key` holds data stored by `set_wind_data`, chunked or not
+    pub async fn has_data(&self, key: &str) -> Result<bool> {
+        let mut conn = self.conn.as_ref().clone();
+        let count: u32 = conn
+            .exists(&[key.to_string(), format!("{}:chunks", key)])
+            .await?;
+        Ok(count > 0)
+    }
+
     /// Store wind data with index for historical tracking
     pub async fn set_wind_data_with_index(
         &self,
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use tracing::{info, warn};
+
+use crate::models::weather::{RouteWeather, RouteWeatherPoint};
+use crate::models::{RouteDocument, WindPoint};
+use crate::services::{RedisClient, PRECIPITATION_POINTS_KEY, WIND_POINTS_KEY};
+use crate::utils::weather_grid::{WeatherGrid, WIND_FIELDS};
+
+/// Maximum number of extra samples taken along the route geometry
+const MAX_GEOMETRY_SAMPLES: usize = 50;
+
+/// Sample the latest wind and precipitation grids at the route points and along its geometry.
+/// Returns `None` when no wind data has been fetched yet.
+pub async fn route_weather(
+    redis: &RedisClient,
+    route: &RouteDocument,
+) -> Result<Option<RouteWeather>> {
+    let Some(wind_data) = redis.get_wind_data(WIND_POINTS_KEY).await? else {
+        return Ok(None);
+    };
+    let wind = WeatherGrid::from_points(&wind_data, &WIND_FIELDS)?;
+
+    // Precipitation is optional, the overlay still makes sense with wind only
+    let precipitation = match redis.get_wind_data(PRECIPITATION_POINTS_KEY).await {
+        Ok(Some(data)) => WeatherGrid::from_points(&data, &["rate"])
+            .map_err(|e| warn!("Ignoring precipitation grid: {}", e))
+            .ok(),
+        Ok(None) => None,
+        Err(e) => {
+            warn!("Failed to fetch precipitation data: {}", e);
+            None
+        }
+    };
+
+    let mut locations: Vec<(f64, f64, Option<String>)> = route
+        .points()
+        .into_iter()
+        .map(|p| (p.lat, p.lon, p.display_name.clone()))
+        .collect();
+
+    let coordinates = route.coordinates();
+    if !coordinates.is_empty() {
+        let step = coordinates.len().div_ceil(MAX_GEOMETRY_SAMPLES).max(1);
+        locations.extend(
+            coordinates
+                .iter()
+                .step_by(step)
+                .map(|position| (position[1], position[0], None)),
+        );
+    }
+
+    let points: Vec<RouteWeatherPoint> = locations
+        .into_iter()
+        .map(|(lat, lon, name)| {
+            // Speed and direction are derived from the interpolated components,
+            // interpolating angles directly breaks around north
+            let wind_point = wind
+                .sample(lat, lon)
+                .filter(|values| values[0].is_finite() && values[1].is_finite())
+                .map(|values| WindPoint::new(lat, lon, values[0], values[1], values[2]));
+            RouteWeatherPoint {
+                lat,
+                lon,
+                name,
+                u: wind_point.as_ref().map(|w| w.u),
+                v: wind_point.as_ref().map(|w| w.v),
+                speed: wind_point.as_ref().map(|w| w.speed),
+                direction: wind_point.as_ref().map(|w| w.direction),
+                gusts: wind_point
+                    .as_ref()
+                    .map(|w| w.gusts)
+                    .filter(|gusts| gusts.is_finite()),
+                precipitation: precipitation
+                    .as_ref()
+                    .and_then(|grid| grid.sample(lat, lon))
+                    .map(|values| values[0])
+                    .filter(|value| value.is_finite()),
+            }
+        })
+        .collect();
+
+    info!("Sampled weather at {} route points", points.len());
+
+    Ok(Some(RouteWeather {
+        data_time: wind_data
+            .get("dataTime")
+            .and_then(|v| v.as_str())
+            .map(String::from),
+        run_name: wind_data
+            .get("runName")
+            .and_then(|v| v.as_str())
+            .map(String::from),
+        points,
+    }))
+}
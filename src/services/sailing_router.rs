@@ -0,0 +1,488 @@
+use chrono::{DateTime, Duration, Utc};
+use thiserror::Error;
+
+use crate::models::{SailingRoute, SailingRouteRequest, SailingTrackPoint, WindPoint};
+use crate::services::{GridCache, WIND_POINTS_KEY};
+use crate::utils::geo::{bearing, destination_point, haversine_distance};
+use crate::utils::land_mask::LandMask;
+use crate::utils::polar::PolarTable;
+use crate::utils::weather_grid::{round_to, WeatherTimeline, WIND_FIELDS};
+
+const KNOT: f64 = 1852.0 / 3600.0;
+/// Land within this distance (m) of the start or the end is ignored: at 0.5°, harbours
+/// and the water next to them usually fall in land cells
+const CLEARING_RADIUS: f64 = 30_000.0;
+/// Angular width (degrees, seen from the start) of the sectors each keeping one point
+/// of an isochrone
+const SECTOR_WIDTH: f64 = 1.0;
+
+#[derive(Debug, Error)]
+pub enum SailingError {
+    #[error("No wind data available yet")]
+    NoWindData,
+    #[error("Land mask not available yet")]
+    NoLandMask,
+    #[error("The {0} is too far inland")]
+    Inland(&'static str),
+    #[error("Destination not reached within {0} hours")]
+    Unreachable(i64),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IsochroneSettings {
+    pub time_step: Duration,
+    pub heading_step: f64,
+    pub max_duration: Duration,
+    pub keep_isochrones: bool,
+}
+
+/// Fastest passage for `request` with the boat `polar`, through the indexed wind grids
+/// and around the land mask, both decoded once in `grids`
+pub async fn sailing_route(
+    grids: &GridCache,
+    polar: PolarTable,
+    request: &SailingRouteRequest,
+) -> Result<SailingRoute, SailingError> {
+    let departure = request.departure.unwrap_or_else(Utc::now);
+    let settings = IsochroneSettings {
+        time_step: request.time_step(),
+        heading_step: request.heading_step(),
+        max_duration: request.max_duration(),
+        keep_isochrones: request.isochrones,
+    };
+
+    let wind = grids
+        .weather_timeline(
+            WIND_POINTS_KEY,
+            &WIND_FIELDS,
+            departure,
+            departure + settings.max_duration,
+        )
+        .await?
+        .ok_or(SailingError::NoWindData)?;
+
+    let land = grids.land_mask().await?.ok_or(SailingError::NoLandMask)?;
+
+    for (name, [lon, lat]) in [("start", request.start), ("end", request.end)] {
+        if !near_water(&land, lat, lon) {
+            return Err(SailingError::Inland(name));
+        }
+    }
+
+    let (start, end) = (request.start, request.end);
+    tokio::task::spawn_blocking(move || {
+        isochrone_route(
+            start,
+            end,
+            departure,
+            &polar,
+            &wind,
+            Some(&*land),
+            &settings,
+        )
+    })
+    .await
+    .map_err(anyhow::Error::from)?
+    .ok_or(SailingError::Unreachable(settings.max_duration.num_hours()))
+}
+
+/// Water at the position or within the clearing radius around it
+fn near_water(land: &LandMask, lat: f64, lon: f64) -> bool {
+    !land.is_land(lat, lon)
+        || (0..16).any(|i| {
+            let (lat, lon) = destination_point(lat, lon, i as f64 * 22.5, CLEARING_RADIUS);
+            !land.is_land(lat, lon)
+        })
+}
+
+/// Point of an isochrone and the leg that reached it from `parent` in the previous one
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    lat: f64,
+    lon: f64,
+    parent: usize,
+    leg: Option<Leg>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Leg {
+    heading: f64,
+    boat_speed: f64,
+    twa: f64,
+    /// Wind where the leg started, knots and degrees from
+    tws: f64,
+    twd: f64,
+}
+
+/// Angle between the course and the direction the wind blows from, 0 to 180
+fn true_wind_angle(heading: f64, twd: f64) -> f64 {
+    let angle = (twd - heading).rem_euclid(360.0);
+    if angle > 180.0 {
+        360.0 - angle
+    } else {
+        angle
+    }
+}
+
+/// Isochrone method: from every point of the current front, sail each heading for one
+/// time step, then keep the point farthest from the start in each sector. Stops at the
+/// first front from which the destination can be reached within a step.
+/// `start` and `end` are `[lon, lat]`; `None` when the destination is not reached within
+/// the maximum duration.
+pub fn isochrone_route(
+    start: [f64; 2],
+    end: [f64; 2],
+    departure: DateTime<Utc>,
+    polar: &PolarTable,
+    wind: &WeatherTimeline,
+    land: Option<&LandMask>,
+    settings: &IsochroneSettings,
+) -> Option<SailingRoute> {
+    let [start_lon, start_lat] = start;
+    let [end_lon, end_lat] = end;
+    let step_seconds = settings.time_step.num_seconds() as f64;
+    let max_steps = (settings.max_duration.num_seconds() as f64 / step_seconds).ceil() as usize;
+    let headings = (360.0 / settings.heading_step).round().max(1.0) as usize;
+    let sectors = (360.0 / SECTOR_WIDTH) as usize;
+
+    let wind_at = |lat: f64, lon: f64, time: DateTime<Utc>| -> Option<(f64, f64)> {
+        let sample = wind.sample(lat, lon, time)?;
+        let (u, v) = (sample[0], sample[1]);
+        if !u.is_finite() || !v.is_finite() {
+            return None;
+        }
+        let point = WindPoint::new(lat, lon, u, v, sample[2]);
+        Some((point.speed / KNOT, point.direction))
+    };
+    let blocked = |lat1: f64, lon1: f64, lat2: f64, lon2: f64| {
+        land.is_some_and(|land| {
+            land.land_along(lat1, lon1, lat2, lon2).any(|(lat, lon)| {
+                haversine_distance(lat, lon, start_lat, start_lon) > CLEARING_RADIUS
+                    && haversine_distance(lat, lon, end_lat, end_lon) > CLEARING_RADIUS
+            })
+        })
+    };
+
+    let mut fronts: Vec<Vec<Node>> = vec![vec![Node {
+        lat: start_lat,
+        lon: start_lon,
+        parent: 0,
+        leg: None,
+    }]];
+
+    for step in 0..max_steps {
+        let time = departure + Duration::seconds((step as f64 * step_seconds) as i64);
+        let front = &fronts[fronts.len() - 1];
+
+        // Earliest arrival straight from the current front
+        let mut arrival: Option<(f64, usize, Option<Leg>)> = None;
+        for (index, node) in front.iter().enumerate() {
+            let distance = haversine_distance(node.lat, node.lon, end_lat, end_lon);
+            if distance < 1.0 {
+                arrival = Some((0.0, index, None));
+                break;
+            }
+            let Some((tws, twd)) = wind_at(node.lat, node.lon, time) else {
+                continue;
+            };
+            let heading = bearing(node.lat, node.lon, end_lat, end_lon);
+            let twa = true_wind_angle(heading, twd);
+            let boat_speed = polar.boat_speed(twa, tws);
+            if boat_speed <= 0.0 {
+                continue;
+            }
+            let seconds = distance / (boat_speed * KNOT);
+            if seconds <= step_seconds
+                && arrival.is_none_or(|(best, _, _)| seconds < best)
+                && !blocked(node.lat, node.lon, end_lat, end_lon)
+            {
+                let leg = Leg {
+                    heading,
+                    boat_speed,
+                    twa,
+                    tws,
+                    twd,
+                };
+                arrival = Some((seconds, index, Some(leg)));
+            }
+        }
+
+        if let Some((seconds, index, leg)) = arrival {
+            let eta = time + Duration::milliseconds((seconds * 1000.0) as i64);
+            return Some(build_route(
+                &fronts, index, leg, end, departure, eta, settings, wind,
+            ));
+        }
+
+        // Next front
+        let mut best: Vec<Option<(f64, Node)>> = vec![None; sectors];
+        for (index, node) in front.iter().enumerate() {
+            let Some((tws, twd)) = wind_at(node.lat, node.lon, time) else {
+                continue;
+            };
+            for h in 0..headings {
+                let heading = h as f64 * settings.heading_step;
+                let twa = true_wind_angle(heading, twd);
+                let boat_speed = polar.boat_speed(twa, tws);
+                if boat_speed <= 0.0 {
+                    continue;
+                }
+
+                let (lat, lon) = destination_point(
+                    node.lat,
+                    node.lon,
+                    heading,
+                    boat_speed * KNOT * step_seconds,
+                );
+                if blocked(node.lat, node.lon, lat, lon) {
+                    continue;
+                }
+
+                let reach = haversine_distance(start_lat, start_lon, lat, lon);
+                let sector = ((bearing(start_lat, start_lon, lat, lon) / SECTOR_WIDTH) as usize)
+                    .min(sectors - 1);
+                if best[sector].is_none_or(|(farthest, _)| reach > farthest) {
+                    let leg = Leg {
+                        heading,
+                        boat_speed,
+                        twa,
+                        tws,
+                        twd,
+                    };
+                    best[sector] = Some((
+                        reach,
+                        Node {
+                            lat,
+                            lon,
+                            parent: index,
+                            leg: Some(leg),
+                        },
+                    ));
+                }
+            }
+        }
+
+        let next: Vec<Node> = best.into_iter().flatten().map(|(_, node)| node).collect();
+        if next.is_empty() {
+            return None;
+        }
+        fronts.push(next);
+    }
+
+    None
+}
+
+/// Walk back from the arrival node of the last front to the start
+#[allow(clippy::too_many_arguments)]
+fn build_route(
+    fronts: &[Vec<Node>],
+    arrival_index: usize,
+    arrival_leg: Option<Leg>,
+    end: [f64; 2],
+    departure: DateTime<Utc>,
+    eta: DateTime<Utc>,
+    settings: &IsochroneSettings,
+    wind: &WeatherTimeline,
+) -> SailingRoute {
+    // Nodes of the track, start first, each with the leg sailed from it
+    let mut path: Vec<(Node, Option<Leg>)> = Vec::new();
+    let mut index = arrival_index;
+    let mut outgoing = arrival_leg;
+    for front in fronts.iter().rev() {
+        let node = front[index];
+        path.push((node, outgoing));
+        outgoing = node.leg;
+        index = node.parent;
+    }
+    path.reverse();
+
+    let step_seconds = settings.time_step.num_seconds();
+    let mut track: Vec<SailingTrackPoint> = path
+        .iter()
+        .enumerate()
+        .map(|(step, (node, leg))| SailingTrackPoint {
+            time: departure + Duration::seconds(step as i64 * step_seconds),
+            position: [node.lon, node.lat],
+            heading: leg.map(|l| round_to(l.heading, 1)),
+            boat_speed: leg.map(|l| round_to(l.boat_speed, 2)),
+            tws: leg.map_or(0.0, |l| round_to(l.tws, 2)),
+            twd: leg.map_or(0.0, |l| round_to(l.twd, 1)),
+            twa: leg.map(|l| round_to(l.twa, 1)),
+        })
+        .collect();
+
+    let [end_lon, end_lat] = end;
+    let (tws, twd) = wind
+        .sample(end_lat, end_lon, eta)
+        .map(|sample| {
+            let point = WindPoint::new(end_lat, end_lon, sample[0], sample[1], sample[2]);
+            (point.speed / KNOT, point.direction)
+        })
+        .filter(|(tws, _)| tws.is_finite())
+        .unwrap_or((0.0, 0.0));
+    track.push(SailingTrackPoint {
+        time: eta,
+        position: end,
+        heading: None,
+        boat_speed: None,
+        tws: round_to(tws, 2),
+        twd: round_to(twd, 1),
+        twa: None,
+    });
+
+    let distance = track
+        .windows(2)
+        .map(|w| {
+            haversine_distance(
+                w[0].position[1],
+                w[0].position[0],
+                w[1].position[1],
+                w[1].position[0],
+            )
+        })
+        .sum::<f64>();
+
+    SailingRoute {
+        departure,
+        eta,
+        duration: round_to((eta - departure).num_milliseconds() as f64 / 1000.0, 1),
+        distance: round_to(distance, 1),
+        data_times: wind.times(),
+        forecast_coverage: wind.covers(departure) && wind.covers(eta),
+        track,
+        isochrones: settings.keep_isochrones.then(|| {
+            fronts
+                .iter()
+                .skip(1)
+                .map(|front| front.iter().map(|node| [node.lon, node.lat]).collect())
+                .collect()
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::polar::parse_polar_csv;
+    use crate::utils::weather_grid::WeatherGrid;
+
+    const POLAR: &str = "TWA;5;10;20\n40;3;5;6\n60;4;6;7\n90;5;7;8\n120;5;7;8\n180;3;5;7\n";
+
+    /// Uniform wind and land cells from `land`
+    fn grids(u: f64, v: f64, land: impl Fn(f64, f64) -> bool) -> (WeatherTimeline, LandMask) {
+        let (mut wind, mut mask) = (Vec::new(), Vec::new());
+        for i in 0..=20 {
+            let lat = 40.0 + i as f64 * 0.5;
+            for j in 0..=20 {
+                let lon = j as f64 * 0.5;
+                wind.push(serde_json::json!({ "lat": lat, "lon": lon, "u": u, "v": v }));
+                let land = if land(lat, lon) { 1.0 } else { 0.0 };
+                mask.push(serde_json::json!({ "lat": lat, "lon": lon, "land": land }));
+            }
+        }
+        let at = DateTime::from_timestamp(1_760_000_000, 0).unwrap();
+        let wind =
+            WeatherGrid::from_points(&serde_json::json!({ "points": wind }), &WIND_FIELDS).unwrap();
+        let mask =
+            WeatherGrid::from_points(&serde_json::json!({ "points": mask }), &["land"]).unwrap();
+        (
+            WeatherTimeline::new(vec![(at, wind)]).unwrap(),
+            LandMask::new(mask),
+        )
+    }
+
+    fn settings() -> IsochroneSettings {
+        IsochroneSettings {
+            time_step: Duration::minutes(60),
+            heading_step: 5.0,
+            max_duration: Duration::hours(72),
+            keep_isochrones: true,
+        }
+    }
+
+    #[test]
+    fn test_beam_reach_in_open_water() {
+        let polar = parse_polar_csv(POLAR).unwrap();
+        // 10 kn (5.14 m/s) from the north, sailing east on a beam reach at 7 kn
+        let (wind, land) = grids(0.0, -10.0 * KNOT, |_, _| false);
+        let departure = wind.times()[0];
+
+        let route = isochrone_route(
+            [2.0, 45.0],
+            [4.0, 45.0],
+            departure,
+            &polar,
+            &wind,
+            Some(&land),
+            &settings(),
+        )
+        .unwrap();
+
+        let direct = haversine_distance(45.0, 2.0, 45.0, 4.0);
+        let hours = route.duration / 3600.0;
+        let expected = direct / (7.0 * KNOT) / 3600.0;
+        assert!(
+            hours >= expected * 0.99 && hours < expected * 1.1,
+            "{}",
+            hours
+        );
+        assert_eq!(route.track.first().unwrap().position, [2.0, 45.0]);
+        assert_eq!(route.track.last().unwrap().position, [4.0, 45.0]);
+        assert!((route.track[0].twa.unwrap() - 90.0).abs() < 15.0);
+        assert!((route.track[0].tws - 10.0).abs() < 0.01);
+        assert_eq!(route.isochrones.unwrap().len(), route.track.len() - 2);
+        // A single wind grid only covers its own forecast time
+        assert!(!route.forecast_coverage);
+    }
+
+    #[test]
+    fn test_upwind_tacks_and_land_detour() {
+        let polar = parse_polar_csv(POLAR).unwrap();
+        // Dead upwind: the boat cannot point closer than 40°
+        let (wind, _) = grids(0.0, -10.0 * KNOT, |_, _| false);
+        let departure = wind.times()[0];
+        let route = isochrone_route(
+            [3.0, 45.0],
+            [3.0, 46.0],
+            departure,
+            &polar,
+            &wind,
+            None,
+            &settings(),
+        )
+        .unwrap();
+        assert!(route
+            .track
+            .iter()
+            .filter_map(|p| p.twa)
+            .all(|twa| twa >= 40.0));
+        assert!(route.distance > haversine_distance(45.0, 3.0, 46.0, 3.0) * 1.1);
+
+        // A wall of land between lon 2.5 and 3.5 at lat 46, open to the east
+        let (wind, land) = grids(10.0 * KNOT, 0.0, |lat, lon| {
+            (lat - 46.0).abs() < 0.01 && (1.0..=3.5).contains(&lon)
+        });
+        let route = isochrone_route(
+            [3.0, 45.0],
+            [3.0, 47.0],
+            departure,
+            &polar,
+            &wind,
+            Some(&land),
+            &settings(),
+        )
+        .unwrap();
+        assert!(route.track.windows(2).all(|w| land
+            .land_along(
+                w[0].position[1],
+                w[0].position[0],
+                w[1].position[1],
+                w[1].position[0]
+            )
+            .next()
+            .is_none()));
+        assert!(route.track.iter().any(|p| p.position[0] > 3.5));
+    }
+}
@@ -1,21 +1,26 @@
 use anyhow::Result;
 use chrono::{Datelike, DateTime, Duration, Timelike, Utc};
+use sqlx::PgPool;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{error, info};
 
 use crate::models::api_responses::LastFetchInfo;
+use crate::models::WindEncoding;
 use crate::services::opendap_downloader::{
-    download_precipitation_data_opendap, download_wind_data_opendap,
+    download_land_mask_opendap, download_precipitation_data_opendap, download_wind_data_opendap,
 };
-use crate::services::RedisClient;
+use crate::services::{evaluate_alert_rules, RedisClient};
 
 // Redis keys
 pub const WIND_POINTS_KEY: &str = "wind:points";
 pub const WIND_PNG_KEY: &str = "wind:png";
 pub const WIND_METADATA_KEY: &str = "wind:metadata";
 pub const PRECIPITATION_POINTS_KEY: &str = "precipitation:points";
+pub const PRECIPITATION_PNG_KEY: &str = "precipitation:png";
+pub const PRECIPITATION_METADATA_KEY: &str = "precipitation:metadata";
 pub const LAST_UPDATE_KEY: &str = "wind:last_update";
+pub const LAND_MASK_KEY: &str = "land:mask";
 
 #[derive(Debug, Clone)]
 pub struct ForecastTarget {
@@ -40,13 +45,18 @@ impl Default for SchedulerStatus {
 
 pub struct Scheduler {
     redis_client: Arc<RedisClient>,
+    /// Alert rules are evaluated after each forecast stored
+    pool: PgPool,
+    wind_encoding: WindEncoding,
     status: Arc<RwLock<SchedulerStatus>>,
 }
 
 impl Scheduler {
-    pub fn new(redis_client: Arc<RedisClient>) -> Self {
+    pub fn new(redis_client: Arc<RedisClient>, pool: PgPool, wind_encoding: WindEncoding) -> Self {
         Self {
             redis_client,
+            pool,
+            wind_encoding,
             status: Arc::new(RwLock::new(SchedulerStatus::default())),
         }
     }
@@ -68,9 +78,14 @@ impl Scheduler {
         if let Err(e) = self.fetch_historical_24h().await {
             error!("Initial 24h fetch failed: {}", e);
         }
+        if let Err(e) = self.ensure_land_mask().await {
+            error!("Land mask fetch failed: {}", e);
+        }
 
         // Schedule recurring fetches
         let redis_client = self.redis_client.clone();
+        let pool = self.pool.clone();
+        let wind_encoding = self.wind_encoding;
         let status = self.status.clone();
 
         tokio::spawn(async move {
@@ -81,17 +96,23 @@ impl Scheduler {
             // Every 5 minutes
             let job = Job::new_async("0 */5 * * * *", move |_uuid, _l| {
                 let redis_client = redis_client.clone();
+                let pool = pool.clone();
                 let status = status.clone();
 
                 Box::pin(async move {
                     info!("[{}] Scheduled latest forecast check triggered", Utc::now());
                     let scheduler = Scheduler {
                         redis_client,
+                        pool,
+                        wind_encoding,
                         status,
                     };
                     if let Err(e) = scheduler.fetch_latest_forecast().await {
                         error!("Latest forecast fetch failed: {}", e);
                     }
+                    if let Err(e) = scheduler.ensure_land_mask().await {
+                        error!("Land mask fetch failed: {}", e);
+                    }
                 })
             })
             .unwrap();
@@ -175,6 +196,7 @@ impl Scheduler {
             90.0,
             -180.0,
             180.0,
+            self.wind_encoding,
         )
         .await?;
 
@@ -284,6 +306,29 @@ impl Scheduler {
                         .await?;
                 }
 
+                // Texture and metadata for the WebGL layer, with the same index
+                let png_indexed_key = format!("{}:{}", PRECIPITATION_PNG_KEY, precip_index);
+                self.redis_client
+                    .set_binary_data(&precip_data.png_buffer, &png_indexed_key)
+                    .await?;
+
+                let precip_metadata = serde_json::to_value(&precip_data.metadata)?;
+                let metadata_indexed_key =
+                    format!("{}:{}", PRECIPITATION_METADATA_KEY, precip_index);
+                self.redis_client
+                    .set_wind_data(&precip_metadata, &metadata_indexed_key)
+                    .await?;
+
+                // The latest texture and metadata must describe the same forecast
+                if run_age == 0 && forecast_offset == 0 {
+                    self.redis_client
+                        .set_binary_data(&precip_data.png_buffer, PRECIPITATION_PNG_KEY)
+                        .await?;
+                    self.redis_client
+                        .set_wind_data(&precip_metadata, PRECIPITATION_METADATA_KEY)
+                        .await?;
+                }
+
                 info!("Precipitation data successfully stored in Redis");
             }
             Err(e) => {
@@ -296,6 +341,16 @@ impl Scheduler {
         }
 
         info!("=== Data for +{}h successfully stored ===\n", forecast_offset);
+
+        // Check the alert rules against the new grids, without failing the fetch
+        match evaluate_alert_rules(&self.redis_client, &self.pool).await {
+            Ok(run) => info!(
+                "Alert rules: {} checked, {} fired, {} resolved",
+                run.checked, run.fired, run.resolved
+            ),
+            Err(e) => error!("Alert rule evaluation failed: {}", e),
+        }
+
         Ok(true)
     }
 
@@ -432,6 +487,44 @@ impl Scheduler {
         Ok(success)
     }
 
+    /// Download the land mask used by sailing routes when Redis does not hold it (anymore)
+    pub async fn ensure_land_mask(&self) -> Result<()> {
+        if self.redis_client.has_data(LAND_MASK_KEY).await? {
+            return Ok(());
+        }
+
+        info!("Downloading land mask...");
+        let mask = download_land_mask_opendap().await?;
+
+        let width = mask.lon_values.len();
+        let points: Vec<serde_json::Value> = mask
+            .land
+            .iter()
+            .enumerate()
+            .map(|(i, land)| {
+                serde_json::json!({
+                    "lat": mask.lat_values[i / width],
+                    "lon": mask.lon_values[i % width],
+                    "land": land,
+                })
+            })
+            .collect();
+
+        let land_mask_json = serde_json::json!({
+            "timestamp": Utc::now().to_rfc3339(),
+            "runName": mask.run_name,
+            "source": "NOAA GFS 0.5° via OpenDAP",
+            "resolution": 0.5,
+            "points": points,
+        });
+        self.redis_client
+            .set_wind_data(&land_mask_json, LAND_MASK_KEY)
+            .await?;
+
+        info!("Stored land mask ({} points)", mask.land.len());
+        Ok(())
+    }
+
     /// Get scheduler status
     pub async fn get_status(&self) -> SchedulerStatus {
         self.status.read().await.clone()
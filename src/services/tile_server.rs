@@ -0,0 +1,79 @@
+use anyhow::Result;
+use hashlink::LruCache;
+use std::sync::{Arc, Mutex};
+
+use crate::services::{GridCache, GridKey, PRECIPITATION_POINTS_KEY, WIND_POINTS_KEY};
+use crate::utils::config::Config;
+use crate::utils::map_tiles::{render_tile, TileLayer};
+use crate::utils::palette::Palettes;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct TileKey {
+    grid: GridKey,
+    layer: TileLayer,
+    z: u8,
+    x: u32,
+    y: u32,
+}
+
+/// Renders the indexed wind and precipitation grids as Web Mercator tiles.
+/// The last tiles rendered are kept in an LRU cache.
+pub struct TileServer {
+    grids: Arc<GridCache>,
+    palettes: Arc<Palettes>,
+    tiles: Mutex<LruCache<TileKey, Vec<u8>>>,
+}
+
+impl TileServer {
+    pub fn new(grids: Arc<GridCache>, palettes: Arc<Palettes>, config: &Config) -> Self {
+        Self {
+            grids,
+            palettes,
+            tiles: Mutex::new(LruCache::new(config.tile_cache_size)),
+        }
+    }
+
+    /// PNG of tile (z, x, y) of `layer` at forecast `index`, `None` when the index is not stored
+    pub async fn tile(
+        &self,
+        layer: TileLayer,
+        index: u32,
+        z: u8,
+        x: u32,
+        y: u32,
+    ) -> Result<Option<Vec<u8>>> {
+        let base_key = match layer {
+            TileLayer::Wind | TileLayer::WindSpeed => WIND_POINTS_KEY,
+            TileLayer::Precipitation => PRECIPITATION_POINTS_KEY,
+        };
+        let Some(entry) = self.grids.entry(base_key, Some(index)).await? else {
+            return Ok(None);
+        };
+
+        let key = TileKey {
+            grid: GridKey::new(base_key, &entry, layer.fields()),
+            layer,
+            z,
+            x,
+            y,
+        };
+        if let Some(tile) = self.tiles.lock().unwrap().get(&key) {
+            return Ok(Some(tile.clone()));
+        }
+
+        let Some(grid) = self.grids.get(&key.grid).await? else {
+            return Ok(None);
+        };
+        let palette = layer
+            .palette()
+            .and_then(|name| self.palettes.get(name))
+            .cloned();
+        let tile = tokio::task::spawn_blocking(move || {
+            render_tile(layer, &grid.grid, &grid.ranges, palette.as_ref(), z, x, y)
+        })
+        .await??;
+
+        self.tiles.lock().unwrap().insert(key, tile.clone());
+        Ok(Some(tile))
+    }
+}
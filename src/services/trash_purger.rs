@@ -0,0 +1,67 @@
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::utils::queries::purge_trash;
+
+/// Permanently deletes routes and addresses that stayed in the trash past the retention period
+#[derive(Clone)]
+pub struct TrashPurger {
+    pool: PgPool,
+    retention_days: i32,
+}
+
+impl TrashPurger {
+    pub fn new(pool: PgPool, retention_days: i32) -> Self {
+        Self {
+            pool,
+            retention_days,
+        }
+    }
+
+    /// Start the daily purge job
+    pub async fn start(&self) {
+        info!(
+            "Starting trash purge job (retention: {} days, daily at 03:30 UTC)",
+            self.retention_days
+        );
+
+        let purger = self.clone();
+
+        tokio::spawn(async move {
+            use tokio_cron_scheduler::{Job, JobScheduler};
+
+            let sched = JobScheduler::new().await.unwrap();
+
+            let job = Job::new_async("0 30 3 * * *", move |_uuid, _l| {
+                let purger = purger.clone();
+
+                Box::pin(async move {
+                    info!("[{}] Scheduled trash purge triggered", Utc::now());
+                    if let Err(e) = purger.purge().await {
+                        error!("Trash purge failed: {}", e);
+                    }
+                })
+            })
+            .unwrap();
+
+            sched.add(job).await.unwrap();
+            sched.start().await.unwrap();
+
+            // Keep the scheduler running
+            loop {
+                tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            }
+        });
+    }
+
+    pub async fn purge(&self) -> Result<()> {
+        let (routes, addresses) = purge_trash(self.retention_days, &self.pool).await?;
+        info!(
+            "Trash purge: deleted {} routes and {} addresses older than {} days",
+            routes, addresses, self.retention_days
+        );
+        Ok(())
+    }
+}
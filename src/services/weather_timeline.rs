@@ -0,0 +1,52 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use tracing::warn;
+
+use crate::services::RedisClient;
+use crate::utils::weather_grid::{WeatherGrid, WeatherTimeline};
+
+/// Load the indexed grids of `base_key` needed to cover `from..=to`:
+/// every frame inside the window plus the closest one on each side.
+/// Returns `None` when no indexed data is available.
+pub async fn load_weather_timeline(
+    redis: &RedisClient,
+    base_key: &str,
+    fields: &[&str],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Option<WeatherTimeline>> {
+    let mut entries: Vec<(DateTime<Utc>, u32)> = redis
+        .get_available_indices(base_key)
+        .await?
+        .into_iter()
+        .filter_map(|entry| {
+            let time = DateTime::parse_from_rfc3339(entry.data_time.as_deref()?).ok()?;
+            Some((time.with_timezone(&Utc), entry.index))
+        })
+        .collect();
+    entries.sort_by_key(|(time, _)| *time);
+
+    let first = entries
+        .iter()
+        .rposition(|(time, _)| *time <= from)
+        .unwrap_or(0);
+    let last = entries
+        .iter()
+        .position(|(time, _)| *time >= to)
+        .unwrap_or(entries.len().saturating_sub(1));
+
+    let mut frames = Vec::new();
+    for (time, index) in entries.iter().take(last + 1).skip(first) {
+        let grid = match redis.get_wind_data_by_index(base_key, *index).await {
+            Ok(Some(data)) => WeatherGrid::from_points(&data, fields),
+            Ok(None) => continue,
+            Err(e) => Err(e),
+        };
+        match grid {
+            Ok(grid) => frames.push((*time, grid)),
+            Err(e) => warn!("Skipping {} index {}: {}", base_key, index, e),
+        }
+    }
+
+    Ok(WeatherTimeline::new(frames))
+}
@@ -0,0 +1,350 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::models::{
+    EffortEstimate, RouteDocument, WindAnalysis, WindAnalysisQuery, WindExposure, WindPoint,
+    WindSegment, WindTotals,
+};
+use crate::services::{load_weather_timeline, RedisClient, WIND_POINTS_KEY};
+use crate::utils::cycling_power::RiderModel;
+use crate::utils::geo::{bearing, haversine_distance};
+use crate::utils::route_formats::position_times;
+use crate::utils::weather_grid::{round_to, WeatherTimeline, WIND_FIELDS};
+
+/// Wind along the computed geometry of `document` for the departure of `query`.
+/// Returns `None` when no indexed wind grid is available.
+pub async fn route_wind_analysis(
+    redis: &RedisClient,
+    document: &RouteDocument,
+    query: &WindAnalysisQuery,
+) -> Result<Option<WindAnalysis>> {
+    let departure = query.departure.unwrap_or_else(Utc::now);
+    let times = position_times(document, departure);
+    let arrival = times.last().copied().unwrap_or(departure);
+
+    let Some(timeline) =
+        load_weather_timeline(redis, WIND_POINTS_KEY, &WIND_FIELDS, departure, arrival).await?
+    else {
+        return Ok(None);
+    };
+
+    let rider = document.profile.is_cycling().then(|| {
+        let defaults = RiderModel::default();
+        RiderModel {
+            power: query
+                .power
+                .filter(|p| p.is_finite())
+                .map_or(defaults.power, |p| p.clamp(50.0, 600.0)),
+            mass: query
+                .mass
+                .filter(|m| m.is_finite())
+                .map_or(defaults.mass, |m| m.clamp(30.0, 200.0)),
+            ..defaults
+        }
+    });
+
+    Ok(Some(analyze_wind(
+        document.coordinates(),
+        &times,
+        &timeline,
+        rider,
+        query.segment_length(),
+        query.worst(),
+    )))
+}
+
+/// Split the geometry into segments of about `segment_length` meters and sample the wind
+/// at the middle of each, at its estimated passage time
+pub fn analyze_wind(
+    coordinates: &[Vec<f64>],
+    times: &[DateTime<Utc>],
+    timeline: &WeatherTimeline,
+    rider: Option<RiderModel>,
+    segment_length: f64,
+    worst: usize,
+) -> WindAnalysis {
+    let departure = times.first().copied().unwrap_or_else(Utc::now);
+    let arrival = times.last().copied().unwrap_or(departure);
+
+    let edge_lengths: Vec<f64> = coordinates
+        .windows(2)
+        .map(|w| haversine_distance(w[0][1], w[0][0], w[1][1], w[1][0]))
+        .collect();
+
+    let mut bounds = Vec::new();
+    let (mut start, mut length) = (0, 0.0);
+    for (i, edge) in edge_lengths.iter().enumerate() {
+        length += edge;
+        if length >= segment_length || i + 1 == edge_lengths.len() {
+            bounds.push((start, i + 1));
+            start = i + 1;
+            length = 0.0;
+        }
+    }
+
+    let mut segments = Vec::new();
+    let mut still_air_duration = 0.0;
+    let mut wind_duration = 0.0;
+    let mut extra_energy = 0.0;
+
+    for (from, to) in bounds {
+        let distance: f64 = edge_lengths[from..to].iter().sum();
+
+        // Middle of the segment, by distance
+        let mut travelled = 0.0;
+        let mut middle = [coordinates[from][0], coordinates[from][1]];
+        for k in from..to {
+            if travelled + edge_lengths[k] >= distance / 2.0 {
+                let ratio = if edge_lengths[k] > 0.0 {
+                    (distance / 2.0 - travelled) / edge_lengths[k]
+                } else {
+                    0.0
+                };
+                middle = [
+                    coordinates[k][0] + (coordinates[k + 1][0] - coordinates[k][0]) * ratio,
+                    coordinates[k][1] + (coordinates[k + 1][1] - coordinates[k][1]) * ratio,
+                ];
+                break;
+            }
+            travelled += edge_lengths[k];
+        }
+        let (start_time, end_time) = (times[from], times[to.min(times.len() - 1)]);
+        let time =
+            start_time + Duration::milliseconds((end_time - start_time).num_milliseconds() / 2);
+
+        let Some(sample) = timeline.sample(middle[1], middle[0], time) else {
+            continue;
+        };
+        let (u, v) = (sample[0], sample[1]);
+        if !u.is_finite() || !v.is_finite() {
+            continue;
+        }
+        let wind = WindPoint::new(middle[1], middle[0], u, v, sample[2]);
+
+        let segment_bearing = bearing(
+            coordinates[from][1],
+            coordinates[from][0],
+            coordinates[to][1],
+            coordinates[to][0],
+        );
+
+        // Components weighted by the length of each edge, so bends are accounted for
+        let (mut headwind, mut crosswind) = (0.0, 0.0);
+        for k in from..to {
+            let theta = if edge_lengths[k] > 0.0 {
+                bearing(
+                    coordinates[k][1],
+                    coordinates[k][0],
+                    coordinates[k + 1][1],
+                    coordinates[k + 1][0],
+                )
+            } else {
+                segment_bearing
+            }
+            .to_radians();
+            let weight = if distance > 0.0 {
+                edge_lengths[k] / distance
+            } else {
+                1.0 / (to - from) as f64
+            };
+            headwind -= (u * theta.sin() + v * theta.cos()) * weight;
+            crosswind += (u * theta.cos() - v * theta.sin()).abs() * weight;
+        }
+
+        let exposure = if crosswind > headwind.abs() {
+            WindExposure::Crosswind
+        } else if headwind > 0.0 {
+            WindExposure::Headwind
+        } else {
+            WindExposure::Tailwind
+        };
+
+        let extra_seconds = rider.map(|rider| {
+            let grade = match (coordinates[from].get(2), coordinates[to].get(2)) {
+                (Some(a), Some(b)) if distance > 0.0 => (b - a) / distance,
+                _ => 0.0,
+            };
+            let still_speed = rider.speed(0.0, grade);
+            let still = distance / still_speed;
+            let windy = distance / rider.speed(headwind, grade);
+            still_air_duration += still;
+            wind_duration += windy;
+            extra_energy += (rider.power_at(still_speed, headwind, grade)
+                - rider.power_at(still_speed, 0.0, grade))
+                * still;
+            round_to(windy - still, 1)
+        });
+
+        segments.push(WindSegment {
+            index: segments.len(),
+            way_points: [from, to],
+            start: [coordinates[from][0], coordinates[from][1]],
+            end: [coordinates[to][0], coordinates[to][1]],
+            distance: round_to(distance, 1),
+            time,
+            bearing: round_to(segment_bearing, 1),
+            wind_speed: round_to(wind.speed, 2),
+            wind_direction: round_to(wind.direction, 1),
+            gusts: wind.gusts.is_finite().then(|| round_to(wind.gusts, 2)),
+            headwind: round_to(headwind, 2),
+            crosswind: round_to(crosswind, 2),
+            exposure,
+            extra_seconds,
+        });
+    }
+
+    let total_distance: f64 = segments.iter().map(|s| s.distance).sum();
+    let exposure_distance = |exposure: WindExposure| {
+        round_to(
+            segments
+                .iter()
+                .filter(|s| s.exposure == exposure)
+                .map(|s| s.distance)
+                .sum(),
+            1,
+        )
+    };
+    let totals = WindTotals {
+        distance: round_to(total_distance, 1),
+        headwind_distance: exposure_distance(WindExposure::Headwind),
+        crosswind_distance: exposure_distance(WindExposure::Crosswind),
+        tailwind_distance: exposure_distance(WindExposure::Tailwind),
+        mean_headwind: if total_distance > 0.0 {
+            round_to(
+                segments
+                    .iter()
+                    .map(|s| s.headwind * s.distance)
+                    .sum::<f64>()
+                    / total_distance,
+                2,
+            )
+        } else {
+            0.0
+        },
+        max_headwind: segments.iter().map(|s| s.headwind).fold(0.0, f64::max),
+        max_crosswind: segments.iter().map(|s| s.crosswind).fold(0.0, f64::max),
+        max_gusts: segments.iter().filter_map(|s| s.gusts).reduce(f64::max),
+    };
+
+    let effort = rider.map(|rider| EffortEstimate {
+        power: rider.power,
+        mass: rider.mass,
+        still_air_duration: round_to(still_air_duration, 1),
+        wind_duration: round_to(wind_duration, 1),
+        extra_duration: round_to(wind_duration - still_air_duration, 1),
+        extra_energy: round_to(extra_energy / 1000.0, 1),
+    });
+
+    let mut worst_segments: Vec<WindSegment> = segments
+        .iter()
+        .filter(|s| s.headwind > 0.0)
+        .cloned()
+        .collect();
+    worst_segments.sort_by(|a, b| b.headwind.total_cmp(&a.headwind));
+    worst_segments.truncate(worst);
+
+    WindAnalysis {
+        departure,
+        arrival,
+        data_times: timeline.times(),
+        forecast_coverage: timeline.covers(departure) && timeline.covers(arrival),
+        totals,
+        effort,
+        worst_segments,
+        segments,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::weather_grid::WeatherGrid;
+
+    /// Uniform wind over the whole test area
+    fn timeline(u: f64, v: f64, at: DateTime<Utc>) -> WeatherTimeline {
+        let mut points = Vec::new();
+        for lat in [40.0, 50.0] {
+            for lon in [0.0, 10.0] {
+                points.push(
+                    serde_json::json!({ "lat": lat, "lon": lon, "u": u, "v": v, "gusts": 12.0 }),
+                );
+            }
+        }
+        let grid = WeatherGrid::from_points(&serde_json::json!({ "points": points }), &WIND_FIELDS)
+            .unwrap();
+        WeatherTimeline::new(vec![(at, grid)]).unwrap()
+    }
+
+    /// About 4.4 km due north then 3 km due east, one position every ~100 m
+    fn route() -> (Vec<Vec<f64>>, Vec<DateTime<Utc>>) {
+        let start = DateTime::from_timestamp(1_760_000_000, 0).unwrap();
+        let mut coordinates: Vec<Vec<f64>> = (0..=40)
+            .map(|i| vec![2.0, 45.0 + i as f64 * 0.001])
+            .collect();
+        coordinates.extend((1..=22).map(|i| vec![2.0 + i as f64 * 0.0013, 45.04]));
+        let times = (0..coordinates.len())
+            .map(|i| start + Duration::seconds(i as i64 * 15))
+            .collect();
+        (coordinates, times)
+    }
+
+    #[test]
+    fn test_wind_components_per_segment() {
+        let (coordinates, times) = route();
+        // Wind from the north at 5 m/s (blowing southward)
+        let analysis = analyze_wind(
+            &coordinates,
+            &times,
+            &timeline(0.0, -5.0, times[0]),
+            Some(RiderModel::default()),
+            1000.0,
+            3,
+        );
+
+        let north = &analysis.segments[0];
+        assert_eq!(north.exposure, WindExposure::Headwind);
+        assert!((north.headwind - 5.0).abs() < 0.05);
+        assert!(north.crosswind < 0.05);
+        assert!(
+            (north.wind_direction - 0.0).abs() < 0.5 || (north.wind_direction - 360.0).abs() < 0.5
+        );
+        assert!(north.extra_seconds.unwrap() > 0.0);
+
+        let east = analysis.segments.last().unwrap();
+        assert_eq!(east.exposure, WindExposure::Crosswind);
+        assert!((east.crosswind - 5.0).abs() < 0.05);
+
+        assert!(analysis.worst_segments.len() <= 3);
+        assert!(analysis
+            .worst_segments
+            .iter()
+            .all(|s| s.exposure == WindExposure::Headwind));
+        assert!(
+            (analysis.totals.distance - analysis.segments.iter().map(|s| s.distance).sum::<f64>())
+                .abs()
+                < 1.0
+        );
+        assert_eq!(analysis.totals.max_gusts, Some(12.0));
+
+        let effort = analysis.effort.unwrap();
+        assert!(effort.extra_duration > 0.0);
+        assert!(effort.extra_energy > 0.0);
+    }
+
+    #[test]
+    fn test_tailwind_helps() {
+        let (coordinates, times) = route();
+        // Wind from the south, pushing northward
+        let analysis = analyze_wind(
+            &coordinates,
+            &times,
+            &timeline(0.0, 5.0, times[0]),
+            Some(RiderModel::default()),
+            1000.0,
+            5,
+        );
+        assert_eq!(analysis.segments[0].exposure, WindExposure::Tailwind);
+        assert!(analysis.totals.mean_headwind < 0.0);
+        assert!(analysis.effort.unwrap().extra_duration < 0.0);
+    }
+}
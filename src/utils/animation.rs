@@ -0,0 +1,214 @@
+use anyhow::{Context, Result};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::codecs::webp::WebPEncoder;
+use image::{Delay, ExtendedColorType, Frame, RgbaImage};
+
+/// Time each forecast is shown
+pub const FRAME_DELAY_MS: u16 = 600;
+
+/// Formats of the animated maps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+    Webp,
+}
+
+impl AnimationFormat {
+    pub const ALL: [AnimationFormat; 3] = [
+        AnimationFormat::Gif,
+        AnimationFormat::Apng,
+        AnimationFormat::Webp,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.as_str() == name)
+    }
+
+    /// Extension in URLs
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::Apng => "apng",
+            AnimationFormat::Webp => "webp",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "image/gif",
+            AnimationFormat::Apng => "image/apng",
+            AnimationFormat::Webp => "image/webp",
+        }
+    }
+}
+
+/// Animation of `frames`, all of the same size, each shown `delay_ms` and looping forever
+pub fn encode_animation(
+    frames: &[RgbaImage],
+    format: AnimationFormat,
+    delay_ms: u16,
+) -> Result<Vec<u8>> {
+    anyhow::ensure!(!frames.is_empty(), "An animation needs at least one frame");
+
+    match format {
+        AnimationFormat::Gif => encode_gif(frames, delay_ms),
+        AnimationFormat::Apng => encode_apng(frames, delay_ms),
+        AnimationFormat::Webp => encode_webp(frames, delay_ms),
+    }
+}
+
+fn encode_gif(frames: &[RgbaImage], delay_ms: u16) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    {
+        // Faster palette quantization, good enough for smooth color ramps
+        let mut encoder = GifEncoder::new_with_speed(&mut buffer, 10);
+        encoder.set_repeat(Repeat::Infinite)?;
+        encoder.encode_frames(frames.iter().map(|frame| {
+            Frame::from_parts(
+                frame.clone(),
+                0,
+                0,
+                Delay::from_numer_denom_ms(u32::from(delay_ms), 1),
+            )
+        }))?;
+    }
+    Ok(buffer)
+}
+
+fn encode_apng(frames: &[RgbaImage], delay_ms: u16) -> Result<Vec<u8>> {
+    let (width, height) = frames[0].dimensions();
+    let mut buffer = Vec::new();
+
+    let mut encoder = png::Encoder::new(&mut buffer, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, 0)?;
+    encoder.set_frame_delay(delay_ms, 1000)?;
+
+    let mut writer = encoder.write_header()?;
+    for frame in frames {
+        writer.write_image_data(frame.as_raw())?;
+    }
+    writer.finish()?;
+    Ok(buffer)
+}
+
+/// The image crate only writes still WebP images: each frame is encoded alone
+/// and its lossless bitstream moved into an animation frame chunk
+fn encode_webp(frames: &[RgbaImage], delay_ms: u16) -> Result<Vec<u8>> {
+    let (width, height) = frames[0].dimensions();
+    let mut chunks = Vec::new();
+
+    // Canvas with alpha (0x10) and animation (0x02)
+    let mut vp8x = vec![0x12, 0, 0, 0];
+    vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+    vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+    write_chunk(&mut chunks, b"VP8X", &vp8x);
+    // Transparent background, looping forever
+    write_chunk(&mut chunks, b"ANIM", &[0, 0, 0, 0, 0, 0]);
+
+    for frame in frames {
+        let mut still = Vec::new();
+        WebPEncoder::new_lossless(&mut still).encode(
+            frame.as_raw(),
+            width,
+            height,
+            ExtendedColorType::Rgba8,
+        )?;
+        let bitstream = find_chunk(&still, b"VP8L").context("No VP8L chunk in WebP frame")?;
+
+        // At (0, 0), replacing the previous frame
+        let mut anmf = vec![0; 6];
+        anmf.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        anmf.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+        anmf.extend_from_slice(&u32::from(delay_ms).to_le_bytes()[..3]);
+        anmf.push(0b10);
+        write_chunk(&mut anmf, b"VP8L", bitstream);
+        write_chunk(&mut chunks, b"ANMF", &anmf);
+    }
+
+    let mut webp = Vec::with_capacity(chunks.len() + 12);
+    webp.extend_from_slice(b"RIFF");
+    webp.extend_from_slice(&(chunks.len() as u32 + 4).to_le_bytes());
+    webp.extend_from_slice(b"WEBP");
+    webp.extend_from_slice(&chunks);
+    Ok(webp)
+}
+
+/// RIFF chunk, padded to an even size
+fn write_chunk(out: &mut Vec<u8>, fourcc: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(fourcc);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// Data of the first `fourcc` chunk of a WebP file
+fn find_chunk<'a>(webp: &'a [u8], fourcc: &[u8; 4]) -> Option<&'a [u8]> {
+    let mut position = 12;
+    while position + 8 <= webp.len() {
+        let size = u32::from_le_bytes(webp[position + 4..position + 8].try_into().ok()?) as usize;
+        let data = webp.get(position + 8..position + 8 + size)?;
+        if &webp[position..position + 4] == fourcc {
+            return Some(data);
+        }
+        position += 8 + size + size % 2;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifDecoder;
+    use image::codecs::png::PngDecoder;
+    use image::codecs::webp::WebPDecoder;
+    use image::{AnimationDecoder, Rgba};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_encode_animation() {
+        let frames: Vec<RgbaImage> = [[200, 30, 30, 255], [30, 30, 200, 255], [0, 0, 0, 0]]
+            .into_iter()
+            .map(|color| RgbaImage::from_pixel(6, 4, Rgba(color)))
+            .collect();
+
+        for format in AnimationFormat::ALL {
+            let data = encode_animation(&frames, format, FRAME_DELAY_MS).unwrap();
+            let decoded = match format {
+                AnimationFormat::Gif => GifDecoder::new(Cursor::new(&data))
+                    .unwrap()
+                    .into_frames()
+                    .collect_frames(),
+                AnimationFormat::Apng => PngDecoder::new(Cursor::new(&data))
+                    .unwrap()
+                    .apng()
+                    .unwrap()
+                    .into_frames()
+                    .collect_frames(),
+                AnimationFormat::Webp => WebPDecoder::new(Cursor::new(&data))
+                    .unwrap()
+                    .into_frames()
+                    .collect_frames(),
+            }
+            .unwrap();
+
+            assert_eq!(decoded.len(), 3, "{}", format.as_str());
+            assert_eq!(decoded[0].buffer().dimensions(), (6, 4));
+            assert_eq!(
+                decoded[1].delay().numer_denom_ms(),
+                (u32::from(FRAME_DELAY_MS), 1)
+            );
+            if format != AnimationFormat::Gif {
+                // Lossless
+                assert_eq!(decoded[1].buffer().get_pixel(3, 2).0, [30, 30, 200, 255]);
+            }
+            assert_eq!(decoded[2].buffer().get_pixel(0, 0).0[3], 0);
+        }
+
+        assert!(encode_animation(&[], AnimationFormat::Gif, FRAME_DELAY_MS).is_err());
+    }
+}
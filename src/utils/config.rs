@@ -1,5 +1,35 @@
 use std::env;
 
+use crate::models::WindEncoding;
+
+/// Engine answering `/api/routing`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoutingEngine {
+    /// OpenRouteService, falling back to the offline graph when one is configured
+    Ors,
+    /// Offline road graph only, no network needed
+    Local,
+}
+
+/// Service answering `/api/geocode` and `/api/reverse-geocode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeocodingProvider {
+    /// Pelias, as hosted by OpenRouteService
+    Ors,
+    /// Any Nominatim-compatible server
+    Nominatim,
+}
+
+impl GeocodingProvider {
+    /// Stored with cached results, so switching provider does not serve stale answers
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GeocodingProvider::Ors => "ors",
+            GeocodingProvider::Nominatim => "nominatim",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub port: u16,
@@ -8,6 +38,24 @@ pub struct Config {
     pub anthropic_api_key: String,
     pub openrouteservice_token: String,
     pub is_production: bool,
+    /// Days a deleted route or address stays in the trash before being purged
+    pub trash_retention_days: i32,
+    pub routing_engine: RoutingEngine,
+    /// Road graph built with `build-road-graph`, required by the local engine
+    pub road_graph_path: Option<String>,
+    pub geocoding_provider: GeocodingProvider,
+    /// Base URL of the geocoding service, the provider's public instance by default
+    pub geocoding_url: String,
+    /// Base URL of the app, for the links sent by email
+    pub public_url: String,
+    /// Key signing the unsubscribe links of digest emails
+    pub unsubscribe_secret: String,
+    /// Map tiles kept in memory once rendered
+    pub tile_cache_size: usize,
+    /// Directory of extra `<name>.json` map palettes, replacing the built-in ones of the same name
+    pub palette_dir: Option<String>,
+    /// Precision of the u and v values in the windgl texture
+    pub wind_encoding: WindEncoding,
 }
 
 impl Config {
@@ -29,13 +77,93 @@ impl Config {
         let anthropic_api_key = env::var("ANTHROPIC_API_KEY")
             .map_err(|_| "ANTHROPIC_API_KEY not found in environment")?;
 
-        let openrouteservice_token = env::var("OPENROUTESERVICE_TOKEN")
-            .map_err(|_| "OPENROUTESERVICE_TOKEN not found in environment")?;
+        let routing_engine = match env::var("ROUTING_ENGINE")
+            .unwrap_or_else(|_| "ors".to_string())
+            .as_str()
+        {
+            "ors" => RoutingEngine::Ors,
+            "local" => RoutingEngine::Local,
+            _ => return Err("Invalid ROUTING_ENGINE value, expected ors or local".to_string()),
+        };
+
+        let road_graph_path = env::var("ROAD_GRAPH_PATH").ok().filter(|p| !p.is_empty());
+        if routing_engine == RoutingEngine::Local && road_graph_path.is_none() {
+            return Err("ROAD_GRAPH_PATH is required when ROUTING_ENGINE is local".to_string());
+        }
+
+        // The local engine runs without OpenRouteService
+        let openrouteservice_token = match env::var("OPENROUTESERVICE_TOKEN") {
+            Ok(token) => token,
+            Err(_) if routing_engine == RoutingEngine::Local => String::new(),
+            Err(_) => return Err("OPENROUTESERVICE_TOKEN not found in environment".to_string()),
+        };
+
+        // Without an ORS token, geocode through Nominatim
+        let geocoding_provider = match env::var("GEOCODING_PROVIDER").ok().as_deref() {
+            Some("ors") if openrouteservice_token.is_empty() => {
+                return Err(
+                    "OPENROUTESERVICE_TOKEN is required when GEOCODING_PROVIDER is ors".to_string(),
+                )
+            }
+            Some("ors") => GeocodingProvider::Ors,
+            Some("nominatim") => GeocodingProvider::Nominatim,
+            Some(_) => {
+                return Err(
+                    "Invalid GEOCODING_PROVIDER value, expected ors or nominatim".to_string(),
+                )
+            }
+            None if openrouteservice_token.is_empty() => GeocodingProvider::Nominatim,
+            None => GeocodingProvider::Ors,
+        };
+
+        let geocoding_url = env::var("GEOCODING_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .unwrap_or_else(|| match geocoding_provider {
+                GeocodingProvider::Ors => "https://api.openrouteservice.org".to_string(),
+                GeocodingProvider::Nominatim => "https://nominatim.openstreetmap.org".to_string(),
+            })
+            .trim_end_matches('/')
+            .to_string();
 
         let is_production = env::var("NODE_ENV")
             .unwrap_or_else(|_| "development".to_string())
             == "production";
 
+        let public_url = env::var("PUBLIC_URL")
+            .unwrap_or_else(|_| format!("http://localhost:{}", port))
+            .trim_end_matches('/')
+            .to_string();
+
+        // A fixed development key keeps links valid across restarts
+        let unsubscribe_secret = match env::var("UNSUBSCRIBE_SECRET") {
+            Ok(secret) if !secret.is_empty() => secret,
+            _ if is_production => {
+                return Err("UNSUBSCRIBE_SECRET is required in production".to_string())
+            }
+            _ => "development-unsubscribe-secret".to_string(),
+        };
+
+        // Zero or less would purge routes as soon as they are trashed
+        let trash_retention_days = env::var("TRASH_RETENTION_DAYS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .ok()
+            .filter(|days: &i32| *days >= 1)
+            .ok_or("Invalid TRASH_RETENTION_DAYS value, expected at least 1 day")?;
+
+        let tile_cache_size = env::var("TILE_CACHE_SIZE")
+            .unwrap_or_else(|_| "1024".to_string())
+            .parse()
+            .map_err(|_| "Invalid TILE_CACHE_SIZE value")?;
+
+        let palette_dir = env::var("PALETTE_DIR").ok().filter(|dir| !dir.is_empty());
+
+        let wind_encoding = WindEncoding::parse(
+            &env::var("WIND_PNG_ENCODING").unwrap_or_else(|_| "uint8".to_string()),
+        )
+        .ok_or("Invalid WIND_PNG_ENCODING value, expected uint8 or uint16")?;
+
         Ok(Config {
             port,
             redis_url,
@@ -43,6 +171,16 @@ impl Config {
             anthropic_api_key,
             openrouteservice_token,
             is_production,
+            trash_retention_days,
+            routing_engine,
+            road_graph_path,
+            geocoding_provider,
+            geocoding_url,
+            public_url,
+            unsubscribe_secret,
+            tile_cache_size,
+            palette_dir,
+            wind_encoding,
         })
     }
 }
@@ -0,0 +1,85 @@
+/// Standard gravity, m/s²
+const GRAVITY: f64 = 9.80665;
+/// Speeds searched when solving for the rider speed, m/s
+const MIN_SPEED: f64 = 1.0;
+const MAX_SPEED: f64 = 25.0;
+
+/// Steady-state cycling power model: rolling resistance, gravity and air drag.
+/// `headwind` is the wind component against the rider in m/s (negative for a tailwind),
+/// `grade` is rise over run.
+#[derive(Debug, Clone, Copy)]
+pub struct RiderModel {
+    /// Sustained power at the pedals, W
+    pub power: f64,
+    /// Rider and bike, kg
+    pub mass: f64,
+    /// Drag area, m²
+    pub cda: f64,
+    pub crr: f64,
+    /// kg/m³
+    pub air_density: f64,
+}
+
+impl Default for RiderModel {
+    /// Recreational rider on a road bike, hoods position
+    fn default() -> Self {
+        RiderModel {
+            power: 150.0,
+            mass: 85.0,
+            cda: 0.4,
+            crr: 0.005,
+            air_density: 1.225,
+        }
+    }
+}
+
+impl RiderModel {
+    /// Power needed to hold `speed` (m/s)
+    pub fn power_at(&self, speed: f64, headwind: f64, grade: f64) -> f64 {
+        let slope = grade.atan();
+        let rolling = self.crr * self.mass * GRAVITY * slope.cos();
+        let climbing = self.mass * GRAVITY * slope.sin();
+        let air = speed + headwind;
+        let drag = 0.5 * self.air_density * self.cda * air * air.abs();
+        (rolling + climbing + drag) * speed
+    }
+
+    /// Speed (m/s) reached with the model power, by bisection
+    pub fn speed(&self, headwind: f64, grade: f64) -> f64 {
+        let (mut low, mut high) = (MIN_SPEED, MAX_SPEED);
+        if self.power_at(low, headwind, grade) >= self.power {
+            return low;
+        }
+        if self.power_at(high, headwind, grade) <= self.power {
+            return high;
+        }
+        for _ in 0..60 {
+            let mid = (low + high) / 2.0;
+            if self.power_at(mid, headwind, grade) < self.power {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        (low + high) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_speed_solves_power_balance() {
+        let rider = RiderModel::default();
+        let still = rider.speed(0.0, 0.0);
+        assert!((rider.power_at(still, 0.0, 0.0) - rider.power).abs() < 1e-6);
+        // About 27 km/h on the flat for 150 W
+        assert!((7.0..8.0).contains(&still));
+
+        assert!(rider.speed(5.0, 0.0) < still);
+        assert!(rider.speed(-5.0, 0.0) > still);
+        assert!(rider.speed(0.0, 0.06) < rider.speed(0.0, 0.0));
+        assert_eq!(rider.speed(15.0, 0.2), MIN_SPEED);
+    }
+}
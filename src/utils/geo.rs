@@ -0,0 +1,104 @@
+/// Mean Earth radius in meters
+pub const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// Great-circle distance in meters between two lat/lon points
+pub fn haversine_distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lon2 - lon1).to_radians();
+
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+/// Total length in meters of a polyline of `[lon, lat, ...]` positions
+pub fn polyline_length(positions: &[Vec<f64>]) -> f64 {
+    positions
+        .windows(2)
+        .map(|w| haversine_distance(w[0][1], w[0][0], w[1][1], w[1][0]))
+        .sum()
+}
+
+/// Initial bearing in degrees (0 = north, clockwise) from the first point to the second
+pub fn bearing(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lambda = (lon2 - lon1).to_radians();
+
+    let y = d_lambda.sin() * phi2.cos();
+    let x = phi1.cos() * phi2.sin() - phi1.sin() * phi2.cos() * d_lambda.cos();
+    (y.atan2(x).to_degrees() + 360.0) % 360.0
+}
+
+/// Point reached after `distance` meters on the great circle leaving (lat, lon) with `bearing`.
+/// The longitude is normalized to [-180, 180).
+pub fn destination_point(lat: f64, lon: f64, bearing: f64, distance: f64) -> (f64, f64) {
+    let delta = distance / EARTH_RADIUS_M;
+    let theta = bearing.to_radians();
+    let (phi1, lambda1) = (lat.to_radians(), lon.to_radians());
+
+    let phi2 = (phi1.sin() * delta.cos() + phi1.cos() * delta.sin() * theta.cos()).asin();
+    let lambda2 = lambda1
+        + (theta.sin() * delta.sin() * phi1.cos()).atan2(delta.cos() - phi1.sin() * phi2.sin());
+
+    (
+        phi2.to_degrees(),
+        (lambda2.to_degrees() + 540.0).rem_euclid(360.0) - 180.0,
+    )
+}
+
+/// Encoded polyline (precision 5) of `[lon, lat, ...]` positions, as in ORS `json` responses
+pub fn encode_polyline(positions: &[Vec<f64>]) -> String {
+    fn push_value(value: i64, out: &mut String) {
+        let mut value = if value < 0 { !(value << 1) } else { value << 1 };
+        while value >= 0x20 {
+            out.push(char::from((0x20 | (value & 0x1f)) as u8 + 63));
+            value >>= 5;
+        }
+        out.push(char::from(value as u8 + 63));
+    }
+
+    let mut out = String::new();
+    let (mut previous_lat, mut previous_lon) = (0i64, 0i64);
+    for position in positions {
+        let lat = (position[1] * 1e5).round() as i64;
+        let lon = (position[0] * 1e5).round() as i64;
+        push_value(lat - previous_lat, &mut out);
+        push_value(lon - previous_lon, &mut out);
+        (previous_lat, previous_lon) = (lat, lon);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_polyline() {
+        // Reference example of the encoded polyline algorithm format
+        let positions = vec![
+            vec![-120.2, 38.5],
+            vec![-120.95, 40.7],
+            vec![-126.453, 43.252],
+        ];
+        assert_eq!(encode_polyline(&positions), "_p~iF~ps|U_ulLnnqC_mqNvxq`@");
+    }
+
+    #[test]
+    fn test_bearing() {
+        assert!((bearing(48.0, 2.0, 49.0, 2.0) - 0.0).abs() < 1e-6);
+        assert!((bearing(0.0, 2.0, 0.0, 3.0) - 90.0).abs() < 1e-6);
+        assert!((bearing(48.0, 2.0, 47.0, 2.0) - 180.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_destination_point() {
+        let (lat, lon) = destination_point(48.0, 2.0, 90.0, 10_000.0);
+        assert!((haversine_distance(48.0, 2.0, lat, lon) - 10_000.0).abs() < 1e-3);
+        assert!((bearing(48.0, 2.0, lat, lon) - 90.0).abs() < 1e-6);
+
+        // Across the antimeridian
+        let (_, lon) = destination_point(0.0, 179.9, 90.0, 50_000.0);
+        assert!(lon < -179.0);
+    }
+}
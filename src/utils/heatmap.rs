@@ -0,0 +1,337 @@
+use anyhow::Result;
+use image::{ImageBuffer, Rgba, RgbaImage};
+
+use crate::models::Bbox;
+use crate::utils::palette::Palette;
+use crate::utils::weather_grid::{WeatherGrid, WIND_FIELDS};
+
+const MS_TO_KMH: f64 = 3.6;
+const TRANSPARENT: [u8; 4] = [0, 0, 0, 0];
+/// Height of the color scale added under the map
+pub const LEGEND_HEIGHT: u32 = 32;
+const LEGEND_MARGIN: u32 = 8;
+const LEGEND_BAR_HEIGHT: u32 = 10;
+const LEGEND_BACKGROUND: [u8; 4] = [255, 255, 255, 255];
+const LEGEND_TEXT: [u8; 4] = [40, 40, 40, 255];
+const CAPTION_PADDING: u32 = 4;
+
+/// Weather values drawn as colored maps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HeatmapLayer {
+    /// Mean wind, km/h
+    WindSpeed,
+    /// Gusts, km/h
+    Gusts,
+    /// Precipitation rate, mm/h
+    Precipitation,
+}
+
+impl HeatmapLayer {
+    pub const ALL: [HeatmapLayer; 3] = [
+        HeatmapLayer::WindSpeed,
+        HeatmapLayer::Gusts,
+        HeatmapLayer::Precipitation,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|layer| layer.as_str() == name)
+    }
+
+    /// Name in URLs, also the name of the layer's default palette
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HeatmapLayer::WindSpeed => "wind-speed",
+            HeatmapLayer::Gusts => "gusts",
+            HeatmapLayer::Precipitation => "precipitation",
+        }
+    }
+
+    /// Fields of the stored points the layer is drawn from
+    pub fn fields(&self) -> &'static [&'static str] {
+        match self {
+            HeatmapLayer::WindSpeed | HeatmapLayer::Gusts => &WIND_FIELDS,
+            HeatmapLayer::Precipitation => &["rate"],
+        }
+    }
+
+    /// Value drawn, from a sample of the layer's `fields`
+    pub fn value(&self, sample: &[f64]) -> f64 {
+        match self {
+            HeatmapLayer::WindSpeed => sample[0].hypot(sample[1]) * MS_TO_KMH,
+            HeatmapLayer::Gusts => sample[2] * MS_TO_KMH,
+            HeatmapLayer::Precipitation => sample[0],
+        }
+    }
+}
+
+/// Map of `layer` over `bbox` in an equirectangular projection, `width` x `height` pixels.
+/// Missing values are transparent. With `legend`, the color scale is added under the map.
+pub fn render_heatmap(
+    grid: &WeatherGrid,
+    layer: HeatmapLayer,
+    palette: &Palette,
+    bbox: &Bbox,
+    width: u32,
+    height: u32,
+    legend: bool,
+) -> RgbaImage {
+    let total_height = if legend {
+        height + LEGEND_HEIGHT
+    } else {
+        height
+    };
+    let mut img: RgbaImage = ImageBuffer::new(width, total_height);
+    let lon_step = bbox.width() / f64::from(width);
+    let lat_step = bbox.height() / f64::from(height);
+
+    for py in 0..height {
+        let lat = bbox.north - (f64::from(py) + 0.5) * lat_step;
+        for px in 0..width {
+            let lon = bbox.west + (f64::from(px) + 0.5) * lon_step;
+            let color = match grid.sample(lat, lon) {
+                Some(sample) => palette.color(layer.value(&sample)),
+                None => TRANSPARENT,
+            };
+            img.put_pixel(px, py, Rgba(color));
+        }
+    }
+
+    if legend {
+        draw_legend(&mut img, height, palette);
+    }
+    img
+}
+
+pub fn encode_png(img: &RgbaImage) -> Result<Vec<u8>> {
+    let mut png_buffer = Vec::new();
+    let encoder = image::codecs::png::PngEncoder::new(&mut png_buffer);
+    img.write_with_encoder(encoder)?;
+    Ok(png_buffer)
+}
+
+/// Color bar of `palette` from `top` to the bottom of `img`, its stops evenly
+/// spaced and labeled, the unit on the right
+fn draw_legend(img: &mut RgbaImage, top: u32, palette: &Palette) {
+    let width = img.width();
+    fill_rect(img, 0, top, width, LEGEND_HEIGHT, LEGEND_BACKGROUND);
+
+    let bar_left = LEGEND_MARGIN;
+    let bar_right = width.saturating_sub(2 * LEGEND_MARGIN + text_width(&palette.unit));
+    let segments = palette.stops.len() - 1;
+    if bar_right <= bar_left + segments as u32 {
+        return;
+    }
+    let bar_width = bar_right - bar_left;
+    let bar_top = top + 4;
+
+    for x in bar_left..bar_right {
+        let t = (f64::from(x - bar_left) + 0.5) / f64::from(bar_width) * segments as f64;
+        let i = (t as usize).min(segments - 1);
+        let (from, to) = (palette.stops[i].0, palette.stops[i + 1].0);
+        let color = over_background(palette.color(from + (to - from) * (t - i as f64)));
+        fill_rect(img, x, bar_top, 1, LEGEND_BAR_HEIGHT, color);
+    }
+
+    // Labels that would overlap the previous one are skipped
+    let label_top = bar_top + LEGEND_BAR_HEIGHT + 3;
+    let mut free_from = 0;
+    for (i, (value, _)) in palette.stops.iter().enumerate() {
+        let label = format_value(*value);
+        let label_width = text_width(&label);
+        let center = bar_left + bar_width * i as u32 / segments as u32;
+        let left = center
+            .saturating_sub(label_width / 2)
+            .min(width.saturating_sub(label_width));
+        if left < free_from {
+            continue;
+        }
+        draw_text(img, left, label_top, &label, LEGEND_TEXT);
+        free_from = left + label_width + GLYPH_ADVANCE;
+    }
+    draw_text(
+        img,
+        bar_right + LEGEND_MARGIN,
+        bar_top,
+        &palette.unit,
+        LEGEND_TEXT,
+    );
+}
+
+/// `text` on an opaque label in the top-left corner of `img`
+pub fn draw_caption(img: &mut RgbaImage, text: &str) {
+    let width = text_width(text) + 2 * CAPTION_PADDING;
+    let height = GLYPH_HEIGHT * GLYPH_SCALE + 2 * CAPTION_PADDING;
+    fill_rect(img, 0, 0, width, height, LEGEND_BACKGROUND);
+    draw_text(img, CAPTION_PADDING, CAPTION_PADDING, text, LEGEND_TEXT);
+}
+
+/// `10`, `0.1`
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{:.0}", value)
+    } else {
+        format!("{}", value)
+    }
+}
+
+/// `color` blended over the legend's background
+fn over_background(color: [u8; 4]) -> [u8; 4] {
+    let alpha = f64::from(color[3]) / 255.0;
+    let mut blended = LEGEND_BACKGROUND;
+    for (b, c) in blended.iter_mut().take(3).zip(color) {
+        *b = (f64::from(c) * alpha + f64::from(*b) * (1.0 - alpha)).round() as u8;
+    }
+    blended
+}
+
+fn fill_rect(img: &mut RgbaImage, left: u32, top: u32, width: u32, height: u32, color: [u8; 4]) {
+    for y in top..(top + height).min(img.height()) {
+        for x in left..(left + width).min(img.width()) {
+            img.put_pixel(x, y, Rgba(color));
+        }
+    }
+}
+
+/// Legend text uses a 3x5 pixel font drawn at twice its size
+const GLYPH_SCALE: u32 = 2;
+const GLYPH_ADVANCE: u32 = 4 * GLYPH_SCALE;
+const GLYPH_HEIGHT: u32 = 5;
+
+/// Rows of a glyph, the 3 low bits of each are its pixels from left to right
+fn glyph(c: char) -> Option<[u8; 5]> {
+    let rows = match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        'h' => [0b100, 0b100, 0b111, 0b101, 0b101],
+        'k' => [0b100, 0b101, 0b110, 0b101, 0b101],
+        'm' => [0b000, 0b111, 0b111, 0b101, 0b101],
+        's' => [0b000, 0b111, 0b110, 0b011, 0b111],
+        'C' => [0b111, 0b100, 0b100, 0b100, 0b111],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        _ => return None,
+    };
+    Some(rows)
+}
+
+/// Width of `text` in pixels, characters without a glyph are drawn as spaces
+pub fn text_width(text: &str) -> u32 {
+    (text.chars().count() as u32 * GLYPH_ADVANCE).saturating_sub(GLYPH_SCALE)
+}
+
+pub fn draw_text(img: &mut RgbaImage, left: u32, top: u32, text: &str, color: [u8; 4]) {
+    for (i, c) in text.chars().enumerate() {
+        let Some(rows) = glyph(c) else {
+            continue;
+        };
+        let glyph_left = left + i as u32 * GLYPH_ADVANCE;
+        for (row, bits) in rows.iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) != 0 {
+                    fill_rect(
+                        img,
+                        glyph_left + column * GLYPH_SCALE,
+                        top + row as u32 * GLYPH_SCALE,
+                        GLYPH_SCALE,
+                        GLYPH_SCALE,
+                        color,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::palette::Palettes;
+
+    fn wind_grid() -> WeatherGrid {
+        let lats = vec![-90.0, 0.0, 90.0];
+        let lons: Vec<f64> = (0..4).map(|i| -180.0 + 90.0 * i as f64).collect();
+        let cells = lats.len() * lons.len();
+        let mut gusts = vec![10.0; cells];
+        // No gusts in the south-west
+        gusts[0] = f64::NAN;
+        WeatherGrid {
+            lats,
+            lons,
+            channels: vec![vec![3.0; cells], vec![4.0; cells], gusts],
+        }
+    }
+
+    #[test]
+    fn test_render_heatmap() {
+        let palettes = Palettes::load(None).unwrap();
+        let palette = palettes.get("gusts").unwrap();
+        let grid = wind_grid();
+
+        let img = render_heatmap(
+            &grid,
+            HeatmapLayer::Gusts,
+            palette,
+            &Bbox::WORLD,
+            360,
+            180,
+            true,
+        );
+        assert_eq!(img.dimensions(), (360, 180 + LEGEND_HEIGHT));
+        assert_eq!(img.get_pixel(300, 20).0, palette.color(36.0));
+        assert_eq!(img.get_pixel(0, 179).0, TRANSPARENT);
+        // The legend is opaque, with the color bar inside its margins
+        assert_eq!(img.get_pixel(0, 190).0, LEGEND_BACKGROUND);
+        assert_ne!(img.get_pixel(LEGEND_MARGIN, 190).0, LEGEND_BACKGROUND);
+
+        // Across the antimeridian
+        let pacific = Bbox::parse("170,-10,-170,10").unwrap();
+        let img = render_heatmap(
+            &grid,
+            HeatmapLayer::WindSpeed,
+            palette,
+            &pacific,
+            40,
+            40,
+            false,
+        );
+        assert_eq!(img.get_pixel(20, 20).0, palette.color(18.0));
+
+        // Missing gusts leave the mean wind drawn
+        let wind_palette = palettes.get("wind-speed").unwrap();
+        let img = render_heatmap(
+            &grid,
+            HeatmapLayer::WindSpeed,
+            wind_palette,
+            &Bbox::WORLD,
+            360,
+            180,
+            false,
+        );
+        assert_eq!(img.get_pixel(0, 179).0, wind_palette.color(18.0));
+    }
+
+    #[test]
+    fn test_text_width() {
+        assert_eq!(text_width("km/h"), 30);
+        assert_eq!(text_width(""), 0);
+        assert_eq!(format_value(0.1), "0.1");
+        assert_eq!(format_value(20.0), "20");
+        // Animation captions
+        assert!("2026-10-18 15:00 UTC"
+            .chars()
+            .all(|c| c == ' ' || glyph(c).is_some()));
+    }
+}
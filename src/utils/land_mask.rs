@@ -0,0 +1,76 @@
+use crate::utils::weather_grid::WeatherGrid;
+
+/// Degrees between the samples checked along a leg, about a fifth of a 0.5° cell
+const SAMPLE_SPACING: f64 = 0.1;
+
+/// Land-sea mask on a regular grid (1 over land, 0 over water).
+/// Coasts fall between cells, so a point is land when the interpolated value exceeds 0.5.
+#[derive(Debug, Clone)]
+pub struct LandMask {
+    grid: WeatherGrid,
+}
+
+impl LandMask {
+    /// `grid` holds the mask in its first channel
+    pub fn new(grid: WeatherGrid) -> Self {
+        Self { grid }
+    }
+
+    pub fn is_land(&self, lat: f64, lon: f64) -> bool {
+        self.grid
+            .sample(lat, lon)
+            .and_then(|values| values.first().copied())
+            .is_some_and(|land| land > 0.5)
+    }
+
+    /// Land positions sampled along the straight (lat/lon) line between two points,
+    /// both ends included
+    pub fn land_along(
+        &self,
+        lat1: f64,
+        lon1: f64,
+        lat2: f64,
+        lon2: f64,
+    ) -> impl Iterator<Item = (f64, f64)> + '_ {
+        let d_lat = lat2 - lat1;
+        // Shortest way around, across the antimeridian if needed
+        let d_lon = (lon2 - lon1 + 540.0).rem_euclid(360.0) - 180.0;
+        let steps = (d_lat.abs().max(d_lon.abs()) / SAMPLE_SPACING)
+            .ceil()
+            .max(1.0) as usize;
+
+        (0..=steps)
+            .map(move |i| {
+                let t = i as f64 / steps as f64;
+                (lat1 + d_lat * t, lon1 + d_lon * t)
+            })
+            .filter(|(lat, lon)| self.is_land(*lat, *lon))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_land_along_a_leg() {
+        // Water west of lon 1, land from lon 1.5
+        let mut points = Vec::new();
+        for lat in [0.0, 0.5, 1.0] {
+            for lon in [0.0, 0.5, 1.0, 1.5, 2.0] {
+                let land = if lon >= 1.5 { 1.0 } else { 0.0 };
+                points.push(serde_json::json!({ "lat": lat, "lon": lon, "land": land }));
+            }
+        }
+        let grid =
+            WeatherGrid::from_points(&serde_json::json!({ "points": points }), &["land"]).unwrap();
+        let mask = LandMask::new(grid);
+
+        assert!(!mask.is_land(0.5, 0.5));
+        assert!(mask.is_land(0.5, 1.9));
+        assert_eq!(mask.land_along(0.0, 0.0, 1.0, 1.0).count(), 0);
+
+        let first = mask.land_along(0.5, 0.0, 0.5, 2.0).next().unwrap();
+        assert!(first.1 > 1.25 && first.1 < 1.4);
+    }
+}
@@ -1,79 +1,432 @@
-use crate::utils::misc::{Asset, Env};
+use crate::models::DigestPayload;
+use crate::utils::mail_templates::{long_date, render_mail, RenderedMail};
+use crate::utils::misc::{generate_random_string, Env, MailTls};
+use chrono::{DateTime, Utc};
+use lettre::address::AddressError;
+use lettre::message::header::{Header, HeaderName, HeaderValue};
+use lettre::message::{Mailbox, MultiPart};
 use lettre::transport::smtp::authentication::Credentials;
-use lettre::transport::smtp::response::Response;
-use lettre::transport::smtp::Error;
-use lettre::{message, transport::smtp::client::Tls, Message, SmtpTransport, Transport};
-
-async fn send_mail(
-    app_env: Env,
-    html: &str,
-    subject: &str,
-    destination: &str,
-) -> Result<<SmtpTransport as Transport>::Ok, <SmtpTransport as Transport>::Error> {
-    let email = Message::builder()
-        .from(app_env.mail_from.parse().unwrap())
-        .to(destination.parse().unwrap())
-        .subject(subject)
-        .header(message::header::ContentType::TEXT_HTML)
-        .body(html.to_string())
-        .unwrap();
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::transport::smtp::PoolConfig;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use minijinja::context;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::info;
+
+/// Longest wait for the SMTP server on each command
+const SMTP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Connections kept open to the SMTP server
+const SMTP_POOL_SIZE: u32 = 4;
+
+#[derive(Debug, Error)]
+pub enum MailError {
+    #[error("invalid email address {address}: {source}")]
+    InvalidAddress {
+        address: String,
+        source: AddressError,
+    },
+    #[error("failed to render the email: {0}")]
+    Template(#[from] minijinja::Error),
+    #[error("invalid email payload: {0}")]
+    Payload(#[from] serde_json::Error),
+    #[error("failed to build the email: {0}")]
+    Message(#[from] lettre::error::Error),
+    #[error("SMTP error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+    #[error("SMTP server is not answering")]
+    Unreachable,
+    #[error("failed to write the email to the sink: {0}")]
+    Sink(#[from] std::io::Error),
+}
+
+impl MailError {
+    /// Sending the same email again cannot succeed: bad address, template or payload,
+    /// or a permanent (5xx) refusal of the SMTP server
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            MailError::InvalidAddress { .. }
+            | MailError::Template(_)
+            | MailError::Payload(_)
+            | MailError::Message(_) => true,
+            MailError::Smtp(e) => e.is_permanent(),
+            MailError::Unreachable | MailError::Sink(_) => false,
+        }
+    }
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox, MailError> {
+    address.parse().map_err(|source| MailError::InvalidAddress {
+        address: address.to_string(),
+        source,
+    })
+}
 
-    //let email = Message::builder()
-    //    .from(smtp_params.mail_from.parse().unwrap())
-    //    .to(destination.parse().unwrap())
-    //    .subject(subject)
-    //    .multipart(
-    //        MultiPart::mixed()
-    //            .singlepart(
-    //                SinglePart::builder()
-    //                    .header(ContentType::TEXT_HTML)
-    //                    .body(html),
-    //            )
-    //            .singlepart(mail_attachment),
-    //    )
-    //    .unwrap();
-
-    // Open a remote connection to gmail
+#[derive(Clone)]
+enum MailTransport {
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+    /// Directory of the `.eml` files, see `Env::mail_sink_dir`
+    Sink(PathBuf),
+}
+
+/// Sends the emails, created once at startup and shared.
+/// SMTP connections are pooled and reused between emails.
+#[derive(Clone)]
+pub struct Mailer {
+    from: Mailbox,
+    transport: MailTransport,
+}
+
+impl Mailer {
+    pub fn new(app_env: &Env) -> Result<Self, MailError> {
+        let transport = match &app_env.mail_sink_dir {
+            Some(dir) => MailTransport::Sink(PathBuf::from(dir)),
+            None => MailTransport::Smtp(smtp_transport(app_env)?),
+        };
+        Ok(Self {
+            from: parse_mailbox(&app_env.mail_from)?,
+            transport,
+        })
+    }
+
+    /// Send `mail` as HTML with its text alternative, or write it to the mail sink when one is set.
+    /// `unsubscribe_url` adds the headers for one-click unsubscribing (RFC 8058).
+    pub async fn send(
+        &self,
+        mail: RenderedMail,
+        destination: &str,
+        unsubscribe_url: Option<&str>,
+    ) -> Result<(), MailError> {
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .to(parse_mailbox(destination)?)
+            .subject(mail.subject);
+        if let Some(url) = unsubscribe_url {
+            builder = builder
+                .header(ListUnsubscribe(format!("<{}>", url)))
+                .header(ListUnsubscribePost);
+        }
+        let message = builder.multipart(MultiPart::alternative_plain_html(mail.text, mail.html))?;
+
+        match &self.transport {
+            MailTransport::Smtp(transport) => {
+                transport.send(message).await?;
+            }
+            MailTransport::Sink(dir) => {
+                write_to_sink(dir, &message).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Readiness of the transport: the SMTP server answers a NOOP, or the sink directory exists
+    pub async fn check(&self) -> Result<(), MailError> {
+        match &self.transport {
+            MailTransport::Smtp(transport) => match transport.test_connection().await? {
+                true => Ok(()),
+                false => Err(MailError::Unreachable),
+            },
+            MailTransport::Sink(dir) => Ok(tokio::fs::create_dir_all(dir).await?),
+        }
+    }
+}
+
+/// Pooled SMTP transport, `SMTP_TLS` picks how the connection is secured
+fn smtp_transport(
+    app_env: &Env,
+) -> Result<AsyncSmtpTransport<Tokio1Executor>, lettre::transport::smtp::Error> {
+    let host = app_env.mail_host.as_str();
+    let builder = match app_env.mail_tls {
+        MailTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        MailTls::Opportunistic => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+            .tls(Tls::Opportunistic(TlsParameters::new(host.to_string())?)),
+        MailTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+        MailTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+    };
     let creds = Credentials::new(app_env.mail_from.to_string(), app_env.smtp_pass.to_string());
-    let mailer = SmtpTransport::starttls_relay(&app_env.mail_host)
-        .unwrap()
-        .credentials(creds)
+
+    Ok(builder
         .port(app_env.mail_port)
-        .tls(Tls::None)
-        .build();
+        .credentials(creds)
+        .timeout(Some(SMTP_TIMEOUT))
+        .pool_config(PoolConfig::new().max_size(SMTP_POOL_SIZE))
+        .build())
+}
 
-    mailer.send(&email)
+/// Keep the message as `<dir>/<time>-<random>.eml` instead of sending it
+async fn write_to_sink(dir: &Path, message: &Message) -> std::io::Result<PathBuf> {
+    tokio::fs::create_dir_all(dir).await?;
+    let path = dir.join(format!(
+        "{}-{}.eml",
+        Utc::now().format("%Y%m%dT%H%M%S%.3f"),
+        generate_random_string(6)
+    ));
+    tokio::fs::write(&path, message.formatted()).await?;
+    info!("Mail written to {}", path.display());
+    Ok(path)
 }
 
 pub async fn send_one_time_code_mail(
     otc: &i32,
     email: &str,
-    app_env: Env,
-) -> Result<Response, Error> {
-    let template_data = Asset::get("emails/one_time_code.html")
-        .expect("emails/one_time_code.html not found")
-        .to_owned();
-
-    let tpl = std::str::from_utf8(template_data.data.as_ref())
-        .expect("invalid template utf8 string")
-        .to_string();
-
-    let body = tpl.replace("{{one_time_code}}", otc.to_string().as_str());
-
-    let html = scaffold_html(body);
-
-    send_mail(app_env, html.as_str(), "Code de connexion", email).await
-}
-
-fn scaffold_html(body: String) -> String {
-    format!(
-        "<!DOCTYPE html>
-        <html>
-        <head>
-            <meta charset=\"UTF-8\">
-        </head>
-            {}
-        </html>",
-        body
-    )
+    language: &str,
+    valid_minutes: i64,
+    mailer: &Mailer,
+) -> Result<(), MailError> {
+    let mail = render_mail(
+        "one_time_code",
+        language,
+        context! { one_time_code => otc, valid_minutes },
+    )?;
+
+    mailer.send(mail, email, None).await
+}
+
+/// Mail for an alert event queued in the notification outbox (`rule`, `event`, `target`).
+/// Errors are returned, the outbox retries the ones that are not permanent.
+pub async fn send_alert_mail(
+    payload: &Value,
+    email: &str,
+    language: &str,
+    mailer: &Mailer,
+) -> Result<(), MailError> {
+    let rule = &payload["rule"];
+    let event = &payload["event"];
+    let unit = match rule["metric"].as_str() {
+        Some("rain_rate") => "mm/h",
+        _ => "km/h",
+    };
+    let time = event["data_time"]
+        .as_str()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| {
+            t.with_timezone(&Utc)
+                .format("%d/%m/%Y %H:%M UTC")
+                .to_string()
+        })
+        .unwrap_or_default();
+
+    let mail = render_mail(
+        "alert",
+        language,
+        context! {
+            metric => rule["metric"].as_str(),
+            comparison => rule["comparison"].as_str(),
+            threshold => rule["threshold"].as_f64().unwrap_or_default().to_string(),
+            value => format!("{:.1}", event["value"].as_f64().unwrap_or_default()),
+            unit,
+            target => payload["target"].as_str().or_else(|| rule["name"].as_str()),
+            time,
+        },
+    )?;
+
+    mailer.send(mail, email, None).await
+}
+
+/// Daily forecast digest, the unsubscribe link is also given in the headers
+pub async fn send_digest_mail(
+    payload: &Value,
+    email: &str,
+    language: &str,
+    mailer: &Mailer,
+) -> Result<(), MailError> {
+    let digest: DigestPayload = serde_json::from_value(payload.clone())?;
+    let mail = render_digest(&digest, language)?;
+
+    mailer
+        .send(mail, email, Some(&digest.unsubscribe_url))
+        .await
+}
+
+/// Context of the digest templates, values formatted for display
+#[derive(Serialize)]
+struct DigestContext<'a> {
+    date: String,
+    summary: Option<&'a str>,
+    places: Vec<DigestPlaceContext<'a>>,
+    unsubscribe_url: &'a str,
+}
+
+#[derive(Serialize)]
+struct DigestPlaceContext<'a> {
+    name: &'a str,
+    address_text: Option<&'a str>,
+    days: Vec<DigestDayContext>,
+}
+
+#[derive(Serialize)]
+struct DigestDayContext {
+    label: String,
+    wind: String,
+    gusts: String,
+    rain: String,
+}
+
+fn render_digest(digest: &DigestPayload, language: &str) -> Result<RenderedMail, minijinja::Error> {
+    let format_value = |value: Option<f64>, unit: &str, decimals: usize| match value {
+        Some(value) => format!("{:.*} {}", decimals, value, unit),
+        None => "-".to_string(),
+    };
+    let context = DigestContext {
+        date: long_date(digest.date, language),
+        summary: digest.summary.as_deref(),
+        places: digest
+            .places
+            .iter()
+            .map(|place| DigestPlaceContext {
+                name: &place.name,
+                address_text: place.address_text.as_deref(),
+                days: place
+                    .days
+                    .iter()
+                    .map(|day| DigestDayContext {
+                        label: long_date(day.date, language),
+                        wind: format_value(day.wind_max, "km/h", 0),
+                        gusts: format_value(day.gusts_max, "km/h", 0),
+                        rain: format_value(day.rain_total, "mm", 1),
+                    })
+                    .collect(),
+            })
+            .collect(),
+        unsubscribe_url: &digest.unsubscribe_url,
+    };
+
+    render_mail("digest", language, context)
+}
+
+/// `List-Unsubscribe` header, the unsubscribe URL between angle brackets
+#[derive(Debug, Clone)]
+struct ListUnsubscribe(String);
+
+impl Header for ListUnsubscribe {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe")
+    }
+
+    fn parse(s: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self(s.to_string()))
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), self.0.clone())
+    }
+}
+
+/// `List-Unsubscribe-Post`, tells mail clients the link accepts a one-click POST
+#[derive(Debug, Clone)]
+struct ListUnsubscribePost;
+
+impl Header for ListUnsubscribePost {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe-Post")
+    }
+
+    fn parse(_s: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self)
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), "List-Unsubscribe=One-Click".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DigestDay, DigestPlace};
+    use chrono::NaiveDate;
+
+    fn sink_env(dir: &Path) -> Env {
+        Env {
+            is_prod: false,
+            database_url: String::new(),
+            http_host: "127.0.0.1".to_string(),
+            http_port: 8080,
+            mail_from: "noreply@example.com".to_string(),
+            mail_host: "localhost".to_string(),
+            mail_port: 25,
+            mail_tls: MailTls::None,
+            smtp_pass: String::new(),
+            otc_exp_minutes: 15,
+            http_domain: "127.0.0.1".to_string(),
+            mail_sink_dir: Some(dir.to_string_lossy().into_owned()),
+        }
+    }
+
+    /// Sent mails of the sink, read back as text
+    fn sunk_mails(dir: &Path) -> Vec<String> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_mail_sink() {
+        let dir = std::env::temp_dir().join(format!("mail-sink-{}", generate_random_string(8)));
+        let mailer = Mailer::new(&sink_env(&dir)).unwrap();
+
+        send_one_time_code_mail(&123456, "user@example.com", "en", 15, &mailer)
+            .await
+            .unwrap();
+        let date = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let digest = DigestPayload {
+            date,
+            places: vec![DigestPlace {
+                name: "Maison <1>".to_string(),
+                address_text: None,
+                days: vec![DigestDay {
+                    date,
+                    wind_max: Some(21.4),
+                    gusts_max: Some(38.6),
+                    rain_total: Some(2.3),
+                }],
+            }],
+            summary: None,
+            unsubscribe_url: "https://example.com/api/digest/unsubscribe?token=1.abc".to_string(),
+        };
+        send_digest_mail(
+            &serde_json::to_value(&digest).unwrap(),
+            "user@example.com",
+            "fr",
+            &mailer,
+        )
+        .await
+        .unwrap();
+
+        let mails = sunk_mails(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(mails.len(), 2);
+        let code = mails
+            .iter()
+            .find(|m| m.contains("Your login code"))
+            .unwrap();
+        assert!(code.contains("To: user@example.com"));
+        assert!(code.contains("multipart/alternative"));
+        assert!(code.contains("text/plain"));
+        assert!(code.contains("123456"));
+
+        let digest = mails
+            .iter()
+            .find(|m| m.contains("List-Unsubscribe"))
+            .unwrap();
+        assert!(digest.contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click"));
+        assert!(digest.contains("lundi 19 octobre : vent 21 km/h, rafales 39 km/h, pluie 2.3 mm"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_address() {
+        let dir = std::env::temp_dir().join(format!("mail-sink-{}", generate_random_string(8)));
+        let mailer = Mailer::new(&sink_env(&dir)).unwrap();
+
+        let e = send_one_time_code_mail(&123456, "not an address", "en", 15, &mailer)
+            .await
+            .unwrap_err();
+        assert!(matches!(e, MailError::InvalidAddress { .. }));
+        assert!(e.is_permanent());
+        assert!(!dir.exists());
+    }
 }
@@ -0,0 +1,310 @@
+use chrono::{Datelike, NaiveDate};
+use minijinja::{context, Environment, ErrorKind, Value};
+use quick_xml::escape::unescape;
+use serde::Serialize;
+use std::sync::OnceLock;
+
+use crate::utils::misc::Asset;
+
+/// Languages emails are written in, the first one is the fallback
+pub const MAIL_LANGUAGES: [&str; 2] = ["fr", "en"];
+pub const DEFAULT_MAIL_LANGUAGE: &str = MAIL_LANGUAGES[0];
+
+/// Email ready to be sent, the text is the alternative for clients without HTML
+#[derive(Debug, Clone)]
+pub struct RenderedMail {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+/// Supported language of a language tag (`en-GB` gives `en`), the default one otherwise
+pub fn mail_language(tag: &str) -> &'static str {
+    let primary = tag.split(['-', '_']).next().unwrap_or_default().trim();
+    MAIL_LANGUAGES
+        .iter()
+        .find(|language| language.eq_ignore_ascii_case(primary))
+        .copied()
+        .unwrap_or(DEFAULT_MAIL_LANGUAGE)
+}
+
+/// Preferred supported language of an `Accept-Language` header
+pub fn language_from_accept(header: &str) -> Option<&'static str> {
+    let mut ranges: Vec<(&str, f32)> = header
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse().ok())?;
+            Some((tag, quality))
+        })
+        .collect();
+    // Stable, so equal weights keep the header's order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    ranges.into_iter().find_map(|(tag, _)| {
+        let primary = tag.split('-').next()?;
+        MAIL_LANGUAGES
+            .iter()
+            .find(|language| language.eq_ignore_ascii_case(primary))
+            .copied()
+    })
+}
+
+/// `lundi 19 octobre` or `Monday 19 October`
+pub fn long_date(date: NaiveDate, language: &str) -> String {
+    const WEEKDAYS_FR: [&str; 7] = [
+        "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+    ];
+    const MONTHS_FR: [&str; 12] = [
+        "janvier",
+        "février",
+        "mars",
+        "avril",
+        "mai",
+        "juin",
+        "juillet",
+        "août",
+        "septembre",
+        "octobre",
+        "novembre",
+        "décembre",
+    ];
+
+    match mail_language(language) {
+        "en" => date.format("%A %-d %B").to_string(),
+        _ => format!(
+            "{} {} {}",
+            WEEKDAYS_FR[date.weekday().num_days_from_monday() as usize],
+            date.day(),
+            MONTHS_FR[date.month0() as usize]
+        ),
+    }
+}
+
+/// Render the `<language>/<name>.html` email, falling back to the default language.
+/// The subject is the template's `subject` block. The text alternative is
+/// `<language>/<name>.txt` when there is one, the HTML turned into text otherwise.
+pub fn render_mail(
+    name: &str,
+    language: &str,
+    context: impl Serialize,
+) -> Result<RenderedMail, minijinja::Error> {
+    let env = templates();
+    let language = mail_language(language);
+    let context = context! { lang => language, ..Value::from_serialize(context) };
+
+    let template =
+        localized_template(env, language, &format!("{}.html", name))?.ok_or_else(|| {
+            minijinja::Error::new(
+                ErrorKind::TemplateNotFound,
+                format!("no {} email template", name),
+            )
+        })?;
+    let mut captured = template.render_captured(&context)?;
+    let subject = captured.with_state_mut(|state| state.render_block("subject"))?;
+    let html = captured.into_output();
+
+    let text = match localized_template(env, language, &format!("{}.txt", name))? {
+        Some(template) => template.render(&context)?,
+        None => html_to_text(&html),
+    };
+
+    Ok(RenderedMail {
+        subject: subject.split_whitespace().collect::<Vec<_>>().join(" "),
+        html,
+        text,
+    })
+}
+
+/// Render the `<language>/<name>.html` page opened from a link of an email,
+/// falling back to the default language
+pub fn render_page(
+    name: &str,
+    language: &str,
+    context: impl Serialize,
+) -> Result<String, minijinja::Error> {
+    let language = mail_language(language);
+    let context = context! { lang => language, ..Value::from_serialize(context) };
+
+    let template = localized_template(templates(), language, &format!("{}.html", name))?
+        .ok_or_else(|| {
+            minijinja::Error::new(ErrorKind::TemplateNotFound, format!("no {} page", name))
+        })?;
+    template.render(context)
+}
+
+fn localized_template<'env>(
+    env: &'env Environment<'static>,
+    language: &str,
+    file: &str,
+) -> Result<Option<minijinja::Template<'env, 'env>>, minijinja::Error> {
+    for language in [language, DEFAULT_MAIL_LANGUAGE] {
+        match env.get_template(&format!("{}/{}", language, file)) {
+            Ok(template) => return Ok(Some(template)),
+            Err(e) if e.kind() == ErrorKind::TemplateNotFound => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+/// Templates of `embedded/emails`: localized emails and pages in `<language>/`, extending
+/// `layouts/` and including `partials/`. Values are escaped in `.html` templates.
+fn templates() -> &'static Environment<'static> {
+    static TEMPLATES: OnceLock<Environment<'static>> = OnceLock::new();
+    TEMPLATES.get_or_init(|| {
+        let mut env = Environment::new();
+        env.set_trim_blocks(true);
+        env.set_lstrip_blocks(true);
+        env.set_loader(|name| {
+            let Some(file) = Asset::get(&format!("emails/{}", name)) else {
+                return Ok(None);
+            };
+            String::from_utf8(file.data.into_owned())
+                .map(Some)
+                .map_err(|e| {
+                    minijinja::Error::new(ErrorKind::InvalidOperation, "template is not utf-8")
+                        .with_source(e)
+                })
+        });
+        env
+    })
+}
+
+/// Readable text of an HTML email: the body's text, one line per block,
+/// links followed by their URL. Like in HTML, line breaks of the source are spaces.
+pub fn html_to_text(html: &str) -> String {
+    const BLOCKS: [&str; 14] = [
+        "br", "p", "div", "tr", "table", "li", "ul", "ol", "h1", "h2", "h3", "h4", "h5", "h6",
+    ];
+
+    let body = match (html.find("<body"), html.rfind("</body>")) {
+        (Some(start), Some(end)) if start < end => &html[start..end],
+        _ => html,
+    };
+
+    let mut text = String::new();
+    let mut href: Option<String> = None;
+    let mut rest = body;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start].replace('\n', " "));
+        let Some(length) = rest[start..].find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[start + 1..start + length];
+        rest = &rest[start + length + 1..];
+
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match name.as_str() {
+            "style" | "script" | "head" if !closing => {
+                let end = format!("</{}>", name);
+                rest = rest.find(&end).map_or("", |i| &rest[i + end.len()..]);
+            }
+            "a" if !closing => {
+                href = tag
+                    .split_once("href=\"")
+                    .and_then(|(_, value)| value.split_once('"'))
+                    .map(|(value, _)| value.to_string());
+            }
+            "a" => {
+                if let Some(href) = href.take() {
+                    text.push_str(&format!(" ({})", href));
+                }
+            }
+            "td" | "th" if closing => text.push(' '),
+            name if BLOCKS.contains(&name) => text.push('\n'),
+            _ => {}
+        }
+    }
+    text.push_str(&rest.replace('\n', " "));
+    let text = unescape(&text).map_or(text.clone(), |t| t.into_owned());
+
+    // One space between words, no empty lines
+    let lines: Vec<String> = text
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect();
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mail_language() {
+        assert_eq!(mail_language("en-GB"), "en");
+        assert_eq!(mail_language("de"), "fr");
+        assert_eq!(language_from_accept("de-DE,en;q=0.8,fr;q=0.9"), Some("fr"));
+        assert_eq!(language_from_accept("en-US,en;q=0.9"), Some("en"));
+        assert_eq!(language_from_accept("de"), None);
+    }
+
+    #[test]
+    fn test_html_to_text() {
+        let html = "<html><head><title>T</title></head><body>
+            <div>Bonjour &lt;toi&gt;</div>
+            <table><tr><td>a</td><td>b</td></tr></table>
+
+            <p><a href=\"https://example.com/?a=1&amp;b=2\">Lien</a></p>
+        </body></html>";
+        assert_eq!(
+            html_to_text(html),
+            "Bonjour <toi>\na b\nLien (https://example.com/?a=1&b=2)\n"
+        );
+    }
+
+    #[test]
+    fn test_render_mail() {
+        let fr = render_mail(
+            "one_time_code",
+            "fr",
+            context! { one_time_code => 123456, valid_minutes => 15 },
+        )
+        .unwrap();
+        assert_eq!(fr.subject, "Code de connexion");
+        assert!(fr.html.contains("123456"));
+        assert!(fr.text.contains("123456"));
+        assert!(!fr.text.contains('<'));
+
+        let en = render_mail(
+            "one_time_code",
+            "en-US",
+            context! { one_time_code => 123456, valid_minutes => 15 },
+        )
+        .unwrap();
+        assert!(en.text.contains("valid for 15 minutes"));
+    }
+
+    #[test]
+    fn test_render_page() {
+        let fr = render_page("unsubscribe", "fr", context! { status => "done" }).unwrap();
+        assert!(fr.contains("<html lang=\"fr\">"));
+        assert!(fr.contains("Vous ne recevrez plus le résumé quotidien"));
+
+        let en = render_page("unsubscribe", "en", context! { status => "invalid" }).unwrap();
+        assert!(en.contains("This unsubscribe link is not valid"));
+
+        let confirm = render_page(
+            "unsubscribe",
+            "en",
+            context! { status => "confirm", token => "digest-unsubscribe.1.2.a-b_c" },
+        )
+        .unwrap();
+        assert!(confirm
+            .contains("<form method=\"post\" action=\"?token=digest-unsubscribe.1.2.a-b_c\">"));
+
+        assert!(render_page("missing", "en", context! {}).is_err());
+    }
+}
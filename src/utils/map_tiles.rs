@@ -0,0 +1,214 @@
+use anyhow::Result;
+use image::{ImageBuffer, Rgba, RgbaImage};
+use std::f64::consts::PI;
+
+use crate::utils::palette::Palette;
+use crate::utils::weather_grid::{WeatherGrid, WIND_FIELDS};
+
+/// Side of a map tile, in pixels
+pub const TILE_SIZE: u32 = 256;
+/// Deepest zoom served, a GFS cell (0.5°) is already ~1500 pixels wide at zoom 12
+pub const MAX_TILE_ZOOM: u8 = 12;
+const MS_TO_KMH: f64 = 3.6;
+const TRANSPARENT: [u8; 4] = [0, 0, 0, 0];
+
+/// Raster layers served as map tiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TileLayer {
+    /// u and v in the red and green channels, scaled like the windgl texture
+    Wind,
+    /// Wind speed through a color map
+    WindSpeed,
+    /// Precipitation rate through a color map, transparent when dry
+    Precipitation,
+}
+
+impl TileLayer {
+    pub const ALL: [TileLayer; 3] = [
+        TileLayer::Wind,
+        TileLayer::WindSpeed,
+        TileLayer::Precipitation,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|layer| layer.as_str() == name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TileLayer::Wind => "wind",
+            TileLayer::WindSpeed => "wind-speed",
+            TileLayer::Precipitation => "precipitation",
+        }
+    }
+
+    /// Fields of the stored points the layer is drawn from
+    pub fn fields(&self) -> &'static [&'static str] {
+        match self {
+            TileLayer::Wind | TileLayer::WindSpeed => &WIND_FIELDS,
+            TileLayer::Precipitation => &["rate"],
+        }
+    }
+
+    /// Palette of the colored layers
+    pub fn palette(&self) -> Option<&'static str> {
+        match self {
+            TileLayer::Wind => None,
+            TileLayer::WindSpeed => Some("wind-speed"),
+            TileLayer::Precipitation => Some("precipitation"),
+        }
+    }
+}
+
+/// Smallest and largest finite value of each channel, `(0, 0)` for an empty one.
+/// Over a wind grid, these are the `uMin`..`vMax` of the windgl metadata.
+pub fn channel_ranges(grid: &WeatherGrid) -> Vec<(f64, f64)> {
+    grid.channels
+        .iter()
+        .map(|channel| {
+            let (min, max) = channel
+                .iter()
+                .filter(|v| v.is_finite())
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &v| {
+                    (min.min(v), max.max(v))
+                });
+            if min <= max {
+                (min, max)
+            } else {
+                (0.0, 0.0)
+            }
+        })
+        .collect()
+}
+
+/// (lat, lon) of the center of pixel (px, py) in tile (z, x, y) of the Web Mercator grid
+pub fn pixel_position(z: u8, x: u32, y: u32, px: u32, py: u32) -> (f64, f64) {
+    let size = f64::from(TILE_SIZE) * 2f64.powi(z as i32);
+    let gx = f64::from(x) * f64::from(TILE_SIZE) + f64::from(px) + 0.5;
+    let gy = f64::from(y) * f64::from(TILE_SIZE) + f64::from(py) + 0.5;
+
+    let lon = gx / size * 360.0 - 180.0;
+    let lat = (PI * (1.0 - 2.0 * gy / size)).sinh().atan().to_degrees();
+    (lat, lon)
+}
+
+/// `value` scaled from `min..max` to a byte, 0 when the range is empty
+fn scale_to_byte(value: f64, (min, max): (f64, f64)) -> u8 {
+    if max > min {
+        ((value - min) / (max - min) * 255.0)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    } else {
+        0
+    }
+}
+
+fn pixel_color(
+    layer: TileLayer,
+    values: &[f64],
+    ranges: &[(f64, f64)],
+    palette: Option<&Palette>,
+) -> [u8; 4] {
+    // Only u and v matter to the wind layers, gusts can be missing
+    if values.iter().take(2).any(|v| !v.is_finite()) {
+        return TRANSPARENT;
+    }
+    match (layer, palette) {
+        (TileLayer::Wind, _) => [
+            scale_to_byte(values[0], ranges[0]),
+            scale_to_byte(values[1], ranges[1]),
+            0,
+            255,
+        ],
+        (TileLayer::WindSpeed, Some(palette)) => {
+            palette.color(values[0].hypot(values[1]) * MS_TO_KMH)
+        }
+        (TileLayer::Precipitation, Some(palette)) => palette.color(values[0]),
+        (_, None) => TRANSPARENT,
+    }
+}
+
+/// PNG of tile (z, x, y), each pixel interpolated from `grid` at its center.
+/// `ranges` are the grid's `channel_ranges`, `palette` the one of the layer.
+/// Missing values are left transparent.
+pub fn render_tile(
+    layer: TileLayer,
+    grid: &WeatherGrid,
+    ranges: &[(f64, f64)],
+    palette: Option<&Palette>,
+    z: u8,
+    x: u32,
+    y: u32,
+) -> Result<Vec<u8>> {
+    let mut img: RgbaImage = ImageBuffer::new(TILE_SIZE, TILE_SIZE);
+
+    for py in 0..TILE_SIZE {
+        for px in 0..TILE_SIZE {
+            let (lat, lon) = pixel_position(z, x, y, px, py);
+            let color = match grid.sample(lat, lon) {
+                Some(values) => pixel_color(layer, &values, ranges, palette),
+                None => TRANSPARENT,
+            };
+            img.put_pixel(px, py, Rgba(color));
+        }
+    }
+
+    let mut png_buffer = Vec::new();
+    let encoder = image::codecs::png::PngEncoder::new(&mut png_buffer);
+    img.write_with_encoder(encoder)?;
+    Ok(png_buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::palette::Palettes;
+
+    fn global_grid(u: f64, v: f64) -> WeatherGrid {
+        let lats: Vec<f64> = (0..=4).map(|i| -90.0 + 45.0 * i as f64).collect();
+        let lons: Vec<f64> = (0..8).map(|i| -180.0 + 45.0 * i as f64).collect();
+        let cells = lats.len() * lons.len();
+        let mut u = vec![u; cells];
+        // One missing value in the north-west corner, and no gusts at all
+        u[4 * lons.len()] = f64::NAN;
+        WeatherGrid {
+            lats,
+            lons,
+            channels: vec![u, vec![v; cells], vec![f64::NAN; cells]],
+        }
+    }
+
+    #[test]
+    fn test_pixel_position() {
+        let (lat, lon) = pixel_position(0, 0, 0, 0, 0);
+        assert!(lat > 84.9 && lat < 85.06);
+        assert!((lon + 180.0).abs() < 1.0);
+
+        // The corner between the four zoom 1 tiles is (0, 0)
+        let (lat, lon) = pixel_position(1, 1, 1, 0, 0);
+        assert!(lat < 0.0 && lat > -1.0);
+        assert!(lon > 0.0 && lon < 1.0);
+    }
+
+    #[test]
+    fn test_render_tile() {
+        let grid = global_grid(3.0, 4.0);
+        let ranges = channel_ranges(&grid);
+        assert_eq!(ranges, vec![(3.0, 3.0), (4.0, 4.0), (0.0, 0.0)]);
+
+        let palettes = Palettes::load(None).unwrap();
+        let palette = palettes.get("wind-speed");
+
+        let png = render_tile(TileLayer::WindSpeed, &grid, &ranges, palette, 1, 1, 1).unwrap();
+        let tile = image::load_from_memory(&png).unwrap().to_rgba8();
+        assert_eq!(tile.dimensions(), (TILE_SIZE, TILE_SIZE));
+        // 5 m/s
+        assert_eq!(tile.get_pixel(128, 128).0, palette.unwrap().color(18.0));
+
+        // Interpolating with the missing value gives a transparent pixel
+        let png = render_tile(TileLayer::Wind, &grid, &ranges, None, 1, 0, 0).unwrap();
+        let tile = image::load_from_memory(&png).unwrap().to_rgba8();
+        assert_eq!(tile.get_pixel(0, 0).0, TRANSPARENT);
+        assert_eq!(tile.get_pixel(255, 255).0, [0, 0, 0, 255]);
+    }
+}
@@ -1,6 +1,6 @@
 use rand::{distr::Alphanumeric, prelude::*};
 use rust_embed::Embed;
-use std::{env, process};
+use std::{env, fmt, process};
 
 fn get_http_port() -> u16 {
     let port_str = env::var("HTTP_PORT").unwrap_or("8080".to_string());
@@ -20,6 +20,37 @@ fn get_mail_port() -> u16 {
     })
 }
 
+/// How the SMTP connection is secured, from `SMTP_TLS`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MailTls {
+    /// Plain text, for a relay on the same host
+    None,
+    /// STARTTLS when the server offers it, plain text otherwise
+    Opportunistic,
+    /// STARTTLS, refusing servers without it
+    Starttls,
+    /// TLS from the start of the connection (SMTPS, usually port 465)
+    Tls,
+}
+
+fn get_mail_tls() -> MailTls {
+    let mode = env::var("SMTP_TLS").unwrap_or("none".to_string());
+
+    match mode.as_str() {
+        "none" => MailTls::None,
+        "opportunistic" => MailTls::Opportunistic,
+        "starttls" => MailTls::Starttls,
+        "tls" => MailTls::Tls,
+        _ => {
+            eprintln!(
+                "Invalid SMTP_TLS value: {}, expected none, opportunistic, starttls or tls",
+                mode
+            );
+            process::exit(1);
+        }
+    }
+}
+
 fn get_otc_exp_minutes() -> i64 {
     let minutes = env::var("OTC_EXP_MINUTES").unwrap_or("15".to_string());
 
@@ -33,6 +64,20 @@ pub fn generate_one_time_code() -> i32 {
     rand::rng().random_range(100000..=999999)
 }
 
+/// `url` with the password of its credentials hidden, for logging
+pub fn redact_url_password(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    let Some((credentials, host)) = rest.rsplit_once('@') else {
+        return url.to_string();
+    };
+    match credentials.split_once(':') {
+        Some((user, _)) => format!("{}://{}:***@{}", scheme, user, host),
+        None => url.to_string(),
+    }
+}
+
 pub fn generate_random_string(len: usize) -> String {
     let s: String = rand::rng()
         .sample_iter(&Alphanumeric)
@@ -42,7 +87,7 @@ pub fn generate_random_string(len: usize) -> String {
     s
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct Env {
     pub is_prod: bool,
     pub database_url: String,
@@ -51,15 +96,39 @@ pub struct Env {
     pub mail_from: String,
     pub mail_host: String,
     pub mail_port: u16,
+    pub mail_tls: MailTls,
     pub smtp_pass: String,
     pub otc_exp_minutes: i64,
     pub http_domain: String,
+    /// Directory mails are written to as `.eml` files instead of being sent, never in production
+    pub mail_sink_dir: Option<String>,
+}
+
+// Printed at startup, so credentials are left out
+impl fmt::Debug for Env {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Env")
+            .field("is_prod", &self.is_prod)
+            .field("database_url", &"<redacted>")
+            .field("http_host", &self.http_host)
+            .field("http_port", &self.http_port)
+            .field("mail_from", &self.mail_from)
+            .field("mail_host", &self.mail_host)
+            .field("mail_port", &self.mail_port)
+            .field("mail_tls", &self.mail_tls)
+            .field("smtp_pass", &"<redacted>")
+            .field("otc_exp_minutes", &self.otc_exp_minutes)
+            .field("http_domain", &self.http_domain)
+            .field("mail_sink_dir", &self.mail_sink_dir)
+            .finish()
+    }
 }
 
 pub fn get_env() -> Env {
+    let is_prod =
+        env::var("ENVIRONMENT").unwrap_or("development".to_string()) == "production".to_string();
     let env: Env = Env {
-        is_prod: env::var("ENVIRONMENT").unwrap_or("development".to_string())
-            == "production".to_string(),
+        is_prod,
         database_url: env::var("DATABASE_URL")
             .expect("DATABASE_URL must be set in .env or environment"),
 
@@ -71,6 +140,10 @@ pub fn get_env() -> Env {
         smtp_pass: env::var("SMTP_PASSWORD").expect("missing SMTP_PASSWORD env var"),
         mail_host: env::var("SMTP_HOST").expect("missing SMTP_HOST env var"),
         mail_port: get_mail_port(),
+        mail_tls: get_mail_tls(),
+        mail_sink_dir: env::var("MAIL_SINK_DIR")
+            .ok()
+            .filter(|dir| !dir.is_empty() && !is_prod),
     };
 
     println!("{:#?}", env);
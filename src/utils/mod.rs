@@ -1,6 +1,22 @@
+pub mod animation;
 pub mod config;
+pub mod cycling_power;
+pub mod geo;
+pub mod heatmap;
+pub mod land_mask;
 pub mod mail;
+pub mod mail_templates;
+pub mod map_tiles;
+pub mod merge_patch;
 pub mod misc;
 pub mod opendap_parser;
+pub mod osm_pbf;
+pub mod palette;
 pub mod png_converter;
+pub mod polar;
 pub mod queries;
+pub mod road_graph;
+pub mod route_formats;
+pub mod unsubscribe;
+pub mod weather_grid;
+pub mod webhook;
@@ -7,6 +7,8 @@ pub struct ParsedWindData {
     pub lon_values: Vec<f64>,
     pub u_data: Vec<f64>,
     pub v_data: Vec<f64>,
+    /// Surface wind gusts (`gustsfc`), m/s
+    pub gust_data: Vec<f64>,
 }
 
 #[derive(Debug, Clone)]
@@ -16,6 +18,13 @@ pub struct ParsedPrecipitationData {
     pub prate_data: Vec<f64>,
 }
 
+#[derive(Debug, Clone)]
+pub struct ParsedVariableData {
+    pub lat_values: Vec<f64>,
+    pub lon_values: Vec<f64>,
+    pub values: Vec<f64>,
+}
+
 /// Parse OpenDAP ASCII response for wind data
 pub fn parse_opendap_ascii(ascii_data: &str) -> Result<ParsedWindData> {
     let lines: Vec<&str> = ascii_data.lines().collect();
@@ -24,6 +33,7 @@ pub fn parse_opendap_ascii(ascii_data: &str) -> Result<ParsedWindData> {
     let mut lon_values = Vec::new();
     let mut u_values = Vec::new();
     let mut v_values = Vec::new();
+    let mut gust_values = Vec::new();
 
     let mut current_variable: Option<&str> = None;
     let mut in_data_section = false;
@@ -33,6 +43,7 @@ pub fn parse_opendap_ascii(ascii_data: &str) -> Result<ParsedWindData> {
     let mut parsed_lon = false;
     let mut parsed_ugrd = false;
     let mut parsed_vgrd = false;
+    let mut parsed_gust = false;
 
     for line in lines {
         let trimmed = line.trim();
@@ -86,6 +97,18 @@ pub fn parse_opendap_ascii(ascii_data: &str) -> Result<ParsedWindData> {
             continue;
         }
 
+        if trimmed.starts_with("gustsfc,") {
+            if !parsed_gust {
+                current_variable = Some("gust");
+                in_data_section = false; // For 3D arrays, wait for [index] lines
+                parsed_gust = true;
+            } else {
+                current_variable = None;
+                in_data_section = false;
+            }
+            continue;
+        }
+
         // Skip time variable
         if trimmed.starts_with("time,") || trimmed.starts_with("time[") {
             current_variable = None;
@@ -107,6 +130,7 @@ pub fn parse_opendap_ascii(ascii_data: &str) -> Result<ParsedWindData> {
             match current_variable {
                 Some("ugrd") => u_values.extend(nums),
                 Some("vgrd") => v_values.extend(nums),
+                Some("gust") => gust_values.extend(nums),
                 _ => {}
             }
             continue;
@@ -121,17 +145,19 @@ pub fn parse_opendap_ascii(ascii_data: &str) -> Result<ParsedWindData> {
                 Some("lon") => lon_values.extend(nums),
                 Some("ugrd") => u_values.extend(nums),
                 Some("vgrd") => v_values.extend(nums),
+                Some("gust") => gust_values.extend(nums),
                 _ => {}
             }
         }
     }
 
     info!(
-        "Parsed: {} lats, {} lons, {} U values, {} V values",
+        "Parsed: {} lats, {} lons, {} U values, {} V values, {} gust values",
         lat_values.len(),
         lon_values.len(),
         u_values.len(),
-        v_values.len()
+        v_values.len(),
+        gust_values.len()
     );
 
     // Safety check
@@ -144,29 +170,51 @@ pub fn parse_opendap_ascii(ascii_data: &str) -> Result<ParsedWindData> {
             v_values.len()
         );
     }
+    if gust_values.len() != u_values.len() {
+        anyhow::bail!(
+            "Invalid parsed gust data: {} values for {} wind values",
+            gust_values.len(),
+            u_values.len()
+        );
+    }
 
     Ok(ParsedWindData {
         lat_values,
         lon_values,
         u_data: u_values,
         v_data: v_values,
+        gust_data: gust_values,
     })
 }
 
 /// Parse OpenDAP ASCII response for precipitation data
 pub fn parse_opendap_precipitation_ascii(ascii_data: &str) -> Result<ParsedPrecipitationData> {
+    let parsed = parse_opendap_variable_ascii(ascii_data, "pratesfc")?;
+
+    Ok(ParsedPrecipitationData {
+        lat_values: parsed.lat_values,
+        lon_values: parsed.lon_values,
+        prate_data: parsed.values,
+    })
+}
+
+/// Parse OpenDAP ASCII response for a single 3D variable (time, lat, lon)
+pub fn parse_opendap_variable_ascii(
+    ascii_data: &str,
+    variable: &str,
+) -> Result<ParsedVariableData> {
     let lines: Vec<&str> = ascii_data.lines().collect();
 
     let mut lat_values = Vec::new();
     let mut lon_values = Vec::new();
-    let mut prate_values = Vec::new();
+    let mut values = Vec::new();
 
     let mut current_variable: Option<&str> = None;
     let mut in_data_section = false;
 
     let mut parsed_lat = false;
     let mut parsed_lon = false;
-    let mut parsed_prate = false;
+    let mut parsed_variable = false;
 
     for line in lines {
         let trimmed = line.trim();
@@ -196,11 +244,11 @@ pub fn parse_opendap_precipitation_ascii(ascii_data: &str) -> Result<ParsedPreci
             continue;
         }
 
-        if trimmed.starts_with("pratesfc,") {
-            if !parsed_prate {
-                current_variable = Some("prate");
+        if trimmed.starts_with(variable) && trimmed[variable.len()..].starts_with(',') {
+            if !parsed_variable {
+                current_variable = Some("values");
                 in_data_section = false; // For 3D arrays, wait for [index] lines
-                parsed_prate = true;
+                parsed_variable = true;
             } else {
                 current_variable = None;
                 in_data_section = false;
@@ -220,13 +268,13 @@ pub fn parse_opendap_precipitation_ascii(ascii_data: &str) -> Result<ParsedPreci
             continue;
         }
 
-        // For precipitation data: lines start with [index][index]
+        // For 3D data: lines start with [index][index]
         if trimmed.starts_with('[') {
             in_data_section = true;
             let nums = extract_numbers_from_indexed_line(trimmed);
 
-            if matches!(current_variable, Some("prate")) {
-                prate_values.extend(nums);
+            if matches!(current_variable, Some("values")) {
+                values.extend(nums);
             }
             continue;
         }
@@ -238,32 +286,34 @@ pub fn parse_opendap_precipitation_ascii(ascii_data: &str) -> Result<ParsedPreci
             match current_variable {
                 Some("lat") => lat_values.extend(nums),
                 Some("lon") => lon_values.extend(nums),
-                Some("prate") => prate_values.extend(nums),
+                Some("values") => values.extend(nums),
                 _ => {}
             }
         }
     }
 
     info!(
-        "Parsed precipitation: {} lats, {} lons, {} prate values",
+        "Parsed {}: {} lats, {} lons, {} values",
+        variable,
         lat_values.len(),
         lon_values.len(),
-        prate_values.len()
+        values.len()
     );
 
-    if lat_values.is_empty() || lon_values.is_empty() || prate_values.is_empty() {
+    if lat_values.is_empty() || lon_values.is_empty() || values.is_empty() {
         anyhow::bail!(
-            "Invalid parsed precipitation data: lats={}, lons={}, prateValues={}",
+            "Invalid parsed {} data: lats={}, lons={}, values={}",
+            variable,
             lat_values.len(),
             lon_values.len(),
-            prate_values.len()
+            values.len()
         );
     }
 
-    Ok(ParsedPrecipitationData {
+    Ok(ParsedVariableData {
         lat_values,
         lon_values,
-        prate_data: prate_values,
+        values,
     })
 }
 
@@ -306,6 +356,31 @@ mod tests {
         assert_eq!(nums, vec![4.2, 5.1]);
     }
 
+    #[test]
+    fn test_parse_single_variable() {
+        let ascii = "landsfc, [1][2][3]\n[0][0], 0, 0, 1\n[0][1], 1, 1, 0\n\ntime, [1]\n738000.0\nlat, [2]\n-90.0, -89.5\nlon, [3]\n0.0, 0.5, 1.0\n";
+        let parsed = parse_opendap_variable_ascii(ascii, "landsfc").unwrap();
+        assert_eq!(parsed.lat_values, vec![-90.0, -89.5]);
+        assert_eq!(parsed.lon_values, vec![0.0, 0.5, 1.0]);
+        assert_eq!(parsed.values, vec![0.0, 0.0, 1.0, 1.0, 1.0, 0.0]);
+
+        assert!(parse_opendap_variable_ascii(ascii, "pratesfc").is_err());
+    }
+
+    #[test]
+    fn test_parse_wind() {
+        let ascii = "ugrd10m, [1][1][2]\n[0][0], 1.5, -2.0\n\nvgrd10m, [1][1][2]\n[0][0], 0.5, 3.0\n\ngustsfc, [1][1][2]\n[0][0], 4.2, 6.1\n\nlat, [1]\n45.0\nlon, [2]\n0.0, 0.5\n";
+        let parsed = parse_opendap_ascii(ascii).unwrap();
+        assert_eq!(parsed.lat_values, vec![45.0]);
+        assert_eq!(parsed.lon_values, vec![0.0, 0.5]);
+        assert_eq!(parsed.u_data, vec![1.5, -2.0]);
+        assert_eq!(parsed.v_data, vec![0.5, 3.0]);
+        assert_eq!(parsed.gust_data, vec![4.2, 6.1]);
+
+        let without_gusts = ascii.replace("gustsfc, [1][1][2]\n[0][0], 4.2, 6.1\n\n", "");
+        assert!(parse_opendap_ascii(&without_gusts).is_err());
+    }
+
     #[test]
     fn test_extract_numbers_from_indexed_line() {
         let nums = extract_numbers_from_indexed_line("[0][0], 17.16, 17.22, 17.28");
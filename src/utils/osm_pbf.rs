@@ -0,0 +1,462 @@
+use anyhow::{anyhow, bail, Context, Result};
+use flate2::read::ZlibDecoder;
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read};
+
+/// Largest blob allowed by the OSM PBF specification
+const MAX_BLOB_SIZE: usize = 32 * 1024 * 1024;
+const MAX_BLOB_HEADER_SIZE: usize = 64 * 1024;
+
+/// Node position, in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OsmNode {
+    pub id: i64,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsmWay {
+    pub id: i64,
+    pub refs: Vec<i64>,
+    pub tags: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OsmElement {
+    Node(OsmNode),
+    Way(OsmWay),
+}
+
+/*
+ * Protobuf wire format, just what the OSM PBF messages use
+ **/
+
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+struct Message<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Message<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Message { buf, pos: 0 }
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self
+                .buf
+                .get(self.pos)
+                .ok_or_else(|| anyhow!("Truncated varint"))?;
+            self.pos += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!("Varint too long")
+    }
+
+    fn skip(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.buf.len())
+            .ok_or_else(|| anyhow!("Truncated field"))?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn next_field(&mut self) -> Result<Option<(u64, Field<'a>)>> {
+        if self.pos >= self.buf.len() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let field = match key & 0x7 {
+            0 => Field::Varint(self.varint()?),
+            1 => {
+                self.skip(8)?;
+                Field::Fixed
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                Field::Bytes(self.skip(len)?)
+            }
+            5 => {
+                self.skip(4)?;
+                Field::Fixed
+            }
+            wire_type => bail!("Unsupported protobuf wire type {}", wire_type),
+        };
+        Ok(Some((key >> 3, field)))
+    }
+}
+
+fn zigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn packed_varints(bytes: &[u8]) -> Result<Vec<u64>> {
+    let mut message = Message::new(bytes);
+    let mut values = Vec::new();
+    while message.pos < bytes.len() {
+        values.push(message.varint()?);
+    }
+    Ok(values)
+}
+
+/// Delta-coded `sint64` values, as used for ids, refs and dense coordinates
+fn packed_deltas(bytes: &[u8]) -> Result<Vec<i64>> {
+    let mut current = 0i64;
+    Ok(packed_varints(bytes)?
+        .into_iter()
+        .map(|v| {
+            current += zigzag(v);
+            current
+        })
+        .collect())
+}
+
+/*
+ * File blocks
+ **/
+
+/// Read the next `(type, data)` blob of the file, `None` at the end
+fn next_blob<R: Read>(reader: &mut R) -> Result<Option<(String, Vec<u8>)>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let header_len = u32::from_be_bytes(len) as usize;
+    if header_len > MAX_BLOB_HEADER_SIZE {
+        bail!("Blob header of {} bytes is too large", header_len);
+    }
+
+    let mut header = vec![0u8; header_len];
+    reader.read_exact(&mut header)?;
+
+    let mut kind = String::new();
+    let mut data_size = 0usize;
+    let mut message = Message::new(&header);
+    while let Some((number, field)) = message.next_field()? {
+        match (number, field) {
+            (1, Field::Bytes(bytes)) => kind = String::from_utf8_lossy(bytes).into_owned(),
+            (3, Field::Varint(size)) => data_size = size as usize,
+            _ => {}
+        }
+    }
+    if data_size > MAX_BLOB_SIZE {
+        bail!("Blob of {} bytes is too large", data_size);
+    }
+
+    let mut blob = vec![0u8; data_size];
+    reader.read_exact(&mut blob)?;
+
+    let mut raw = None;
+    let mut zlib = None;
+    let mut raw_size = 0usize;
+    let mut message = Message::new(&blob);
+    while let Some((number, field)) = message.next_field()? {
+        match (number, field) {
+            (1, Field::Bytes(bytes)) => raw = Some(bytes),
+            (2, Field::Varint(size)) => raw_size = size as usize,
+            (3, Field::Bytes(bytes)) => zlib = Some(bytes),
+            _ => {}
+        }
+    }
+
+    let data = match (raw, zlib) {
+        (Some(raw), _) => raw.to_vec(),
+        (None, Some(zlib)) => {
+            let mut data = Vec::with_capacity(raw_size.min(MAX_BLOB_SIZE));
+            ZlibDecoder::new(zlib)
+                .take(MAX_BLOB_SIZE as u64)
+                .read_to_end(&mut data)?;
+            data
+        }
+        (None, None) => bail!("Unsupported blob compression, only zlib is handled"),
+    };
+
+    Ok(Some((kind, data)))
+}
+
+/// Coordinates scaling of one primitive block
+struct BlockScale {
+    granularity: i64,
+    lat_offset: i64,
+    lon_offset: i64,
+}
+
+impl BlockScale {
+    fn lat(&self, value: i64) -> f64 {
+        (self.lat_offset + self.granularity * value) as f64 * 1e-9
+    }
+
+    fn lon(&self, value: i64) -> f64 {
+        (self.lon_offset + self.granularity * value) as f64 * 1e-9
+    }
+}
+
+fn read_primitive_block(
+    data: &[u8],
+    nodes: bool,
+    ways: bool,
+    f: &mut impl FnMut(OsmElement),
+) -> Result<()> {
+    let mut strings: Vec<String> = Vec::new();
+    let mut groups = Vec::new();
+    let mut scale = BlockScale {
+        granularity: 100,
+        lat_offset: 0,
+        lon_offset: 0,
+    };
+
+    let mut message = Message::new(data);
+    while let Some((number, field)) = message.next_field()? {
+        match (number, field) {
+            (1, Field::Bytes(table)) => {
+                let mut table = Message::new(table);
+                while let Some((number, field)) = table.next_field()? {
+                    if let (1, Field::Bytes(s)) = (number, field) {
+                        strings.push(String::from_utf8_lossy(s).into_owned());
+                    }
+                }
+            }
+            (2, Field::Bytes(group)) => groups.push(group),
+            (17, Field::Varint(v)) => scale.granularity = v as i64,
+            (19, Field::Varint(v)) => scale.lat_offset = v as i64,
+            (20, Field::Varint(v)) => scale.lon_offset = v as i64,
+            _ => {}
+        }
+    }
+
+    for group in groups {
+        let mut group = Message::new(group);
+        while let Some((number, field)) = group.next_field()? {
+            match (number, field) {
+                (1, Field::Bytes(node)) if nodes => f(OsmElement::Node(read_node(node, &scale)?)),
+                (2, Field::Bytes(dense)) if nodes => {
+                    for node in read_dense_nodes(dense, &scale)? {
+                        f(OsmElement::Node(node));
+                    }
+                }
+                (3, Field::Bytes(way)) if ways => f(OsmElement::Way(read_way(way, &strings)?)),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn read_node(data: &[u8], scale: &BlockScale) -> Result<OsmNode> {
+    let (mut id, mut lat, mut lon) = (0, 0, 0);
+    let mut message = Message::new(data);
+    while let Some((number, field)) = message.next_field()? {
+        match (number, field) {
+            (1, Field::Varint(v)) => id = zigzag(v),
+            (8, Field::Varint(v)) => lat = zigzag(v),
+            (9, Field::Varint(v)) => lon = zigzag(v),
+            _ => {}
+        }
+    }
+    Ok(OsmNode {
+        id,
+        lat: scale.lat(lat),
+        lon: scale.lon(lon),
+    })
+}
+
+fn read_dense_nodes(data: &[u8], scale: &BlockScale) -> Result<Vec<OsmNode>> {
+    let (mut ids, mut lats, mut lons) = (Vec::new(), Vec::new(), Vec::new());
+    let mut message = Message::new(data);
+    while let Some((number, field)) = message.next_field()? {
+        match (number, field) {
+            (1, Field::Bytes(bytes)) => ids = packed_deltas(bytes)?,
+            (8, Field::Bytes(bytes)) => lats = packed_deltas(bytes)?,
+            (9, Field::Bytes(bytes)) => lons = packed_deltas(bytes)?,
+            _ => {}
+        }
+    }
+    if ids.len() != lats.len() || ids.len() != lons.len() {
+        bail!("Dense nodes with mismatched id and coordinate counts");
+    }
+
+    Ok(ids
+        .into_iter()
+        .zip(lats.into_iter().zip(lons))
+        .map(|(id, (lat, lon))| OsmNode {
+            id,
+            lat: scale.lat(lat),
+            lon: scale.lon(lon),
+        })
+        .collect())
+}
+
+fn read_way(data: &[u8], strings: &[String]) -> Result<OsmWay> {
+    let (mut id, mut keys, mut vals, mut refs) = (0, Vec::new(), Vec::new(), Vec::new());
+    let mut message = Message::new(data);
+    while let Some((number, field)) = message.next_field()? {
+        match (number, field) {
+            (1, Field::Varint(v)) => id = v as i64,
+            (2, Field::Bytes(bytes)) => keys = packed_varints(bytes)?,
+            (3, Field::Bytes(bytes)) => vals = packed_varints(bytes)?,
+            (8, Field::Bytes(bytes)) => refs = packed_deltas(bytes)?,
+            _ => {}
+        }
+    }
+
+    let string = |index: u64| {
+        strings
+            .get(index as usize)
+            .cloned()
+            .ok_or_else(|| anyhow!("Way {} references a missing string", id))
+    };
+    let tags = keys
+        .into_iter()
+        .zip(vals)
+        .map(|(k, v)| Ok((string(k)?, string(v)?)))
+        .collect::<Result<_>>()?;
+
+    Ok(OsmWay { id, refs, tags })
+}
+
+/// Stream the nodes and/or ways of an `.osm.pbf` extract to `f`, in file order
+pub fn read_pbf<R: Read>(
+    mut reader: R,
+    nodes: bool,
+    ways: bool,
+    mut f: impl FnMut(OsmElement),
+) -> Result<()> {
+    while let Some((kind, data)) = next_blob(&mut reader)? {
+        match kind.as_str() {
+            "OSMHeader" => {}
+            "OSMData" => read_primitive_block(&data, nodes, ways, &mut f)
+                .context("Invalid OSM data block")?,
+            other => bail!("Unknown blob type {}", other),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    fn varint(mut value: u64, out: &mut Vec<u8>) {
+        while value >= 0x80 {
+            out.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn bytes_field(number: u64, bytes: &[u8], out: &mut Vec<u8>) {
+        varint(number << 3 | 2, out);
+        varint(bytes.len() as u64, out);
+        out.extend_from_slice(bytes);
+    }
+
+    fn packed_sint(values: &[i64]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut previous = 0;
+        for value in values {
+            let delta = value - previous;
+            varint(((delta << 1) ^ (delta >> 63)) as u64, &mut out);
+            previous = *value;
+        }
+        out
+    }
+
+    fn blob(kind: &str, data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        let mut blob = Vec::new();
+        bytes_field(3, &encoder.finish().unwrap(), &mut blob);
+
+        let mut header = Vec::new();
+        bytes_field(1, kind.as_bytes(), &mut header);
+        varint(3 << 3, &mut header);
+        varint(blob.len() as u64, &mut header);
+
+        let mut out = (header.len() as u32).to_be_bytes().to_vec();
+        out.extend(header);
+        out.extend(blob);
+        out
+    }
+
+    #[test]
+    fn test_read_dense_nodes_and_ways() {
+        let mut strings = Vec::new();
+        for s in ["", "highway", "residential", "name", "Rue de Rivoli"] {
+            bytes_field(1, s.as_bytes(), &mut strings);
+        }
+
+        let mut dense = Vec::new();
+        bytes_field(1, &packed_sint(&[10, 11, 12]), &mut dense);
+        // Granularity 100: 1e7 units per degree
+        bytes_field(
+            8,
+            &packed_sint(&[488_600_000, 488_610_000, 488_620_000]),
+            &mut dense,
+        );
+        bytes_field(
+            9,
+            &packed_sint(&[23_500_000, 23_510_000, 23_520_000]),
+            &mut dense,
+        );
+
+        let mut way = Vec::new();
+        varint(1 << 3, &mut way);
+        varint(7, &mut way);
+        bytes_field(2, &[1, 3], &mut way);
+        bytes_field(3, &[2, 4], &mut way);
+        bytes_field(8, &packed_sint(&[10, 11, 12]), &mut way);
+
+        let mut group = Vec::new();
+        bytes_field(2, &dense, &mut group);
+        bytes_field(3, &way, &mut group);
+
+        let mut block = Vec::new();
+        bytes_field(1, &strings, &mut block);
+        bytes_field(2, &group, &mut block);
+
+        let mut file = blob("OSMHeader", &[]);
+        file.extend(blob("OSMData", &block));
+
+        let mut elements = Vec::new();
+        read_pbf(file.as_slice(), true, true, |e| elements.push(e)).unwrap();
+
+        assert_eq!(elements.len(), 4);
+        let OsmElement::Node(node) = &elements[1] else {
+            panic!("expected a node");
+        };
+        assert_eq!(node.id, 11);
+        assert!((node.lat - 48.861).abs() < 1e-9);
+        assert!((node.lon - 2.351).abs() < 1e-9);
+
+        let OsmElement::Way(way) = &elements[3] else {
+            panic!("expected a way");
+        };
+        assert_eq!(way.id, 7);
+        assert_eq!(way.refs, vec![10, 11, 12]);
+        assert_eq!(way.tags["highway"], "residential");
+        assert_eq!(way.tags["name"], "Rue de Rivoli");
+
+        let mut ways_only = 0;
+        read_pbf(file.as_slice(), false, true, |_| ways_only += 1).unwrap();
+        assert_eq!(ways_only, 1);
+    }
+}
@@ -0,0 +1,171 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::utils::misc::Asset;
+
+const TRANSPARENT: [u8; 4] = [0, 0, 0, 0];
+
+/// Color ramp of a weather value, read from a JSON file:
+/// `{"unit": "km/h", "min": 0.1, "stops": [[0, "#6271b7"], [10, "#39619fcc"], ...]}`.
+/// Colors are `#rrggbb` or `#rrggbbaa`, values below `min` are transparent.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "PaletteFile")]
+pub struct Palette {
+    pub unit: String,
+    pub min: Option<f64>,
+    /// Ascending values and their RGBA color
+    pub stops: Vec<(f64, [u8; 4])>,
+}
+
+#[derive(Deserialize)]
+struct PaletteFile {
+    #[serde(default)]
+    unit: String,
+    min: Option<f64>,
+    stops: Vec<(f64, String)>,
+}
+
+impl TryFrom<PaletteFile> for Palette {
+    type Error = String;
+
+    fn try_from(file: PaletteFile) -> Result<Self, Self::Error> {
+        if file.stops.len() < 2 {
+            return Err("a palette needs at least 2 stops".to_string());
+        }
+        let stops = file
+            .stops
+            .into_iter()
+            .map(|(value, color)| Ok((value, parse_color(&color)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        if stops.windows(2).any(|w| w[0].0 >= w[1].0) {
+            return Err("palette stops must be in ascending order".to_string());
+        }
+
+        Ok(Self {
+            unit: file.unit,
+            min: file.min,
+            stops,
+        })
+    }
+}
+
+fn parse_color(color: &str) -> Result<[u8; 4], String> {
+    let hex = color
+        .strip_prefix('#')
+        .filter(|hex| (hex.len() == 6 || hex.len() == 8) && hex.is_ascii())
+        .ok_or_else(|| format!("invalid color {}, expected #rrggbb or #rrggbbaa", color))?;
+
+    let mut rgba = [255; 4];
+    for (i, channel) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+        *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| format!("invalid color {}", color))?;
+    }
+    Ok(rgba)
+}
+
+impl Palette {
+    /// Color of `value`, linear between stops and clamped outside them.
+    /// Missing values and values below `min` are transparent.
+    pub fn color(&self, value: f64) -> [u8; 4] {
+        if !value.is_finite() || self.min.is_some_and(|min| value < min) {
+            return TRANSPARENT;
+        }
+        let Some(upper) = self.stops.iter().position(|(stop, _)| value < *stop) else {
+            return self.stops[self.stops.len() - 1].1;
+        };
+        if upper == 0 {
+            return self.stops[0].1;
+        }
+
+        let (from, low) = self.stops[upper - 1];
+        let (to, high) = self.stops[upper];
+        let t = (value - from) / (to - from);
+        let mut color = [0; 4];
+        for (c, (l, h)) in color.iter_mut().zip(low.iter().zip(high.iter())) {
+            *c = (f64::from(*l) + (f64::from(*h) - f64::from(*l)) * t).round() as u8;
+        }
+        color
+    }
+}
+
+/// Palettes by name: the ones of `embedded/palettes`, then the `<name>.json`
+/// files of the palette directory, which can replace them
+#[derive(Debug, Clone, Default)]
+pub struct Palettes(HashMap<String, Palette>);
+
+impl Palettes {
+    pub fn load(dir: Option<&str>) -> Result<Self> {
+        let mut palettes = HashMap::new();
+
+        for file in Asset::iter().filter(|file| file.starts_with("palettes/")) {
+            let Some(name) = palette_name(&file) else {
+                continue;
+            };
+            let data = Asset::get(&file).context("embedded palette vanished")?;
+            let palette = serde_json::from_slice(&data.data)
+                .with_context(|| format!("Invalid embedded palette {}", file))?;
+            palettes.insert(name.to_string(), palette);
+        }
+
+        if let Some(dir) = dir {
+            for entry in std::fs::read_dir(dir)
+                .with_context(|| format!("Cannot read palette directory {}", dir))?
+            {
+                let path = entry?.path();
+                let Some(name) = path.to_str().and_then(palette_name) else {
+                    continue;
+                };
+                let palette = serde_json::from_slice(&std::fs::read(&path)?)
+                    .with_context(|| format!("Invalid palette {}", path.display()))?;
+                palettes.insert(name.to_string(), palette);
+            }
+        }
+
+        Ok(Self(palettes))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Palette> {
+        self.0.get(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.0.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+fn palette_name(path: &str) -> Option<&str> {
+    Path::new(path).file_name()?.to_str()?.strip_suffix(".json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_color() {
+        let palette: Palette = serde_json::from_str(
+            r##"{"unit": "mm/h", "min": 0.5, "stops": [[0, "#000000"], [10, "#c8640080"]]}"##,
+        )
+        .unwrap();
+        assert_eq!(palette.color(5.0), [100, 50, 0, 192]);
+        assert_eq!(palette.color(50.0), [200, 100, 0, 128]);
+        assert_eq!(palette.color(0.2), [0, 0, 0, 0]);
+        assert_eq!(palette.color(f64::NAN), [0, 0, 0, 0]);
+
+        let unordered = r##"{"stops": [[5, "#000000"], [1, "#ffffff"]]}"##;
+        assert!(serde_json::from_str::<Palette>(unordered).is_err());
+        let bad_color = r##"{"stops": [[0, "black"], [1, "#ffffff"]]}"##;
+        assert!(serde_json::from_str::<Palette>(bad_color).is_err());
+    }
+
+    #[test]
+    fn test_embedded_palettes() {
+        let palettes = Palettes::load(None).unwrap();
+        assert_eq!(palettes.names(), ["gusts", "precipitation", "wind-speed"]);
+        assert_eq!(palettes.get("precipitation").unwrap().min, Some(0.1));
+    }
+}
@@ -1,6 +1,8 @@
 use anyhow::Result;
 use image::{ImageBuffer, Rgba, RgbaImage};
-use tracing::info;
+use tracing::{info, warn};
+
+use crate::models::WindEncoding;
 
 pub struct WindPngData {
     pub png_buffer: Vec<u8>,
@@ -12,31 +14,85 @@ pub struct WindPngData {
     pub v_max: f64,
 }
 
-/// Convert wind data to PNG for windgl
+/// Largest code of a 16-bit component, 0 marks a missing value
+const UINT16_MAX_CODE: f64 = 65534.0;
+
+/// Min and max of the finite values, `(0, 0)` when there are none
+pub fn finite_range(values: &[f64]) -> (f64, f64) {
+    let (min, max) = values
+        .iter()
+        .filter(|value| value.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &value| {
+            (min.min(value), max.max(value))
+        });
+    if min <= max {
+        (min, max)
+    } else {
+        (0.0, 0.0)
+    }
+}
+
+/// Position of `value` between `min` and `max`, from 0 to 1, `None` when missing.
+/// Over a degenerate range every value is at 0 and decodes back to `min`.
+fn normalize(value: f64, min: f64, max: f64) -> Option<f64> {
+    if !value.is_finite() {
+        return None;
+    }
+    let span = max - min;
+    if span <= 0.0 || !span.is_finite() {
+        return Some(0.0);
+    }
+    Some(((value - min) / span).clamp(0.0, 1.0))
+}
+
+/// Texture pixel of a (u, v) sample, see `WindEncoding` for the layouts
+fn encode_wind(u: Option<f64>, v: Option<f64>, encoding: WindEncoding) -> [u8; 4] {
+    match encoding {
+        WindEncoding::Uint8 => match (u, v) {
+            (Some(u), Some(v)) => [(u * 255.0).round() as u8, (v * 255.0).round() as u8, 0, 255],
+            _ => [0, 0, 0, 0],
+        },
+        WindEncoding::Uint16 => {
+            let code = |t: Option<f64>| t.map_or(0, |t| 1 + (t * UINT16_MAX_CODE).round() as u16);
+            let [u_high, u_low] = code(u).to_be_bytes();
+            let [v_high, v_low] = code(v).to_be_bytes();
+            [u_high, u_low, v_high, v_low]
+        }
+    }
+}
+
+/// Convert wind data to PNG for windgl, u and v scaled between the min and max of their finite values
 pub fn convert_to_png(
     width: usize,
     height: usize,
     u_data: &[f64],
     v_data: &[f64],
-    u_min: f64,
-    u_max: f64,
-    v_min: f64,
-    v_max: f64,
+    encoding: WindEncoding,
 ) -> Result<WindPngData> {
-    info!("Creating {}x{} PNG...", width, height);
+    info!("Creating {}x{} {:?} PNG...", width, height, encoding);
+
+    let (u_min, u_max) = finite_range(u_data);
+    let (v_min, v_max) = finite_range(v_data);
+    if u_max == u_min || v_max == v_min {
+        warn!(
+            "Degenerate wind range (u {}..{}, v {}..{}), encoded as the minimum",
+            u_min, u_max, v_min, v_max
+        );
+    }
 
     // Create RGBA image
     let mut img: RgbaImage = ImageBuffer::new(width as u32, height as u32);
 
-    for i in 0..u_data.len() {
+    for (i, (u, v)) in u_data.iter().zip(v_data).enumerate() {
         let x = (i % width) as u32;
         let y = (i / width) as u32;
 
-        // Normalize U and V to 0-255
-        let u_norm = ((u_data[i] - u_min) / (u_max - u_min) * 255.0).round() as u8;
-        let v_norm = ((v_data[i] - v_min) / (v_max - v_min) * 255.0).round() as u8;
-
-        img.put_pixel(x, y, Rgba([u_norm, v_norm, 0, 255]));
+        let pixel = encode_wind(
+            normalize(*u, u_min, u_max),
+            normalize(*v, v_min, v_max),
+            encoding,
+        );
+        img.put_pixel(x, y, Rgba(pixel));
     }
 
     // Encode to PNG
@@ -57,3 +113,116 @@ pub fn convert_to_png(
         v_max,
     })
 }
+
+pub struct PrecipitationPngData {
+    pub png_buffer: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    pub rate_min: f64,
+    pub rate_max: f64,
+}
+
+/// Red channel of a precipitation rate (mm/h), log-scaled so light rain keeps
+/// most of the 256 levels: `ln(1 + rate) / ln(1 + rate_max)`
+pub fn encode_precipitation(rate: f64, rate_max: f64) -> u8 {
+    if rate_max <= 0.0 {
+        return 0;
+    }
+    (rate.max(0.0).ln_1p() / rate_max.ln_1p() * 255.0)
+        .round()
+        .clamp(0.0, 255.0) as u8
+}
+
+/// Convert precipitation rates (mm/h) to PNG for the WebGL layer, missing values are transparent
+pub fn convert_precipitation_to_png(
+    width: usize,
+    height: usize,
+    rates: &[f64],
+) -> Result<PrecipitationPngData> {
+    info!("Creating {}x{} precipitation PNG...", width, height);
+
+    let (rate_min, rate_max) = finite_range(rates);
+
+    let mut img: RgbaImage = ImageBuffer::new(width as u32, height as u32);
+
+    for (i, rate) in rates.iter().enumerate() {
+        let x = (i % width) as u32;
+        let y = (i / width) as u32;
+
+        let pixel = if rate.is_finite() {
+            Rgba([encode_precipitation(*rate, rate_max), 0, 0, 255])
+        } else {
+            Rgba([0, 0, 0, 0])
+        };
+        img.put_pixel(x, y, pixel);
+    }
+
+    let mut png_buffer = Vec::new();
+    let encoder = image::codecs::png::PngEncoder::new(&mut png_buffer);
+
+    img.write_with_encoder(encoder)?;
+
+    info!("Precipitation PNG created: {} bytes", png_buffer.len());
+
+    Ok(PrecipitationPngData {
+        png_buffer,
+        width,
+        height,
+        rate_min,
+        rate_max,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_uint16(high: u8, low: u8, min: f64, max: f64) -> Option<f64> {
+        let code = u16::from_be_bytes([high, low]);
+        (code > 0).then(|| min + f64::from(code - 1) / UINT16_MAX_CODE * (max - min))
+    }
+
+    #[test]
+    fn test_wind_encoding() {
+        let u = [-40.0, 0.123, 40.0, f64::NAN];
+        let v = [5.0; 4];
+        assert_eq!(finite_range(&u), (-40.0, 40.0));
+        assert_eq!(finite_range(&[f64::NAN]), (0.0, 0.0));
+
+        let png = convert_to_png(4, 1, &u, &v, WindEncoding::Uint16).unwrap();
+        let img = image::load_from_memory(&png.png_buffer).unwrap().to_rgba8();
+        let [u_high, u_low, v_high, v_low] = img.get_pixel(1, 0).0;
+        let decoded = decode_uint16(u_high, u_low, png.u_min, png.u_max).unwrap();
+        assert!((decoded - 0.123).abs() < 0.001);
+        // Degenerate v range, decoded as its only value
+        assert_eq!((png.v_min, png.v_max), (5.0, 5.0));
+        assert_eq!(decode_uint16(v_high, v_low, 5.0, 5.0), Some(5.0));
+        // Missing u, v still there
+        let [u_high, u_low, v_high, v_low] = img.get_pixel(3, 0).0;
+        assert_eq!(decode_uint16(u_high, u_low, -40.0, 40.0), None);
+        assert_eq!(decode_uint16(v_high, v_low, 5.0, 5.0), Some(5.0));
+
+        let png = convert_to_png(4, 1, &u, &v, WindEncoding::Uint8).unwrap();
+        let img = image::load_from_memory(&png.png_buffer).unwrap().to_rgba8();
+        assert_eq!(img.get_pixel(2, 0).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(3, 0).0, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_precipitation_encoding() {
+        assert_eq!(encode_precipitation(0.0, 40.0), 0);
+        assert_eq!(encode_precipitation(40.0, 40.0), 255);
+        assert_eq!(encode_precipitation(-1.0, 40.0), 0);
+        assert_eq!(encode_precipitation(3.0, 0.0), 0);
+        // Light rain keeps a fine resolution
+        assert!(encode_precipitation(0.5, 40.0) > 20);
+        let decoded = (f64::from(encode_precipitation(1.0, 40.0)) / 255.0 * 40f64.ln_1p()).exp_m1();
+        assert!((decoded - 1.0).abs() < 0.02);
+
+        let png = convert_precipitation_to_png(2, 1, &[2.0, f64::NAN]).unwrap();
+        assert_eq!(png.rate_max, 2.0);
+        let img = image::load_from_memory(&png.png_buffer).unwrap().to_rgba8();
+        assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(1, 0).0[3], 0);
+    }
+}
@@ -0,0 +1,196 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+const MAX_POLAR_WINDS: usize = 50;
+const MAX_POLAR_ANGLES: usize = 181;
+
+/// Boat speed table: rows of true wind angles, columns of true wind speeds.
+/// Angles in degrees (0 to 180), wind and boat speeds in knots.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PolarTable {
+    pub angles: Vec<f64>,
+    pub winds: Vec<f64>,
+    /// One row per angle, one value per wind speed
+    pub speeds: Vec<Vec<f64>>,
+}
+
+impl PolarTable {
+    /// Boat speed in knots for a true wind angle (degrees, either tack) and speed (knots).
+    /// Closer to the wind than the first angle of the table is a no-go zone, beyond the
+    /// strongest wind the last column is used and below the lightest the speed drops
+    /// linearly to zero.
+    pub fn boat_speed(&self, twa: f64, tws: f64) -> f64 {
+        if !twa.is_finite() || !tws.is_finite() || tws <= 0.0 {
+            return 0.0;
+        }
+        let twa = {
+            let folded = twa.rem_euclid(360.0);
+            if folded > 180.0 {
+                360.0 - folded
+            } else {
+                folded
+            }
+        };
+        if twa < self.angles[0] {
+            return 0.0;
+        }
+
+        let (a0, a1, ta) = axis_position(&self.angles, twa);
+        let (w0, w1, tw) = axis_position(&self.winds, tws);
+        let at_angle = |row: &[f64]| row[w0] + (row[w1] - row[w0]) * tw;
+        let speed = at_angle(&self.speeds[a0])
+            + (at_angle(&self.speeds[a1]) - at_angle(&self.speeds[a0])) * ta;
+
+        if tws < self.winds[0] {
+            speed * tws / self.winds[0]
+        } else {
+            speed
+        }
+    }
+}
+
+/// Indices around `value` in an ascending axis and the fraction between them, clamped
+fn axis_position(axis: &[f64], value: f64) -> (usize, usize, f64) {
+    let last = axis.len() - 1;
+    if value <= axis[0] {
+        return (0, 0, 0.0);
+    }
+    if value >= axis[last] {
+        return (last, last, 0.0);
+    }
+    let upper = axis.partition_point(|v| *v <= value).min(last);
+    let lower = upper - 1;
+    (
+        lower,
+        upper,
+        (value - axis[lower]) / (axis[upper] - axis[lower]),
+    )
+}
+
+/// Parse a polar in the common CSV layout (qtVlm, OpenCPN, ORC exports):
+///
+/// ```text
+/// TWA\TWS;6;8;10;12
+/// 45;4.1;5.0;5.6;5.9
+/// 90;5.2;6.3;7.0;7.4
+/// ```
+///
+/// Cells are separated by `;`, tabs or `,`. Empty lines and `#` comments are skipped.
+pub fn parse_polar_csv(content: &str) -> Result<PolarTable> {
+    let mut lines = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+    let header = lines
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Empty polar file"))?;
+    let separator = if header.contains(';') {
+        ';'
+    } else if header.contains('\t') {
+        '\t'
+    } else {
+        ','
+    };
+    let cells = |line: &str| -> Vec<String> {
+        line.split(separator)
+            .map(|cell| cell.trim().to_string())
+            .collect()
+    };
+    let number = |cell: &str, what: &str| -> Result<f64> {
+        cell.parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite() && *v >= 0.0)
+            .ok_or_else(|| anyhow::anyhow!("Invalid {} '{}'", what, cell))
+    };
+
+    // The first cell is a label such as "TWA\TWS"
+    let winds = cells(header)
+        .iter()
+        .skip(1)
+        .filter(|cell| !cell.is_empty())
+        .map(|cell| number(cell, "wind speed"))
+        .collect::<Result<Vec<f64>>>()?;
+    if winds.is_empty() || winds.len() > MAX_POLAR_WINDS {
+        anyhow::bail!("A polar needs 1 to {} wind speeds", MAX_POLAR_WINDS);
+    }
+    if winds.windows(2).any(|w| w[1] <= w[0]) || winds[0] <= 0.0 {
+        anyhow::bail!("Wind speeds must be positive and increasing");
+    }
+
+    let (mut angles, mut speeds) = (Vec::new(), Vec::new());
+    for line in lines {
+        let row = cells(line);
+        let angle = number(&row[0], "wind angle")?;
+        if angle > 180.0 {
+            anyhow::bail!("Wind angle {} is above 180", angle);
+        }
+        if angles.last().is_some_and(|previous| angle <= *previous) {
+            anyhow::bail!("Wind angles must be increasing");
+        }
+
+        let values = row[1..]
+            .iter()
+            .take(winds.len())
+            .map(|cell| number(cell, "boat speed"))
+            .collect::<Result<Vec<f64>>>()?;
+        if values.len() != winds.len() {
+            anyhow::bail!(
+                "Row for {}° has {} speeds, expected {}",
+                angle,
+                values.len(),
+                winds.len()
+            );
+        }
+
+        angles.push(angle);
+        speeds.push(values);
+    }
+
+    if angles.len() < 2 || angles.len() > MAX_POLAR_ANGLES {
+        anyhow::bail!("A polar needs 2 to {} wind angles", MAX_POLAR_ANGLES);
+    }
+
+    Ok(PolarTable {
+        angles,
+        winds,
+        speeds,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLAR: &str = "TWA\\TWS;6;12\n# comment\n\n40;4;6\n90;6;8\n180;4;7\n";
+
+    #[test]
+    fn test_parse_and_interpolate() {
+        let polar = parse_polar_csv(POLAR).unwrap();
+        assert_eq!(polar.angles, vec![40.0, 90.0, 180.0]);
+        assert_eq!(polar.winds, vec![6.0, 12.0]);
+
+        assert_eq!(polar.boat_speed(90.0, 6.0), 6.0);
+        assert_eq!(polar.boat_speed(90.0, 9.0), 7.0);
+        assert_eq!(polar.boat_speed(65.0, 6.0), 5.0);
+        // Either tack, no-go zone, light air and strong wind
+        assert_eq!(polar.boat_speed(-90.0, 6.0), 6.0);
+        assert_eq!(polar.boat_speed(270.0, 6.0), 6.0);
+        assert_eq!(polar.boat_speed(30.0, 12.0), 0.0);
+        assert_eq!(polar.boat_speed(90.0, 3.0), 3.0);
+        assert_eq!(polar.boat_speed(90.0, 30.0), 8.0);
+
+        let tabs = parse_polar_csv("twa/tws\t6\t12\n40\t4\t6\n90\t6\t8").unwrap();
+        assert_eq!(tabs.speeds[1], vec![6.0, 8.0]);
+    }
+
+    #[test]
+    fn test_rejects_malformed_polars() {
+        assert!(parse_polar_csv("").is_err());
+        assert!(parse_polar_csv("TWA;6;12\n40;4\n90;6;8").is_err());
+        assert!(parse_polar_csv("TWA;12;6\n40;4;6\n90;6;8").is_err());
+        assert!(parse_polar_csv("TWA;6;12\n90;4;6\n40;6;8").is_err());
+        assert!(parse_polar_csv("TWA;6;12\n40;4;x\n90;6;8").is_err());
+        assert!(parse_polar_csv("TWA;6;12\n40;4;6").is_err());
+    }
+}
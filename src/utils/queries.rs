@@ -1,7 +1,17 @@
 use crate::models::auth::{AppData, OneTimeCode, User};
-use crate::models::prefered_address::{NewPreferedAddress, PreferedAddress};
+use crate::models::prefered_address::{NewPreferedAddress, PreferedAddress, UpdatePreferedAddress};
+use crate::models::{
+    decode_saved_routes, notification_limit, AlertCondition, AlertEvent, AlertRule, AlertRuleRow,
+    AlertTargetRow, BoatPolar, BoatPolarRow, DigestSettings, DigestSubscription, NewAlertRule,
+    Notification, NotificationChannel, NotificationDelivery, NotificationRow, NotificationStatus,
+    RouteCursor, RouteFolder, RouteRevision, RouteRevisionRow, RouteSearchQuery, RouteShare,
+    RouteTag, RouteTagUsage, SavedRoute, SavedRouteRow, Webhook,
+};
+use crate::utils::misc::generate_random_string;
 use actix_web::web;
-use sqlx::{self, migrate::Migrator, postgres::types::PgInterval, PgPool};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::Value;
+use sqlx::{self, migrate::Migrator, postgres::types::PgInterval, PgPool, Postgres, Transaction};
 
 static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
 
@@ -20,14 +30,16 @@ pub async fn insert_user(
     name: &str,
     email: &str,
     api_token: &str,
+    language: &str,
     data: &AppData,
 ) -> Result<User, sqlx::Error> {
     sqlx::query_as!(
         User,
-        "INSERT INTO users (name, email, api_token) values ($1, $2, $3) returning *",
+        "INSERT INTO users (name, email, api_token, language) values ($1, $2, $3, $4) returning *",
         name,
         email,
-        api_token
+        api_token,
+        language
     )
     .fetch_one(&data.db)
     .await
@@ -105,7 +117,7 @@ pub async fn get_prefered_addresses(
 ) -> Result<Vec<PreferedAddress>, sqlx::Error> {
     sqlx::query_as!(
         PreferedAddress,
-        "SELECT * from prefered_addresses where user_id = $1 and deleted_at is null",
+        "SELECT * from prefered_addresses where user_id = $1 and deleted_at is null ORDER BY sort_order, id",
         user_id
     )
     .fetch_all(&data.db)
@@ -126,7 +138,8 @@ pub async fn do_save_address(
             user_id,
             name,
             created_at,
-            updated_at
+            updated_at,
+            sort_order
     ) values (
             $1,
             $2,
@@ -134,7 +147,8 @@ pub async fn do_save_address(
             $4,
             $5,
             CURRENT_TIMESTAMP,
-            CURRENT_TIMESTAMP
+            CURRENT_TIMESTAMP,
+            (SELECT COALESCE(MAX(sort_order) + 1, 0) FROM prefered_addresses WHERE user_id = $4 AND deleted_at IS NULL)
         ) returning *;",
         addr.address_text,
         addr.lat,
@@ -146,6 +160,71 @@ pub async fn do_save_address(
     .await
 }
 
+pub async fn update_prefered_address(
+    user_id: i64,
+    id: i64,
+    addr: &UpdatePreferedAddress,
+    data: &AppData,
+) -> Result<Option<PreferedAddress>, sqlx::Error> {
+    sqlx::query_as!(
+        PreferedAddress,
+        "UPDATE prefered_addresses SET address_text = $3, lat = $4, lng = $5, name = $6, updated_at = CURRENT_TIMESTAMP
+        WHERE user_id = $1 AND id = $2 AND deleted_at IS NULL RETURNING *",
+        user_id,
+        id,
+        addr.address_text,
+        addr.lat,
+        addr.lng,
+        addr.name
+    )
+    .fetch_optional(&data.db)
+    .await
+}
+
+/// Reorder the user's addresses as listed in `ids`, which must name each of them once.
+/// `None` when it does not.
+pub async fn reorder_prefered_addresses(
+    user_id: i64,
+    ids: &[i64],
+    data: &AppData,
+) -> Result<Option<Vec<PreferedAddress>>, sqlx::Error> {
+    let mut tx = data.db.begin().await?;
+
+    let mut current: Vec<i64> = sqlx::query_scalar!(
+        "SELECT id FROM prefered_addresses WHERE user_id = $1 AND deleted_at IS NULL FOR UPDATE",
+        user_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut requested = ids.to_vec();
+    current.sort_unstable();
+    requested.sort_unstable();
+    if current != requested {
+        return Ok(None);
+    }
+
+    sqlx::query!(
+        "UPDATE prefered_addresses p SET sort_order = (o.position - 1)::integer
+        FROM unnest($2::bigint[]) WITH ORDINALITY AS o(id, position)
+        WHERE p.id = o.id AND p.user_id = $1",
+        user_id,
+        ids
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let addresses = sqlx::query_as!(
+        PreferedAddress,
+        "SELECT * from prefered_addresses where user_id = $1 and deleted_at is null ORDER BY sort_order, id",
+        user_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(Some(addresses))
+}
+
 pub async fn do_delete_prefered_address(
     user_id: i64,
     id: i64,
@@ -153,10 +232,1219 @@ pub async fn do_delete_prefered_address(
 ) -> Result<Option<PreferedAddress>, sqlx::Error> {
     sqlx::query_as!(
         PreferedAddress,
-        "DELETE from prefered_addresses WHERE user_id = $1 AND id = $2 RETURNING *",
+        "UPDATE prefered_addresses SET deleted_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND id = $2 AND deleted_at IS NULL RETURNING *",
+        user_id,
+        id,
+    )
+    .fetch_optional(&data.db)
+    .await
+}
+
+pub async fn get_trashed_prefered_addresses(
+    user_id: i64,
+    data: &AppData,
+) -> Result<Vec<PreferedAddress>, sqlx::Error> {
+    sqlx::query_as!(
+        PreferedAddress,
+        "SELECT * from prefered_addresses WHERE user_id = $1 AND deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+        user_id
+    )
+    .fetch_all(&data.db)
+    .await
+}
+
+pub async fn do_restore_prefered_address(
+    user_id: i64,
+    id: i64,
+    data: &AppData,
+) -> Result<Option<PreferedAddress>, sqlx::Error> {
+    sqlx::query_as!(
+        PreferedAddress,
+        "UPDATE prefered_addresses SET deleted_at = NULL WHERE user_id = $1 AND id = $2 AND deleted_at IS NOT NULL RETURNING *",
+        user_id,
+        id
+    )
+    .fetch_optional(&data.db)
+    .await
+}
+
+pub async fn get_saved_route(
+    uuid: &str,
+    user_id: i64,
+    data: &AppData,
+) -> Result<SavedRoute, sqlx::Error> {
+    sqlx::query_as!(
+        SavedRouteRow,
+        "SELECT * FROM saved_routes WHERE uuid = $1 AND user_id = $2 AND deleted_at IS NULL",
+        uuid,
+        user_id
+    )
+    .fetch_one(&data.db)
+    .await
+    .and_then(SavedRoute::try_from)
+}
+
+pub async fn insert_saved_route(
+    user_id: i64,
+    name: &str,
+    route: &Value,
+    data: &AppData,
+) -> Result<SavedRoute, sqlx::Error> {
+    let mut tx = data.db.begin().await?;
+
+    let row = sqlx::query_as!(
+        SavedRouteRow,
+        "INSERT INTO saved_routes (user_id, name, route, created_at, updated_at) values ($1, $2, $3, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP) returning *",
+        user_id,
+        name,
+        route
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    insert_route_revision(&row, &mut tx).await?;
+    tx.commit().await?;
+
+    SavedRoute::try_from(row)
+}
+
+/// Overwrite a route, keeping the new version in its revision history.
+/// With `expected_revisions`, the update only happens if the current revision is one of them,
+/// otherwise `RowNotFound` is returned.
+pub async fn update_saved_route(
+    uuid: &str,
+    user_id: i64,
+    name: &str,
+    route: &Value,
+    expected_revisions: Option<&[i32]>,
+    data: &AppData,
+) -> Result<SavedRoute, sqlx::Error> {
+    let mut tx = data.db.begin().await?;
+
+    let row = sqlx::query_as!(
+        SavedRouteRow,
+        "UPDATE saved_routes SET name = $1, route = $2, updated_at = CURRENT_TIMESTAMP, revision = revision + 1 WHERE uuid = $3 AND user_id = $4 AND deleted_at IS NULL AND ($5::int[] IS NULL OR revision = ANY($5)) RETURNING *",
+        name,
+        route,
+        uuid,
+        user_id,
+        expected_revisions
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    insert_route_revision(&row, &mut tx).await?;
+    tx.commit().await?;
+
+    SavedRoute::try_from(row)
+}
+
+async fn insert_route_revision(
+    row: &SavedRouteRow,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO saved_route_revisions (route_id, revision, name, route, created_at) values ($1, $2, $3, $4, $5)",
+        row.id,
+        row.revision,
+        row.name,
+        row.route,
+        row.updated_at
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn list_route_revisions(
+    route_id: i64,
+    data: &AppData,
+) -> Result<Vec<RouteRevision>, sqlx::Error> {
+    sqlx::query_as!(
+        RouteRevisionRow,
+        "SELECT revision, name, route, created_at FROM saved_route_revisions WHERE route_id = $1 ORDER BY revision DESC",
+        route_id
+    )
+    .fetch_all(&data.db)
+    .await
+    .and_then(|rows| rows.into_iter().map(RouteRevision::try_from).collect())
+}
+
+pub async fn get_route_revision(
+    route_id: i64,
+    revision: i32,
+    data: &AppData,
+) -> Result<RouteRevision, sqlx::Error> {
+    sqlx::query_as!(
+        RouteRevisionRow,
+        "SELECT revision, name, route, created_at FROM saved_route_revisions WHERE route_id = $1 AND revision = $2",
+        route_id,
+        revision
+    )
+    .fetch_one(&data.db)
+    .await
+    .and_then(RouteRevision::try_from)
+}
+
+pub async fn insert_route_share(
+    route_id: i64,
+    expires_at: Option<DateTime<Utc>>,
+    data: &AppData,
+) -> Result<RouteShare, sqlx::Error> {
+    sqlx::query_as!(
+        RouteShare,
+        "INSERT INTO route_share_tokens (route_id, token, expires_at) values ($1, $2, $3) returning *",
+        route_id,
+        generate_random_string(32),
+        expires_at
+    )
+    .fetch_one(&data.db)
+    .await
+}
+
+pub async fn list_route_shares(
+    route_id: i64,
+    data: &AppData,
+) -> Result<Vec<RouteShare>, sqlx::Error> {
+    sqlx::query_as!(
+        RouteShare,
+        "SELECT * FROM route_share_tokens WHERE route_id = $1 ORDER BY created_at DESC",
+        route_id
+    )
+    .fetch_all(&data.db)
+    .await
+}
+
+pub async fn revoke_route_share(
+    route_id: i64,
+    token: &str,
+    data: &AppData,
+) -> Result<Option<RouteShare>, sqlx::Error> {
+    sqlx::query_as!(
+        RouteShare,
+        "UPDATE route_share_tokens SET revoked_at = COALESCE(revoked_at, CURRENT_TIMESTAMP) WHERE route_id = $1 AND token = $2 RETURNING *",
+        route_id,
+        token
+    )
+    .fetch_optional(&data.db)
+    .await
+}
+
+/// Resolve a share token to its route, only while the token is neither revoked nor expired
+pub async fn get_shared_route(
+    token: &str,
+    data: &AppData,
+) -> Result<(RouteShare, SavedRoute), sqlx::Error> {
+    let share = sqlx::query_as!(
+        RouteShare,
+        "SELECT * FROM route_share_tokens WHERE token = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)",
+        token
+    )
+    .fetch_one(&data.db)
+    .await?;
+
+    let route = sqlx::query_as!(
+        SavedRouteRow,
+        "SELECT * FROM saved_routes WHERE id = $1 AND deleted_at IS NULL",
+        share.route_id
+    )
+    .fetch_one(&data.db)
+    .await
+    .and_then(SavedRoute::try_from)?;
+
+    Ok((share, route))
+}
+
+pub async fn trash_saved_route(
+    uuid: &str,
+    user_id: i64,
+    data: &AppData,
+) -> Result<Option<SavedRoute>, sqlx::Error> {
+    sqlx::query_as!(
+        SavedRouteRow,
+        "UPDATE saved_routes SET deleted_at = CURRENT_TIMESTAMP WHERE uuid = $1 AND user_id = $2 AND deleted_at IS NULL RETURNING *",
+        uuid,
+        user_id
+    )
+    .fetch_optional(&data.db)
+    .await?
+    .map(SavedRoute::try_from)
+    .transpose()
+}
+
+pub async fn get_trashed_routes(
+    user_id: i64,
+    data: &AppData,
+) -> Result<Vec<SavedRoute>, sqlx::Error> {
+    sqlx::query_as!(
+        SavedRouteRow,
+        "SELECT * FROM saved_routes WHERE user_id = $1 AND deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+        user_id
+    )
+    .fetch_all(&data.db)
+    .await
+    .map(decode_saved_routes)
+}
+
+pub async fn restore_saved_route(
+    uuid: &str,
+    user_id: i64,
+    data: &AppData,
+) -> Result<Option<SavedRoute>, sqlx::Error> {
+    sqlx::query_as!(
+        SavedRouteRow,
+        "UPDATE saved_routes SET deleted_at = NULL WHERE uuid = $1 AND user_id = $2 AND deleted_at IS NOT NULL RETURNING *",
+        uuid,
+        user_id
+    )
+    .fetch_optional(&data.db)
+    .await?
+    .map(SavedRoute::try_from)
+    .transpose()
+}
+
+/// Permanently delete routes and addresses trashed more than `retention_days` ago.
+/// Returns the number of purged routes and addresses.
+pub async fn purge_trash(retention_days: i32, pool: &PgPool) -> Result<(u64, u64), sqlx::Error> {
+    let routes = sqlx::query!(
+        "DELETE FROM saved_routes WHERE deleted_at < CURRENT_TIMESTAMP - make_interval(days => $1)",
+        retention_days
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    let addresses = sqlx::query!(
+        "DELETE FROM prefered_addresses WHERE deleted_at < CURRENT_TIMESTAMP - make_interval(days => $1)",
+        retention_days
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok((routes, addresses))
+}
+
+/// One page of a user's routes matching `query`, plus one extra row telling whether more follow
+pub async fn search_saved_routes(
+    user_id: i64,
+    query: &RouteSearchQuery,
+    cursor: Option<RouteCursor>,
+    data: &AppData,
+) -> Result<Vec<SavedRouteRow>, sqlx::Error> {
+    let tag_names = query.tag_names();
+
+    sqlx::query_as!(
+        SavedRouteRow,
+        "SELECT * FROM saved_routes
+        WHERE user_id = $1 AND deleted_at IS NULL
+        AND ($2::text IS NULL OR saved_route_search_vector(name, route) @@ to_tsquery('simple', $2))
+        AND ($3::text IS NULL OR route ->> 'transportMode' = $3)
+        AND ($4::float8 IS NULL OR (route #>> '{apiResponse,features,0,properties,summary,distance}')::float8 >= $4)
+        AND ($5::float8 IS NULL OR (route #>> '{apiResponse,features,0,properties,summary,distance}')::float8 <= $5)
+        AND ($6::timestamptz IS NULL OR created_at >= $6)
+        AND ($7::timestamptz IS NULL OR created_at < $7)
+        AND ($8::bigint IS NULL OR folder_id = $8)
+        AND ($9::text[] IS NULL OR id IN (
+            SELECT st.route_id FROM saved_route_tags st JOIN route_tags t ON t.id = st.tag_id
+            WHERE t.user_id = $1 AND t.name = ANY($9)
+            GROUP BY st.route_id HAVING count(*) = cardinality($9)
+        ))
+        AND ($10::timestamptz IS NULL OR (updated_at, id) < ($10, $11))
+        ORDER BY updated_at DESC, id DESC
+        LIMIT $12",
+        user_id,
+        query.ts_query(),
+        query.profile.map(|p| p.as_str()),
+        query.min_distance,
+        query.max_distance,
+        query.created_after,
+        query.created_before,
+        query.folder_id,
+        tag_names.as_deref(),
+        cursor.map(|c| c.updated_at),
+        cursor.map(|c| c.id),
+        query.limit() + 1
+    )
+    .fetch_all(&data.db)
+    .await
+}
+
+/// `(route_id, tag name)` pairs for the given routes
+pub async fn get_route_tag_names(
+    route_ids: &[i64],
+    data: &AppData,
+) -> Result<Vec<(i64, String)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT st.route_id, t.name FROM saved_route_tags st JOIN route_tags t ON t.id = st.tag_id
+        WHERE st.route_id = ANY($1) ORDER BY t.name",
+        route_ids
+    )
+    .fetch_all(&data.db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.route_id, row.name))
+        .collect())
+}
+
+/// Replace the tags of a route, creating the user's missing tags
+pub async fn set_route_tags(
+    route_id: i64,
+    user_id: i64,
+    names: &[String],
+    data: &AppData,
+) -> Result<(), sqlx::Error> {
+    let mut tx = data.db.begin().await?;
+
+    sqlx::query!(
+        "INSERT INTO route_tags (user_id, name) SELECT $1, unnest($2::text[]) ON CONFLICT (user_id, name) DO NOTHING",
+        user_id,
+        names
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!("DELETE FROM saved_route_tags WHERE route_id = $1", route_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query!(
+        "INSERT INTO saved_route_tags (route_id, tag_id) SELECT $1, id FROM route_tags WHERE user_id = $2 AND name = ANY($3)",
+        route_id,
+        user_id,
+        names
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+pub async fn get_route_tags(
+    user_id: i64,
+    data: &AppData,
+) -> Result<Vec<RouteTagUsage>, sqlx::Error> {
+    sqlx::query_as!(
+        RouteTagUsage,
+        r#"SELECT t.id, t.name, count(r.id) AS "routes!"
+        FROM route_tags t
+        LEFT JOIN saved_route_tags st ON st.tag_id = t.id
+        LEFT JOIN saved_routes r ON r.id = st.route_id AND r.deleted_at IS NULL
+        WHERE t.user_id = $1
+        GROUP BY t.id, t.name
+        ORDER BY t.name"#,
+        user_id
+    )
+    .fetch_all(&data.db)
+    .await
+}
+
+pub async fn delete_route_tag(
+    user_id: i64,
+    id: i64,
+    data: &AppData,
+) -> Result<Option<RouteTag>, sqlx::Error> {
+    sqlx::query_as!(
+        RouteTag,
+        "DELETE FROM route_tags WHERE user_id = $1 AND id = $2 RETURNING *",
+        user_id,
+        id
+    )
+    .fetch_optional(&data.db)
+    .await
+}
+
+pub async fn get_route_folders(
+    user_id: i64,
+    data: &AppData,
+) -> Result<Vec<RouteFolder>, sqlx::Error> {
+    sqlx::query_as!(
+        RouteFolder,
+        "SELECT * FROM route_folders WHERE user_id = $1 ORDER BY name",
+        user_id
+    )
+    .fetch_all(&data.db)
+    .await
+}
+
+pub async fn insert_route_folder(
+    user_id: i64,
+    name: &str,
+    data: &AppData,
+) -> Result<RouteFolder, sqlx::Error> {
+    sqlx::query_as!(
+        RouteFolder,
+        "INSERT INTO route_folders (user_id, name) values ($1, $2) returning *",
+        user_id,
+        name
+    )
+    .fetch_one(&data.db)
+    .await
+}
+
+pub async fn rename_route_folder(
+    user_id: i64,
+    id: i64,
+    name: &str,
+    data: &AppData,
+) -> Result<Option<RouteFolder>, sqlx::Error> {
+    sqlx::query_as!(
+        RouteFolder,
+        "UPDATE route_folders SET name = $3 WHERE user_id = $1 AND id = $2 RETURNING *",
         user_id,
         id,
+        name
+    )
+    .fetch_optional(&data.db)
+    .await
+}
+
+/// Routes of a deleted folder are kept, without folder
+pub async fn delete_route_folder(
+    user_id: i64,
+    id: i64,
+    data: &AppData,
+) -> Result<Option<RouteFolder>, sqlx::Error> {
+    sqlx::query_as!(
+        RouteFolder,
+        "DELETE FROM route_folders WHERE user_id = $1 AND id = $2 RETURNING *",
+        user_id,
+        id
     )
     .fetch_optional(&data.db)
     .await
 }
+
+/// Move a route to one of the user's folders, `None` if the route or folder does not exist
+pub async fn set_route_folder(
+    uuid: &str,
+    user_id: i64,
+    folder_id: Option<i64>,
+    data: &AppData,
+) -> Result<Option<SavedRoute>, sqlx::Error> {
+    sqlx::query_as!(
+        SavedRouteRow,
+        "UPDATE saved_routes SET folder_id = $3
+        WHERE uuid = $1 AND user_id = $2 AND deleted_at IS NULL
+        AND ($3::bigint IS NULL OR EXISTS (SELECT 1 FROM route_folders WHERE id = $3 AND user_id = $2))
+        RETURNING *",
+        uuid,
+        user_id,
+        folder_id
+    )
+    .fetch_optional(&data.db)
+    .await?
+    .map(SavedRoute::try_from)
+    .transpose()
+}
+
+pub async fn get_boat_polars(user_id: i64, data: &AppData) -> Result<Vec<BoatPolar>, sqlx::Error> {
+    sqlx::query_as!(
+        BoatPolarRow,
+        "SELECT * FROM boat_polars WHERE user_id = $1 ORDER BY name",
+        user_id
+    )
+    .fetch_all(&data.db)
+    .await?
+    .into_iter()
+    .map(BoatPolar::try_from)
+    .collect()
+}
+
+pub async fn get_boat_polar(
+    user_id: i64,
+    id: i64,
+    data: &AppData,
+) -> Result<Option<BoatPolar>, sqlx::Error> {
+    sqlx::query_as!(
+        BoatPolarRow,
+        "SELECT * FROM boat_polars WHERE user_id = $1 AND id = $2",
+        user_id,
+        id
+    )
+    .fetch_optional(&data.db)
+    .await?
+    .map(BoatPolar::try_from)
+    .transpose()
+}
+
+pub async fn insert_boat_polar(
+    user_id: i64,
+    name: &str,
+    polar: &Value,
+    data: &AppData,
+) -> Result<BoatPolar, sqlx::Error> {
+    sqlx::query_as!(
+        BoatPolarRow,
+        "INSERT INTO boat_polars (user_id, name, polar) values ($1, $2, $3) returning *",
+        user_id,
+        name,
+        polar
+    )
+    .fetch_one(&data.db)
+    .await
+    .and_then(BoatPolar::try_from)
+}
+
+pub async fn delete_boat_polar(
+    user_id: i64,
+    id: i64,
+    data: &AppData,
+) -> Result<Option<BoatPolar>, sqlx::Error> {
+    sqlx::query_as!(
+        BoatPolarRow,
+        "DELETE FROM boat_polars WHERE user_id = $1 AND id = $2 RETURNING *",
+        user_id,
+        id
+    )
+    .fetch_optional(&data.db)
+    .await?
+    .map(BoatPolar::try_from)
+    .transpose()
+}
+
+/// Cached geocoding answer, ignored once older than `max_age_days`
+pub async fn get_geocoding_cache(
+    provider: &str,
+    kind: &str,
+    query_key: &str,
+    max_age_days: i32,
+    data: &AppData,
+) -> Result<Option<Value>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT results FROM geocoding_cache
+        WHERE provider = $1 AND kind = $2 AND query_key = $3
+        AND created_at > CURRENT_TIMESTAMP - make_interval(days => $4)",
+        provider,
+        kind,
+        query_key,
+        max_age_days
+    )
+    .fetch_optional(&data.db)
+    .await
+}
+
+/// Delete the entries `get_geocoding_cache` no longer returns, returns how many were deleted
+pub async fn purge_geocoding_cache(max_age_days: i32, pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let deleted = sqlx::query!(
+        "DELETE FROM geocoding_cache WHERE created_at <= CURRENT_TIMESTAMP - make_interval(days => $1)",
+        max_age_days
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Ok(deleted)
+}
+
+pub async fn store_geocoding_cache(
+    provider: &str,
+    kind: &str,
+    query_key: &str,
+    results: &Value,
+    data: &AppData,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO geocoding_cache (provider, kind, query_key, results) values ($1, $2, $3, $4)
+        ON CONFLICT (provider, kind, query_key)
+        DO UPDATE SET results = EXCLUDED.results, created_at = CURRENT_TIMESTAMP",
+        provider,
+        kind,
+        query_key,
+        results
+    )
+    .execute(&data.db)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_alert_rules(user_id: i64, data: &AppData) -> Result<Vec<AlertRule>, sqlx::Error> {
+    sqlx::query_as!(
+        AlertRuleRow,
+        r#"SELECT r.id, r.user_id, r.prefered_address_id, s.uuid as "route_uuid?",
+            r.metric, r.comparison, r.threshold, r.within_hours, r.name, r.enabled, r.created_at, r.updated_at
+        FROM alert_rules r
+        LEFT JOIN saved_routes s ON s.id = r.saved_route_id
+        WHERE r.user_id = $1
+        ORDER BY r.id"#,
+        user_id
+    )
+    .fetch_all(&data.db)
+    .await?
+    .into_iter()
+    .map(AlertRule::try_from)
+    .collect()
+}
+
+pub async fn get_alert_rule(
+    user_id: i64,
+    id: i64,
+    data: &AppData,
+) -> Result<Option<AlertRule>, sqlx::Error> {
+    sqlx::query_as!(
+        AlertRuleRow,
+        r#"SELECT r.id, r.user_id, r.prefered_address_id, s.uuid as "route_uuid?",
+            r.metric, r.comparison, r.threshold, r.within_hours, r.name, r.enabled, r.created_at, r.updated_at
+        FROM alert_rules r
+        LEFT JOIN saved_routes s ON s.id = r.saved_route_id
+        WHERE r.user_id = $1 AND r.id = $2"#,
+        user_id,
+        id
+    )
+    .fetch_optional(&data.db)
+    .await?
+    .map(AlertRule::try_from)
+    .transpose()
+}
+
+/// `None` when the address or route is not one of the user's (or is in the trash)
+pub async fn insert_alert_rule(
+    user_id: i64,
+    rule: &NewAlertRule,
+    data: &AppData,
+) -> Result<Option<AlertRule>, sqlx::Error> {
+    let condition = &rule.condition;
+    let id = sqlx::query_scalar!(
+        "INSERT INTO alert_rules
+            (user_id, prefered_address_id, saved_route_id, metric, comparison, threshold, within_hours, name, enabled)
+        SELECT $1, a.id, s.id, $4, $5, $6, $7, $8, $9
+        FROM (SELECT 1) target
+        LEFT JOIN prefered_addresses a ON a.id = $2 AND a.user_id = $1 AND a.deleted_at IS NULL
+        LEFT JOIN saved_routes s ON s.uuid = $3 AND s.user_id = $1 AND s.deleted_at IS NULL
+        WHERE (a.id IS NULL) <> (s.id IS NULL)
+        RETURNING id",
+        user_id,
+        rule.prefered_address_id,
+        rule.route_uuid,
+        condition.metric.as_str(),
+        condition.comparison.as_str(),
+        condition.threshold,
+        condition.within_hours,
+        condition.name,
+        condition.enabled
+    )
+    .fetch_optional(&data.db)
+    .await?;
+
+    match id {
+        Some(id) => get_alert_rule(user_id, id, data).await,
+        None => Ok(None),
+    }
+}
+
+/// A changed condition starts over, the open event of the rule is resolved
+pub async fn update_alert_rule(
+    user_id: i64,
+    id: i64,
+    condition: &AlertCondition,
+    data: &AppData,
+) -> Result<Option<AlertRule>, sqlx::Error> {
+    let mut tx = data.db.begin().await?;
+
+    let updated = sqlx::query_scalar!(
+        "UPDATE alert_rules SET metric = $3, comparison = $4, threshold = $5, within_hours = $6,
+            name = $7, enabled = $8, updated_at = CURRENT_TIMESTAMP
+        WHERE user_id = $1 AND id = $2
+        RETURNING id",
+        user_id,
+        id,
+        condition.metric.as_str(),
+        condition.comparison.as_str(),
+        condition.threshold,
+        condition.within_hours,
+        condition.name,
+        condition.enabled
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if updated.is_none() {
+        return Ok(None);
+    }
+
+    sqlx::query!(
+        "UPDATE alert_events SET resolved_at = CURRENT_TIMESTAMP WHERE rule_id = $1 AND resolved_at IS NULL",
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    get_alert_rule(user_id, id, data).await
+}
+
+pub async fn delete_alert_rule(
+    user_id: i64,
+    id: i64,
+    data: &AppData,
+) -> Result<Option<AlertRule>, sqlx::Error> {
+    let Some(rule) = get_alert_rule(user_id, id, data).await? else {
+        return Ok(None);
+    };
+
+    sqlx::query!(
+        "DELETE FROM alert_rules WHERE user_id = $1 AND id = $2",
+        user_id,
+        id
+    )
+    .execute(&data.db)
+    .await?;
+
+    Ok(Some(rule))
+}
+
+/// Latest events of the user's rules, newest first
+pub async fn get_alert_events(
+    user_id: i64,
+    open_only: bool,
+    limit: i64,
+    data: &AppData,
+) -> Result<Vec<AlertEvent>, sqlx::Error> {
+    sqlx::query_as!(
+        AlertEvent,
+        "SELECT * FROM alert_events
+        WHERE user_id = $1 AND ($2 = false OR resolved_at IS NULL)
+        ORDER BY fired_at DESC, id DESC
+        LIMIT $3",
+        user_id,
+        open_only,
+        limit
+    )
+    .fetch_all(&data.db)
+    .await
+}
+
+pub async fn get_enabled_alert_rules(pool: &PgPool) -> Result<Vec<AlertRule>, sqlx::Error> {
+    sqlx::query_as!(
+        AlertRuleRow,
+        r#"SELECT r.id, r.user_id, r.prefered_address_id, s.uuid as "route_uuid?",
+            r.metric, r.comparison, r.threshold, r.within_hours, r.name, r.enabled, r.created_at, r.updated_at
+        FROM alert_rules r
+        LEFT JOIN saved_routes s ON s.id = r.saved_route_id
+        WHERE r.enabled
+        ORDER BY r.id"#
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(AlertRule::try_from)
+    .collect()
+}
+
+/// Positions of the enabled rules, left empty when the target is in the trash
+pub async fn get_alert_targets(pool: &PgPool) -> Result<Vec<AlertTargetRow>, sqlx::Error> {
+    sqlx::query_as!(
+        AlertTargetRow,
+        r#"SELECT r.id as rule_id, COALESCE(a.name, s.name) as "name?",
+            a.lat as "lat?", a.lng as "lng?", s.route as "route?"
+        FROM alert_rules r
+        LEFT JOIN prefered_addresses a ON a.id = r.prefered_address_id AND a.deleted_at IS NULL
+        LEFT JOIN saved_routes s ON s.id = r.saved_route_id AND s.deleted_at IS NULL
+        WHERE r.enabled"#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Record that the rule's condition holds, unless an event of the rule is already open.
+/// A new event is queued for delivery in the same transaction.
+pub async fn open_alert_event(
+    rule: &AlertRule,
+    data_time: DateTime<Utc>,
+    value: f64,
+    lat: f64,
+    lng: f64,
+    target_name: Option<&str>,
+    pool: &PgPool,
+) -> Result<Option<AlertEvent>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let event = sqlx::query_as!(
+        AlertEvent,
+        "INSERT INTO alert_events (rule_id, user_id, data_time, value, lat, lng)
+        values ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (rule_id) WHERE resolved_at IS NULL DO NOTHING
+        RETURNING *",
+        rule.id,
+        rule.user_id,
+        data_time,
+        value,
+        lat,
+        lng
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if let Some(event) = &event {
+        let payload = serde_json::json!({
+            "rule": rule,
+            "event": event,
+            "target": target_name,
+        });
+        enqueue_notification(rule.user_id, "alert", &payload, &mut tx).await?;
+    }
+
+    tx.commit().await?;
+    Ok(event)
+}
+
+/// Close the open event of a rule whose condition stopped holding
+pub async fn resolve_alert_event(rule_id: i64, pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let resolved = sqlx::query!(
+        "UPDATE alert_events SET resolved_at = CURRENT_TIMESTAMP WHERE rule_id = $1 AND resolved_at IS NULL",
+        rule_id
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(resolved > 0)
+}
+
+/// Queue a notification by email and to each enabled webhook of the user
+pub async fn enqueue_notification(
+    user_id: i64,
+    kind: &str,
+    payload: &Value,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO notification_outbox (user_id, channel, webhook_id, kind, payload)
+        SELECT $1::bigint, 'email', NULL::bigint, $2::text, $3::jsonb
+        UNION ALL
+        SELECT $1, 'webhook', id, $2, $3 FROM webhooks WHERE user_id = $1 AND enabled",
+        user_id,
+        kind,
+        payload
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Take up to `limit` due notifications of a channel for `lease_seconds`.
+/// Rows left in `sending` by a worker that died are due again once their lease ran out.
+pub async fn claim_notifications(
+    channel: NotificationChannel,
+    limit: i64,
+    lease_seconds: i32,
+    pool: &PgPool,
+) -> Result<Vec<Notification>, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationRow,
+        "UPDATE notification_outbox SET status = 'sending',
+            next_attempt_at = CURRENT_TIMESTAMP + make_interval(secs => $3)
+        WHERE id IN (
+            SELECT id FROM notification_outbox
+            WHERE channel = $1 AND status IN ('pending', 'sending') AND next_attempt_at <= CURRENT_TIMESTAMP
+            ORDER BY next_attempt_at
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING *",
+        channel.as_str(),
+        limit,
+        lease_seconds as f64
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(Notification::try_from)
+    .collect()
+}
+
+/// Log an attempt and move the notification on: delivered, retried at `retry_at`,
+/// or dead when there is no retry left
+pub async fn finish_delivery_attempt(
+    notification: &Notification,
+    response_status: Option<i32>,
+    error: Option<&str>,
+    retry_at: Option<DateTime<Utc>>,
+    pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    let attempt = notification.attempts + 1;
+    let status = match (error, retry_at) {
+        (None, _) => NotificationStatus::Delivered,
+        (Some(_), Some(_)) => NotificationStatus::Pending,
+        (Some(_), None) => NotificationStatus::Dead,
+    };
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "INSERT INTO notification_deliveries (notification_id, attempt, success, response_status, error)
+        values ($1, $2, $3, $4, $5)",
+        notification.id,
+        attempt,
+        error.is_none(),
+        response_status,
+        error
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE notification_outbox SET status = $2::text, attempts = $3, last_error = $4,
+            next_attempt_at = COALESCE($5, next_attempt_at),
+            delivered_at = CASE WHEN $2::text = 'delivered' THEN CURRENT_TIMESTAMP END
+        WHERE id = $1",
+        notification.id,
+        status.as_str(),
+        attempt,
+        error,
+        retry_at
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+pub async fn get_notifications(
+    user_id: i64,
+    status: Option<NotificationStatus>,
+    limit: Option<i64>,
+    data: &AppData,
+) -> Result<Vec<Notification>, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationRow,
+        "SELECT * FROM notification_outbox
+        WHERE user_id = $1 AND ($2::text IS NULL OR status = $2)
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3",
+        user_id,
+        status.map(|s| s.as_str()),
+        notification_limit(limit)
+    )
+    .fetch_all(&data.db)
+    .await?
+    .into_iter()
+    .map(Notification::try_from)
+    .collect()
+}
+
+/// Delivery log of the user's notifications, newest first
+pub async fn get_notification_deliveries(
+    user_id: i64,
+    notification_id: Option<i64>,
+    limit: Option<i64>,
+    data: &AppData,
+) -> Result<Vec<NotificationDelivery>, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationDelivery,
+        "SELECT d.* FROM notification_deliveries d
+        JOIN notification_outbox n ON n.id = d.notification_id
+        WHERE n.user_id = $1 AND ($2::bigint IS NULL OR d.notification_id = $2)
+        ORDER BY d.created_at DESC, d.id DESC
+        LIMIT $3",
+        user_id,
+        notification_id,
+        notification_limit(limit)
+    )
+    .fetch_all(&data.db)
+    .await
+}
+
+/// Give a dead notification a fresh set of attempts
+pub async fn retry_notification(
+    user_id: i64,
+    id: i64,
+    data: &AppData,
+) -> Result<Option<Notification>, sqlx::Error> {
+    sqlx::query_as!(
+        NotificationRow,
+        "UPDATE notification_outbox SET status = 'pending', attempts = 0, next_attempt_at = CURRENT_TIMESTAMP
+        WHERE user_id = $1 AND id = $2 AND status = 'dead'
+        RETURNING *",
+        user_id,
+        id
+    )
+    .fetch_optional(&data.db)
+    .await?
+    .map(Notification::try_from)
+    .transpose()
+}
+
+/// Recipient of an email notification
+pub async fn get_user(user_id: i64, pool: &PgPool) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as!(User, "SELECT * FROM users WHERE id = $1", user_id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn update_user_language(
+    user_id: i64,
+    language: &str,
+    data: &AppData,
+) -> Result<User, sqlx::Error> {
+    sqlx::query_as!(
+        User,
+        "UPDATE users SET language = $2 WHERE id = $1 RETURNING *",
+        user_id,
+        language
+    )
+    .fetch_one(&data.db)
+    .await
+}
+
+pub async fn get_webhooks(user_id: i64, data: &AppData) -> Result<Vec<Webhook>, sqlx::Error> {
+    sqlx::query_as!(
+        Webhook,
+        "SELECT * FROM webhooks WHERE user_id = $1 ORDER BY id",
+        user_id
+    )
+    .fetch_all(&data.db)
+    .await
+}
+
+pub async fn get_webhook(id: i64, pool: &PgPool) -> Result<Option<Webhook>, sqlx::Error> {
+    sqlx::query_as!(Webhook, "SELECT * FROM webhooks WHERE id = $1", id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn insert_webhook(
+    user_id: i64,
+    url: &str,
+    secret: &str,
+    data: &AppData,
+) -> Result<Webhook, sqlx::Error> {
+    sqlx::query_as!(
+        Webhook,
+        "INSERT INTO webhooks (user_id, url, secret) values ($1, $2, $3) returning *",
+        user_id,
+        url,
+        secret
+    )
+    .fetch_one(&data.db)
+    .await
+}
+
+/// Pending deliveries to the webhook go with it
+pub async fn delete_webhook(
+    user_id: i64,
+    id: i64,
+    data: &AppData,
+) -> Result<Option<Webhook>, sqlx::Error> {
+    sqlx::query_as!(
+        Webhook,
+        "DELETE FROM webhooks WHERE user_id = $1 AND id = $2 RETURNING *",
+        user_id,
+        id
+    )
+    .fetch_optional(&data.db)
+    .await
+}
+
+pub async fn get_digest_subscription(
+    user_id: i64,
+    data: &AppData,
+) -> Result<Option<DigestSubscription>, sqlx::Error> {
+    sqlx::query_as!(
+        DigestSubscription,
+        "SELECT * FROM digest_subscriptions WHERE user_id = $1",
+        user_id
+    )
+    .fetch_optional(&data.db)
+    .await
+}
+
+pub async fn save_digest_subscription(
+    user_id: i64,
+    settings: &DigestSettings,
+    data: &AppData,
+) -> Result<DigestSubscription, sqlx::Error> {
+    sqlx::query_as!(
+        DigestSubscription,
+        "INSERT INTO digest_subscriptions (user_id, enabled, send_at, timezone, days, ai_summary)
+        values ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (user_id) DO UPDATE SET enabled = $2, send_at = $3, timezone = $4, days = $5,
+            ai_summary = $6, updated_at = CURRENT_TIMESTAMP
+        RETURNING *",
+        user_id,
+        settings.enabled,
+        settings.send_at,
+        settings.timezone,
+        settings.days,
+        settings.ai_summary
+    )
+    .fetch_one(&data.db)
+    .await
+}
+
+/// Turn the digest off, returns whether the user was subscribed
+pub async fn unsubscribe_digest(user_id: i64, data: &AppData) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query!(
+        "UPDATE digest_subscriptions SET enabled = false, updated_at = CURRENT_TIMESTAMP
+        WHERE user_id = $1 AND enabled",
+        user_id
+    )
+    .execute(&data.db)
+    .await?
+    .rows_affected();
+    Ok(updated > 0)
+}
+
+pub async fn get_enabled_digest_subscriptions(
+    pool: &PgPool,
+) -> Result<Vec<DigestSubscription>, sqlx::Error> {
+    sqlx::query_as!(
+        DigestSubscription,
+        "SELECT * FROM digest_subscriptions WHERE enabled ORDER BY user_id"
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Preferred addresses of a user that have coordinates, in the user's order
+pub async fn get_digest_addresses(
+    user_id: i64,
+    pool: &PgPool,
+) -> Result<Vec<PreferedAddress>, sqlx::Error> {
+    sqlx::query_as!(
+        PreferedAddress,
+        "SELECT * FROM prefered_addresses
+        WHERE user_id = $1 AND deleted_at IS NULL AND lat IS NOT NULL
+        ORDER BY sort_order, id",
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Mark the digest of `local_date` as sent and queue its email.
+/// Returns false when it was already queued, e.g. by another instance.
+pub async fn queue_digest(
+    user_id: i64,
+    local_date: NaiveDate,
+    payload: &Value,
+    pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let claimed = sqlx::query!(
+        "UPDATE digest_subscriptions SET last_sent_on = $2
+        WHERE user_id = $1 AND enabled AND last_sent_on IS DISTINCT FROM $2",
+        user_id,
+        local_date
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if claimed == 0 {
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        "INSERT INTO notification_outbox (user_id, channel, kind, payload)
+        values ($1, 'email', 'digest', $2)",
+        user_id,
+        payload
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
@@ -0,0 +1,662 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use crate::models::RouteProfile;
+use crate::utils::geo::{haversine_distance, EARTH_RADIUS_M};
+use crate::utils::osm_pbf::{read_pbf, OsmElement, OsmNode, OsmWay};
+
+/// Bumped whenever the serialized layout changes, old graphs must be rebuilt
+const GRAPH_VERSION: u32 = 1;
+/// Size in degrees of the cells of the nearest-node index
+const GRID_CELL_DEGREES: f64 = 0.01;
+/// Rings of cells searched around a coordinate, about 5 km
+const MAX_SNAP_RINGS: i32 = 5;
+const MAX_CAR_SPEED_KMH: u8 = 130;
+
+/// Vehicle a graph edge can be travelled with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphProfile {
+    Car,
+    Bicycle,
+}
+
+impl GraphProfile {
+    /// Only car and bicycle profiles are modelled
+    pub fn from_route_profile(profile: RouteProfile) -> Option<Self> {
+        match profile {
+            RouteProfile::DrivingCar => Some(GraphProfile::Car),
+            RouteProfile::CyclingRegular
+            | RouteProfile::CyclingRoad
+            | RouteProfile::CyclingMountain
+            | RouteProfile::CyclingElectric => Some(GraphProfile::Bicycle),
+            _ => None,
+        }
+    }
+
+    /// Upper bound of edge speeds, keeps the A* heuristic admissible
+    fn max_speed(self) -> u8 {
+        match self {
+            GraphProfile::Car => MAX_CAR_SPEED_KMH,
+            GraphProfile::Bicycle => 18,
+        }
+    }
+}
+
+/// Directed road section between two consecutive way nodes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Edge {
+    pub target: u32,
+    /// Meters
+    pub distance: f32,
+    /// km/h, 0 when the profile may not use the edge
+    pub car_speed: u8,
+    pub bike_speed: u8,
+    /// Index into the graph street names, 0 is the unnamed street
+    pub name: u32,
+}
+
+impl Edge {
+    pub fn speed(&self, profile: GraphProfile) -> u8 {
+        match profile {
+            GraphProfile::Car => self.car_speed,
+            GraphProfile::Bicycle => self.bike_speed,
+        }
+    }
+
+    /// Seconds needed to travel the edge
+    pub fn duration(&self, profile: GraphProfile) -> f64 {
+        f64::from(self.distance) / (f64::from(self.speed(profile)) / 3.6)
+    }
+}
+
+/// Sequence of edges found by [`RoadGraph::shortest_path`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphPath {
+    pub nodes: Vec<u32>,
+    pub edges: Vec<usize>,
+}
+
+/// Road network preprocessed from an OSM extract.
+/// Edges are stored by source node (`first_edge[n]..first_edge[n + 1]`).
+#[derive(Serialize, Deserialize)]
+pub struct RoadGraph {
+    version: u32,
+    lats: Vec<f64>,
+    lons: Vec<f64>,
+    first_edge: Vec<u32>,
+    edges: Vec<Edge>,
+    names: Vec<String>,
+    #[serde(skip)]
+    grid: HashMap<(i32, i32), Vec<u32>>,
+}
+
+/// Speeds of a way in km/h, per profile and direction
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct WaySpeeds {
+    car_forward: u8,
+    car_backward: u8,
+    bike_forward: u8,
+    bike_backward: u8,
+}
+
+fn default_car_speed(highway: &str) -> u8 {
+    match highway {
+        "motorway" => 110,
+        "trunk" => 90,
+        "primary" => 70,
+        "secondary" => 60,
+        "motorway_link" | "tertiary" => 50,
+        "trunk_link" | "unclassified" => 40,
+        "primary_link" | "secondary_link" => 40,
+        "residential" | "road" | "tertiary_link" => 30,
+        "service" => 15,
+        "living_street" => 10,
+        _ => 0,
+    }
+}
+
+fn default_bike_speed(highway: &str) -> u8 {
+    match highway {
+        "cycleway" => 18,
+        "residential" | "unclassified" | "tertiary" | "tertiary_link" => 16,
+        "secondary" | "secondary_link" | "road" | "service" => 15,
+        "primary" | "primary_link" => 14,
+        "track" | "path" => 12,
+        "living_street" => 10,
+        _ => 0,
+    }
+}
+
+/// `maxspeed` in km/h, `50`, `30 mph` and `FR:urban` style values
+fn parse_maxspeed(value: &str) -> Option<u8> {
+    let value = value.trim();
+    let speed = if let Some(mph) = value.strip_suffix("mph") {
+        mph.trim().parse::<f64>().ok()? * 1.609
+    } else {
+        match value {
+            "FR:urban" | "DE:urban" | "BE:urban" => 50.0,
+            "FR:rural" => 80.0,
+            "DE:rural" | "BE:rural" => 100.0,
+            "FR:motorway" => 130.0,
+            "walk" => 6.0,
+            _ => value.parse().ok()?,
+        }
+    };
+    (speed > 0.0).then(|| speed.min(f64::from(MAX_CAR_SPEED_KMH)).round() as u8)
+}
+
+fn way_speeds(tags: &HashMap<String, String>) -> Option<WaySpeeds> {
+    let highway = tags.get("highway")?.as_str();
+    let tag = |key: &str| tags.get(key).map(String::as_str);
+
+    if tag("area") == Some("yes") {
+        return None;
+    }
+
+    let denied = |value: Option<&str>| matches!(value, Some("no" | "private"));
+    let allowed = |value: Option<&str>| matches!(value, Some("yes" | "designated" | "permissive"));
+    let general_denied = denied(tag("access"));
+
+    let mut car = default_car_speed(highway);
+    if let Some(maxspeed) = tag("maxspeed").and_then(parse_maxspeed) {
+        if car > 0 {
+            car = maxspeed;
+        }
+    }
+    let car_override = tag("motor_vehicle").or(tag("motorcar"));
+    if denied(car_override) || (general_denied && !allowed(car_override)) {
+        car = 0;
+    }
+
+    let mut bike = default_bike_speed(highway);
+    let bicycle = tag("bicycle");
+    if bike == 0 && allowed(bicycle) {
+        bike = 12;
+    }
+    if denied(bicycle) || bicycle == Some("use_sidepath") || (general_denied && !allowed(bicycle)) {
+        bike = 0;
+    }
+
+    if car == 0 && bike == 0 {
+        return None;
+    }
+
+    let oneway = tag("oneway");
+    let implied_oneway = highway == "motorway" || tag("junction") == Some("roundabout");
+    let (forward, backward) = match oneway {
+        Some("yes" | "true" | "1") => (true, false),
+        Some("-1" | "reverse") => (false, true),
+        Some("no" | "false" | "0") => (true, true),
+        _ if implied_oneway => (true, false),
+        _ => (true, true),
+    };
+    let bike_both_ways = tag("oneway:bicycle") == Some("no")
+        || tag("cycleway").is_some_and(|c| c.starts_with("opposite"));
+
+    let directed = |speed: u8, allowed: bool| if allowed { speed } else { 0 };
+    Some(WaySpeeds {
+        car_forward: directed(car, forward),
+        car_backward: directed(car, backward),
+        bike_forward: directed(bike, forward || bike_both_ways),
+        bike_backward: directed(bike, backward || bike_both_ways),
+    })
+}
+
+/// Min-heap entry of the A* search
+#[derive(PartialEq)]
+struct QueueEntry {
+    estimate: f64,
+    node: u32,
+}
+
+impl Eq for QueueEntry {}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn grid_cell(lat: f64, lon: f64) -> (i32, i32) {
+    (
+        (lat / GRID_CELL_DEGREES).floor() as i32,
+        (lon / GRID_CELL_DEGREES).floor() as i32,
+    )
+}
+
+impl RoadGraph {
+    /// Build the graph out of an `.osm.pbf` extract, reading it twice (ways, then their nodes)
+    pub fn from_pbf(path: &Path) -> Result<Self> {
+        let open = || -> Result<BufReader<File>> {
+            Ok(BufReader::new(File::open(path).with_context(|| {
+                format!("Failed to open {}", path.display())
+            })?))
+        };
+
+        let mut ways = Vec::new();
+        read_pbf(open()?, false, true, |element| {
+            if let OsmElement::Way(way) = element {
+                if way.tags.contains_key("highway") {
+                    ways.push(way);
+                }
+            }
+        })?;
+
+        let mut wanted: HashMap<i64, Option<(f64, f64)>> = ways
+            .iter()
+            .flat_map(|way| way.refs.iter().map(|id| (*id, None)))
+            .collect();
+        read_pbf(open()?, true, false, |element| {
+            if let OsmElement::Node(node) = element {
+                if let Some(position) = wanted.get_mut(&node.id) {
+                    *position = Some((node.lat, node.lon));
+                }
+            }
+        })?;
+
+        let nodes = wanted
+            .into_iter()
+            .filter_map(|(id, position)| position.map(|(lat, lon)| OsmNode { id, lat, lon }))
+            .collect::<Vec<_>>();
+
+        Ok(Self::from_osm(&ways, &nodes))
+    }
+
+    /// Build the graph out of OSM ways and the nodes they reference
+    pub fn from_osm(ways: &[OsmWay], nodes: &[OsmNode]) -> Self {
+        let positions: HashMap<i64, (f64, f64)> =
+            nodes.iter().map(|n| (n.id, (n.lat, n.lon))).collect();
+
+        let mut indices: HashMap<i64, u32> = HashMap::new();
+        let mut lats = Vec::new();
+        let mut lons = Vec::new();
+        let mut names = vec![String::new()];
+        let mut name_indices: HashMap<String, u32> = HashMap::new();
+        let mut sourced_edges: Vec<(u32, Edge)> = Vec::new();
+
+        for way in ways {
+            let Some(speeds) = way_speeds(&way.tags) else {
+                continue;
+            };
+            let name = match way.tags.get("name").or(way.tags.get("ref")) {
+                Some(name) => *name_indices.entry(name.clone()).or_insert_with(|| {
+                    names.push(name.clone());
+                    (names.len() - 1) as u32
+                }),
+                None => 0,
+            };
+
+            let mut node_index = |id: i64| -> Option<u32> {
+                let (lat, lon) = *positions.get(&id)?;
+                Some(*indices.entry(id).or_insert_with(|| {
+                    lats.push(lat);
+                    lons.push(lon);
+                    (lats.len() - 1) as u32
+                }))
+            };
+
+            for pair in way.refs.windows(2) {
+                let (Some(a), Some(b)) = (node_index(pair[0]), node_index(pair[1])) else {
+                    continue;
+                };
+                let ((lat_a, lon_a), (lat_b, lon_b)) = (positions[&pair[0]], positions[&pair[1]]);
+                let distance = haversine_distance(lat_a, lon_a, lat_b, lon_b) as f32;
+
+                if speeds.car_forward > 0 || speeds.bike_forward > 0 {
+                    sourced_edges.push((
+                        a,
+                        Edge {
+                            target: b,
+                            distance,
+                            car_speed: speeds.car_forward,
+                            bike_speed: speeds.bike_forward,
+                            name,
+                        },
+                    ));
+                }
+                if speeds.car_backward > 0 || speeds.bike_backward > 0 {
+                    sourced_edges.push((
+                        b,
+                        Edge {
+                            target: a,
+                            distance,
+                            car_speed: speeds.car_backward,
+                            bike_speed: speeds.bike_backward,
+                            name,
+                        },
+                    ));
+                }
+            }
+        }
+
+        sourced_edges.sort_by_key(|(source, _)| *source);
+        let mut first_edge = vec![0u32; lats.len() + 1];
+        for (source, _) in &sourced_edges {
+            first_edge[*source as usize + 1] += 1;
+        }
+        for i in 1..first_edge.len() {
+            first_edge[i] += first_edge[i - 1];
+        }
+
+        let mut graph = RoadGraph {
+            version: GRAPH_VERSION,
+            lats,
+            lons,
+            first_edge,
+            edges: sourced_edges.into_iter().map(|(_, edge)| edge).collect(),
+            names,
+            grid: HashMap::new(),
+        };
+        graph.index();
+        graph
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        bincode::serialize_into(BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut graph: RoadGraph = bincode::deserialize_from(BufReader::new(file))
+            .with_context(|| format!("Invalid road graph {}", path.display()))?;
+        if graph.version != GRAPH_VERSION {
+            bail!(
+                "Road graph {} has version {}, expected {}; rebuild it",
+                path.display(),
+                graph.version,
+                GRAPH_VERSION
+            );
+        }
+        graph.index();
+        Ok(graph)
+    }
+
+    fn index(&mut self) {
+        self.grid.clear();
+        for node in 0..self.lats.len() {
+            self.grid
+                .entry(grid_cell(self.lats[node], self.lons[node]))
+                .or_default()
+                .push(node as u32);
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.lats.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// `(lat, lon)` of a node
+    pub fn position(&self, node: u32) -> (f64, f64) {
+        (self.lats[node as usize], self.lons[node as usize])
+    }
+
+    pub fn edge(&self, edge: usize) -> &Edge {
+        &self.edges[edge]
+    }
+
+    pub fn name(&self, edge: &Edge) -> &str {
+        self.names
+            .get(edge.name as usize)
+            .map(String::as_str)
+            .unwrap_or("")
+    }
+
+    fn edge_range(&self, node: u32) -> std::ops::Range<usize> {
+        self.first_edge[node as usize] as usize..self.first_edge[node as usize + 1] as usize
+    }
+
+    /// Closest node the profile can leave from, within a few kilometers
+    pub fn nearest_node(&self, lat: f64, lon: f64, profile: GraphProfile) -> Option<u32> {
+        let (row, col) = grid_cell(lat, lon);
+        let mut best: Option<(f64, u32)> = None;
+
+        for ring in 0..=MAX_SNAP_RINGS {
+            for dr in -ring..=ring {
+                for dc in -ring..=ring {
+                    if dr.abs() != ring && dc.abs() != ring {
+                        continue;
+                    }
+                    let Some(nodes) = self.grid.get(&(row + dr, col + dc)) else {
+                        continue;
+                    };
+                    for node in nodes {
+                        let usable = self
+                            .edge_range(*node)
+                            .any(|e| self.edges[e].speed(profile) > 0);
+                        if !usable {
+                            continue;
+                        }
+                        let (node_lat, node_lon) = self.position(*node);
+                        let distance = haversine_distance(lat, lon, node_lat, node_lon);
+                        if best.is_none_or(|(d, _)| distance < d) {
+                            best = Some((distance, *node));
+                        }
+                    }
+                }
+            }
+            // Nodes of the next ring are at least `ring` cells away, fewer meters along
+            // a parallel than along a meridian
+            let cell_width = EARTH_RADIUS_M
+                * GRID_CELL_DEGREES.to_radians()
+                * (lat.abs() + f64::from(ring + 1) * GRID_CELL_DEGREES)
+                    .min(90.0)
+                    .to_radians()
+                    .cos();
+            if best.is_some_and(|(d, _)| d <= f64::from(ring) * cell_width) {
+                break;
+            }
+        }
+
+        best.map(|(_, node)| node)
+    }
+
+    /// Fastest path with A*, using straight-line distance at the profile top speed as heuristic
+    pub fn shortest_path(&self, from: u32, to: u32, profile: GraphProfile) -> Option<GraphPath> {
+        let (to_lat, to_lon) = self.position(to);
+        let max_speed = f64::from(profile.max_speed()) / 3.6;
+        let heuristic = |node: u32| {
+            let (lat, lon) = self.position(node);
+            haversine_distance(lat, lon, to_lat, to_lon) / max_speed
+        };
+
+        let mut cost = vec![f64::INFINITY; self.node_count()];
+        let mut via_edge = vec![usize::MAX; self.node_count()];
+        let mut previous = vec![u32::MAX; self.node_count()];
+        let mut queue = BinaryHeap::new();
+
+        cost[from as usize] = 0.0;
+        queue.push(QueueEntry {
+            estimate: heuristic(from),
+            node: from,
+        });
+
+        while let Some(QueueEntry { estimate, node }) = queue.pop() {
+            if node == to {
+                let mut nodes = vec![to];
+                let mut edges = Vec::new();
+                let mut current = to;
+                while current != from {
+                    edges.push(via_edge[current as usize]);
+                    current = previous[current as usize];
+                    nodes.push(current);
+                }
+                nodes.reverse();
+                edges.reverse();
+                return Some(GraphPath { nodes, edges });
+            }
+
+            let node_cost = cost[node as usize];
+            if estimate > node_cost + heuristic(node) + 1e-9 {
+                // Stale entry, the node was reached faster since
+                continue;
+            }
+
+            for e in self.edge_range(node) {
+                let edge = &self.edges[e];
+                if edge.speed(profile) == 0 {
+                    continue;
+                }
+                let next_cost = node_cost + edge.duration(profile);
+                if next_cost < cost[edge.target as usize] {
+                    cost[edge.target as usize] = next_cost;
+                    via_edge[edge.target as usize] = e;
+                    previous[edge.target as usize] = node;
+                    queue.push(QueueEntry {
+                        estimate: next_cost + heuristic(edge.target),
+                        node: edge.target,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn way(id: i64, refs: &[i64], tags: &[(&str, &str)]) -> OsmWay {
+        OsmWay {
+            id,
+            refs: refs.to_vec(),
+            tags: tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    /// A square of ~1.1 km sides: 1 - 2 - 3 along the top, 1 - 4 - 3 along the bottom
+    fn square() -> RoadGraph {
+        let nodes = [
+            (1, 48.85, 2.35),
+            (2, 48.86, 2.35),
+            (3, 48.86, 2.365),
+            (4, 48.85, 2.365),
+        ]
+        .map(|(id, lat, lon)| OsmNode { id, lat, lon });
+        let ways = [
+            way(
+                10,
+                &[1, 2, 3],
+                &[("highway", "primary"), ("name", "Route du Nord")],
+            ),
+            way(
+                11,
+                &[1, 4, 3],
+                &[
+                    ("highway", "cycleway"),
+                    ("name", "Piste du Sud"),
+                    ("oneway", "yes"),
+                ],
+            ),
+            way(12, &[4, 3], &[("highway", "footway")]),
+        ];
+        RoadGraph::from_osm(&ways, &nodes)
+    }
+
+    #[test]
+    fn test_way_speeds() {
+        let tags = |pairs: &[(&str, &str)]| way(0, &[], pairs).tags;
+
+        let oneway = way_speeds(&tags(&[("highway", "residential"), ("oneway", "yes")])).unwrap();
+        assert_eq!((oneway.car_forward, oneway.car_backward), (30, 0));
+        assert_eq!((oneway.bike_forward, oneway.bike_backward), (16, 0));
+
+        let contraflow = way_speeds(&tags(&[
+            ("highway", "residential"),
+            ("oneway", "yes"),
+            ("oneway:bicycle", "no"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            (contraflow.bike_forward, contraflow.bike_backward),
+            (16, 16)
+        );
+
+        let limited = way_speeds(&tags(&[("highway", "primary"), ("maxspeed", "30 mph")])).unwrap();
+        assert_eq!(limited.car_forward, 48);
+
+        assert_eq!(way_speeds(&tags(&[("highway", "footway")])), None);
+        assert_eq!(
+            way_speeds(&tags(&[("highway", "service"), ("access", "private")])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_shortest_path_per_profile() {
+        let graph = square();
+        assert_eq!(graph.node_count(), 4);
+
+        let from = graph
+            .nearest_node(48.8501, 2.3501, GraphProfile::Car)
+            .unwrap();
+        let to = graph
+            .nearest_node(48.8599, 2.3649, GraphProfile::Car)
+            .unwrap();
+
+        // Cars only have the primary road
+        let car = graph.shortest_path(from, to, GraphProfile::Car).unwrap();
+        let names: Vec<&str> = car
+            .edges
+            .iter()
+            .map(|e| graph.name(graph.edge(*e)))
+            .collect();
+        assert_eq!(names, vec!["Route du Nord", "Route du Nord"]);
+
+        // Bicycles are faster on the cycleway, but only in its direction
+        let bike = graph
+            .shortest_path(from, to, GraphProfile::Bicycle)
+            .unwrap();
+        assert_eq!(graph.name(graph.edge(bike.edges[0])), "Piste du Sud");
+        let back = graph
+            .shortest_path(to, from, GraphProfile::Bicycle)
+            .unwrap();
+        assert_eq!(graph.name(graph.edge(back.edges[0])), "Route du Nord");
+    }
+
+    #[test]
+    fn test_nearest_node_beyond_first_ring() {
+        // Node 1 is in a corner of the first ring, node 3 two cells east but closer
+        let nodes = [
+            (1, 48.8699, 2.3699),
+            (2, 48.8799, 2.3799),
+            (3, 48.855, 2.3705),
+            (4, 48.84, 2.39),
+        ]
+        .map(|(id, lat, lon)| OsmNode { id, lat, lon });
+        let ways = [
+            way(20, &[1, 2], &[("highway", "residential")]),
+            way(21, &[3, 4], &[("highway", "residential")]),
+        ];
+        let graph = RoadGraph::from_osm(&ways, &nodes);
+
+        let node = graph
+            .nearest_node(48.855, 2.355, GraphProfile::Car)
+            .unwrap();
+        let (lat, lon) = graph.position(node);
+        assert!((lat - 48.855).abs() < 1e-5 && (lon - 2.3705).abs() < 1e-5);
+    }
+}
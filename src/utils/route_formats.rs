@@ -0,0 +1,912 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde_json::Value;
+
+use crate::models::{
+    Directions, DirectionsFeature, RouteDocument, RouteGeometry, RoutePoint, RouteProfile,
+    RouteProperties, RouteSegment, RouteSummary, SavedRoute, CURRENT_ROUTE_VERSION,
+    MAX_ROUTE_POINTS,
+};
+use crate::utils::geo::{haversine_distance, polyline_length};
+
+const CREATOR: &str = "PlanMyTrip";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteFileFormat {
+    Gpx,
+    Kml,
+    GeoJson,
+}
+
+impl RouteFileFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "gpx" => Some(RouteFileFormat::Gpx),
+            "kml" => Some(RouteFileFormat::Kml),
+            "geojson" | "json" => Some(RouteFileFormat::GeoJson),
+            _ => None,
+        }
+    }
+
+    /// Guess the format from the file extension, then from the content itself
+    pub fn detect(filename: Option<&str>, content: &str) -> Option<Self> {
+        let from_extension = filename
+            .and_then(|f| f.rsplit_once('.'))
+            .and_then(|(_, ext)| Self::parse(ext));
+        if from_extension.is_some() {
+            return from_extension;
+        }
+
+        let head: String = content.trim_start().chars().take(512).collect();
+        if head.starts_with('{') {
+            Some(RouteFileFormat::GeoJson)
+        } else if head.contains("<gpx") {
+            Some(RouteFileFormat::Gpx)
+        } else if head.contains("<kml") {
+            Some(RouteFileFormat::Kml)
+        } else {
+            None
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            RouteFileFormat::Gpx => "application/gpx+xml",
+            RouteFileFormat::Kml => "application/vnd.google-earth.kml+xml",
+            RouteFileFormat::GeoJson => "application/geo+json",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RouteFileFormat::Gpx => "gpx",
+            RouteFileFormat::Kml => "kml",
+            RouteFileFormat::GeoJson => "geojson",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportedPoint {
+    pub lat: f64,
+    pub lon: f64,
+    pub ele: Option<f64>,
+    pub time: Option<DateTime<Utc>>,
+    pub name: Option<String>,
+}
+
+/// Points read from an uploaded file, before they become a route document
+#[derive(Debug, Clone, Default)]
+pub struct ImportedRoute {
+    pub name: Option<String>,
+    pub waypoints: Vec<ImportedPoint>,
+    pub track: Vec<ImportedPoint>,
+}
+
+pub fn parse_route_file(format: RouteFileFormat, content: &str) -> Result<ImportedRoute> {
+    match format {
+        RouteFileFormat::Gpx => parse_gpx(content),
+        RouteFileFormat::Kml => parse_kml(content),
+        RouteFileFormat::GeoJson => parse_geojson(content),
+    }
+}
+
+impl ImportedRoute {
+    /// Turn the imported points into a route document.
+    /// Waypoints become the start/intermediate/end points and the track becomes the geometry.
+    pub fn into_document(self, profile: RouteProfile) -> Result<RouteDocument> {
+        let mut waypoints = self.waypoints;
+        let mut track = self.track;
+
+        // Long routes (e.g. a GPX <rte> with every turn) are better kept as geometry
+        if track.is_empty() && waypoints.len() > MAX_ROUTE_POINTS {
+            track = std::mem::take(&mut waypoints);
+        }
+        // With a track, the waypoints are only landmarks along it: keep some of them
+        if waypoints.len() > MAX_ROUTE_POINTS {
+            waypoints = thin_points(waypoints, MAX_ROUTE_POINTS);
+        }
+        if waypoints.is_empty() && track.len() >= 2 {
+            let mut first = track[0].clone();
+            let mut last = track[track.len() - 1].clone();
+            first.name = None;
+            last.name = None;
+            waypoints = vec![first, last];
+        }
+        if waypoints.len() < 2 {
+            anyhow::bail!("A route needs at least two points");
+        }
+
+        let to_point = |p: ImportedPoint| RoutePoint {
+            lat: p.lat,
+            lon: p.lon,
+            id: None,
+            display_name: p.name,
+        };
+
+        let end_point = waypoints.pop().map(to_point);
+        let start_point = Some(to_point(waypoints.remove(0)));
+        let directions = if track.len() >= 2 {
+            Some(track_directions(&track))
+        } else {
+            None
+        };
+
+        Ok(RouteDocument {
+            version: CURRENT_ROUTE_VERSION,
+            start_point,
+            end_point,
+            waypoints: waypoints.into_iter().map(to_point).collect(),
+            profile,
+            timestamp: Some(Utc::now()),
+            directions,
+        })
+    }
+}
+
+/// `max` of `points` (at least 2), evenly spread and keeping the first and the last
+fn thin_points(points: Vec<ImportedPoint>, max: usize) -> Vec<ImportedPoint> {
+    let last = points.len() - 1;
+    let mut kept = (0..max).map(|i| i * last / (max - 1)).peekable();
+    points
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, point)| {
+            kept.next_if_eq(&index)?;
+            Some(point)
+        })
+        .collect()
+}
+
+/// Build a single-feature directions collection out of a recorded track
+fn track_directions(track: &[ImportedPoint]) -> Directions {
+    let with_elevation = track.iter().all(|p| p.ele.is_some());
+    let coordinates: Vec<Vec<f64>> = track
+        .iter()
+        .map(|p| match (with_elevation, p.ele) {
+            (true, Some(ele)) => vec![p.lon, p.lat, ele],
+            _ => vec![p.lon, p.lat],
+        })
+        .collect();
+
+    let distance = polyline_length(&coordinates);
+    let duration = match (track[0].time, track[track.len() - 1].time) {
+        (Some(start), Some(end)) if end > start => (end - start).num_seconds() as f64,
+        _ => 0.0,
+    };
+
+    let (ascent, descent) = if with_elevation {
+        let (up, down) = coordinates.windows(2).fold((0.0, 0.0), |(up, down), w| {
+            let diff = w[1][2] - w[0][2];
+            if diff > 0.0 {
+                (up + diff, down)
+            } else {
+                (up, down - diff)
+            }
+        });
+        (Some(up), Some(down))
+    } else {
+        (None, None)
+    };
+
+    let last_index = coordinates.len() - 1;
+
+    Directions {
+        kind: "FeatureCollection".to_string(),
+        features: vec![DirectionsFeature {
+            kind: "Feature".to_string(),
+            bbox: None,
+            geometry: RouteGeometry {
+                kind: "LineString".to_string(),
+                coordinates,
+            },
+            properties: RouteProperties {
+                ascent,
+                descent,
+                extras: Default::default(),
+                segments: vec![RouteSegment {
+                    ascent,
+                    descent,
+                    distance,
+                    duration,
+                    steps: Vec::new(),
+                }],
+                summary: RouteSummary { distance, duration },
+                warnings: Vec::new(),
+                way_points: vec![0, last_index],
+            },
+        }],
+        bbox: None,
+        metadata: None,
+    }
+}
+
+/*
+ * Import
+ **/
+
+fn local_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).into_owned()
+}
+
+fn float_attribute(e: &BytesStart, name: &str) -> Result<f64> {
+    let attribute = e
+        .try_get_attribute(name)?
+        .with_context(|| format!("<{}> without {} attribute", local_name(e), name))?;
+    let value = attribute.unescape_value()?;
+    value
+        .trim()
+        .parse::<f64>()
+        .with_context(|| format!("Invalid {} value: {}", name, value))
+}
+
+fn parse_time(text: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(text.trim())
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Text or CDATA content of the current element
+fn event_text(event: &Event) -> Result<Option<String>> {
+    match event {
+        Event::Text(t) => Ok(Some(t.unescape()?.into_owned())),
+        Event::CData(c) => Ok(Some(String::from_utf8_lossy(c.as_ref()).into_owned())),
+        _ => Ok(None),
+    }
+}
+
+pub fn parse_gpx(xml: &str) -> Result<ImportedRoute> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut route = ImportedRoute::default();
+    let mut path: Vec<String> = Vec::new();
+    // (is_track_point, point being read)
+    let mut current: Option<(bool, ImportedPoint)> = None;
+
+    loop {
+        let event = reader.read_event().context("Invalid GPX file")?;
+        match &event {
+            Event::Start(e) | Event::Empty(e) => {
+                let name = local_name(e);
+                if matches!(name.as_str(), "wpt" | "rtept" | "trkpt") {
+                    let point = ImportedPoint {
+                        lat: float_attribute(e, "lat")?,
+                        lon: float_attribute(e, "lon")?,
+                        ..Default::default()
+                    };
+                    current = Some((name == "trkpt", point));
+                }
+
+                if matches!(event, Event::Start(_)) {
+                    path.push(name);
+                } else if let Some((is_track, point)) = current.take() {
+                    push_gpx_point(&mut route, is_track, point);
+                }
+            }
+            Event::Text(_) | Event::CData(_) => {
+                let text = event_text(&event)?.unwrap_or_default();
+                let tag = path.last().map(String::as_str).unwrap_or("");
+                let parent = path.len().checked_sub(2).map(|i| path[i].as_str());
+
+                match (&mut current, tag) {
+                    (Some((_, point)), "name") => point.name = Some(text),
+                    (Some((_, point)), "ele") => point.ele = text.trim().parse().ok(),
+                    (Some((_, point)), "time") => point.time = parse_time(&text),
+                    (None, "name")
+                        if route.name.is_none()
+                            && matches!(parent, Some("metadata" | "trk" | "rte")) =>
+                    {
+                        route.name = Some(text)
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(_) => {
+                let closed = path.pop();
+                if matches!(closed.as_deref(), Some("wpt" | "rtept" | "trkpt")) {
+                    if let Some((is_track, point)) = current.take() {
+                        push_gpx_point(&mut route, is_track, point);
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(route)
+}
+
+fn push_gpx_point(route: &mut ImportedRoute, is_track: bool, point: ImportedPoint) {
+    if is_track {
+        route.track.push(point);
+    } else {
+        route.waypoints.push(point);
+    }
+}
+
+/// Parse a KML `coordinates` block: whitespace separated `lon,lat[,alt]` tuples
+fn parse_kml_coordinates(text: &str) -> Vec<ImportedPoint> {
+    text.split_whitespace()
+        .filter_map(|tuple| {
+            let mut values = tuple.split(',').map(|v| v.trim().parse::<f64>());
+            let lon = values.next()?.ok()?;
+            let lat = values.next()?.ok()?;
+            let ele = values.next().and_then(|v| v.ok());
+            Some(ImportedPoint {
+                lat,
+                lon,
+                ele,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct KmlPlacemark {
+    name: Option<String>,
+    points: Vec<ImportedPoint>,
+    track: Vec<ImportedPoint>,
+    when: Vec<DateTime<Utc>>,
+}
+
+pub fn parse_kml(xml: &str) -> Result<ImportedRoute> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut route = ImportedRoute::default();
+    let mut path: Vec<String> = Vec::new();
+    let mut placemark: Option<KmlPlacemark> = None;
+
+    loop {
+        let event = reader.read_event().context("Invalid KML file")?;
+        match &event {
+            Event::Start(e) => {
+                let name = local_name(e);
+                if name == "Placemark" {
+                    placemark = Some(KmlPlacemark::default());
+                }
+                path.push(name);
+            }
+            Event::Text(_) | Event::CData(_) => {
+                let text = event_text(&event)?.unwrap_or_default();
+                let tag = path.last().map(String::as_str).unwrap_or("");
+                let parent = path.len().checked_sub(2).map(|i| path[i].as_str());
+                let in_path = |name: &str| path.iter().any(|p| p == name);
+
+                match (&mut placemark, tag) {
+                    (Some(pm), "name") if parent == Some("Placemark") => pm.name = Some(text),
+                    (Some(pm), "coordinates") if in_path("Point") => {
+                        pm.points.extend(parse_kml_coordinates(&text))
+                    }
+                    (Some(pm), "coordinates") if in_path("LineString") => {
+                        pm.track.extend(parse_kml_coordinates(&text))
+                    }
+                    // gx:Track stores "lon lat alt" per <gx:coord> with matching <when>
+                    (Some(pm), "coord") => pm.track.extend(parse_kml_coordinates(
+                        &text.split_whitespace().collect::<Vec<_>>().join(","),
+                    )),
+                    (Some(pm), "when") => pm.when.extend(parse_time(&text)),
+                    (None, "name")
+                        if route.name.is_none()
+                            && matches!(parent, Some("Document" | "Folder")) =>
+                    {
+                        route.name = Some(text)
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(_) => {
+                if path.pop().as_deref() != Some("Placemark") {
+                    continue;
+                }
+                let Some(mut pm) = placemark.take() else {
+                    continue;
+                };
+
+                for point in pm.points.iter_mut() {
+                    point.name = pm.name.clone();
+                }
+                if pm.when.len() == pm.track.len() {
+                    for (point, when) in pm.track.iter_mut().zip(pm.when) {
+                        point.time = Some(when);
+                    }
+                }
+                if route.name.is_none() && !pm.track.is_empty() {
+                    route.name = pm.name;
+                }
+                route.waypoints.extend(pm.points);
+                route.track.extend(pm.track);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(route)
+}
+
+fn geojson_position(value: &Value) -> Option<ImportedPoint> {
+    let position = value.as_array()?;
+    Some(ImportedPoint {
+        lon: position.first()?.as_f64()?,
+        lat: position.get(1)?.as_f64()?,
+        ele: position.get(2).and_then(Value::as_f64),
+        ..Default::default()
+    })
+}
+
+pub fn parse_geojson(content: &str) -> Result<ImportedRoute> {
+    let value: Value = serde_json::from_str(content).context("Invalid GeoJSON file")?;
+
+    let features: Vec<Value> = match value.get("type").and_then(Value::as_str) {
+        Some("FeatureCollection") => value
+            .get("features")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default(),
+        Some("Feature") => vec![value.clone()],
+        Some(_) => vec![serde_json::json!({ "type": "Feature", "geometry": value })],
+        None => anyhow::bail!("GeoJSON object without a type"),
+    };
+
+    let mut route = ImportedRoute {
+        name: value.get("name").and_then(Value::as_str).map(String::from),
+        ..Default::default()
+    };
+
+    for feature in features {
+        let properties = feature.get("properties").cloned().unwrap_or(Value::Null);
+        let name = properties
+            .get("name")
+            .and_then(Value::as_str)
+            .map(String::from);
+        let geometry = feature.get("geometry").cloned().unwrap_or(Value::Null);
+        let coordinates = geometry.get("coordinates").cloned().unwrap_or(Value::Null);
+
+        match geometry.get("type").and_then(Value::as_str) {
+            Some("Point") => {
+                if let Some(mut point) = geojson_position(&coordinates) {
+                    point.name = name;
+                    route.waypoints.push(point);
+                }
+            }
+            Some("LineString") => {
+                let mut line: Vec<ImportedPoint> = coordinates
+                    .as_array()
+                    .map(|arr| arr.iter().filter_map(geojson_position).collect())
+                    .unwrap_or_default();
+
+                // `coordTimes` is the usual convention for per-position timestamps
+                if let Some(times) = properties.get("coordTimes").and_then(Value::as_array) {
+                    for (point, time) in line.iter_mut().zip(times) {
+                        point.time = time.as_str().and_then(parse_time);
+                    }
+                }
+                if route.name.is_none() {
+                    route.name = name;
+                }
+                route.track.append(&mut line);
+            }
+            Some("MultiLineString") => {
+                for line in coordinates.as_array().into_iter().flatten() {
+                    route.track.extend(
+                        line.as_array()
+                            .into_iter()
+                            .flatten()
+                            .filter_map(geojson_position),
+                    );
+                }
+                if route.name.is_none() {
+                    route.name = name;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(route)
+}
+
+/*
+ * Export
+ **/
+
+/// Estimated passage time at every geometry position.
+/// Each ORS step duration is spread over its positions proportionally to distance.
+pub fn position_times(document: &RouteDocument, departure: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+    let coordinates = document.coordinates();
+    let mut seconds: Vec<Option<f64>> = vec![None; coordinates.len()];
+    if coordinates.is_empty() {
+        return Vec::new();
+    }
+
+    let spread =
+        |seconds: &mut Vec<Option<f64>>, from: usize, to: usize, start: f64, duration: f64| {
+            let lengths: Vec<f64> = (from..to)
+                .map(|i| {
+                    haversine_distance(
+                        coordinates[i][1],
+                        coordinates[i][0],
+                        coordinates[i + 1][1],
+                        coordinates[i + 1][0],
+                    )
+                })
+                .collect();
+            let total: f64 = lengths.iter().sum();
+
+            seconds[from].get_or_insert(start);
+            let mut travelled = 0.0;
+            for (offset, length) in lengths.iter().enumerate() {
+                travelled += length;
+                let ratio = if total > 0.0 {
+                    travelled / total
+                } else {
+                    (offset + 1) as f64 / lengths.len() as f64
+                };
+                seconds[from + offset + 1] = Some(start + duration * ratio);
+            }
+        };
+
+    let steps: Vec<_> = document
+        .feature()
+        .into_iter()
+        .flat_map(|f| f.properties.segments.iter())
+        .flat_map(|s| s.steps.iter())
+        .collect();
+
+    if steps.is_empty() {
+        let duration = document
+            .feature()
+            .map(|f| f.properties.summary.duration)
+            .unwrap_or(0.0);
+        spread(&mut seconds, 0, coordinates.len() - 1, 0.0, duration);
+    } else {
+        let mut elapsed = 0.0;
+        for step in steps {
+            let [from, to] = step.way_points;
+            if to < coordinates.len() && from <= to {
+                spread(&mut seconds, from, to, elapsed, step.duration);
+            }
+            elapsed += step.duration;
+        }
+    }
+
+    let mut last = 0.0;
+    seconds
+        .into_iter()
+        .map(|s| {
+            last = s.unwrap_or(last);
+            departure + Duration::milliseconds((last * 1000.0).round() as i64)
+        })
+        .collect()
+}
+
+/// Geometry to export: the computed directions, or straight lines between the points
+fn export_positions(document: &RouteDocument) -> Vec<Vec<f64>> {
+    if document.coordinates().len() >= 2 {
+        document.coordinates().to_vec()
+    } else {
+        document
+            .points()
+            .iter()
+            .map(|p| vec![p.lon, p.lat])
+            .collect()
+    }
+}
+
+fn point_name(point: &RoutePoint, index: usize, count: usize) -> String {
+    point.display_name.clone().unwrap_or_else(|| {
+        if index == 0 {
+            "Start".to_string()
+        } else if index + 1 == count {
+            "End".to_string()
+        } else {
+            format!("Waypoint {}", index)
+        }
+    })
+}
+
+pub fn export_gpx(route: &SavedRoute, departure: DateTime<Utc>) -> String {
+    let document = &route.route;
+    let name = escape(route.name.as_str());
+    let mut gpx = String::new();
+
+    gpx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    gpx.push_str(&format!(
+        "<gpx version=\"1.1\" creator=\"{}\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n",
+        CREATOR
+    ));
+    gpx.push_str(&format!(
+        "  <metadata>\n    <name>{}</name>\n    <time>{}</time>\n  </metadata>\n",
+        name,
+        route.updated_at.to_rfc3339()
+    ));
+
+    let points = document.points();
+    for (i, point) in points.iter().enumerate() {
+        gpx.push_str(&format!(
+            "  <wpt lat=\"{}\" lon=\"{}\">\n    <name>{}</name>\n  </wpt>\n",
+            point.lat,
+            point.lon,
+            escape(point_name(point, i, points.len()).as_str())
+        ));
+    }
+
+    if document.coordinates().len() >= 2 {
+        let times = position_times(document, departure);
+        gpx.push_str(&format!(
+            "  <trk>\n    <name>{}</name>\n    <trkseg>\n",
+            name
+        ));
+        for (position, time) in document.coordinates().iter().zip(times) {
+            gpx.push_str(&format!(
+                "      <trkpt lat=\"{}\" lon=\"{}\">",
+                position[1], position[0]
+            ));
+            if let Some(ele) = position.get(2) {
+                gpx.push_str(&format!("<ele>{}</ele>", ele));
+            }
+            gpx.push_str(&format!("<time>{}</time></trkpt>\n", time.to_rfc3339()));
+        }
+        gpx.push_str("    </trkseg>\n  </trk>\n");
+    } else {
+        gpx.push_str(&format!("  <rte>\n    <name>{}</name>\n", name));
+        for (i, point) in points.iter().enumerate() {
+            gpx.push_str(&format!(
+                "    <rtept lat=\"{}\" lon=\"{}\"><name>{}</name></rtept>\n",
+                point.lat,
+                point.lon,
+                escape(point_name(point, i, points.len()).as_str())
+            ));
+        }
+        gpx.push_str("  </rte>\n");
+    }
+
+    gpx.push_str("</gpx>\n");
+    gpx
+}
+
+pub fn export_kml(route: &SavedRoute, departure: DateTime<Utc>) -> String {
+    let document = &route.route;
+    let name = escape(route.name.as_str());
+    let mut kml = String::new();
+
+    kml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    kml.push_str("<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n");
+    kml.push_str(&format!("  <name>{}</name>\n", name));
+    kml.push_str(&format!(
+        "  <TimeStamp><when>{}</when></TimeStamp>\n",
+        route.updated_at.to_rfc3339()
+    ));
+
+    let points = document.points();
+    for (i, point) in points.iter().enumerate() {
+        kml.push_str(&format!(
+            "  <Placemark>\n    <name>{}</name>\n    <Point><coordinates>{},{}</coordinates></Point>\n  </Placemark>\n",
+            escape(point_name(point, i, points.len()).as_str()),
+            point.lon,
+            point.lat
+        ));
+    }
+
+    let positions = export_positions(document);
+    if positions.len() >= 2 {
+        let times = position_times(document, departure);
+        let coordinates: Vec<String> = positions
+            .iter()
+            .map(|p| {
+                p.iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect();
+
+        kml.push_str(&format!("  <Placemark>\n    <name>{}</name>\n", name));
+        if let (Some(begin), Some(end)) = (times.first(), times.last()) {
+            kml.push_str(&format!(
+                "    <TimeSpan><begin>{}</begin><end>{}</end></TimeSpan>\n",
+                begin.to_rfc3339(),
+                end.to_rfc3339()
+            ));
+        }
+        kml.push_str(&format!(
+            "    <LineString>\n      <tessellate>1</tessellate>\n      <coordinates>{}</coordinates>\n    </LineString>\n  </Placemark>\n",
+            coordinates.join(" ")
+        ));
+    }
+
+    kml.push_str("</Document>\n</kml>\n");
+    kml
+}
+
+pub fn export_geojson(route: &SavedRoute, departure: DateTime<Utc>) -> Value {
+    let document = &route.route;
+    let points = document.points();
+    let mut features: Vec<Value> = points
+        .iter()
+        .enumerate()
+        .map(|(i, point)| {
+            let role = if i == 0 {
+                "start"
+            } else if i + 1 == points.len() {
+                "end"
+            } else {
+                "waypoint"
+            };
+            serde_json::json!({
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": [point.lon, point.lat] },
+                "properties": { "name": point_name(point, i, points.len()), "role": role }
+            })
+        })
+        .collect();
+
+    let positions = export_positions(document);
+    if positions.len() >= 2 {
+        let times: Vec<String> = position_times(document, departure)
+            .iter()
+            .map(|t| t.to_rfc3339())
+            .collect();
+        let summary = document.feature().map(|f| f.properties.summary);
+
+        features.push(serde_json::json!({
+            "type": "Feature",
+            "geometry": { "type": "LineString", "coordinates": positions },
+            "properties": {
+                "name": route.name,
+                "profile": document.profile,
+                "distance": summary.map(|s| s.distance),
+                "duration": summary.map(|s| s.duration),
+                "departure": times.first(),
+                "arrival": times.last(),
+                "updated_at": route.updated_at,
+                "coordTimes": times,
+            }
+        }));
+    }
+
+    serde_json::json!({
+        "type": "FeatureCollection",
+        "name": route.name,
+        "features": features,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GPX: &str = r#"<?xml version="1.0"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+  <metadata><name>Morning ride</name></metadata>
+  <wpt lat="48.85" lon="2.35"><name><![CDATA[Café]]></name></wpt>
+  <wpt lat="48.87" lon="2.37"><name>Parc</name></wpt>
+  <trk><name>Track</name><trkseg>
+    <trkpt lat="48.85" lon="2.35"><ele>35</ele><time>2026-05-01T08:00:00Z</time></trkpt>
+    <trkpt lat="48.86" lon="2.36"><ele>45</ele><time>2026-05-01T08:05:00Z</time></trkpt>
+    <trkpt lat="48.87" lon="2.37"><ele>40</ele><time>2026-05-01T08:10:00Z</time></trkpt>
+  </trkseg></trk>
+</gpx>"#;
+
+    #[test]
+    fn test_parse_gpx() {
+        let imported = parse_gpx(GPX).unwrap();
+        assert_eq!(imported.name.as_deref(), Some("Morning ride"));
+        assert_eq!(imported.waypoints.len(), 2);
+        assert_eq!(imported.waypoints[0].name.as_deref(), Some("Café"));
+        assert_eq!(imported.track.len(), 3);
+
+        let document = imported
+            .into_document(RouteProfile::CyclingRegular)
+            .unwrap();
+        document.validate().unwrap();
+        let summary = document.feature().unwrap().properties.summary;
+        assert_eq!(summary.duration, 600.0);
+        assert!(summary.distance > 2000.0 && summary.distance < 3000.0);
+        assert_eq!(document.feature().unwrap().properties.ascent, Some(10.0));
+    }
+
+    #[test]
+    fn test_too_many_waypoints_are_thinned() {
+        let point = |i: usize| ImportedPoint {
+            lat: 48.0 + i as f64 * 0.001,
+            lon: 2.0,
+            ele: None,
+            time: None,
+            name: Some(format!("P{}", i)),
+        };
+        let imported = ImportedRoute {
+            name: None,
+            waypoints: (0..120).map(point).collect(),
+            track: (0..500).map(point).collect(),
+        };
+
+        let document = imported
+            .into_document(RouteProfile::CyclingRegular)
+            .unwrap();
+        document.validate().unwrap();
+        assert_eq!(document.points().len(), MAX_ROUTE_POINTS);
+        assert_eq!(
+            document.start_point.unwrap().display_name.as_deref(),
+            Some("P0")
+        );
+        assert_eq!(
+            document.end_point.unwrap().display_name.as_deref(),
+            Some("P119")
+        );
+    }
+
+    #[test]
+    fn test_parse_kml() {
+        let kml = r#"<kml xmlns="http://www.opengis.net/kml/2.2"><Document><name>Trip</name>
+            <Placemark><name>Home</name><Point><coordinates>2.35,48.85,0</coordinates></Point></Placemark>
+            <Placemark><name>Line</name><LineString><coordinates>
+                2.35,48.85 2.36,48.86 2.37,48.87
+            </coordinates></LineString></Placemark>
+        </Document></kml>"#;
+
+        let imported = parse_kml(kml).unwrap();
+        assert_eq!(imported.name.as_deref(), Some("Trip"));
+        assert_eq!(imported.waypoints.len(), 1);
+        assert_eq!(imported.waypoints[0].name.as_deref(), Some("Home"));
+        assert_eq!(imported.track.len(), 3);
+        assert_eq!(imported.track[2].lat, 48.87);
+    }
+
+    #[test]
+    fn test_geojson_round_trip() {
+        let document = parse_gpx(GPX)
+            .unwrap()
+            .into_document(RouteProfile::CyclingRegular)
+            .unwrap();
+        let now = Utc::now();
+        let route = SavedRoute {
+            id: 1,
+            user_id: 1,
+            name: "Morning ride".to_string(),
+            route: document,
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+            uuid: "uuid".to_string(),
+            revision: 1,
+            folder_id: None,
+        };
+
+        let exported = export_geojson(&route, now);
+        let imported = parse_geojson(&exported.to_string()).unwrap();
+        assert_eq!(imported.waypoints.len(), 2);
+        assert_eq!(imported.track.len(), 3);
+        assert_eq!(imported.track[2].time, Some(now + Duration::seconds(600)));
+
+        let gpx = export_gpx(&route, now);
+        let again = parse_gpx(&gpx).unwrap();
+        assert_eq!(again.name.as_deref(), Some("Morning ride"));
+        assert_eq!(again.track.len(), 3);
+        assert_eq!(again.track[1].ele, Some(45.0));
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            RouteFileFormat::detect(Some("ride.GPX"), ""),
+            Some(RouteFileFormat::Gpx)
+        );
+        assert_eq!(
+            RouteFileFormat::detect(None, "  {\"type\": \"Feature\"}"),
+            Some(RouteFileFormat::GeoJson)
+        );
+        assert_eq!(
+            RouteFileFormat::detect(Some("export"), "<?xml?><kml>"),
+            Some(RouteFileFormat::Kml)
+        );
+        assert_eq!(RouteFileFormat::detect(None, "hello"), None);
+    }
+}
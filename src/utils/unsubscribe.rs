@@ -0,0 +1,80 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Action allowed by an unsubscribe token, signed so the token cannot be used for another one
+pub const UNSUBSCRIBE_PURPOSE: &str = "digest-unsubscribe";
+/// Unsubscribe links stop working this long after the digest was queued
+pub const UNSUBSCRIBE_TOKEN_VALIDITY_DAYS: i64 = 60;
+
+fn digest_mac(secret: &str, payload: &str) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// `<purpose>.<user_id>.<issued_at>.<signature>` token of the unsubscribe link,
+/// `issued_at` in Unix seconds
+pub fn unsubscribe_token(secret: &str, user_id: i64, issued_at: DateTime<Utc>) -> String {
+    let payload = format!(
+        "{}.{}.{}",
+        UNSUBSCRIBE_PURPOSE,
+        user_id,
+        issued_at.timestamp()
+    );
+    let signature = digest_mac(secret, &payload).finalize().into_bytes();
+    format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(signature))
+}
+
+/// User of a token made by `unsubscribe_token`, `None` when the signature does not match,
+/// the token is for another purpose or it was issued more than
+/// `UNSUBSCRIBE_TOKEN_VALIDITY_DAYS` before `now`
+pub fn verify_unsubscribe_token(secret: &str, token: &str, now: DateTime<Utc>) -> Option<i64> {
+    let (payload, signature) = token.rsplit_once('.')?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+    digest_mac(secret, payload).verify_slice(&signature).ok()?;
+
+    let mut fields = payload.split('.');
+    let (purpose, user_id, issued_at) = (fields.next()?, fields.next()?, fields.next()?);
+    if purpose != UNSUBSCRIBE_PURPOSE || fields.next().is_some() {
+        return None;
+    }
+    let issued_at = DateTime::from_timestamp(issued_at.parse().ok()?, 0)?;
+    // A little ahead is clock skew between servers
+    let age = now - issued_at;
+    if age > Duration::days(UNSUBSCRIBE_TOKEN_VALIDITY_DAYS) || age < -Duration::minutes(5) {
+        return None;
+    }
+    user_id.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsubscribe_token() {
+        let now = Utc::now();
+        let token = unsubscribe_token("secret", 42, now);
+        assert_eq!(verify_unsubscribe_token("secret", &token, now), Some(42));
+        assert_eq!(verify_unsubscribe_token("other", &token, now), None);
+
+        let forged = token.replacen(".42.", ".43.", 1);
+        assert_eq!(verify_unsubscribe_token("secret", &forged, now), None);
+        assert_eq!(verify_unsubscribe_token("secret", "42", now), None);
+
+        // Expired
+        let later = now + Duration::days(UNSUBSCRIBE_TOKEN_VALIDITY_DAYS) + Duration::hours(1);
+        assert_eq!(verify_unsubscribe_token("secret", &token, later), None);
+        let earlier = now - Duration::hours(1);
+        assert_eq!(verify_unsubscribe_token("secret", &token, earlier), None);
+
+        // Signed for another purpose
+        let payload = format!("delete-account.42.{}", now.timestamp());
+        let signature = digest_mac("secret", &payload).finalize().into_bytes();
+        let other = format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(signature));
+        assert_eq!(verify_unsubscribe_token("secret", &other, now), None);
+    }
+}
@@ -0,0 +1,279 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// Fields of the stored wind points used to rebuild wind grids: u and v, then gusts
+pub const WIND_FIELDS: [&str; 3] = ["u", "v", "gusts"];
+
+/// `value` rounded to `decimals` decimal places, for API answers
+pub fn round_to(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    (value * factor).round() / factor
+}
+
+/// Regular lat/lon grid rebuilt from the point arrays stored in Redis.
+/// Points are stored row by row (one row per latitude), which is how the
+/// OpenDAP downloader emits them.
+#[derive(Debug, Clone)]
+pub struct WeatherGrid {
+    pub lats: Vec<f64>,
+    pub lons: Vec<f64>,
+    /// One row-major array per requested field
+    pub channels: Vec<Vec<f64>>,
+}
+
+impl WeatherGrid {
+    /// Build a grid from a stored payload (`{"points": [...]}`), keeping `fields` of each point
+    pub fn from_points(data: &Value, fields: &[&str]) -> Result<Self> {
+        let points = data
+            .get("points")
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow::anyhow!("Grid data without points"))?;
+
+        let first_lat = points
+            .first()
+            .and_then(|p| p.get("lat"))
+            .and_then(Value::as_f64)
+            .ok_or_else(|| anyhow::anyhow!("Empty grid"))?;
+
+        let width = points
+            .iter()
+            .take_while(|p| p.get("lat").and_then(Value::as_f64) == Some(first_lat))
+            .count();
+        if width < 2 || points.len() % width != 0 || points.len() / width < 2 {
+            anyhow::bail!(
+                "Points do not form a regular grid ({} points)",
+                points.len()
+            );
+        }
+
+        let height = points.len() / width;
+        let value_of =
+            |p: &Value, key: &str| p.get(key).and_then(Value::as_f64).unwrap_or(f64::NAN);
+
+        let mut lats: Vec<f64> = (0..height)
+            .map(|y| value_of(&points[y * width], "lat"))
+            .collect();
+        let lons: Vec<f64> = points[..width].iter().map(|p| value_of(p, "lon")).collect();
+        let mut channels: Vec<Vec<f64>> = fields
+            .iter()
+            .map(|field| points.iter().map(|p| value_of(p, field)).collect())
+            .collect();
+
+        // Keep latitudes ascending so lookups can binary search
+        if lats[0] > lats[height - 1] {
+            lats.reverse();
+            for channel in channels.iter_mut() {
+                let rows: Vec<Vec<f64>> =
+                    channel.chunks(width).rev().map(<[f64]>::to_vec).collect();
+                *channel = rows.concat();
+            }
+        }
+
+        Ok(Self {
+            lats,
+            lons,
+            channels,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.lons.len()
+    }
+
+    /// Bilinear interpolation of every channel at (lat, lon).
+    /// Longitudes wrap around the antimeridian; latitudes are clamped to the grid.
+    pub fn sample(&self, lat: f64, lon: f64) -> Option<Vec<f64>> {
+        if !lat.is_finite() || !lon.is_finite() {
+            return None;
+        }
+
+        let (y0, y1, ty) = axis_position(&self.lats, lat);
+        let (x0, x1, tx) = self.lon_position(lon);
+        let width = self.width();
+
+        let values = self
+            .channels
+            .iter()
+            .map(|channel| {
+                let v00 = channel[y0 * width + x0];
+                let v01 = channel[y0 * width + x1];
+                let v10 = channel[y1 * width + x0];
+                let v11 = channel[y1 * width + x1];
+                let top = v00 + (v01 - v00) * tx;
+                let bottom = v10 + (v11 - v10) * tx;
+                top + (bottom - top) * ty
+            })
+            .collect();
+
+        Some(values)
+    }
+
+    fn lon_position(&self, lon: f64) -> (usize, usize, f64) {
+        let first = self.lons[0];
+        let last = self.lons[self.lons.len() - 1];
+        let step = self.lons[1] - self.lons[0];
+        let global = (last - first + step - 360.0).abs() < 1e-6;
+
+        let mut lon = lon;
+        if global {
+            lon = first + (lon - first).rem_euclid(360.0);
+            if lon > last {
+                // Between the last column and the first one, across the antimeridian
+                let t = (lon - last) / step;
+                return (self.lons.len() - 1, 0, t);
+            }
+        }
+
+        axis_position(&self.lons, lon)
+    }
+}
+
+/// Successive grids of the same fields, one per forecast time
+#[derive(Debug, Clone)]
+pub struct WeatherTimeline {
+    frames: Vec<(DateTime<Utc>, WeatherGrid)>,
+}
+
+impl WeatherTimeline {
+    /// `None` without any frame
+    pub fn new(mut frames: Vec<(DateTime<Utc>, WeatherGrid)>) -> Option<Self> {
+        if frames.is_empty() {
+            return None;
+        }
+        frames.sort_by_key(|(time, _)| *time);
+        Some(Self { frames })
+    }
+
+    pub fn frames(&self) -> &[(DateTime<Utc>, WeatherGrid)] {
+        &self.frames
+    }
+
+    pub fn times(&self) -> Vec<DateTime<Utc>> {
+        self.frames.iter().map(|(time, _)| *time).collect()
+    }
+
+    /// Whether `time` falls between the first and last frames
+    pub fn covers(&self, time: DateTime<Utc>) -> bool {
+        self.frames[0].0 <= time && time <= self.frames[self.frames.len() - 1].0
+    }
+
+    /// Sample at (lat, lon), interpolated linearly between the frames around `time`.
+    /// Before the first or after the last frame, that frame is used as is.
+    pub fn sample(&self, lat: f64, lon: f64, time: DateTime<Utc>) -> Option<Vec<f64>> {
+        let after = self.frames.partition_point(|(t, _)| *t < time);
+        if after == 0 {
+            return self.frames[0].1.sample(lat, lon);
+        }
+        if after == self.frames.len() {
+            return self.frames[after - 1].1.sample(lat, lon);
+        }
+
+        let (t0, grid0) = &self.frames[after - 1];
+        let (t1, grid1) = &self.frames[after];
+        let span = (*t1 - *t0).num_milliseconds() as f64;
+        let ratio = if span > 0.0 {
+            (time - *t0).num_milliseconds() as f64 / span
+        } else {
+            0.0
+        };
+
+        let before = grid0.sample(lat, lon)?;
+        let after = grid1.sample(lat, lon)?;
+        Some(
+            before
+                .iter()
+                .zip(after)
+                .map(|(a, b)| a + (b - a) * ratio)
+                .collect(),
+        )
+    }
+}
+
+/// Indices around `value` in an ascending axis and the fraction between them
+fn axis_position(axis: &[f64], value: f64) -> (usize, usize, f64) {
+    let last = axis.len() - 1;
+    if value <= axis[0] {
+        return (0, 0, 0.0);
+    }
+    if value >= axis[last] {
+        return (last, last, 0.0);
+    }
+
+    let upper = axis.partition_point(|v| *v <= value).min(last);
+    let lower = upper - 1;
+    let span = axis[upper] - axis[lower];
+    let t = if span > 0.0 {
+        (value - axis[lower]) / span
+    } else {
+        0.0
+    };
+    (lower, upper, t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn grid() -> WeatherGrid {
+        let mut points = Vec::new();
+        for lat in [-1.0, 0.0, 1.0] {
+            for lon in [-180.0, -90.0, 0.0, 90.0] {
+                points.push(json!({ "lat": lat, "lon": lon, "u": lon, "v": lat * 10.0 }));
+            }
+        }
+        WeatherGrid::from_points(&json!({ "points": points }), &["u", "v"]).unwrap()
+    }
+
+    #[test]
+    fn test_timeline_interpolates_in_time() {
+        let start = DateTime::from_timestamp(1_760_000_000, 0).unwrap();
+        let mut later = grid();
+        later.channels[0].iter_mut().for_each(|u| *u += 10.0);
+        let timeline = WeatherTimeline::new(vec![
+            (start + chrono::Duration::hours(3), later),
+            (start, grid()),
+        ])
+        .unwrap();
+
+        let midway = start + chrono::Duration::minutes(90);
+        assert!(timeline.covers(midway));
+        assert!((timeline.sample(0.0, 0.0, midway).unwrap()[0] - 5.0).abs() < 1e-9);
+        // Clamped to the first frame before the timeline
+        assert_eq!(
+            timeline
+                .sample(0.0, 0.0, start - chrono::Duration::hours(1))
+                .unwrap()[0],
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_grid_shape() {
+        let grid = grid();
+        assert_eq!(grid.width(), 4);
+        assert_eq!(grid.lats.len(), 3);
+    }
+
+    #[test]
+    fn test_bilinear_sample() {
+        let grid = grid();
+        let values = grid.sample(0.5, 45.0).unwrap();
+        assert!((values[0] - 45.0).abs() < 1e-9);
+        assert!((values[1] - 5.0).abs() < 1e-9);
+
+        // Clamped outside the latitude range
+        assert_eq!(grid.sample(5.0, 0.0).unwrap()[1], 10.0);
+    }
+
+    #[test]
+    fn test_sample_wraps_antimeridian() {
+        let grid = grid();
+        // Halfway between lon 90 (u = 90) and lon 180 == -180 (u = -180)
+        let values = grid.sample(0.0, 135.0).unwrap();
+        assert!((values[0] - -45.0).abs() < 1e-9);
+        let wrapped = grid.sample(0.0, -225.0).unwrap();
+        assert!((wrapped[0] - -45.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,205 @@
+use hmac::{Hmac, Mac};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use sha2::Sha256;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// Header carrying the signature of a webhook delivery
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+/// Header carrying the Unix time that was signed along with the body
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+
+/// `sha256=<hex>` HMAC of `"{timestamp}.{body}"` with the webhook's secret.
+/// Receivers recompute it and reject old timestamps, which also defeats replays.
+pub fn sign_payload(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+/// Webhooks only go to public addresses, never to the server itself or its network
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            !(a == 0
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                // Reserved 240.0.0.0/4
+                || a >= 240
+                // Shared address space (CGNAT) 100.64.0.0/10
+                || (a == 100 && b & 0xc0 == 64)
+                // IETF protocol assignments 192.0.0.0/24
+                || (a == 192 && b == 0 && c == 0)
+                // Benchmarking 198.18.0.0/15
+                || (a == 198 && b & 0xfe == 18))
+        }
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(ip) => is_public_address(IpAddr::V4(ip)),
+            // Multicast ff00::/8, unique local fc00::/7 and link-local fe80::/10
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.segments()[0] & 0xfe00 == 0xfc00
+                    || ip.segments()[0] & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// IPv4 address an IPv6 one routes to: IPv4-mapped `::ffff:a.b.c.d`, IPv4-compatible
+/// `::a.b.c.d`, NAT64 `64:ff9b::a.b.c.d` and 6to4 `2002:aabb:ccdd::/48`
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let octets = ip.octets();
+    let compatible = segments[..6] == [0; 6] && !ip.is_loopback() && !ip.is_unspecified();
+    let nat64 = segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0];
+    if let Some(ip) = ip.to_ipv4_mapped() {
+        Some(ip)
+    } else if compatible || nat64 {
+        Some(Ipv4Addr::new(
+            octets[12], octets[13], octets[14], octets[15],
+        ))
+    } else if segments[0] == 0x2002 {
+        Some(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5]))
+    } else {
+        None
+    }
+}
+
+/// Resolve the host of a webhook `url` and check that it only points to public addresses
+pub async fn check_webhook_url(url: &str) -> Result<(), String> {
+    let url = reqwest::Url::parse(url).map_err(|_| "url is not a valid URL")?;
+    let host = url
+        .host_str()
+        .ok_or("url has no host")?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.')
+        .to_ascii_lowercase();
+    if host == "localhost" || host.ends_with(".localhost") {
+        return Err("url must point to a public address".to_string());
+    }
+
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .map_err(|_| format!("{} cannot be resolved", host))?
+        .collect();
+    if addresses.is_empty() || !addresses.iter().all(|a| is_public_address(a.ip())) {
+        return Err("url must point to a public address".to_string());
+    }
+    Ok(())
+}
+
+/// Resolver of the webhook client: drops private addresses, so a name resolving
+/// differently at delivery than when it was checked cannot reach the network
+pub struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|a| is_public_address(a.ip()))
+                .collect();
+            if addresses.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_payload() {
+        // Reference value from `printf '1700000000.{"a":1}' | openssl dgst -sha256 -hmac secret`
+        assert_eq!(
+            sign_payload("secret", 1_700_000_000, br#"{"a":1}"#),
+            "sha256=49f24e537407743fa4a0242bb63b94b9a47ee99cbbe071ccd8a22550ae411686"
+        );
+    }
+
+    #[test]
+    fn test_is_public_address() {
+        for (ip, public) in [
+            ("93.184.215.14", true),
+            ("8.8.8.8", true),
+            ("100.63.255.255", true),
+            ("100.128.0.1", true),
+            ("192.0.1.1", true),
+            ("198.20.0.1", true),
+            ("223.255.255.255", true),
+            ("0.0.0.0", false),
+            ("0.1.2.3", false),
+            ("127.0.0.1", false),
+            ("10.1.2.3", false),
+            ("172.16.0.1", false),
+            ("192.168.1.10", false),
+            ("169.254.169.254", false),
+            ("100.64.0.1", false),
+            ("100.127.255.254", false),
+            ("192.0.0.8", false),
+            ("198.18.0.1", false),
+            ("198.19.255.255", false),
+            ("224.0.0.1", false),
+            ("239.255.255.250", false),
+            ("240.0.0.1", false),
+            ("255.255.255.255", false),
+            ("2606:4700::1111", true),
+            ("64:ff9b::5db8:d70e", true),
+            ("2002:5db8:d70e::1", true),
+            ("::", false),
+            ("::1", false),
+            ("fd00::1", false),
+            ("fe80::1", false),
+            ("ff02::1", false),
+            ("::ffff:127.0.0.1", false),
+            ("::ffff:10.0.0.1", false),
+            ("::127.0.0.1", false),
+            ("::192.168.0.1", false),
+            ("64:ff9b::7f00:1", false),
+            ("64:ff9b::a9fe:a9fe", false),
+            ("2002:7f00:1::1", false),
+            ("2002:c0a8:101::1", false),
+        ] {
+            let ip: IpAddr = ip.parse().unwrap();
+            assert_eq!(is_public_address(ip), public, "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_webhook_url() {
+        for url in [
+            "http://localhost:8080/hook",
+            "https://api.localhost/hook",
+            "http://127.0.0.1/hook",
+            "http://10.1.2.3/hook",
+            "http://172.16.0.1/hook",
+            "http://192.168.1.10/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://0.0.0.0/hook",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+        ] {
+            assert!(check_webhook_url(url).await.is_err(), "{}", url);
+        }
+        assert!(check_webhook_url("https://93.184.215.14/hook")
+            .await
+            .is_ok());
+        assert!(check_webhook_url("https://[2606:4700::1111]/hook")
+            .await
+            .is_ok());
+    }
+}